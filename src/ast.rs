@@ -1,5 +1,7 @@
-use crate::token::Token;
+use crate::symbol::{Symbol, Symbols};
+use crate::token::{Span, Token};
 use crate::utils::ParserError;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /*
@@ -11,7 +13,7 @@ use serde::{Deserialize, Serialize};
 
 /// Represents an identifier in the syntax tree. An identifier may have an
 /// associated error from the parsing process.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Identifier {
     /// The token representing the identifier.
     pub id: Option<Token>,
@@ -20,34 +22,38 @@ pub struct Identifier {
 }
 
 /// Represents various literal values such as integers, floats, strings,
-/// or characters. In case of a parsing error, the `Error` variant is used.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// characters, booleans or `null`. In case of a parsing error, the `Error`
+/// variant is used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Literal {
     Integer(Token),
     Float(Token),
     String(Token),
     Character(Token),
+    Boolean(Token),
+    /// The `null` reference, which refers to nothing.
+    Null(Token),
     /// Captures an error during the parsing of a literal.
     Error(ParserError),
 }
 
 /// Represents an array access operation in the syntax tree.
 /// Contains an expression for indexing, and allows for chained accesses.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ArrayAccess {
     /// Specifies the depth of array access.
     pub level: u32,
     /// Expression for the current index.
     pub index: Box<Expression>,
     /// Recursive next access for multidimensional arrays.
-    pub next: Box<ArrayAccess>,
+    pub next: Option<Box<ArrayAccess>>,
     /// Optional error encountered while parsing the array access.
     pub error: Option<ParserError>,
 }
 
 /// Represents a function call in the syntax tree, including the function
 /// identifier and arguments. Supports parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct FunctionCall {
     /// The identifier of the function being called.
     pub id: Box<Identifier>,
@@ -58,22 +64,33 @@ pub struct FunctionCall {
 }
 
 /// Represents primary expressions such as literals, identifiers, groups,
-/// array accesses, or function calls. Parsing errors are represented using
+/// array accesses, function calls, ranges, or field accesses. Parsing errors are represented using
 /// the `Error` variant.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Primary {
     Literal(Box<Literal>),
     Identifier(Box<Identifier>),
     Group(Box<Expression>),
     ArrayAccess(Box<Identifier>, Box<ArrayAccess>),
     FunctionCall(FunctionCall),
+    /// A `match` used as a value. Each arm yields the value its block ends with.
+    Match(Box<MatchStatement>),
+    /// A `loop` used as a value, which is the value of the `break` leaving
+    /// it. It never loops over a range, since it could then end without one.
+    Loop(Box<LoopStatement>),
+    /// A range such as `0..10`. It binds more loosely than every operator.
+    Range(Box<Range>),
+    /// A member of a value, such as `a.b`. The member is an identifier, a
+    /// function call for `a.b()` or an array access for `a.b[0]`, and is
+    /// never a field access itself, so `a.b.c` nests to the left.
+    FieldAccess(Box<Expression>, Box<Primary>),
     /// Captures an error during parsing of primary expressions.
     Error(ParserError),
 }
 
 /// Represents an operator in an expression. This includes binary and unary
 /// operations. Errors are captured via the `Error` variant.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Operator {
     /// A binary operation with an operator and two expressions.
     Binary(String, Box<Expression>, Box<Expression>),
@@ -83,9 +100,18 @@ pub enum Operator {
     Error(ParserError),
 }
 
+/// A range of values from `start` to `end`, written `start..end` when `end`
+/// is excluded and `start..=end` when it is included.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Range {
+    pub start: Box<Expression>,
+    pub end: Box<Expression>,
+    pub is_inclusive: bool,
+}
+
 /// Represents an expression in the syntax tree. An expression can either
 /// be an operation, a primary value, or an error.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Expression {
     Operation(Box<Operator>),
     Primary(Box<Primary>),
@@ -96,17 +122,20 @@ pub enum Expression {
 /// Represents different variants of generics in the syntax tree. This includes
 /// identifiers or implementations with types. Parsing errors are represented
 /// using the `Error` variant.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum GenericVariants {
     Identifier(Box<Identifier>),
     Implements(Box<Identifier>, Box<Identifier>),
+    /// A type given for a generic parameter where a generic type is used,
+    /// such as the `u8` of `enum Option<u8>`.
+    Type(Box<Type>),
     /// Captures an error during parsing of a generic variant.
     Error(ParserError),
 }
 
 /// Represents a collection of generic parameters in a declaration. Parsing
 /// errors are optional.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct GenericParameters {
     /// A vector of generic variants.
     pub generics: Vec<Box<GenericVariants>>,
@@ -117,12 +146,15 @@ pub struct GenericParameters {
 /// Represents different type variants such as primitives, structures,
 /// enumerations, arrays, references, generics, or interfaces. Errors are
 /// represented using the `Error` variant.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum TypeVariant {
     Primitive(String),
     Structure(Box<Identifier>, Option<Box<GenericParameters>>),
     Enumeration(Box<Identifier>, Option<Box<GenericParameters>>),
     Array(Box<TypeVariant>, Box<Expression>),
+    /// A reference, written `ref T`. References have no mutability of their
+    /// own: only variables can be assigned to, so nothing is ever written
+    /// through a reference.
     Reference(Box<TypeVariant>),
     Generic(Box<Identifier>),
     Interface(Box<Identifier>, Option<Box<GenericParameters>>),
@@ -132,7 +164,7 @@ pub enum TypeVariant {
 
 /// Represents a type in the syntax tree, encapsulating the variant and
 /// any parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Type {
     /// The variant of the type.
     pub variant: Box<TypeVariant>,
@@ -140,16 +172,22 @@ pub struct Type {
     pub error: Option<ParserError>,
 }
 
+/// Represents a function parameter, as a type and an identifier.
+pub type Parameter = (Box<Type>, Box<Identifier>);
+
 /// Represents a block of statements in the syntax tree.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Block {
     /// A list of statements in the block.
     pub statements: Vec<Statement>,
+    /// The expression the block ends with, without a `;`. Only the arms of a
+    /// `match` expression have one, and it is the value of the arm.
+    pub value: Option<Box<Expression>>,
 }
 
 /// Represents an assignment statement, containing an identifier and an
 /// expression.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Assignment {
     /// The identifier to assign a value to.
     pub id: Box<Identifier>,
@@ -160,7 +198,7 @@ pub struct Assignment {
 /// Represents an `if` statement in the syntax tree, including the condition,
 /// the `if` block, optional `elif` statements, and an optional `else` block.
 /// Errors are handled optionally.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct IfStatement {
     pub condition: Box<Expression>,
     pub if_block: Box<Block>,
@@ -171,7 +209,7 @@ pub struct IfStatement {
 
 /// Represents an `elif` (else-if) statement in the syntax tree, containing
 /// a condition and a block of statements. Optional errors are included.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ElifStatement {
     pub condition: Box<Expression>,
     pub block: Box<Block>,
@@ -181,19 +219,44 @@ pub struct ElifStatement {
 /// Represents a variable declaration, including its state (e.g., volatile or
 /// constant), type, identifier, and initializer expression. Optional parsing
 /// errors are handled.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct VariableDeclaration {
+    /// One of [`VariableDeclaration::PLAIN`], [`VariableDeclaration::VOLATILE`]
+    /// or [`VariableDeclaration::CONST`].
     pub state: u8,
     pub var_type: Box<Type>,
     pub id: Box<Identifier>,
-    pub init: Box<Expression>,
+    pub init: Option<Box<Expression>>,
     pub error: Option<ParserError>,
 }
 
-/// Represents a `match` statement, which includes case clauses,
-/// an optional default clause, and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Represents a `loop` statement, with an optional label that a nested
+/// `break` or `continue` can target.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LoopStatement {
+    /// The optional label token of the loop, such as `'outer`.
+    pub label: Option<Token>,
+    /// The variable and range of a loop over a range, if it is one.
+    pub iteration: Option<RangeIteration>,
+    /// The body of the loop.
+    pub block: Box<Block>,
+}
+
+/// The `i in 0..10` of a loop over a range, which binds the variable `i` to
+/// each value of the range in turn.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RangeIteration {
+    pub variable: Box<Identifier>,
+    pub range: Box<Range>,
+}
+
+/// Represents a `match` statement or expression, which includes the matched
+/// value, case clauses, an optional default clause, and optional parsing
+/// errors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct MatchStatement {
+    /// The value being matched.
+    pub expr: Box<Expression>,
     /// The clauses of the `match` statement.
     pub case_clauses: Vec<CaseClause>,
     /// An optional default clause.
@@ -202,12 +265,20 @@ pub struct MatchStatement {
     pub error: Option<ParserError>,
 }
 
+/// Represents a case of a `match` statement: a literal the value is compared
+/// to, or the name of an enum variant.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum Pattern {
+    Literal(Literal),
+    Variant(Box<Identifier>),
+}
+
 /// Represents a case clause in a `match` statement, including cases and
 /// the associated block of statements.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CaseClause {
-    /// A list of literals representing the cases.
-    pub cases: Vec<Literal>,
+    /// A list of patterns representing the cases.
+    pub cases: Vec<Pattern>,
     /// The block of statements to execute for the matched case.
     pub case_block: Box<Block>,
     /// Optional error encountered while parsing the case clause.
@@ -215,25 +286,25 @@ pub struct CaseClause {
 }
 
 /// Represents a `return` statement in the syntax tree, containing an
-/// expression and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// optional expression and optional parsing errors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ReturnStatement {
-    /// The expression to return.
-    pub expr: Box<Expression>,
+    /// The expression to return, if any.
+    pub expr: Option<Box<Expression>>,
     /// Optional error encountered while parsing the return statement.
     pub error: Option<ParserError>,
 }
 
 /// Represents a variant of a block string literal, which could either be
 /// a string literal or an identifier.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum BlockStringLiteralVariant {
     StringLiteral(Box<Literal>),
     Identifier(Box<Identifier>),
 }
 
 /// Represents a block of LLVM code. Contains statements and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct LLVMBlock {
     /// A list of statements in the LLVM block.
     pub statements: Vec<BlockStringLiteralVariant>,
@@ -243,7 +314,7 @@ pub struct LLVMBlock {
 
 /// Represents a block of inline assembly (ASM) code. Contains statements
 /// and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ASMBlock {
     /// A list of statements in the ASM block.
     pub statements: Vec<BlockStringLiteralVariant>,
@@ -254,15 +325,19 @@ pub struct ASMBlock {
 /// Represents different kinds of statements in the syntax tree, including
 /// conditional, loop, assignment, variable declaration, and more. Errors
 /// are captured using the `Error` variant.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Statement {
     If(IfStatement),
-    Loop(Box<Block>),
+    Loop(LoopStatement),
     Assign(Assignment),
     Var(VariableDeclaration),
     Match(MatchStatement),
-    Break,
-    Continue,
+    Return(ReturnStatement),
+    /// A `break`, with the label of the loop it targets, if any, and the
+    /// value it gives a loop used as a value.
+    Break(Option<Token>, Option<Box<Expression>>),
+    /// A `continue`, with the label of the loop it targets, if any.
+    Continue(Option<Token>),
     FunctionCall(FunctionCall),
     LLVM(LLVMBlock),
     ASM(ASMBlock),
@@ -272,7 +347,7 @@ pub enum Statement {
 
 /// Represents named fields in structures or other data types.
 /// Each field has a type and an identifier, with optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct NamedFields {
     /// A list of fields, each represented by a type and identifier.
     pub fields: Vec<(Box<Type>, Box<Identifier>)>,
@@ -282,7 +357,7 @@ pub struct NamedFields {
 
 /// Represents tuple fields in a structure or other data type.
 /// Each field is simply a type, with optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct TupleFields {
     /// A list of fields, each represented by a type.
     pub fields: Vec<Box<Type>>,
@@ -291,18 +366,19 @@ pub struct TupleFields {
 }
 
 /// Represents the variants of a structure or enumeration.
-/// A variant can be named, a tuple, or a unit.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// A variant can be named, a tuple, or a unit. A unit variant of an enum may
+/// set its discriminant, as in `A = 5`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Variant {
     Named(Box<Identifier>, Box<NamedFields>),
     Tuple(Box<Identifier>, Box<TupleFields>),
-    Unit(Box<Identifier>),
+    Unit(Box<Identifier>, Option<Box<Expression>>),
 }
 
 /// Represents an enumeration (enum) declaration in the syntax tree.
 /// Includes an identifier, optional generics, variants, and optional
 /// parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct EnumDeclaration {
     /// The identifier of the enum.
     pub id: Box<Identifier>,
@@ -310,23 +386,39 @@ pub struct EnumDeclaration {
     pub generics: Option<Box<GenericParameters>>,
     /// A list of optional variants for the enum.
     pub variants: Option<Vec<Variant>>,
+    /// The attributes written before the declaration.
+    pub attributes: Vec<Attribute>,
     /// Optional error encountered while parsing the enum declaration.
     pub error: Option<ParserError>,
 }
 
+/// An attribute such as `@inline` or `@deprecated("use g")`, written before
+/// a declaration to give hints to later phases.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Attribute {
+    /// The name of the attribute.
+    pub id: Box<Identifier>,
+    /// The arguments of the attribute, if it has parentheses.
+    pub args: Vec<Box<Expression>>,
+    /// Optional error encountered while parsing the attribute.
+    pub error: Option<ParserError>,
+}
+
 /// Represents a structure (struct) declaration in the syntax tree.
 /// Encapsulates a variant and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct StructDeclaration {
     /// The variant that defines the structure.
     pub variant: Box<Variant>,
+    /// The attributes written before the declaration.
+    pub attributes: Vec<Attribute>,
     /// Optional error encountered while parsing the struct declaration.
     pub error: Option<ParserError>,
 }
 
 /// Represents a function declaration in the syntax tree, including its identifier,
 /// visibility, constants, generics, parameters, and body. Parsing errors are optional.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct FunctionDeclaration {
     /// The identifier of the function.
     pub id: Box<Identifier>,
@@ -334,65 +426,456 @@ pub struct FunctionDeclaration {
     pub is_pub: bool,
     /// Whether the function is constant.
     pub is_const: bool,
+    /// Whether the function is an interface method with a default body.
+    pub is_default: bool,
     /// Optional generics for the function.
     pub generics: Option<Box<GenericParameters>>,
+    /// The `self` receiver of a method, which is not one of its parameters.
+    pub receiver: Option<Box<Receiver>>,
     /// Optional parameters for the function, each represented by a type and an identifier.
-    pub parameters: Option<Vec<(Box<Type>, Box<Identifier>)>>,
+    pub parameters: Option<Vec<Parameter>>,
+    /// The return type of the function, if it returns a value.
+    pub return_type: Option<Box<Type>>,
     /// The body of the function, represented as a block of statements.
     pub block: Box<Block>,
+    /// The body of the function while it is left unparsed, in which case
+    /// `block` is empty. See [`Parser::lazy_bodies`](crate::parser::Parser::lazy_bodies).
+    pub lazy_body: Option<Box<LazyBody>>,
+    /// The attributes written before the declaration.
+    pub attributes: Vec<Attribute>,
     /// Optional error encountered while parsing the function declaration.
     pub error: Option<ParserError>,
 }
 
+/// The `self` or `ref self` receiver of a method, written as its first
+/// parameter.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Receiver {
+    /// The `self` identifier.
+    pub id: Box<Identifier>,
+    /// Whether the method takes `ref self`.
+    pub is_ref: bool,
+}
+
+/// The tokens of a function body, from its `{` to its `}`, kept for parsing
+/// on demand.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LazyBody {
+    pub span: Span,
+    pub tokens: Vec<Token>,
+}
+
 /// Represents an interface (trait) declaration in the syntax tree.
 /// Includes an identifier, optional generics, a list of methods,
 /// and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct InterfaceDeclaration {
     /// The identifier of the interface.
     pub id: Box<Identifier>,
+    /// Whether the interface is public.
+    pub is_pub: bool,
     /// Optional generics for the interface.
     pub generics: Option<Box<GenericParameters>>,
-    /// A list of methods (function declarations) in the interface.
+    /// A list of methods (function declarations) in the interface. Only
+    /// methods marked as default have a body.
     pub methods: Vec<Box<FunctionDeclaration>>,
+    /// The attributes written before the declaration.
+    pub attributes: Vec<Attribute>,
     /// Optional error encountered while parsing the interface declaration.
     pub error: Option<ParserError>,
 }
 
 /// Represents an implementation of an interface for a specific type.
-/// Includes the interface identifier, type identifier, generics, methods,
+/// Includes the interface identifier, implementing type, generics, methods,
 /// and optional parsing errors.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct InterfaceImplementation {
     /// The identifier of the interface being implemented.
     pub intf_id: Box<Identifier>,
-    /// The identifier of the type implementing the interface.
-    pub for_id: Box<Identifier>,
-    /// The generics for the implementation.
-    pub generics: Box<GenericParameters>,
+    /// The type implementing the interface.
+    pub for_type: Box<Type>,
+    /// Optional generics for the implementation.
+    pub generics: Option<Box<GenericParameters>>,
     /// A list of method implementations.
     pub methods: Vec<Box<FunctionDeclaration>>,
+    /// The attributes written before the declaration.
+    pub attributes: Vec<Attribute>,
     /// Optional error encountered while parsing the implementation.
     pub error: Option<ParserError>,
 }
 
+/// Represents an `import` of a module, such as `import std.io;`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ImportDeclaration {
+    /// The names making up the path of the module, separated by `.`.
+    pub segments: Vec<Box<Identifier>>,
+    /// Optional error encountered while parsing the import.
+    pub error: Option<ParserError>,
+}
+
 /// Represents a top-level declaration in the syntax tree, which could be
-/// an enum, struct, function, or interface. Parsing errors are represented
-/// using the `Error` variant.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// an enum, struct, function, interface, interface implementation or import.
+/// Parsing errors are represented using the `Error` variant.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Declaration {
     Enum(Box<EnumDeclaration>),
     Struct(Box<StructDeclaration>),
     Function(Box<FunctionDeclaration>),
     Interface(Box<InterfaceDeclaration>),
+    Implementation(Box<InterfaceImplementation>),
+    Import(Box<ImportDeclaration>),
     /// Captures an error during parsing of a declaration.
     Error(ParserError),
 }
 
 /// Represents the Abstract Syntax Tree (AST) for a particular module.
 /// It consists of a collection of top-level declarations.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct AST {
     /// A vector of top-level declarations.
     pub declarations: Vec<Box<Declaration>>,
 }
+
+impl AST {
+    /// Returns the path of each module the tree imports, as written, in
+    /// order.
+    pub fn imports(&self) -> Vec<String> {
+        self.declarations
+            .iter()
+            .filter_map(|decl| match decl.as_ref() {
+                Declaration::Import(import) => Some(import.path()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl ImportDeclaration {
+    /// Returns the path of the imported module, such as `std.io`.
+    pub fn path(&self) -> String {
+        self.segments
+            .iter()
+            .filter_map(|segment| segment.id.as_ref())
+            .map(Token::get_lexeme)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl Variant {
+    /// Returns the name of the variant.
+    pub fn id(&self) -> &Identifier {
+        match self {
+            Variant::Named(id, _) | Variant::Tuple(id, _) | Variant::Unit(id, _) => id,
+        }
+    }
+}
+
+impl VariableDeclaration {
+    pub const PLAIN: u8 = 0;
+    pub const VOLATILE: u8 = 1;
+    pub const CONST: u8 = 2;
+
+    pub fn is_const(&self) -> bool {
+        self.state == Self::CONST
+    }
+}
+
+/*
+ * Constructors for assembling trees by hand, e.g. in tests or when the compiler
+ * synthesizes code. Tokens created here are placed at line 0, column 0 since
+ * they do not come from any source file.
+ */
+
+impl Identifier {
+    /// Creates an identifier with the given name.
+    pub fn named(name: &str) -> Box<Identifier> {
+        Box::new(Identifier {
+            id: Some(Token::Identifier(0, 0, Symbol::from(name))),
+            error: None,
+        })
+    }
+}
+
+impl Type {
+    /// Creates a primitive type such as `i32` or `bool`.
+    pub fn primitive(name: &str) -> Box<Type> {
+        Box::new(Type {
+            variant: Box::new(TypeVariant::Primitive(name.to_string())),
+            error: None,
+        })
+    }
+}
+
+impl Block {
+    pub fn new(statements: Vec<Statement>) -> Box<Block> {
+        Box::new(Block {
+            statements,
+            value: None,
+        })
+    }
+}
+
+impl Expression {
+    /// Creates an integer literal expression from its lexeme, e.g. `"42"`.
+    pub fn int(value: &str) -> Box<Expression> {
+        Expression::literal(Literal::Integer(Token::IntLiteral(0, 0, value.to_string())))
+    }
+
+    pub fn boolean(value: bool) -> Box<Expression> {
+        Expression::literal(Literal::Boolean(Token::Keyword(0, 0, value.to_string())))
+    }
+
+    pub fn literal(literal: Literal) -> Box<Expression> {
+        Box::new(Expression::Primary(Box::new(Primary::Literal(Box::new(
+            literal,
+        )))))
+    }
+
+    /// Creates an expression reading the variable `name`.
+    pub fn identifier(name: &str) -> Box<Expression> {
+        Box::new(Expression::Primary(Box::new(Primary::Identifier(
+            Identifier::named(name),
+        ))))
+    }
+
+    pub fn call(name: &str, args: Vec<Box<Expression>>) -> Box<Expression> {
+        Box::new(Expression::Primary(Box::new(Primary::FunctionCall(
+            FunctionCall {
+                id: Identifier::named(name),
+                args,
+                error: None,
+            },
+        ))))
+    }
+
+    pub fn binary(op: &str, lhs: Box<Expression>, rhs: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Operation(Box::new(Operator::Binary(
+            op.to_string(),
+            lhs,
+            rhs,
+        ))))
+    }
+
+    pub fn unary(op: &str, operand: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Operation(Box::new(Operator::Unary(
+            op.to_string(),
+            operand,
+        ))))
+    }
+}
+
+impl Operator {
+    /// The binding power of unary operators, which bind tighter than any
+    /// binary operator.
+    pub const UNARY_PRECEDENCE: u8 = 10;
+
+    /// Returns the binding power of a binary operator, following the precedence
+    /// levels of the grammar (higher binds tighter), or `None` if `op` is not a
+    /// binary operator.
+    pub fn precedence_of_token(op: &str) -> Option<u8> {
+        match op {
+            "||" => Some(1),
+            "&&" => Some(2),
+            "==" | "!=" | "<" | "<=" | ">" | ">=" => Some(3),
+            "|" => Some(4),
+            "^" => Some(5),
+            "&" => Some(6),
+            "<<" | ">>" => Some(7),
+            "+" | "-" => Some(8),
+            "*" | "/" | "%" => Some(9),
+            _ => None,
+        }
+    }
+
+    /// Returns the binding power of the operation, or `None` for an error.
+    pub fn precedence(&self) -> Option<u8> {
+        match self {
+            Operator::Binary(op, _, _) => Operator::precedence_of_token(op),
+            Operator::Unary(_, _) => Some(Operator::UNARY_PRECEDENCE),
+            Operator::Error(_) => None,
+        }
+    }
+
+    /// Returns whether `child`, an operand of `parent`, must be wrapped in
+    /// parentheses to parse back as that operand. `is_rhs` tells whether it is
+    /// the right operand of a binary operation. Since every binary operator is
+    /// left-associative, a right operand binding as loosely as its parent
+    /// needs them too.
+    pub fn needs_parens(parent: &Operator, child: &Operator, is_rhs: bool) -> bool {
+        match (parent.precedence(), child.precedence()) {
+            (Some(parent), Some(child)) => child < parent || (is_rhs && child == parent),
+            _ => false,
+        }
+    }
+}
+
+impl ArrayAccess {
+    /// Returns the number of accesses in the chain starting at this one.
+    pub fn depth(&self) -> u32 {
+        1 + self.next.as_ref().map_or(0, |next| next.depth())
+    }
+
+    /// Checks that this access is at `level` and that the level of every
+    /// access after it is one more than the one before, as the parser numbers
+    /// them. A chain that fails this was built or rewritten incorrectly.
+    pub fn has_consistent_levels(&self, level: u32) -> bool {
+        let mut expected = level;
+        let mut access = Some(self);
+        while let Some(current) = access {
+            if current.level != expected {
+                return false;
+            }
+            expected += 1;
+            access = current.next.as_deref();
+        }
+        true
+    }
+}
+
+impl FunctionDeclaration {
+    /// Creates a private, non-const function without generics, parameters or
+    /// a return type. Those can be filled in through the public fields.
+    pub fn new(name: &str, block: Box<Block>) -> Box<FunctionDeclaration> {
+        Box::new(FunctionDeclaration {
+            id: Identifier::named(name),
+            block,
+            ..Default::default()
+        })
+    }
+}
+
+/// Serializes `ast` to JSON, for tools that consume the syntax tree.
+pub fn ast_to_json(ast: &AST) -> String {
+    serde_json::to_string(ast).expect("the syntax tree is always representable in JSON")
+}
+
+/// Reads back a syntax tree serialized by `ast_to_json`.
+pub fn ast_from_json(json: &str) -> Result<AST, serde_json::Error> {
+    Symbols::new().deserializing(|| serde_json::from_str(json))
+}
+
+/// Returns the JSON Schema that the output of `ast_to_json` conforms to,
+/// generated from the same serde structure.
+pub fn ast_json_schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(AST))
+        .expect("the schema is always representable in JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_array_access_levels() {
+        let (expr, errors) = Parser::parse_expression_str("cube[x][y + 1][f(z)]");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        let Expression::Primary(primary) = expr.as_ref() else {
+            panic!("Expected a primary, got {:?}", expr);
+        };
+        let Primary::ArrayAccess(_, access) = primary.as_ref() else {
+            panic!("Expected an array access, got {:?}", primary);
+        };
+
+        assert_eq!(access.depth(), 3);
+        assert!(access.has_consistent_levels(1));
+        let mut levels = Vec::new();
+        let mut current = Some(access.as_ref());
+        while let Some(access) = current {
+            levels.push(access.level);
+            current = access.next.as_deref();
+        }
+        assert_eq!(levels, [1, 2, 3]);
+
+        let mut broken = access.clone();
+        broken.next.as_mut().unwrap().next.as_mut().unwrap().level = 2;
+        assert!(!broken.has_consistent_levels(1));
+        assert!(!access.has_consistent_levels(0));
+    }
+
+    #[test]
+    fn test_constructors() {
+        let mut function = FunctionDeclaration::new(
+            "add",
+            Block::new(vec![Statement::Return(ReturnStatement {
+                expr: Some(Expression::binary(
+                    "+",
+                    Expression::identifier("a"),
+                    Expression::int("1"),
+                )),
+                error: None,
+            })]),
+        );
+        function.parameters = Some(vec![(Type::primitive("i32"), Identifier::named("a"))]);
+        function.return_type = Some(Type::primitive("i32"));
+
+        assert_eq!(
+            function.id.id,
+            Some(Token::Identifier(0, 0, Symbol::from("add")))
+        );
+        assert!(!function.is_pub && !function.is_const);
+        assert!(function.generics.is_none() && function.error.is_none());
+        match &function.block.statements[..] {
+            [Statement::Return(ReturnStatement {
+                expr: Some(expr), ..
+            })] => match expr.as_ref() {
+                Expression::Operation(operator) => match operator.as_ref() {
+                    Operator::Binary(op, lhs, rhs) => {
+                        assert_eq!(op, "+");
+                        assert_eq!(lhs, &Expression::identifier("a"));
+                        assert_eq!(rhs, &Expression::int("1"));
+                    }
+                    operator => panic!("Expected a binary operation, got {:?}", operator),
+                },
+                expr => panic!("Expected an operation, got {:?}", expr),
+            },
+            statements => panic!("Expected a single return, got {:?}", statements),
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let src = "pub intf Shape { fn area() -> i32 default fn sides() -> u8 { ret 0; } } \
+                   impl intf Shape for struct Square { fn area() -> i32 { ret 4; } } \
+                   fn f<type T impl Shape>(type T shape, ref [f64; 3] xs) -> bool { \
+                   const char c = '\\n'; 'outer loop { if -xs[0] > 1.5 { break 'outer; } \
+                   elif !true { continue; } else { c = f(shape, xs); } } ret \"s\" == c; }";
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let ast = parser.parse();
+        assert!(!parser.has_error(), "unexpected parser error in {:?}", ast);
+
+        let json = ast_to_json(&ast);
+        assert_eq!(ast_from_json(&json).unwrap(), *ast);
+        assert!(ast_from_json("{\"declarations\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_ast_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&ast_json_schema()).unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+
+        let src = "import geometry.shapes; \
+                   enum Shape<type T> { Empty, Circle(u8), Rect { f32 w, f32 h } } \
+                   intf Area { fn area() -> i32 } \
+                   @inline fn main(i32 n) { \
+                       i32 x = 1.5 + 'c'; \
+                       'rows loop i in 0..=n - 1 { if n > 1 { break 'rows; } elif n == 0 { } } \
+                       match n { 1, 2 -> { ret; } default -> { f(x, \"s\", true); } } \
+                   }";
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let ast = parser.parse();
+        assert!(!parser.has_error(), "unexpected parser error in {:?}", ast);
+        let json: serde_json::Value = serde_json::from_str(&ast_to_json(&ast)).unwrap();
+        assert!(schema.is_valid(&json), "{}", json);
+
+        // Trees with error nodes conform as well.
+        let ast = Parser::new(Lexer::new("fn f() { x = ; } 42").lex()).parse();
+        let json: serde_json::Value = serde_json::from_str(&ast_to_json(&ast)).unwrap();
+        assert!(schema.is_valid(&json), "{}", json);
+
+        assert!(!schema.is_valid(&serde_json::json!({ "declarations": 1 })));
+        assert!(!schema.is_valid(&serde_json::json!({ "declarations": [{ "Loop": {} }] })));
+    }
+}
@@ -1,28 +1,319 @@
-use std::fs::File;
-use crate::ast::AST;
-use std::io::{Read, Write, BufReader};
-use std::path::PathBuf;
-use sha2::{Sha512, Digest};
-use bincode;
+use crate::ast::{Declaration, AST};
+use crate::parser::Parser;
+use crate::symbol::Symbols;
+use crate::token::Token;
+use crate::utils::ParserError;
+use bincode::{self, Options};
 use hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// The extension of the files stored in the cache directory.
+pub const CACHE_EXTENSION: &str = "zxcache";
+
+/// The extension of the fingerprints stored next to the cache entries.
+const FINGERPRINT_EXTENSION: &str = "zxfingerprint";
+
+/// The extension of the declaration caches stored for each source file.
+const DECLARATIONS_EXTENSION: &str = "zxdecls";
+
+/// The bytes that cache entries start with, followed by the version of their
+/// format. Entries without them were written before the format was versioned.
+const FORMAT_MAGIC: &[u8; 4] = b"ZXC\0";
+
+/// The version of the format of cache entries and declaration caches, to be
+/// increased whenever the encoding of the syntax tree changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The coarsest modification time resolution of the file systems we expect to
+/// run on. A file modified less than this long before it was fingerprinted
+/// may have been modified again without its modification time changing.
+const MTIME_GRANULARITY: Duration = Duration::from_secs(2);
+
+/// What a source file looked like when its contents were last hashed.
+#[derive(Debug, Serialize, Deserialize)]
+struct Fingerprint {
+    modified: SystemTime,
+    len: u64,
+    /// When the fingerprint was taken.
+    recorded: SystemTime,
+    hash: String,
+}
+
+/// How `get_hash_fingerprinted` obtained the hash of a file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HashSource {
+    /// The file was unchanged since it was last hashed, and was not read.
+    Fingerprint,
+    /// The contents of the file were hashed.
+    Contents,
+}
+
+/// A summary of the contents of a cache directory.
+#[derive(Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub total_bytes: u64,
+    /// When the least recently written entry was written.
+    pub oldest: Option<SystemTime>,
+    /// When the most recently written entry was written.
+    pub newest: Option<SystemTime>,
+}
+
+/// What `migrate` did to the entries of a cache directory.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Entries already in the current format, which were left untouched.
+    pub current: usize,
+    /// Entries in an older format, rewritten in the current one.
+    pub migrated: usize,
+    /// Entries that could not be read, which were deleted.
+    pub removed: usize,
+}
+
+/// The parsed top-level declarations of a source file, keyed by a hash of
+/// their tokens, so that parsing the file again only parses the declarations
+/// that changed.
+///
+/// The hash only covers where the tokens are relative to the first one, so an
+/// edit re-parses the declaration it is in, and the declarations it moves are
+/// reused at their new place.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeclarationCache {
+    entries: HashMap<String, Entry>,
+    /// How many declarations the last call to `parse` parsed, rather than
+    /// took from the cache.
+    #[serde(skip)]
+    parsed: usize,
+}
+
+/// The declarations parsed from one chunk of tokens.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    /// The line and column of the first token of the chunk.
+    start: (usize, usize),
+    declarations: Vec<Declaration>,
+}
+
+impl Entry {
+    /// Returns the declarations, moved to a chunk starting at `start`.
+    fn declarations_at(&self, start: (usize, usize)) -> Vec<Declaration> {
+        if start == self.start {
+            return self.declarations.clone();
+        }
+        let lines = start.0 as isize - self.start.0 as isize;
+        let cols = start.1 as isize - self.start.1 as isize;
+        let encoded =
+            bincode::serialize(&self.declarations).expect("Declarations are always serializable");
+        Token::moving(lines, cols, || bincode::deserialize(&encoded))
+            .expect("Declarations are always deserializable")
+    }
+}
+
+impl DeclarationCache {
+    /// Parses `tokens` like [`Parser::parse_with_diagnostics`], reusing the
+    /// declarations cached by the previous call. Afterwards, the cache holds
+    /// the declarations of `tokens` only.
+    ///
+    /// Declarations that fail to parse are not cached, and the whole input is
+    /// parsed at once instead, so errors are recovered from the same way.
+    pub fn parse(&mut self, tokens: &[Token]) -> (Box<AST>, Vec<ParserError>) {
+        let mut entries = HashMap::new();
+        let mut declarations = Vec::new();
+        self.parsed = 0;
+        for chunk in split_declarations(tokens) {
+            let key = hash_tokens(chunk);
+            let start = (chunk[0].get_line(), chunk[0].get_col());
+            let parsed = match self.entries.remove(&key) {
+                Some(entry) => entry.declarations_at(start),
+                None => {
+                    let mut chunk = chunk.to_vec();
+                    chunk.push(Token::Eof);
+                    let (ast, errors) = Parser::new(chunk).parse_with_diagnostics();
+                    if !errors.is_empty() {
+                        self.entries.extend(entries);
+                        let (ast, errors) = Parser::new(tokens.to_vec()).parse_with_diagnostics();
+                        self.parsed = ast.declarations.len();
+                        return (ast, errors);
+                    }
+                    self.parsed += ast.declarations.len();
+                    ast.declarations.into_iter().map(|decl| *decl).collect()
+                }
+            };
+            declarations.extend(parsed.iter().cloned().map(Box::new));
+            let entry = Entry {
+                start,
+                declarations: parsed,
+            };
+            entries.insert(key, entry);
+        }
+        self.entries = entries;
+        (Box::new(AST { declarations }), Vec::new())
+    }
+
+    /// Returns how many declarations the last call to `parse` parsed rather
+    /// than took from the cache.
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+}
+
+/// Splits `tokens` after every `}` or `;` outside of any brackets, which is
+/// where a top-level declaration ends. A wrong split makes a declaration fail
+/// to parse, and is then undone by parsing everything at once.
+fn split_declarations(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut chunks = Vec::new();
+    let (mut start, mut depth) = (0, 0usize);
+    for (i, token) in tokens.iter().enumerate() {
+        if *token == Token::Eof {
+            break;
+        }
+        let Token::Separator(_, _, lexeme) = token else {
+            continue;
+        };
+        match lexeme.as_str() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 && (lexeme == "}" || lexeme == ";") {
+            chunks.push(&tokens[start..=i]);
+            start = i + 1;
+        }
+    }
+    let end = tokens
+        .iter()
+        .position(|token| *token == Token::Eof)
+        .unwrap_or(tokens.len());
+    if start < end {
+        chunks.push(&tokens[start..end]);
+    }
+    chunks
+}
+
+/// Hashes `tokens` as if they started at line 0, column 0, so that moving
+/// them keeps their hash.
+fn hash_tokens(tokens: &[Token]) -> String {
+    let (line, col) = tokens
+        .first()
+        .map_or((0, 0), |first| (first.get_line(), first.get_col()));
+    let moved: Vec<Token> = tokens
+        .iter()
+        .map(|token| token.clone().moved(-(line as isize), -(col as isize)))
+        .collect();
+    let encoded = bincode::serialize(&moved).expect("Tokens are always serializable");
+    hex::encode(&Sha512::digest(&encoded)[..16])
+}
 
 pub fn save_ast_to_file(ast: &AST, file_path: &str) -> Result<(), std::io::Error> {
-    let encoded: Vec<u8> = bincode::serialize(ast)
-        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+    let encoded: Vec<u8> =
+        bincode::serialize(ast).map_err(|err| std::io::Error::other(err.to_string()))?;
     let mut file = File::create(file_path)?;
+    file.write_all(FORMAT_MAGIC)?;
+    file.write_all(&FORMAT_VERSION.to_le_bytes())?;
     file.write_all(&encoded)?;
     Ok(())
 }
 
+/// Loads a syntax tree saved by `save_ast_to_file`. Entries in an older format
+/// are an error, since they may decode to the wrong tree; `migrate` upgrades
+/// them.
 pub fn load_ast_from_file(file_path: &str) -> Result<AST, std::io::Error> {
     let mut file = File::open(file_path)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
-    let decoded: AST = bincode::deserialize(&buffer)
-        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+    let encoded = current_format(&buffer)?;
+    let decoded: AST = Symbols::new()
+        .deserializing(|| bincode::deserialize(encoded))
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
     Ok(decoded)
 }
 
+/// Returns what follows the header of a cache entry in the current format, or
+/// an error if the entry is in another one.
+fn current_format(bytes: &[u8]) -> Result<&[u8], std::io::Error> {
+    match format_version(bytes) {
+        Some((FORMAT_VERSION, encoded)) => Ok(encoded),
+        version => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "the cache entry is in format version {}, not {}",
+                version.map_or(0, |(version, _)| version),
+                FORMAT_VERSION
+            ),
+        )),
+    }
+}
+
+/// Splits a cache entry into the version of its format and the encoded tree
+/// that follows, or returns `None` if it has no header.
+fn format_version(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let rest = bytes.strip_prefix(FORMAT_MAGIC)?;
+    let (version, encoded) = rest.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*version), encoded))
+}
+
+/// Rewrites the entries of the cache in `cache_dir` written in an older format
+/// in the current one, so that they stay usable, and deletes the ones that
+/// cannot be read. Only entries from before the format was versioned can be
+/// migrated, and only if they still decode to a syntax tree in full.
+/// Declaration caches in an older format are deleted, since they only save
+/// parsing a file again.
+pub fn migrate(cache_dir: &str) -> Result<MigrationReport, std::io::Error> {
+    let mut report = MigrationReport::default();
+    for entry in fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        if path.extension() == Some(DECLARATIONS_EXTENSION.as_ref()) {
+            if current_format(&fs::read(&path)?).is_ok() {
+                report.current += 1;
+            } else {
+                fs::remove_file(&path)?;
+                report.removed += 1;
+            }
+            continue;
+        }
+        if path.extension() != Some(CACHE_EXTENSION.as_ref()) {
+            continue;
+        }
+
+        let bytes = fs::read(&path)?;
+        let legacy = match format_version(&bytes) {
+            Some((FORMAT_VERSION, _)) => {
+                report.current += 1;
+                continue;
+            }
+            Some(_) => None,
+            None => Symbols::new()
+                .deserializing(|| {
+                    bincode::options()
+                        .with_fixint_encoding()
+                        .deserialize::<AST>(&bytes)
+                })
+                .ok(),
+        };
+        let path_str = path.to_str().expect("Invalid cache directory");
+        match legacy {
+            Some(ast) => {
+                save_ast_to_file(&ast, path_str)?;
+                report.migrated += 1;
+            }
+            None => {
+                fs::remove_file(&path)?;
+                report.removed += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
 pub fn get_hash(file_path: &str) -> Result<String, std::io::Error> {
     let file = File::open(file_path)?;
     let mut reader = BufReader::new(file);
@@ -42,12 +333,279 @@ pub fn get_hash(file_path: &str) -> Result<String, std::io::Error> {
     Ok(hex::encode(&result[..16])) // First 16 bytes of the hash
 }
 
-pub fn file_exists_in_cache(file_path: &str, cache_dir: &str) -> bool {
-    match get_hash(file_path) {
-        Ok(hash) => {
-            let cache_file_path = PathBuf::from(cache_dir).join(format!("{}.zxcache", hash));
-            cache_file_path.exists()
+/// Returns the path of the cache entry of a file whose contents hash to `hash`,
+/// compiled with `settings`. Only trees that compiled without diagnostics are
+/// cached, and which ones do depends on the checks the settings enable as well
+/// as on the contents, so each combination has an entry of its own.
+pub fn entry_path(hash: &str, settings: &str, cache_dir: &str) -> PathBuf {
+    let mut hasher = Sha512::new();
+    hasher.update(hash.as_bytes());
+    hasher.update([0]);
+    hasher.update(settings.as_bytes());
+    let key = hex::encode(&hasher.finalize()[..16]);
+    PathBuf::from(cache_dir).join(format!("{}.{}", key, CACHE_EXTENSION))
+}
+
+fn fingerprint_path(file_path: &str, cache_dir: &str) -> PathBuf {
+    let key = hex::encode(&Sha512::digest(file_path.as_bytes())[..16]);
+    PathBuf::from(cache_dir).join(format!("{}.{}", key, FINGERPRINT_EXTENSION))
+}
+
+/// Returns the same hash as `get_hash`, without reading the file if its
+/// modification time and size match those recorded in `cache_dir` the last
+/// time it was hashed. Files modified too shortly before they were last hashed
+/// are always read again, since a later change could have kept the same
+/// modification time.
+pub fn get_hash_fingerprinted(
+    file_path: &str,
+    cache_dir: &str,
+) -> Result<(String, HashSource), std::io::Error> {
+    let metadata = fs::metadata(file_path)?;
+    let modified = metadata.modified()?;
+    let sidecar = fingerprint_path(file_path, cache_dir);
+
+    let recorded: Option<Fingerprint> = fs::read(&sidecar)
+        .ok()
+        .and_then(|bytes| bincode::deserialize(&bytes).ok());
+    if let Some(fingerprint) = recorded {
+        let settled = fingerprint
+            .recorded
+            .duration_since(modified)
+            .is_ok_and(|age| age >= MTIME_GRANULARITY);
+        if fingerprint.modified == modified && fingerprint.len == metadata.len() && settled {
+            return Ok((fingerprint.hash, HashSource::Fingerprint));
         }
+    }
+
+    let hash = get_hash(file_path)?;
+    let fingerprint = Fingerprint {
+        modified,
+        len: metadata.len(),
+        recorded: SystemTime::now(),
+        hash: hash.clone(),
+    };
+    // The fingerprint only saves work on the next build, so failing to store
+    // it is not an error.
+    if let Ok(bytes) = bincode::serialize(&fingerprint) {
+        let _ = fs::write(&sidecar, bytes);
+    }
+    Ok((hash, HashSource::Contents))
+}
+
+/// Returns the path of the declaration cache of the source file at
+/// `file_path`.
+pub fn declarations_path(file_path: &str, cache_dir: &str) -> PathBuf {
+    let key = hex::encode(&Sha512::digest(file_path.as_bytes())[..16]);
+    PathBuf::from(cache_dir).join(format!("{}.{}", key, DECLARATIONS_EXTENSION))
+}
+
+pub fn save_declarations_to_file(
+    declarations: &DeclarationCache,
+    file_path: &str,
+) -> Result<(), std::io::Error> {
+    let encoded =
+        bincode::serialize(declarations).map_err(|err| std::io::Error::other(err.to_string()))?;
+    let mut file = File::create(file_path)?;
+    file.write_all(FORMAT_MAGIC)?;
+    file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    file.write_all(&encoded)?;
+    Ok(())
+}
+
+/// Loads a declaration cache saved by `save_declarations_to_file`. Like cache
+/// entries, caches in an older format are an error.
+pub fn load_declarations_from_file(file_path: &str) -> Result<DeclarationCache, std::io::Error> {
+    let buffer = fs::read(file_path)?;
+    let encoded = current_format(&buffer)?;
+    Symbols::new()
+        .deserializing(|| bincode::deserialize(encoded))
+        .map_err(|err| std::io::Error::other(err.to_string()))
+}
+
+pub fn file_exists_in_cache(file_path: &str, settings: &str, cache_dir: &str) -> bool {
+    match get_hash(file_path) {
+        Ok(hash) => entry_path(&hash, settings, cache_dir).exists(),
         Err(_) => false, // If hash calculation fails, assume that file does not exist
     }
 }
+
+/// Counts the entries of the cache in `cache_dir` and the bytes they take up.
+/// Files that are not cache entries are ignored.
+pub fn stats(cache_dir: &str) -> Result<CacheStats, std::io::Error> {
+    let mut stats = CacheStats::default();
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+        if !metadata.is_file() || path.extension() != Some(CACHE_EXTENSION.as_ref()) {
+            continue;
+        }
+
+        stats.entries += 1;
+        stats.total_bytes += metadata.len();
+        let modified = metadata.modified()?;
+        stats.oldest = Some(stats.oldest.map_or(modified, |oldest| oldest.min(modified)));
+        stats.newest = Some(stats.newest.map_or(modified, |newest| newest.max(modified)));
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprinted_hash() {
+        let dir = std::env::temp_dir().join(format!("zuroxc-fingerprint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let cache_dir = dir.to_str().unwrap();
+        let source = dir.join("main.zx");
+        let source_path = source.to_str().unwrap();
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+
+        fs::write(&source, "fn main() {}").unwrap();
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+        let (hash, via) = get_hash_fingerprinted(source_path, cache_dir).unwrap();
+        assert_eq!(via, HashSource::Contents);
+        assert_eq!(hash, get_hash(source_path).unwrap());
+
+        let (cached, via) = get_hash_fingerprinted(source_path, cache_dir).unwrap();
+        assert_eq!(via, HashSource::Fingerprint);
+        assert_eq!(cached, hash);
+
+        // Same size, different contents and modification time.
+        fs::write(&source, "fn nope() {}").unwrap();
+        let (touched, via) = get_hash_fingerprinted(source_path, cache_dir).unwrap();
+        assert_eq!(via, HashSource::Contents);
+        assert_ne!(touched, hash);
+
+        // Just modified, so the modification time cannot be trusted yet.
+        let (_, via) = get_hash_fingerprinted(source_path, cache_dir).unwrap();
+        assert_eq!(via, HashSource::Contents);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stats() {
+        let dir = std::env::temp_dir().join(format!("zuroxc-cache-stats-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested.zxcache")).unwrap();
+        let cache_dir = dir.to_str().unwrap();
+
+        assert_eq!(stats(cache_dir).unwrap(), CacheStats::default());
+
+        fs::write(dir.join("a.zxcache"), [0; 10]).unwrap();
+        fs::write(dir.join("b.zxcache"), [0; 32]).unwrap();
+        fs::write(dir.join("c.zxcache"), []).unwrap();
+        fs::write(dir.join("notes.txt"), [0; 100]).unwrap();
+        let stats = stats(cache_dir).unwrap();
+
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.total_bytes, 42);
+        assert!(stats.oldest.unwrap() <= stats.newest.unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_declaration_cache() {
+        let tokens = |src: &str| crate::lexer::Lexer::new(src).lex();
+        let mut cache = DeclarationCache::default();
+        let src = "fn f() -> i32 { ret (1 + 2) * 3; }\nfn g([i32; 2] a) -> i32 { ret f(); }";
+
+        let (ast, errors) = cache.parse(&tokens(src));
+        assert!(errors.is_empty());
+        assert_eq!(cache.parsed(), 2);
+        assert_eq!(ast, Parser::new(tokens(src)).parse());
+
+        // Only the edited function is parsed again.
+        let edited = "fn f() -> i32 { ret (1 + 2) * 3; }\nfn g([i32; 2] a) -> i32 { ret f() + 1; }";
+        let (ast, _) = cache.parse(&tokens(edited));
+        assert_eq!(cache.parsed(), 1);
+        assert_eq!(ast, Parser::new(tokens(edited)).parse());
+
+        // Declarations moved by an edit are reused at their new place.
+        let moved = "// f\nfn f() -> i32 { ret (1 + 2) * 3; }\n\n  fn g([i32; 2] a) -> i32 { ret f() + 1; }";
+        let (ast, errors) = cache.parse(&tokens(moved));
+        assert!(errors.is_empty());
+        assert_eq!(cache.parsed(), 0);
+        assert_eq!(ast, Parser::new(tokens(moved)).parse());
+        cache.parse(&tokens(edited));
+        assert_eq!(cache.parsed(), 0);
+
+        // The cache survives being written out.
+        let dir = std::env::temp_dir().join(format!("zuroxc-declarations-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = declarations_path("main.zx", dir.to_str().unwrap());
+        let path = path.to_str().unwrap();
+        save_declarations_to_file(&cache, path).unwrap();
+        let mut cache = load_declarations_from_file(path).unwrap();
+        cache.parse(&tokens(edited));
+        assert_eq!(cache.parsed(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let broken = "fn f() -> i32 { ret (1 + 2) * 3; }\nfn g( { }";
+        let (ast, errors) = cache.parse(&tokens(broken));
+        assert_eq!(
+            (ast, errors),
+            Parser::new(tokens(broken)).parse_with_diagnostics()
+        );
+        cache.parse(&tokens(edited));
+        assert_eq!(cache.parsed(), 0);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let dir = std::env::temp_dir().join(format!("zuroxc-migrate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let cache_dir = dir.to_str().unwrap();
+        let ast = *Parser::new(crate::lexer::Lexer::new("fn main() { ret; }").lex()).parse();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        save_ast_to_file(&ast, &path("current.zxcache")).unwrap();
+        let current = fs::read(path("current.zxcache")).unwrap();
+        // Entries used to be the encoded tree alone.
+        fs::write(path("legacy.zxcache"), bincode::serialize(&ast).unwrap()).unwrap();
+        fs::write(path("garbage.zxcache"), [1, 2, 3]).unwrap();
+        fs::write(path("notes.txt"), [1, 2, 3]).unwrap();
+        assert!(load_ast_from_file(&path("legacy.zxcache")).is_err());
+        // So were declaration caches, which are deleted rather than migrated.
+        let mut declarations = DeclarationCache::default();
+        declarations.parse(&crate::lexer::Lexer::new("fn main() { ret; }").lex());
+        save_declarations_to_file(&declarations, &path("current.zxdecls")).unwrap();
+        let legacy = bincode::serialize(&declarations).unwrap();
+        fs::write(path("legacy.zxdecls"), legacy).unwrap();
+        assert!(load_declarations_from_file(&path("legacy.zxdecls")).is_err());
+
+        let report = migrate(cache_dir).unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                current: 2,
+                migrated: 1,
+                removed: 2,
+            }
+        );
+        let mut loaded = load_declarations_from_file(&path("current.zxdecls")).unwrap();
+        loaded.parse(&crate::lexer::Lexer::new("fn main() { ret; }").lex());
+        assert_eq!(loaded.parsed(), 0);
+        assert!(!dir.join("legacy.zxdecls").exists());
+        assert_eq!(fs::read(path("current.zxcache")).unwrap(), current);
+        assert_eq!(load_ast_from_file(&path("legacy.zxcache")).unwrap(), ast);
+        assert!(!dir.join("garbage.zxcache").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        assert_eq!(migrate(cache_dir).unwrap().current, 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use crate::ast::*;
+use crate::fold;
+use crate::target::TargetTriple;
+use crate::token::Token;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CodegenError {
+    /// A construct code cannot be generated for: (line, column, construct).
+    Unsupported(usize, usize, String),
+    /// A name with no definition to generate a reference to: (line, column,
+    /// name).
+    UnresolvedSymbol(usize, usize, String),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::Unsupported(line, col, construct) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unsupported construct at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    construct.blue()
+                )
+            }
+            CodegenError::UnresolvedSymbol(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unresolved symbol at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+        }
+    }
+}
+
+/// Generates code for a checked syntax tree. No backend exists yet, so every
+/// declaration is reported as unsupported and only an empty tree generates
+/// (empty) code.
+#[derive(Debug, Default)]
+pub struct Codegen {
+    target: TargetTriple,
+    output: String,
+    errors: Vec<CodegenError>,
+}
+
+impl Codegen {
+    /// Creates a generator for the host.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a generator for `target`, which decides e.g. the width of
+    /// pointers.
+    pub fn for_target(target: TargetTriple) -> Self {
+        Codegen {
+            target,
+            ..Self::default()
+        }
+    }
+
+    pub fn target(&self) -> &TargetTriple {
+        &self.target
+    }
+
+    /// Returns the bytes a value of `var_type` takes up on the target, or
+    /// `None` if that is not known from the type alone: for `void`, for the
+    /// types declared in the source and for arrays whose size is not constant.
+    pub fn size_of(&self, var_type: &TypeVariant) -> Option<u64> {
+        match var_type {
+            TypeVariant::Primitive(name) => self.target.size_of_primitive(name).map(u64::from),
+            TypeVariant::Reference(_) => Some(u64::from(self.target.pointer_width() / 8)),
+            TypeVariant::Array(element, size) => {
+                let fold::Value::Int(len) = fold::fold(size)? else {
+                    return None;
+                };
+                self.size_of(element)?.checked_mul(u64::try_from(len).ok()?)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the generated code, or every error found while generating it.
+    pub fn generate(mut self, ast: &AST) -> Result<String, Vec<CodegenError>> {
+        for decl in &ast.declarations {
+            self.declaration(decl);
+        }
+        if self.errors.is_empty() {
+            Ok(self.output)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn declaration(&mut self, decl: &Declaration) {
+        let (kind, id) = match decl {
+            Declaration::Enum(enumeration) => ("enum", enumeration.id.as_ref()),
+            Declaration::Struct(structure) => ("struct", structure.variant.id()),
+            Declaration::Function(function) => ("function", function.id.as_ref()),
+            Declaration::Interface(interface) => ("interface", interface.id.as_ref()),
+            Declaration::Implementation(implementation) => {
+                ("implementation of", implementation.intf_id.as_ref())
+            }
+            // Imports generate no code, and errors were reported by the
+            // parser.
+            Declaration::Import(_) | Declaration::Error(_) => return,
+        };
+        self.unsupported(kind, id.id.as_ref());
+    }
+
+    fn unsupported(&mut self, kind: &str, token: Option<&Token>) {
+        let Some(token) = token else {
+            return;
+        };
+        self.errors.push(CodegenError::Unsupported(
+            token.get_line(),
+            token.get_col(),
+            format!("{} `{}`", kind, token.get_lexeme()),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn generate(src: &str) -> Result<String, Vec<CodegenError>> {
+        Codegen::new().generate(&Parser::new(Lexer::new(src).lex()).parse())
+    }
+
+    #[test]
+    fn test_codegen_errors() {
+        assert_eq!(generate(""), Ok(String::new()));
+        assert_eq!(
+            generate("enum E { A }\nfn main() { }"),
+            Err(vec![
+                CodegenError::Unsupported(1, 5, String::from("enum `E`")),
+                CodegenError::Unsupported(2, 16, String::from("function `main`")),
+            ])
+        );
+
+        colored::control::set_override(false);
+        assert_eq!(
+            CodegenError::UnresolvedSymbol(3, 4, String::from("f")).to_string(),
+            "Unresolved symbol at line 3, col 4 -> f"
+        );
+    }
+
+    #[test]
+    fn test_codegen_target() {
+        let target = TargetTriple::parse("i686-pc-windows-msvc").unwrap();
+        assert_eq!(Codegen::for_target(target).target().pointer_width(), 32);
+        assert_eq!(Codegen::new().target(), &TargetTriple::host());
+    }
+
+    #[test]
+    fn test_size_of() {
+        let size_of = |triple: &str, src: &str| {
+            let ast = Parser::new(Lexer::new(&format!("fn f({} x) {{ }}", src)).lex()).parse();
+            let Declaration::Function(function) = ast.declarations[0].as_ref() else {
+                panic!("Expected a function, got {:?}", ast.declarations[0]);
+            };
+            let (param_type, _) = &function.parameters.as_ref().unwrap()[0];
+            Codegen::for_target(TargetTriple::parse(triple).unwrap()).size_of(&param_type.variant)
+        };
+        let wide = "x86_64-unknown-linux-gnu";
+        let narrow = "wasm32-unknown-unknown";
+        assert_eq!(size_of(wide, "usize"), Some(8));
+        assert_eq!(size_of(narrow, "usize"), Some(4));
+        assert_eq!(size_of(wide, "isize"), Some(8));
+        assert_eq!(size_of(narrow, "isize"), Some(4));
+        assert_eq!(size_of(wide, "ref i8"), Some(8));
+        assert_eq!(size_of(narrow, "ref i8"), Some(4));
+        assert_eq!(size_of(wide, "[usize; 2 * 3]"), Some(48));
+        assert_eq!(size_of(narrow, "[usize; 2 * 3]"), Some(24));
+        assert_eq!(size_of(wide, "u16"), Some(2));
+        assert_eq!(size_of(wide, "struct Point"), None);
+    }
+}
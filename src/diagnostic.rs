@@ -0,0 +1,928 @@
+use crate::codegen::CodegenError;
+use crate::source_map::SourceMap;
+use crate::token::Span;
+use crate::utils::{LexerError, ParserError, SemanticError, SemanticWarning};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// How serious a diagnostic is. Only errors prevent compilation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A message reported by one of the compiler passes, located at a line and
+/// column of the source, along with notes that help explain it. The column is
+/// the primary position of the diagnostic, and the span, if known, covers all
+/// of the source it is about.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+    pub notes: Vec<String>,
+    pub span: Option<Span>,
+    /// The name of the lint a warning comes from, which `--deny` and `--warn`
+    /// refer to it by.
+    pub code: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(line: usize, col: usize, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            line,
+            col,
+            message: message.into(),
+            notes: Vec::new(),
+            span: None,
+            code: None,
+        }
+    }
+
+    pub fn warning(line: usize, col: usize, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(line, col, message)
+        }
+    }
+
+    /// Attaches an explanatory note to the diagnostic.
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Sets the range of source the diagnostic is about.
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Sets the name of the lint the diagnostic comes from.
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Marks the `len` bytes starting at the column of the diagnostic, e.g.
+    /// the name it reports.
+    fn spanning(self, len: usize) -> Self {
+        let start = self.col;
+        self.with_span(Span {
+            start,
+            end: start + len,
+        })
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title = match self.severity {
+            Severity::Error => "Error at".red().bold(),
+            Severity::Warning => "Warning at".yellow().bold(),
+        };
+        write!(
+            f,
+            "{} {} {} {}",
+            title,
+            format!("line {}, col {}", self.line, self.col).yellow(),
+            "->".cyan(),
+            self.message.blue()
+        )?;
+        if let Some(code) = &self.code {
+            write!(f, " {}", format!("[{}]", code).cyan())?;
+        }
+        for note in &self.notes {
+            write!(f, "\n  {} {}", "note:".cyan().bold(), note)?;
+        }
+        Ok(())
+    }
+}
+
+/// The lints that `--deny` and `--warn` accept, besides `all`.
+pub const LINTS: &[&str] = &[
+    "dead-code",
+    "empty-array",
+    "mixed-indentation",
+    "naming",
+    "unknown-attribute",
+    "unused",
+];
+
+/// Whether the warnings of each lint are reported as warnings or as errors. A
+/// level set for a lint takes precedence over the level set for `all`, and
+/// `warn` takes precedence over `deny`.
+#[derive(Clone, Debug, Default)]
+pub struct LintLevels {
+    denied: HashSet<String>,
+    warned: HashSet<String>,
+}
+
+impl LintLevels {
+    /// Reports the warnings of `lint`, or of every lint for `all`, as errors.
+    pub fn deny(&mut self, lint: &str) -> Result<(), String> {
+        LintLevels::validate(lint)?;
+        self.denied.insert(lint.to_string());
+        Ok(())
+    }
+
+    /// Reports the warnings of `lint`, or of every lint for `all`, as
+    /// warnings, even if it is also denied.
+    pub fn warn(&mut self, lint: &str) -> Result<(), String> {
+        LintLevels::validate(lint)?;
+        self.warned.insert(lint.to_string());
+        Ok(())
+    }
+
+    fn validate(lint: &str) -> Result<(), String> {
+        if lint == "all" || LINTS.contains(&lint) {
+            return Ok(());
+        }
+        Err(format!(
+            "Unknown lint `{}`. The known lints are `all`, `{}`.",
+            lint,
+            LINTS.join("`, `")
+        ))
+    }
+
+    /// Returns whether the warnings of `lint` are reported as errors.
+    pub fn is_denied(&self, lint: &str) -> bool {
+        self.denied_by(lint).is_some()
+    }
+
+    /// Returns the `--deny` argument that turns the warnings of `lint` into
+    /// errors, if any does.
+    fn denied_by<'a>(&self, lint: &'a str) -> Option<&'a str> {
+        if self.warned.contains(lint) {
+            return None;
+        }
+        if self.denied.contains(lint) {
+            return Some(lint);
+        }
+        if self.warned.contains("all") || !self.denied.contains("all") {
+            return None;
+        }
+        Some("all")
+    }
+
+    /// Turns `diagnostic` into an error if it is a warning of a denied lint.
+    pub fn apply(&self, diagnostic: &mut Diagnostic) {
+        if diagnostic.is_error() {
+            return;
+        }
+        let Some(denied_by) = diagnostic
+            .code
+            .as_deref()
+            .and_then(|code| self.denied_by(code))
+        else {
+            return;
+        };
+        let note = format!("`--deny {}` turns this warning into an error.", denied_by);
+        diagnostic.severity = Severity::Error;
+        diagnostic.notes.push(note);
+    }
+}
+
+/// The fewest source characters `highlight` shows, however narrow the width.
+const MIN_SOURCE_WIDTH: usize = 16;
+
+/// Renders the source line containing the byte offset `col` of the source
+/// mapped by `source`, with a caret under `col` and `~` under the rest of
+/// `range`. The parts of `range` on other lines are left out.
+///
+/// Lines longer than `width` characters are cut around the caret, and each
+/// cut is marked with an ellipsis. Returns `None` if `col` is not a position
+/// in the source.
+pub fn highlight(
+    source: &SourceMap,
+    line: usize,
+    col: usize,
+    range: Span,
+    width: usize,
+) -> Option<String> {
+    let src = source.src();
+    if !src.is_char_boundary(col) {
+        return None;
+    }
+    let gutter = format!("{} | ", line);
+    let available = width.saturating_sub(gutter.len()).max(MIN_SOURCE_WIDTH);
+
+    // Only the characters that can be shown on either side of the caret are
+    // looked at, so that a diagnostic on a long line costs no more to render
+    // than one on a short line.
+    let (start, end) = source.line_bounds(col);
+    let before: Vec<char> = src[start..col].chars().rev().take(available).collect();
+    let after = src[col..end].chars().take(available + 1);
+    let window_start = col - before.iter().map(|c| c.len_utf8()).sum::<usize>();
+    let mut chars: Vec<char> = before.into_iter().rev().chain(after).collect();
+    let window_end = window_start + chars.iter().map(|c| c.len_utf8()).sum::<usize>();
+    let (cut_before, cut_after) = (window_start > start, window_end < end);
+    let column = |offset: usize| {
+        let offset = offset.clamp(start, end);
+        if !src.is_char_boundary(offset) {
+            return None;
+        }
+        let offset = offset.clamp(window_start, window_end);
+        Some(src[window_start..offset].chars().count())
+    };
+    let caret = column(col)?;
+    let underline = column(range.start)?..column(range.end)?;
+    // A caret past the end of the line, e.g. at a missing `;`, is shown after
+    // its last character.
+    if chars.len() <= caret {
+        chars.resize(caret + 1, ' ');
+    }
+
+    // The columns below count from the start of the window rather than of the
+    // line, which only makes a difference where the window cuts the line.
+    let (mut from, mut to) = (0, chars.len());
+    if cut_before || cut_after || chars.len() > available {
+        from = caret.saturating_sub(available / 2);
+        if !cut_after {
+            from = from.min(chars.len() - available);
+        }
+        to = from + available;
+        if from > 0 || cut_before {
+            from += 1;
+        }
+        if to < chars.len() || cut_after {
+            to -= 1;
+        }
+    }
+
+    let mut source = String::new();
+    let mut marker = String::new();
+    if from > 0 {
+        source.push('…');
+        marker.push(' ');
+    }
+    for (i, c) in chars.iter().enumerate().take(to).skip(from) {
+        source.push(*c);
+        marker.push(match i {
+            _ if i == caret => '^',
+            _ if underline.contains(&i) => '~',
+            _ => ' ',
+        });
+    }
+    if to < chars.len() {
+        source.push('…');
+    }
+
+    Some(format!(
+        "{}{}\n{}| {}",
+        gutter,
+        source.trim_end(),
+        " ".repeat(gutter.len() - 2),
+        marker.trim_end()
+    ))
+}
+
+impl From<SemanticError> for Diagnostic {
+    fn from(err: SemanticError) -> Self {
+        match err {
+            SemanticError::UndefinedLabel(line, col, label) => {
+                Diagnostic::error(line, col, format!("Undefined loop label `{}`.", label))
+                    .spanning(label.len())
+            }
+            SemanticError::UndefinedVariable(line, col, name) => {
+                Diagnostic::error(line, col, format!("Undefined variable `{}`.", name))
+                    .spanning(name.len())
+            }
+            SemanticError::UndefinedFunction(line, col, name) => {
+                Diagnostic::error(line, col, format!("Undefined function `{}`.", name))
+                    .spanning(name.len())
+            }
+            SemanticError::Redefinition(line, col, name) => {
+                Diagnostic::error(line, col, format!("`{}` is defined more than once.", name))
+                    .spanning(name.len())
+            }
+            SemanticError::TypeMismatch(line, col, message) => {
+                Diagnostic::error(line, col, format!("Mismatched types: {}.", message))
+            }
+            SemanticError::ArgumentCountMismatch(line, col, message) => Diagnostic::error(
+                line,
+                col,
+                format!("Wrong number of arguments: {}.", message),
+            ),
+            SemanticError::UndefinedInterface(line, col, name) => {
+                Diagnostic::error(line, col, format!("Undefined interface `{}`.", name))
+                    .spanning(name.len())
+            }
+            SemanticError::MissingInterfaceMethod(line, col, method) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "Missing an implementation of interface method `{}`.",
+                    method
+                ),
+            ),
+            SemanticError::AssignToConst(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("Cannot assign twice to constant `{}`.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::NonConstantInitializer(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "Constant `{}` must be initialized with a constant expression.",
+                    name
+                ),
+            )
+            .spanning(name.len()),
+            SemanticError::MethodNotInBound(line, col, message) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "Method not available through the generic bound: {}.",
+                    message
+                ),
+            ),
+            SemanticError::UnsatisfiedBound(line, col, message) => Diagnostic::error(
+                line,
+                col,
+                format!("Unsatisfied generic bound: {}.", message),
+            ),
+            SemanticError::DuplicateParameter(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("Parameter `{}` is bound more than once.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::DuplicateGeneric(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("Generic parameter `{}` is declared more than once.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::NonExhaustiveMatch(line, col, missing) => Diagnostic::error(
+                line,
+                col,
+                format!("Non-exhaustive match: {} not covered.", missing),
+            ),
+            SemanticError::VoidVariable(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("Variable `{}` cannot have type `void`.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::NonConstantDiscriminant(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("The discriminant of `{}` must be a constant integer.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::DuplicateDiscriminant(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "The discriminant of `{}` is already used by another variant.",
+                    name
+                ),
+            )
+            .spanning(name.len()),
+            SemanticError::DiscriminantOverflow(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("The discriminant of `{}` does not fit in `i32`.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::SelfOutsideMethod(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "`{}` is only available in methods that take `self` or `ref self`.",
+                    name
+                ),
+            )
+            .spanning(name.len()),
+            SemanticError::ModuleCollision(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("`{}` is already declared as public by another file.", name),
+            )
+            .spanning(name.len()),
+            SemanticError::CircularImport(line, col, cycle) => Diagnostic::error(
+                line,
+                col,
+                format!("Modules import each other in a cycle: {}.", cycle),
+            ),
+            SemanticError::GenericArityMismatch(line, col, name, expected, found) => {
+                let plural = if expected == 1 { "" } else { "s" };
+                Diagnostic::error(
+                    line,
+                    col,
+                    format!(
+                        "`{}` takes {} generic argument{}, found {}.",
+                        name, expected, plural, found
+                    ),
+                )
+                .spanning(name.len())
+            }
+            SemanticError::InvalidArraySize(line, col, size) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "The size of an array must be a non-negative integer, found `{}`.",
+                    size
+                ),
+            ),
+            SemanticError::NullForNonReference(line, col, expected) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "`null` can only be used for references, but `{}` is expected.",
+                    expected
+                ),
+            )
+            .spanning("null".len()),
+            SemanticError::MissingBreakValue(line, col) => Diagnostic::error(
+                line,
+                col,
+                String::from(
+                    "The `loop` is used as a value, so every `break` leaving it must give it one.",
+                ),
+            ),
+            SemanticError::ArrayTooLarge(line, col, target, max) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "The array is too large for `{}`, where a value can take up at most {} bytes.",
+                    target, max
+                ),
+            ),
+        }
+    }
+}
+
+impl From<CodegenError> for Diagnostic {
+    fn from(err: CodegenError) -> Self {
+        match err {
+            CodegenError::Unsupported(line, col, construct) => Diagnostic::error(
+                line,
+                col,
+                format!("Code cannot be generated for {} yet.", construct),
+            ),
+            CodegenError::UnresolvedSymbol(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!("No definition of `{}` to generate code for.", name),
+            )
+            .spanning(name.len()),
+        }
+    }
+}
+
+impl From<SemanticWarning> for Diagnostic {
+    fn from(warning: SemanticWarning) -> Self {
+        match warning {
+            SemanticWarning::MixedIndentation(line, col) => {
+                Diagnostic::warning(line, col, "The indentation mixes tabs and spaces.")
+                    .with_code("mixed-indentation")
+            }
+            SemanticWarning::UnusedVariable(line, col, name) => {
+                Diagnostic::warning(line, col, format!("Variable `{}` is never used.", name))
+                    .spanning(name.len())
+                    .with_code("unused")
+            }
+            SemanticWarning::UnusedFunction(line, col, name) => {
+                Diagnostic::warning(line, col, format!("Function `{}` is never called.", name))
+                    .spanning(name.len())
+                    .with_code("dead-code")
+            }
+            SemanticWarning::NonConventionalName(line, col, name, style) => Diagnostic::warning(
+                line,
+                col,
+                format!("`{}` should be written in {}.", name, style),
+            )
+            .spanning(name.len())
+            .with_code("naming"),
+            SemanticWarning::UnknownAttribute(line, col, name) => {
+                Diagnostic::warning(line, col, format!("Unknown attribute `@{}`.", name))
+                    .spanning(name.len())
+                    .with_code("unknown-attribute")
+            }
+            SemanticWarning::EmptyArray(line, col) => Diagnostic::warning(
+                line,
+                col,
+                "The array has a size of 0 and holds no elements.",
+            )
+            .with_code("empty-array"),
+            SemanticWarning::DeadBranch(line, col, condition) => Diagnostic::warning(
+                line,
+                col,
+                format!(
+                    "`{}` is constant, so the code it guards is never run and is removed.",
+                    condition
+                ),
+            )
+            .with_code("dead-code"),
+        }
+    }
+}
+
+impl From<LexerError> for Diagnostic {
+    fn from(err: LexerError) -> Self {
+        let note = number_note(&err);
+        let span = err.span();
+        let (line, col, message) = match err {
+            LexerError::UnexpectedEOF(line, col, _, value) => (
+                line,
+                col,
+                format!("Unexpected end of file after `{}`.", value),
+            ),
+            LexerError::InvalidBinary(line, col, _, value) => {
+                (line, col, format!("Invalid binary number `{}`.", value))
+            }
+            LexerError::InvalidOctal(line, col, _, value) => {
+                (line, col, format!("Invalid octal number `{}`.", value))
+            }
+            LexerError::InvalidDecimal(line, col, _, value) => {
+                (line, col, format!("Invalid decimal number `{}`.", value))
+            }
+            LexerError::InvalidHexaDecimal(line, col, _, value) => (
+                line,
+                col,
+                format!("Invalid hexadecimal number `{}`.", value),
+            ),
+            LexerError::InvalidFloat(line, col, _, value) => {
+                (line, col, format!("Invalid float number `{}`.", value))
+            }
+            LexerError::UnterminatedStringLiteral(line, col, _, value) => {
+                (line, col, format!("Unclosed string literal `{}`.", value))
+            }
+            LexerError::UnterminatedCharacterLiteral(line, col, _, value) => (
+                line,
+                col,
+                format!("Unclosed character literal `{}`.", value),
+            ),
+            LexerError::UnterminatedComment(line, col, _, value) => {
+                (line, col, format!("Unclosed comment `{}`.", value))
+            }
+            LexerError::InvalidEscape(line, col, _, value) if value == "\\" => (
+                line,
+                col,
+                String::from("Dangling escape `\\` at the end of the string."),
+            ),
+            LexerError::InvalidEscape(line, col, _, value) => {
+                (line, col, format!("Invalid escape sequence `{}`.", value))
+            }
+            LexerError::InvalidIdentifierCharacter(line, col, _, value) => (
+                line,
+                col,
+                format!("The character `{}` cannot be part of an identifier.", value),
+            ),
+        };
+        let diagnostic = Diagnostic::error(line, col, message).with_span(span);
+        match note {
+            Some(note) => diagnostic.with_note(note),
+            None => diagnostic,
+        }
+    }
+}
+
+/// Suggests how to fix an invalid integer literal.
+fn number_note(err: &LexerError) -> Option<String> {
+    let (value, radix, name, digits) = match err {
+        LexerError::InvalidBinary(_, _, _, value) => (value, 2, "binary", "0 and 1"),
+        LexerError::InvalidOctal(_, _, _, value) => (value, 8, "octal", "0 to 7"),
+        LexerError::InvalidHexaDecimal(_, _, _, value) => {
+            (value, 16, "hexadecimal", "0 to 9 and a to f")
+        }
+        LexerError::InvalidDecimal(_, _, _, value) => {
+            let trimmed = value.trim_start_matches('0');
+            if value.len() < 2 || !value.starts_with('0') || trimmed.is_empty() {
+                return None;
+            }
+            return Some(format!(
+                "`{}` looks like a decimal number with a leading zero. Did you mean `{}`? Octal numbers start with `0o`.",
+                value, trimmed
+            ));
+        }
+        _ => return None,
+    };
+    let prefix = value.get(..2).unwrap_or(value);
+    match value.chars().skip(2).find(|c| !c.is_digit(radix)) {
+        Some(c) => Some(format!(
+            "`{}` is not a {} digit. The {} digits are {}.",
+            c, name, name, digits
+        )),
+        None => Some(format!(
+            "`{}` must be followed by {} digits, which are {}.",
+            prefix, name, digits
+        )),
+    }
+}
+
+impl From<ParserError> for Diagnostic {
+    fn from(err: ParserError) -> Self {
+        match err {
+            ParserError::UnexpectedToken(line, col, token) => {
+                Diagnostic::error(line, col, format!("Unexpected token `{}`.", token))
+            }
+            ParserError::MissingToken(line, col, message) => Diagnostic::error(line, col, message),
+            ParserError::InvalidSyntax(line, col, message) => {
+                Diagnostic::error(line, col, format!("Invalid syntax: {}", message))
+            }
+            ParserError::UnexpectedEOF(line, col, message) => {
+                Diagnostic::error(line, col, format!("Unexpected end of file: {}", message))
+            }
+            ParserError::ReservedKeyword(line, col, word) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "`{}` is a reserved keyword and cannot be used as a name.",
+                    word
+                ),
+            ),
+            ParserError::ReservedTypeName(line, col, name) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "`{}` is a reserved type name and cannot be used as an identifier here.",
+                    name
+                ),
+            ),
+            ParserError::UnclosedDelimiter(line, col, delimiter) => Diagnostic::error(
+                line,
+                col,
+                format!(
+                    "`{}` is never closed before the end of the file.",
+                    delimiter
+                ),
+            )
+            .spanning(delimiter.len()),
+            ParserError::DanglingElif(line, col) => Diagnostic::error(
+                line,
+                col,
+                String::from("`elif` must follow the block of an `if`."),
+            )
+            .spanning("elif".len()),
+            ParserError::DanglingElse(line, col) => Diagnostic::error(
+                line,
+                col,
+                String::from("`else` must follow the block of an `if` or `elif`."),
+            )
+            .spanning("else".len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the character of `source` above the first caret of `marker`.
+    fn highlighted(rendered: &str) -> char {
+        let (source, marker) = rendered.split_once('\n').unwrap();
+        let caret = marker.chars().position(|c| c == '^').unwrap();
+        source.chars().nth(caret).unwrap()
+    }
+
+    #[test]
+    fn test_number_notes() {
+        let diagnostic = Diagnostic::from(LexerError::InvalidDecimal(1, 0, 2, String::from("08")));
+        assert_eq!(
+            diagnostic.notes,
+            ["`08` looks like a decimal number with a leading zero. Did you mean `8`? Octal numbers start with `0o`."]
+        );
+
+        let diagnostic = Diagnostic::from(LexerError::InvalidBinary(1, 0, 4, String::from("0b12")));
+        assert_eq!(
+            diagnostic.notes,
+            ["`2` is not a binary digit. The binary digits are 0 and 1."]
+        );
+
+        let diagnostic =
+            Diagnostic::from(LexerError::InvalidHexaDecimal(1, 0, 2, String::from("0x")));
+        assert_eq!(
+            diagnostic.notes,
+            ["`0x` must be followed by hexadecimal digits, which are 0 to 9 and a to f."]
+        );
+
+        let overflow = "99999999999999999999";
+        let diagnostic =
+            Diagnostic::from(LexerError::InvalidDecimal(1, 0, 20, overflow.to_string()));
+        assert!(diagnostic.notes.is_empty());
+    }
+
+    #[test]
+    fn test_lint_levels() {
+        let unused =
+            || Diagnostic::warning(1, 4, "Variable `x` is never used.").with_code("unused");
+        let mixed = || Diagnostic::warning(2, 0, "Mixed.").with_code("mixed-indentation");
+        let apply = |levels: &LintLevels, mut diagnostic: Diagnostic| {
+            levels.apply(&mut diagnostic);
+            diagnostic
+        };
+
+        let mut levels = LintLevels::default();
+        assert!(!apply(&levels, unused()).is_error());
+        levels.deny("unused").unwrap();
+        let denied = apply(&levels, unused());
+        assert!(denied.is_error());
+        assert_eq!(
+            denied.notes,
+            ["`--deny unused` turns this warning into an error."]
+        );
+        assert!(!apply(&levels, mixed()).is_error());
+
+        let mut levels = LintLevels::default();
+        levels.deny("all").unwrap();
+        levels.warn("unused").unwrap();
+        assert!(!apply(&levels, unused()).is_error());
+        assert!(apply(&levels, mixed()).is_error());
+        assert!(!apply(&levels, Diagnostic::warning(1, 0, "No code.")).is_error());
+
+        assert!(levels.deny("unusde").is_err());
+    }
+
+    #[test]
+    fn test_highlight_short_line() {
+        let src = "fn main() {\n    i32 x = 1\n}";
+        let rendered = highlight(
+            &SourceMap::new(src),
+            2,
+            24,
+            Span { start: 24, end: 25 },
+            100,
+        )
+        .unwrap();
+        assert_eq!(rendered, "2 |     i32 x = 1\n  |             ^");
+
+        let rendered = highlight(
+            &SourceMap::new(src),
+            2,
+            25,
+            Span { start: 25, end: 25 },
+            100,
+        )
+        .unwrap();
+        assert_eq!(rendered, "2 |     i32 x = 1\n  |              ^");
+    }
+
+    #[test]
+    fn test_highlight_range() {
+        let src = "ret (a + b * c);\nx = 1;";
+        let rendered =
+            highlight(&SourceMap::new(src), 1, 5, Span { start: 5, end: 10 }, 100).unwrap();
+        assert_eq!(rendered, "1 | ret (a + b * c);\n  |      ^~~~~");
+
+        let rendered =
+            highlight(&SourceMap::new(src), 1, 9, Span { start: 5, end: 10 }, 100).unwrap();
+        assert_eq!(rendered, "1 | ret (a + b * c);\n  |      ~~~~^");
+
+        let rendered = highlight(
+            &SourceMap::new(src),
+            1,
+            13,
+            Span { start: 13, end: 19 },
+            100,
+        )
+        .unwrap();
+        assert_eq!(rendered, "1 | ret (a + b * c);\n  |              ^~~");
+
+        let rendered =
+            highlight(&SourceMap::new(src), 2, 17, Span { start: 9, end: 18 }, 100).unwrap();
+        assert_eq!(rendered, "2 | x = 1;\n  | ^");
+    }
+
+    #[test]
+    fn test_highlight_truncates_long_line() {
+        let src = format!(
+            "fn main() {{ i32 x = {}y{}; }}",
+            "1 + ".repeat(50),
+            " + 1".repeat(50)
+        );
+        let col = src.find('y').unwrap();
+        let rendered = highlight(
+            &SourceMap::new(&src),
+            1,
+            col,
+            Span {
+                start: col,
+                end: col + 1,
+            },
+            60,
+        )
+        .unwrap();
+
+        assert!(rendered.lines().all(|line| line.chars().count() <= 60));
+        assert!(rendered.starts_with("1 | …"));
+        assert!(rendered.lines().next().unwrap().ends_with('…'));
+        assert_eq!(highlighted(&rendered), 'y');
+
+        let rendered =
+            highlight(&SourceMap::new(&src), 1, 3, Span { start: 3, end: 7 }, 60).unwrap();
+        assert!(rendered.starts_with("1 | fn main()"));
+        assert!(rendered.ends_with("\n  |    ^~~~"));
+
+        let col = src.find('}').unwrap();
+        let rendered = highlight(
+            &SourceMap::new(&src),
+            1,
+            col,
+            Span {
+                start: col,
+                end: col + 1,
+            },
+            60,
+        )
+        .unwrap();
+        assert!(rendered.lines().next().unwrap().ends_with("+ 1; }"));
+        assert_eq!(highlighted(&rendered), '}');
+    }
+
+    #[test]
+    fn test_highlight_long_line_with_many_errors() {
+        // A single 8 MiB line with an error every 4 KiB. Rendering each one
+        // from the start of the line would take minutes.
+        let src = "i32 x = y; ".repeat(800_000);
+        let source = SourceMap::new(&src);
+        let start = std::time::Instant::now();
+        for col in (8..src.len()).step_by(4096) {
+            let rendered = highlight(
+                &source,
+                1,
+                col,
+                Span {
+                    start: col,
+                    end: col + 1,
+                },
+                80,
+            )
+            .unwrap();
+            assert!(rendered.lines().all(|line| line.chars().count() <= 80));
+        }
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+
+        let end = src.len() - 1;
+        let rendered = highlight(
+            &source,
+            1,
+            end,
+            Span {
+                start: end,
+                end: end + 1,
+            },
+            40,
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "1 | …; i32 x = y; i32 x = y; i32 x = y;\n  |                                    ^"
+        );
+    }
+
+    #[test]
+    fn test_lexer_error_span() {
+        let src = "i32 x = 0xFFFFFFFFFFFFFFFFFF;";
+        let (_, errors) = crate::lex_str(src);
+        let [err] = errors.as_slice() else {
+            panic!("Expected one error, found {:?}", errors);
+        };
+        assert_eq!(err.span(), Span { start: 8, end: 28 });
+        assert_eq!(&src[8..28], "0xFFFFFFFFFFFFFFFFFF");
+        colored::control::set_override(false);
+        assert!(err
+            .to_string()
+            .ends_with("line 1, col 8 -> 0xFFFFFFFFFFFFFFFFFF"));
+
+        let diagnostic = Diagnostic::from(err.clone());
+        let span = diagnostic.span.unwrap();
+        let rendered = highlight(&SourceMap::new(src), 1, diagnostic.col, span, 100).unwrap();
+        assert_eq!(
+            rendered,
+            "1 | i32 x = 0xFFFFFFFFFFFFFFFFFF;\n  |         ^~~~~~~~~~~~~~~~~~~~"
+        );
+    }
+
+    #[test]
+    fn test_dangling_escape() {
+        let src = "str s = \"abc\\";
+        let (_, errors) = crate::lex_str(src);
+        let [err] = errors.as_slice() else {
+            panic!("Expected one error, found {:?}", errors);
+        };
+        let diagnostic = Diagnostic::from(err.clone());
+        assert_eq!(
+            diagnostic.message,
+            "Dangling escape `\\` at the end of the string."
+        );
+        assert_eq!(diagnostic.span, Some(Span { start: 8, end: 13 }));
+    }
+}
@@ -0,0 +1,806 @@
+use crate::ast::*;
+use crate::pretty;
+use crate::semantic;
+use crate::token::Token;
+use crate::utils::SemanticWarning;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem;
+
+/// A value computed at compile time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+}
+
+/// Evaluates `expr` at compile time. Returns `None` if the expression is not
+/// constant, e.g. because it reads a variable or calls a function, or if its
+/// evaluation fails on overflow or division by zero. The right operand of `&&`
+/// and `||` only needs to be constant if the left one does not decide the
+/// result, since it is not evaluated otherwise.
+pub fn fold(expr: &Expression) -> Option<Value> {
+    fold_with(expr, &|_| None)
+}
+
+/// Evaluates `expr` like [`fold`], where the variables for which `constants`
+/// returns a value are constant too.
+pub fn fold_with(expr: &Expression, constants: &dyn Fn(&str) -> Option<Value>) -> Option<Value> {
+    match expr {
+        Expression::Primary(primary) => match primary.as_ref() {
+            Primary::Literal(literal) => fold_literal(literal),
+            Primary::Identifier(id) => constants(id.id.as_ref()?.get_lexeme()),
+            Primary::Group(inner) => fold_with(inner, constants),
+            Primary::Match(match_statement) => fold_match(match_statement, constants),
+            Primary::Loop(loop_statement) => fold_loop(loop_statement, constants),
+            _ => None,
+        },
+        Expression::Operation(operator) => match operator.as_ref() {
+            Operator::Binary(op, lhs, rhs) => {
+                let lhs = fold_with(lhs, constants)?;
+                match (op.as_str(), lhs) {
+                    ("&&", Value::Bool(false)) => Some(Value::Bool(false)),
+                    ("||", Value::Bool(true)) => Some(Value::Bool(true)),
+                    (op, lhs) => fold_binary(op, lhs, fold_with(rhs, constants)?),
+                }
+            }
+            Operator::Unary(op, operand) => fold_unary(op, fold_with(operand, constants)?),
+            Operator::Error(_) => None,
+        },
+        Expression::Error(_) => None,
+    }
+}
+
+fn fold_literal(literal: &Literal) -> Option<Value> {
+    match literal {
+        Literal::Integer(token) => parse_int(token.get_lexeme()).map(Value::Int),
+        Literal::Float(token) => parse_float(token.get_lexeme()).map(Value::Float),
+        Literal::Boolean(token) => Some(Value::Bool(token.get_lexeme() == "true")),
+        Literal::Character(token) => token.decoded_value()?.chars().next().map(Value::Char),
+        Literal::String(token) => token.decoded_value().map(Value::Str),
+        Literal::Null(_) | Literal::Error(_) => None,
+    }
+}
+
+/// Folds a `match` on a constant to the value of the arm it selects, if that
+/// arm is nothing but a constant value. Cases are compared in order, and enum
+/// variants cannot be folded.
+fn fold_match(
+    match_statement: &MatchStatement,
+    constants: &dyn Fn(&str) -> Option<Value>,
+) -> Option<Value> {
+    let matched = fold_with(&match_statement.expr, constants)?;
+    for clause in &match_statement.case_clauses {
+        for case in &clause.cases {
+            let Pattern::Literal(literal) = case else {
+                return None;
+            };
+            if fold_literal(literal)? == matched {
+                return fold_block(&clause.case_block, constants);
+            }
+        }
+    }
+    fold_block(match_statement.default_clause.as_ref()?, constants)
+}
+
+/// Folds a loop used as a value that leaves right away, by a `break` with a
+/// constant value.
+fn fold_loop(
+    loop_statement: &LoopStatement,
+    constants: &dyn Fn(&str) -> Option<Value>,
+) -> Option<Value> {
+    let Some(Statement::Break(label, Some(value))) = loop_statement.block.statements.first() else {
+        return None;
+    };
+    let leaves_loop = match (label, &loop_statement.label) {
+        (Some(label), Some(loop_label)) => label.get_lexeme() == loop_label.get_lexeme(),
+        (label, _) => label.is_none(),
+    };
+    if !leaves_loop || loop_statement.iteration.is_some() {
+        return None;
+    }
+    fold_with(value, constants)
+}
+
+fn fold_block(block: &Block, constants: &dyn Fn(&str) -> Option<Value>) -> Option<Value> {
+    if !block.statements.is_empty() {
+        return None;
+    }
+    fold_with(block.value.as_ref()?, constants)
+}
+
+/// Parses an integer lexeme such as `42`, `0xFFu8`, `0b101` or the `-7` of a
+/// negative literal pattern, ignoring its type suffix.
+fn parse_int(lexeme: &str) -> Option<i128> {
+    if let Some(magnitude) = lexeme.strip_prefix('-') {
+        return parse_int(magnitude)?.checked_neg();
+    }
+    let digits = match lexeme.find(['i', 'u']) {
+        Some(suffix) => &lexeme[..suffix],
+        None => lexeme,
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") | Some("0X") => (16, &digits[2..]),
+        Some("0o") | Some("0O") => (8, &digits[2..]),
+        Some("0b") | Some("0B") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    i128::from_str_radix(digits, radix).ok()
+}
+
+fn parse_float(lexeme: &str) -> Option<f64> {
+    let digits = ["f32", "f64", "f80", "f128"]
+        .iter()
+        .find_map(|suffix| lexeme.strip_suffix(suffix))
+        .unwrap_or(lexeme);
+    digits.parse().ok()
+}
+
+fn fold_binary(op: &str, lhs: Value, rhs: Value) -> Option<Value> {
+    if matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=") {
+        return fold_comparison(op, &lhs, &rhs);
+    }
+    match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => {
+            let value = match op {
+                "+" => lhs.checked_add(rhs),
+                "-" => lhs.checked_sub(rhs),
+                "*" => lhs.checked_mul(rhs),
+                "/" => lhs.checked_div(rhs),
+                "%" => lhs.checked_rem(rhs),
+                "&" => Some(lhs & rhs),
+                "|" => Some(lhs | rhs),
+                "^" => Some(lhs ^ rhs),
+                "<<" => lhs.checked_shl(u32::try_from(rhs).ok()?),
+                ">>" => lhs.checked_shr(u32::try_from(rhs).ok()?),
+                _ => None,
+            };
+            value.map(Value::Int)
+        }
+        (Value::Float(lhs), Value::Float(rhs)) => {
+            let value = match op {
+                "+" => lhs + rhs,
+                "-" => lhs - rhs,
+                "*" => lhs * rhs,
+                "/" => lhs / rhs,
+                "%" => lhs % rhs,
+                _ => return None,
+            };
+            Some(Value::Float(value))
+        }
+        (Value::Bool(lhs), Value::Bool(rhs)) => match op {
+            "&&" => Some(Value::Bool(lhs && rhs)),
+            "||" => Some(Value::Bool(lhs || rhs)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Compares two values of the same type. Floats compare as they do at run
+/// time, so `NaN` is neither equal to nor ordered with anything.
+fn fold_comparison(op: &str, lhs: &Value, rhs: &Value) -> Option<Value> {
+    let ordering = match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Bool(lhs), Value::Bool(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Char(lhs), Value::Char(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Str(lhs), Value::Str(rhs)) => lhs.partial_cmp(rhs),
+        _ => return None,
+    };
+    let value = match op {
+        "==" => ordering == Some(Ordering::Equal),
+        "!=" => ordering != Some(Ordering::Equal),
+        "<" => ordering == Some(Ordering::Less),
+        "<=" => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        ">" => ordering == Some(Ordering::Greater),
+        ">=" => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        _ => return None,
+    };
+    Some(Value::Bool(value))
+}
+
+fn fold_unary(op: &str, operand: Value) -> Option<Value> {
+    match (op, operand) {
+        ("+", value @ (Value::Int(_) | Value::Float(_))) => Some(value),
+        ("-", Value::Int(value)) => value.checked_neg().map(Value::Int),
+        ("-", Value::Float(value)) => Some(Value::Float(-value)),
+        ("~", Value::Int(value)) => Some(Value::Int(!value)),
+        ("!", Value::Bool(value)) => Some(Value::Bool(!value)),
+        _ => None,
+    }
+}
+
+impl Value {
+    /// Returns a literal expression with the value, or `None` for values that
+    /// are not propagated: characters, strings and floats that are not
+    /// finite. Negative numbers are negated literals.
+    pub fn to_expression(&self) -> Option<Box<Expression>> {
+        match self {
+            Value::Int(value) if *value < 0 => Some(Expression::unary(
+                "-",
+                Expression::int(&value.unsigned_abs().to_string()),
+            )),
+            Value::Int(value) => Some(Expression::int(&value.to_string())),
+            Value::Float(value) if !value.is_finite() => None,
+            Value::Float(value) if value.is_sign_negative() => Some(Expression::unary(
+                "-",
+                Value::Float(-value).to_expression()?,
+            )),
+            Value::Float(value) => Some(Expression::literal(Literal::Float(Token::FloatLiteral(
+                0,
+                0,
+                format!("{:?}", value),
+            )))),
+            Value::Bool(value) => Some(Expression::boolean(*value)),
+            Value::Char(_) | Value::Str(_) => None,
+        }
+    }
+}
+
+/// Substitutes the value of each `const` variable into the expressions after
+/// its declaration in its scope, and replaces every expression that becomes
+/// constant with its value, so that `const i32 a = 2; const i32 b = a + 3;`
+/// initializes `b` to `5`. Other variables are never substituted, including
+/// those shadowing a constant. Meant for checked trees, before codegen.
+pub fn propagate_constants(ast: &mut AST) {
+    Propagator::new(false).ast(ast);
+}
+
+/// Propagates constants like [`propagate_constants`], and removes the code
+/// that the constant conditions make unreachable: the branches of an `if`
+/// whose condition is `false`, those after a condition that is `true`, and
+/// the loops over an empty constant range. An `if` left with a single branch
+/// that is always taken is replaced by the statements of that branch, unless
+/// they declare variables that would then outlive it.
+///
+/// Returns a warning for each condition or range that made code unreachable.
+/// Conditions that are not constant, such as those calling a function, are
+/// kept along with their branches.
+pub fn eliminate_dead_branches(ast: &mut AST) -> Vec<SemanticWarning> {
+    let mut propagator = Propagator::new(true);
+    propagator.ast(ast);
+    propagator.warnings
+}
+
+struct Propagator {
+    /// The value of each variable in scope, which is `None` for those that
+    /// are not constant.
+    scopes: Vec<HashMap<String, Option<Value>>>,
+    /// Whether unreachable branches are removed.
+    prune: bool,
+    warnings: Vec<SemanticWarning>,
+}
+
+impl Propagator {
+    fn new(prune: bool) -> Self {
+        Propagator {
+            scopes: Vec::new(),
+            prune,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn ast(&mut self, ast: &mut AST) {
+        for decl in &mut ast.declarations {
+            match decl.as_mut() {
+                Declaration::Function(function) => self.function(function),
+                Declaration::Interface(interface) => {
+                    for method in &mut interface.methods {
+                        self.function(method);
+                    }
+                }
+                Declaration::Implementation(implementation) => {
+                    for method in &mut implementation.methods {
+                        self.function(method);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn define(&mut self, id: &Identifier, value: Option<Value>) {
+        let (Some(token), Some(frame)) = (&id.id, self.scopes.last_mut()) else {
+            return;
+        };
+        frame.insert(token.get_lexeme().to_string(), value);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|frame| frame.get(name))
+            .cloned()
+            .flatten()
+    }
+
+    fn function(&mut self, function: &mut FunctionDeclaration) {
+        self.scopes.push(HashMap::new());
+        if let Some(receiver) = &function.receiver {
+            self.define(&receiver.id, None);
+        }
+        for (_, id) in function.parameters.iter().flatten() {
+            self.define(id, None);
+        }
+        self.block(&mut function.block);
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        self.scopes.push(HashMap::new());
+        if self.prune {
+            for statement in mem::take(&mut block.statements) {
+                let statements = self.pruned(statement);
+                block.statements.extend(statements);
+            }
+        } else {
+            for statement in &mut block.statements {
+                self.statement(statement);
+            }
+        }
+        if let Some(value) = &mut block.value {
+            self.expression(value);
+        }
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Var(declaration) => {
+                if let Some(init) = &mut declaration.init {
+                    self.expression(init);
+                }
+                let value = match &declaration.init {
+                    Some(init) if declaration.is_const() => {
+                        fold_with(init, &|name| self.lookup(name))
+                    }
+                    _ => None,
+                };
+                self.define(&declaration.id, value);
+            }
+            Statement::Assign(assignment) => self.expression(&mut assignment.expr),
+            Statement::If(if_statement) => {
+                self.expression(&mut if_statement.condition);
+                self.block(&mut if_statement.if_block);
+                for elif in if_statement.elif_statements.iter_mut().flatten() {
+                    self.expression(&mut elif.condition);
+                    self.block(&mut elif.block);
+                }
+                if let Some(else_block) = &mut if_statement.else_block {
+                    self.block(else_block);
+                }
+            }
+            Statement::Loop(loop_statement) => self.loop_statement(loop_statement),
+            Statement::Match(match_statement) => self.match_statement(match_statement),
+            Statement::Return(ret) => {
+                if let Some(expr) = &mut ret.expr {
+                    self.expression(expr);
+                }
+            }
+            Statement::FunctionCall(call) => self.call(call),
+            Statement::Break(_, value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::Continue(_)
+            | Statement::LLVM(_)
+            | Statement::ASM(_)
+            | Statement::Error(_) => {}
+        }
+    }
+
+    fn loop_statement(&mut self, loop_statement: &mut LoopStatement) {
+        self.scopes.push(HashMap::new());
+        if let Some(iteration) = &mut loop_statement.iteration {
+            self.expression(&mut iteration.range.start);
+            self.expression(&mut iteration.range.end);
+            self.define(&iteration.variable, None);
+        }
+        self.block(&mut loop_statement.block);
+        self.scopes.pop();
+    }
+
+    /// Propagates constants into `statement` and returns what is left of it
+    /// once its unreachable branches are removed.
+    fn pruned(&mut self, statement: Statement) -> Vec<Statement> {
+        match statement {
+            Statement::If(if_statement) => self.pruned_if(if_statement),
+            Statement::Loop(mut loop_statement) => {
+                let Some(iteration) = &loop_statement.iteration else {
+                    self.loop_statement(&mut loop_statement);
+                    return vec![Statement::Loop(loop_statement)];
+                };
+                let position = semantic::expression_position(&iteration.range.start);
+                let source = pretty::to_source(&Expression::Primary(Box::new(Primary::Range(
+                    iteration.range.clone(),
+                ))));
+                self.loop_statement(&mut loop_statement);
+                let is_empty = loop_statement.iteration.as_ref().is_some_and(|iteration| {
+                    let range = &iteration.range;
+                    match (fold(&range.start), fold(&range.end)) {
+                        (Some(Value::Int(start)), Some(Value::Int(end))) => {
+                            start > end || (start == end && !range.is_inclusive)
+                        }
+                        _ => false,
+                    }
+                });
+                if is_empty {
+                    self.warn(position, source);
+                    return Vec::new();
+                }
+                vec![Statement::Loop(loop_statement)]
+            }
+            mut statement => {
+                self.statement(&mut statement);
+                vec![statement]
+            }
+        }
+    }
+
+    /// Removes the branches of `if_statement` that are never taken. The
+    /// conditions are folded in order, and the branches after one that is
+    /// always true become unreachable.
+    fn pruned_if(&mut self, if_statement: IfStatement) -> Vec<Statement> {
+        let IfStatement {
+            condition,
+            if_block,
+            elif_statements,
+            else_block,
+            error,
+        } = if_statement;
+        let mut branches = vec![(condition, if_block, error)];
+        branches.extend(
+            elif_statements
+                .into_iter()
+                .flatten()
+                .map(|elif| (elif.condition, elif.block, elif.error)),
+        );
+        let has_else = else_block.is_some();
+
+        let mut kept = Vec::new();
+        let mut always_taken = None;
+        let count = branches.len();
+        for (i, (mut condition, mut block, error)) in branches.into_iter().enumerate() {
+            let position = semantic::expression_position(&condition);
+            let source = pretty::to_source(&condition);
+            self.expression(&mut condition);
+            match fold(&condition) {
+                Some(Value::Bool(false)) => self.warn(position, source),
+                Some(Value::Bool(true)) => {
+                    if i + 1 < count || has_else {
+                        self.warn(position, source);
+                    }
+                    self.block(&mut block);
+                    always_taken = Some((condition, block, error));
+                    break;
+                }
+                _ => {
+                    self.block(&mut block);
+                    kept.push((condition, block, error));
+                }
+            }
+        }
+        let else_block = match always_taken {
+            Some(branch) => Some(branch),
+            None => else_block.map(|mut block| {
+                self.block(&mut block);
+                (Expression::boolean(true), block, None)
+            }),
+        };
+
+        let mut kept = kept.into_iter();
+        let Some((condition, if_block, error)) = kept.next() else {
+            return match else_block {
+                Some((_, block, _)) if can_inline(&block) => block.statements,
+                Some((condition, if_block, error)) => vec![Statement::If(IfStatement {
+                    condition,
+                    if_block,
+                    elif_statements: None,
+                    else_block: None,
+                    error,
+                })],
+                None => Vec::new(),
+            };
+        };
+        let elif_statements: Vec<_> = kept
+            .map(|(condition, block, error)| {
+                Box::new(ElifStatement {
+                    condition,
+                    block,
+                    error,
+                })
+            })
+            .collect();
+        vec![Statement::If(IfStatement {
+            condition,
+            if_block,
+            elif_statements: (!elif_statements.is_empty()).then_some(elif_statements),
+            else_block: else_block.map(|(_, block, _)| block),
+            error,
+        })]
+    }
+
+    fn warn(&mut self, position: Option<(usize, usize)>, source: String) {
+        if let Some((line, col)) = position {
+            self.warnings
+                .push(SemanticWarning::DeadBranch(line, col, source));
+        }
+    }
+
+    fn match_statement(&mut self, match_statement: &mut MatchStatement) {
+        self.expression(&mut match_statement.expr);
+        for clause in &mut match_statement.case_clauses {
+            self.block(&mut clause.case_block);
+        }
+        if let Some(default) = &mut match_statement.default_clause {
+            self.block(default);
+        }
+    }
+
+    fn call(&mut self, call: &mut FunctionCall) {
+        for arg in &mut call.args {
+            self.expression(arg);
+        }
+    }
+
+    /// Replaces `expr` with its value if it is constant, or else its
+    /// operands that are.
+    fn expression(&mut self, expr: &mut Expression) {
+        if let Expression::Primary(primary) = expr {
+            if let Primary::Literal(_) = primary.as_ref() {
+                return;
+            }
+        }
+        let folded = fold_with(expr, &|name| self.lookup(name));
+        if let Some(folded) = folded.as_ref().and_then(Value::to_expression) {
+            *expr = *folded;
+            return;
+        }
+
+        match expr {
+            Expression::Primary(primary) => self.primary(primary),
+            Expression::Operation(operator) => match operator.as_mut() {
+                Operator::Binary(_, lhs, rhs) => {
+                    self.expression(lhs);
+                    self.expression(rhs);
+                }
+                Operator::Unary(_, operand) => self.expression(operand),
+                Operator::Error(_) => {}
+            },
+            Expression::Error(_) => {}
+        }
+    }
+
+    fn primary(&mut self, primary: &mut Primary) {
+        match primary {
+            Primary::Group(inner) => self.expression(inner),
+            Primary::ArrayAccess(_, access) => self.array_access(access),
+            Primary::FunctionCall(call) => self.call(call),
+            Primary::Match(match_statement) => self.match_statement(match_statement),
+            Primary::Loop(loop_statement) => self.loop_statement(loop_statement),
+            Primary::Range(range) => {
+                self.expression(&mut range.start);
+                self.expression(&mut range.end);
+            }
+            Primary::FieldAccess(base, member) => {
+                self.expression(base);
+                // A member named by an identifier is a field, not a variable.
+                match member.as_mut() {
+                    Primary::FunctionCall(call) => self.call(call),
+                    Primary::ArrayAccess(_, access) => self.array_access(access),
+                    _ => {}
+                }
+            }
+            Primary::Literal(_) | Primary::Identifier(_) | Primary::Error(_) => {}
+        }
+    }
+
+    fn array_access(&mut self, access: &mut ArrayAccess) {
+        self.expression(&mut access.index);
+        if let Some(next) = &mut access.next {
+            self.array_access(next);
+        }
+    }
+}
+
+/// Returns whether the statements of `block` can replace the statement it
+/// belongs to without changing what they mean: the block declares no
+/// variables, which would outlive it, and has no value.
+fn can_inline(block: &Block) -> bool {
+    block.value.is_none()
+        && !block
+            .statements
+            .iter()
+            .any(|statement| matches!(statement, Statement::Var(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::pretty::to_source;
+
+    fn fold_str(src: &str) -> Option<Value> {
+        let (expr, errors) = Parser::parse_expression_str(src);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        fold(&expr)
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold_str("(1 + 2) * 3 - 0x10 % 5"), Some(Value::Int(8)));
+        assert_eq!(fold_str("-(1 << 4) | 0b1"), Some(Value::Int(-15)));
+        assert_eq!(fold_str("1.5 * 2.0"), Some(Value::Float(3.0)));
+        assert_eq!(fold_str("'\\n'"), Some(Value::Char('\n')));
+        assert_eq!(
+            fold_str("match 3 - 5 { 1 -> { 10 } -2, 2 -> { 20 } default -> { 0 } }"),
+            Some(Value::Int(20))
+        );
+
+        assert_eq!(fold_str("1 < 2"), Some(Value::Bool(true)));
+        assert_eq!(fold_str("3 == 3 && !(2.5 >= 3.0)"), Some(Value::Bool(true)));
+        assert_eq!(
+            fold_str("'a' != 'a' || \"ab\" < \"b\""),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(fold_str("true && false"), Some(Value::Bool(false)));
+        assert_eq!(fold_str("!true"), Some(Value::Bool(false)));
+        // The right operand is not evaluated, so it need not be constant.
+        assert_eq!(fold_str("true || crash()"), Some(Value::Bool(true)));
+        assert_eq!(fold_str("1 > 2 && crash()"), Some(Value::Bool(false)));
+        assert_eq!(fold_str("false || crash()"), None);
+        assert_eq!(fold_str("1 == 1.0"), None);
+
+        assert_eq!(fold_str("1 / 0"), None);
+        assert_eq!(fold_str("1 + x"), None);
+        assert_eq!(fold_str("f(1)"), None);
+        assert_eq!(fold_str("1 + 2.0"), None);
+        assert_eq!(fold_str("match 7 { 1 -> { 10 } }"), None);
+    }
+
+    fn propagate(src: &str) -> Vec<Statement> {
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let mut ast = parser.parse();
+        assert!(!parser.has_error(), "unexpected errors in {:?}", ast);
+        propagate_constants(&mut ast);
+        match *ast.declarations.remove(0) {
+            Declaration::Function(function) => function.block.statements,
+            decl => panic!("Expected a function, got {:?}", decl),
+        }
+    }
+
+    fn init(statement: &Statement) -> String {
+        match statement {
+            Statement::Var(declaration) => to_source(declaration.init.as_ref().unwrap()),
+            statement => panic!("Expected a variable declaration, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_propagate_constants() {
+        let statements = propagate(
+            "fn f(i32 n) { const i32 a = 2; const i32 b = a + 3; i32 c = b * n; \
+             ret -b - 10; }",
+        );
+        assert_eq!(init(&statements[1]), "5");
+        assert_eq!(init(&statements[2]), "5 * n");
+        let Statement::Return(ret) = &statements[3] else {
+            panic!("Expected a return");
+        };
+        assert_eq!(to_source(ret.expr.as_ref().unwrap()), "-15");
+    }
+
+    #[test]
+    fn test_propagate_constants_stops_at_variables() {
+        let statements = propagate(
+            "fn f() { i32 a = 2; const i32 b = 1; i32 c = a + 3; \
+             if true { i32 b = 4; c = b; } c = b; }",
+        );
+        assert_eq!(init(&statements[2]), "a + 3");
+        let Statement::If(if_statement) = &statements[3] else {
+            panic!("Expected an if statement");
+        };
+        let Statement::Assign(shadowed) = &if_statement.if_block.statements[1] else {
+            panic!("Expected an assignment");
+        };
+        assert_eq!(to_source(&shadowed.expr), "b");
+        let Statement::Assign(assignment) = &statements[4] else {
+            panic!("Expected an assignment");
+        };
+        assert_eq!(to_source(&assignment.expr), "1");
+    }
+
+    #[test]
+    fn test_propagate_conditions() {
+        let statements = propagate(
+            "fn f(i32 n) { bool a = 1 < 2; bool b = true || g(); bool c = n > 0 && 2 > 3; }",
+        );
+        assert_eq!(init(&statements[0]), "true");
+        assert_eq!(init(&statements[1]), "true");
+        assert_eq!(init(&statements[2]), "n > 0 && false");
+    }
+
+    fn eliminate(src: &str) -> (Vec<Statement>, Vec<SemanticWarning>) {
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let mut ast = parser.parse();
+        assert!(!parser.has_error(), "unexpected errors in {:?}", ast);
+        let warnings = eliminate_dead_branches(&mut ast);
+        match *ast.declarations.remove(0) {
+            Declaration::Function(function) => (function.block.statements, warnings),
+            decl => panic!("Expected a function, got {:?}", decl),
+        }
+    }
+
+    fn call_name(statement: &Statement) -> &str {
+        match statement {
+            Statement::FunctionCall(call) => call.id.id.as_ref().unwrap().get_lexeme(),
+            statement => panic!("Expected a call, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_eliminate_dead_branches() {
+        let (statements, warnings) = eliminate("fn f() { if false { a(); } else { b(); } }");
+        assert_eq!(statements.len(), 1);
+        assert_eq!(call_name(&statements[0]), "b");
+        assert_eq!(
+            warnings,
+            [SemanticWarning::DeadBranch(1, 12, String::from("false"))]
+        );
+
+        let (statements, warnings) = eliminate(
+            "fn f(bool c) { if c { a(); } elif 1 < 2 { b(); } else { d(); } \
+             const bool DEBUG = false; if DEBUG { a(); } elif c { b(); } \
+             loop i in 0..0 { a(); } loop i in 0..=0 { b(); } }",
+        );
+        assert_eq!(
+            warnings,
+            [
+                SemanticWarning::DeadBranch(1, 34, String::from("1 < 2")),
+                SemanticWarning::DeadBranch(1, 92, String::from("DEBUG")),
+                SemanticWarning::DeadBranch(1, 133, String::from("0..0")),
+            ]
+        );
+        assert_eq!(statements.len(), 4);
+        let Statement::If(if_statement) = &statements[0] else {
+            panic!("Expected an if statement, got {:?}", statements[0]);
+        };
+        assert!(if_statement.elif_statements.is_none());
+        let else_block = if_statement.else_block.as_ref().unwrap();
+        assert_eq!(call_name(&else_block.statements[0]), "b");
+        let Statement::If(if_statement) = &statements[2] else {
+            panic!("Expected an if statement, got {:?}", statements[2]);
+        };
+        assert_eq!(to_source(&if_statement.condition), "c");
+        assert!(if_statement.elif_statements.is_none());
+        assert!(matches!(statements[3], Statement::Loop(_)));
+    }
+
+    #[test]
+    fn test_eliminate_dead_branches_keeps_scopes_and_calls() {
+        // Inlining the block would let `x` outlive it.
+        let (statements, warnings) = eliminate("fn f() { if true { i32 x = 1; } else { a(); } }");
+        assert_eq!(warnings.len(), 1);
+        let Statement::If(if_statement) = &statements[0] else {
+            panic!("Expected an if statement, got {:?}", statements[0]);
+        };
+        assert_eq!(to_source(&if_statement.condition), "true");
+        assert!(if_statement.else_block.is_none());
+
+        let src = "fn f(i32 n) { if g() { a(); } elif n > 0 { b(); } else { d(); } }";
+        let (statements, warnings) = eliminate(src);
+        assert!(warnings.is_empty());
+        let ast = Parser::new(Lexer::new(src).lex()).parse();
+        let Declaration::Function(function) = ast.declarations[0].as_ref() else {
+            panic!("Expected a function, got {:?}", ast.declarations[0]);
+        };
+        assert_eq!(statements, function.block.statements);
+    }
+}
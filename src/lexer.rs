@@ -1,5 +1,9 @@
+use crate::source_map::SourceMap;
+use crate::symbol::Symbols;
 use crate::token::{self, Token};
-use crate::utils::{self, LexerError};
+use crate::utils::{self, LexerError, SemanticWarning};
+use serde::Serialize;
+use std::time::{Duration, Instant};
 
 pub struct Lexer<'a> {
     line: usize,
@@ -7,11 +11,73 @@ pub struct Lexer<'a> {
     input: &'a str,
     tokens: Vec<Token>,
     has_error: bool,
+    /// Whether to warn about lines indented with both tabs and spaces.
+    warn_mixed_indentation: bool,
+    identifier_set: IdentifierSet,
+    /// The indentation of the current line so far, or `None` once a token
+    /// has been lexed on it.
+    indentation: Option<Indentation>,
+    warnings: Vec<SemanticWarning>,
+    /// The byte offset of the first character of each line lexed so far.
+    line_starts: Vec<usize>,
+    comments: Vec<Comment>,
+    /// The names of the identifiers lexed so far.
+    symbols: Symbols,
 }
 
-pub const DATA_TYPES: [&str; 16] = [
-    "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64", "u128", "i128", "f80",
-    "f128", "char", "bool",
+/// A comment, which is not a token but is kept for tools that print the
+/// source back, such as the formatter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    /// The line the comment starts on.
+    pub line: usize,
+    /// The column the comment starts at on its line, counting bytes from 1.
+    pub col: usize,
+    /// Whether code precedes the comment on its line, rather than the
+    /// comment being on a line of its own.
+    pub is_trailing: bool,
+    /// The comment as written, including its `//` or `/* */`.
+    pub text: String,
+}
+
+/// The characters identifiers may be made of. Either way, an identifier
+/// cannot start with a digit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentifierSet {
+    /// Characters with the Unicode `XID_Start` property, or `_`, followed by
+    /// characters with the `XID_Continue` property.
+    #[default]
+    Xid,
+    /// ASCII letters, digits and `_`.
+    Ascii,
+}
+
+impl IdentifierSet {
+    fn allows(self, c: char, is_start: bool) -> bool {
+        match self {
+            IdentifierSet::Xid if is_start => c == '_' || unicode_ident::is_xid_start(c),
+            IdentifierSet::Xid => unicode_ident::is_xid_continue(c),
+            IdentifierSet::Ascii if is_start => c == '_' || c.is_ascii_alphabetic(),
+            IdentifierSet::Ascii => c == '_' || c.is_ascii_alphanumeric(),
+        }
+    }
+}
+
+/// The kinds of whitespace a line is indented with.
+#[derive(Clone, Copy, Default)]
+struct Indentation {
+    tabs: bool,
+    spaces: bool,
+}
+
+/// The primitive types. `usize` and `isize` are as wide as a pointer on the
+/// target, see [`TargetTriple::size_of_primitive`].
+///
+/// [`TargetTriple::size_of_primitive`]: crate::target::TargetTriple::size_of_primitive
+pub const DATA_TYPES: [&str; 18] = [
+    "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64", "u128", "i128", "usize",
+    "isize", "f80", "f128", "char", "bool",
 ];
 
 pub const MAX_DATA_TYPE_LEN: usize = {
@@ -27,10 +93,17 @@ pub const MAX_DATA_TYPE_LEN: usize = {
     max_len
 };
 
-pub const KEYWORDS: [&str; 27] = [
+pub const KEYWORDS: [&str; 28] = [
     "asm", "if", "elif", "else", "loop", "fn", "ret", "true", "false", "ref", "deref", "impl",
     "struct", "async", "enum", "void", "volatile", "null", "import", "llvm", "break", "continue",
-    "match", "def", "pub", "const", "default",
+    "match", "def", "pub", "const", "default", "in",
+];
+
+/// Operators spanning several characters, lexed with maximal munch. Every
+/// prefix of an operator is an operator too, so one more character is only
+/// taken if it extends the operator read so far.
+pub const MULTI_CHAR_OPERATORS: [&str; 11] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "->", "..", "..=",
 ];
 
 pub const MAX_KEYWORDS_LEN: usize = {
@@ -46,50 +119,289 @@ pub const MAX_KEYWORDS_LEN: usize = {
     max_len
 };
 
+/// Decodes the escape sequences in the body of a string or character literal
+/// (without its quotes) into the Unicode content it denotes.
+///
+/// Supports `\n`, `\t`, `\r`, `\0`, `\\`, `\'`, `\"`, ASCII escapes `\xHH` and
+/// Unicode escapes `\u{H..}` of up to six hex digits. On failure the offending
+/// escape sequence is returned.
+pub fn decode_escapes(raw: &str) -> Result<String, String> {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+
+        let escape = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('\'') => '\'',
+            Some('"') => '"',
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                let byte = if digits.len() == 2 && digits.chars().all(|d| d.is_ascii_hexdigit()) {
+                    u8::from_str_radix(&digits, 16).ok().filter(u8::is_ascii)
+                } else {
+                    None
+                };
+                match byte {
+                    Some(byte) => byte as char,
+                    None => return Err(format!("\\x{}", digits)),
+                }
+            }
+            Some('u') => {
+                let rest = chars.as_str();
+                let end = match (rest.starts_with('{'), rest.find('}')) {
+                    (true, Some(end)) => end,
+                    _ => return Err(String::from("\\u")),
+                };
+                let digits = &rest[1..end];
+                let scalar = if (1..=6).contains(&digits.len())
+                    && digits.chars().all(|d| d.is_ascii_hexdigit())
+                {
+                    u32::from_str_radix(digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                } else {
+                    None
+                };
+                match scalar {
+                    Some(scalar) => {
+                        chars = rest[end + 1..].chars();
+                        scalar
+                    }
+                    None => return Err(format!("\\u{}", &rest[..=end])),
+                }
+            }
+            Some(other) => return Err(format!("\\{}", other)),
+            None => return Err(String::from("\\")),
+        };
+        decoded.push(escape);
+    }
+
+    Ok(decoded)
+}
+
+/// How fast the lexer went through an input, as measured by [`bench`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct BenchResult {
+    /// The size of the input.
+    pub bytes: usize,
+    pub duration: Duration,
+    /// Mebibytes of input lexed per second.
+    pub throughput_mb_s: f64,
+}
+
+impl BenchResult {
+    /// Returns whether the throughput is lower than that of `baseline` by more
+    /// than `tolerance`, a fraction such as `0.1` for 10%.
+    pub fn regressed_from(&self, baseline: &BenchResult, tolerance: f64) -> bool {
+        self.throughput_mb_s < baseline.throughput_mb_s * (1.0 - tolerance)
+    }
+}
+
+/// Lexes `input`, returning the tokens along with how long it took.
+pub fn bench(input: &str) -> (Vec<Token>, BenchResult) {
+    let mut lexer = Lexer::new(input);
+    let start_time = Instant::now();
+    let tokens = lexer.lex();
+    let duration = start_time.elapsed();
+
+    let input_size_mb = input.len() as f64 / (1024.0 * 1024.0);
+    let result = BenchResult {
+        bytes: input.len(),
+        duration,
+        throughput_mb_s: input_size_mb / duration.as_secs_f64(),
+    };
+    (tokens, result)
+}
+
+/// Returns `src` without the UTF-8 byte order mark that some editors write at
+/// the start of a file. Positions in the lexed tokens are relative to the
+/// returned string, so it is also what diagnostics should be shown against.
+pub fn strip_bom(src: &str) -> &str {
+    src.strip_prefix('\u{FEFF}').unwrap_or(src)
+}
+
+/// Returns a description of the first token whose span does not cover its own
+/// lexeme in `input`, if any.
+fn span_mismatch(input: &str, tokens: &[Token]) -> Option<String> {
+    tokens.iter().find_map(|token| {
+        let span = token.span()?;
+        match input.get(span.start..span.end) {
+            Some(text) if text == token.get_lexeme() => None,
+            text => Some(format!(
+                "{:?} spans {:?}, which covers {:?} in the input",
+                token, span, text
+            )),
+        }
+    })
+}
+
 impl<'a> Lexer<'a> {
+    /// Lexes `input` and checks that the span of every token covers exactly
+    /// its lexeme, returning the tokens or a description of the first
+    /// mismatch.
+    pub fn validate_spans(input: &str) -> Result<Vec<Token>, String> {
+        let input = strip_bom(input);
+        let tokens = Lexer::new(input).lex();
+        match span_mismatch(input, &tokens) {
+            Some(mismatch) => Err(mismatch),
+            None => Ok(tokens),
+        }
+    }
+
+    /// Creates a lexer for `input`, ignoring a byte order mark at its start.
     pub fn new(input: &'a str) -> Self {
         Lexer {
             line: 1,
             col: 0,
-            input,
+            input: strip_bom(input),
             tokens: Vec::new(),
             has_error: false,
+            warn_mixed_indentation: false,
+            identifier_set: IdentifierSet::default(),
+            indentation: Some(Indentation::default()),
+            warnings: Vec::new(),
+            line_starts: vec![0],
+            comments: Vec::new(),
+            symbols: Symbols::new(),
         }
     }
 
+    /// Enables warnings for lines whose indentation mixes tabs and spaces.
+    pub fn warn_mixed_indentation(mut self) -> Self {
+        self.warn_mixed_indentation = true;
+        self
+    }
+
+    /// Restricts identifiers to the characters of `set`.
+    pub fn identifier_set(mut self, set: IdentifierSet) -> Self {
+        self.identifier_set = set;
+        self
+    }
+
     pub fn has_error(&self) -> bool {
         self.has_error
     }
 
+    /// Returns the style warnings found while lexing.
+    pub fn warnings(&self) -> &[SemanticWarning] {
+        &self.warnings
+    }
+
+    /// Returns the table interning the names of the identifiers lexed so far,
+    /// which every occurrence of a name shares.
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    /// Returns the comments found while lexing, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Returns the byte offset at which each line starts, once the input has
+    /// been lexed. Offsets are relative to the input without its byte order
+    /// mark, like the positions of the tokens.
+    pub fn line_starts(&self) -> &[usize] {
+        &self.line_starts
+    }
+
+    /// Returns the map of the lines of the input, once it has been lexed,
+    /// from the lines found while lexing. Like the positions of the tokens,
+    /// it is of the input without its byte order mark.
+    pub fn source_map(&self) -> SourceMap<'a> {
+        SourceMap::from_line_starts(self.input, self.line_starts.clone())
+    }
+
+    /// Returns the text of `src`, the input of the lexer, that `tok` was
+    /// lexed from, as written. Unlike [`Token::decoded_value`], escape
+    /// sequences are left as they are. Errors and EOF have no text.
+    pub fn source_text<'s>(&self, src: &'s str, tok: &Token) -> &'s str {
+        let src = strip_bom(src);
+        tok.span()
+            .and_then(|span| src.get(span.start..span.end))
+            .unwrap_or("")
+    }
+
+    /// Tracks the leading whitespace of each line, and reports the
+    /// indentation of a line once its first token starts at `c`.
+    fn track_indentation(&mut self, c: char) {
+        match (c, self.indentation.as_mut()) {
+            ('\n', _) => self.indentation = Some(Indentation::default()),
+            ('\t', Some(indentation)) => indentation.tabs = true,
+            (' ', Some(indentation)) => indentation.spaces = true,
+            (_, Some(indentation)) if !c.is_whitespace() => {
+                if indentation.tabs && indentation.spaces {
+                    let start = self.input[..self.col].rfind('\n').map_or(0, |i| i + 1);
+                    self.warnings
+                        .push(SemanticWarning::MixedIndentation(self.line, start));
+                }
+                self.indentation = None;
+            }
+            _ => {}
+        }
+    }
+
     fn find_dt(&self, x: &str) -> Option<usize> {
-        DATA_TYPES.iter().position(|&s| s == x).map(|pos| pos)
+        DATA_TYPES.iter().position(|&s| s == x)
     }
 
     fn find_keyword(&self, x: &str) -> Option<usize> {
-        KEYWORDS.iter().position(|&s| s == x).map(|pos| pos)
+        KEYWORDS.iter().position(|&s| s == x)
     }
 
+    /// Returns the character at `col`, or `None` at the end of the input.
     fn current(&self) -> Option<char> {
-        self.input[self.col..].chars().next()
+        self.input.get(self.col..)?.chars().next()
     }
 
+    /// Returns the character after the current one, which may be several
+    /// bytes long.
     fn peek(&self) -> Option<char> {
-        self.input[self.col + 1..].chars().next()
+        self.input.get(self.col..)?.chars().nth(1)
     }
 
+    /// Moves past the current character. `col` stops at the end of the input.
     fn advance(&mut self) {
         if let Some(c) = self.current() {
-            self.col += c.len_utf8();
+            self.col = (self.col + c.len_utf8()).min(self.input.len());
         }
     }
 
+    /// Starts a new line, once the newline ending the current one has been
+    /// consumed.
+    fn next_line(&mut self) {
+        self.line += 1;
+        self.line_starts.push(self.col);
+    }
+
     fn eof(&self) -> bool {
         self.col >= self.input.len()
     }
 
+    /// Skips a `#!` interpreter line at the start of the input, leaving its
+    /// newline to be lexed so that the next line is still line 2.
+    fn skip_shebang(&mut self) {
+        if self.col == 0 && self.input.starts_with("#!") {
+            self.col = self.input.find('\n').unwrap_or(self.input.len());
+        }
+    }
+
     pub fn lex(&mut self) -> Vec<token::Token> {
-        while self.col < self.input.len() {
+        self.skip_shebang();
+        while !self.eof() {
             let c = self.current().unwrap_or('\0');
+            if self.warn_mixed_indentation {
+                self.track_indentation(c);
+            }
             if c.is_numeric() {
                 self.number();
             } else if self.is_separator(c) {
@@ -99,40 +411,57 @@ impl<'a> Lexer<'a> {
             } else if self.is_operator(c) {
                 self.handle_operator();
             } else if c.is_whitespace() {
+                self.advance();
                 if c == '\n' {
-                    self.line += 1;
+                    self.next_line();
                 }
-                self.advance();
             } else if c == '"' {
                 self.handle_string_literal();
             } else if c == '\'' {
-                self.handle_char_literal();
+                if self.is_label_start() {
+                    self.handle_label();
+                } else {
+                    self.handle_char_literal();
+                }
             } else {
                 self.keyword_or_datatype_or_identifier();
             }
         }
         self.tokens.push(Token::Eof);
+        debug_assert_eq!(span_mismatch(self.input, &self.tokens), None);
         self.tokens.clone()
     }
 
     fn keyword_or_datatype_or_identifier(&mut self) {
-        let mut str = String::new();
-        str.reserve(8);
-
+        let start = self.col;
         while let Some(c) = self.current() {
             if self.is_operator(c) || self.is_separator(c) || c.is_whitespace() {
                 break;
             }
-            str.push(c);
             self.advance();
         }
 
-        let token = if self.find_dt(&str).is_some() {
-            Token::DataType(self.line, self.col - str.len(), str)
-        } else if self.find_keyword(&str).is_some() {
-            Token::Keyword(self.line, self.col - str.len(), str)
+        // Identifiers are interned straight from the input, so only the first
+        // occurrence of a name is copied.
+        let str = &self.input[start..self.col];
+        let invalid = str
+            .char_indices()
+            .find(|&(i, c)| !self.identifier_set.allows(c, i == 0));
+
+        let token = if let Some((i, c)) = invalid {
+            self.has_error = true;
+            Token::Error(LexerError::InvalidIdentifierCharacter(
+                self.line,
+                start + i,
+                start + i + c.len_utf8(),
+                c.to_string(),
+            ))
+        } else if self.find_dt(str).is_some() {
+            Token::DataType(self.line, start, str.to_string())
+        } else if self.find_keyword(str).is_some() {
+            Token::Keyword(self.line, start, str.to_string())
         } else {
-            Token::Identifier(self.line, self.col - str.len(), str)
+            Token::Identifier(self.line, start, self.symbols.intern(str))
         };
 
         self.tokens.push(token);
@@ -143,15 +472,15 @@ impl<'a> Lexer<'a> {
 
         if let Some(c) = self.current() {
             if c == '0' {
+                str.push(c);
                 self.advance();
                 if let Some(next_c) = self.current() {
                     match next_c {
                         'x' | 'X' => {
-                            str.push('0');
                             str.push(next_c);
                             self.advance();
                             while let Some(c) = self.current() {
-                                if c.is_digit(16) {
+                                if c.is_ascii_hexdigit() {
                                     str.push(c);
                                     self.advance();
                                 } else {
@@ -159,12 +488,13 @@ impl<'a> Lexer<'a> {
                                 }
                             }
 
-                            if let Err(_) = u64::from_str_radix(&str[2..], 16) {
+                            if u64::from_str_radix(&str[2..], 16).is_err() {
                                 self.has_error = true;
                                 self.tokens.push(Token::Error(
                                     utils::LexerError::InvalidHexaDecimal(
                                         self.line,
                                         self.col - str.len(),
+                                        self.col,
                                         str,
                                     ),
                                 ));
@@ -178,7 +508,6 @@ impl<'a> Lexer<'a> {
                             return;
                         }
                         'o' | 'O' => {
-                            str.push('0');
                             str.push(next_c);
                             self.advance();
                             while let Some(c) = self.current() {
@@ -189,13 +518,15 @@ impl<'a> Lexer<'a> {
                                     break;
                                 }
                             }
+                            self.take_stray_digits(&mut str);
 
-                            if let Err(_) = u64::from_str_radix(&str[2..], 8) {
+                            if u64::from_str_radix(&str[2..], 8).is_err() {
                                 self.has_error = true;
                                 self.tokens
                                     .push(Token::Error(utils::LexerError::InvalidOctal(
                                         self.line,
                                         self.col - str.len(),
+                                        self.col,
                                         str,
                                     )));
                             } else {
@@ -208,7 +539,6 @@ impl<'a> Lexer<'a> {
                             return;
                         }
                         'b' | 'B' => {
-                            str.push('0');
                             str.push(next_c);
                             self.advance();
                             while let Some(c) = self.current() {
@@ -219,13 +549,15 @@ impl<'a> Lexer<'a> {
                                     break;
                                 }
                             }
+                            self.take_stray_digits(&mut str);
 
-                            if let Err(_) = u64::from_str_radix(&str[2..], 2) {
+                            if u64::from_str_radix(&str[2..], 2).is_err() {
                                 self.has_error = true;
                                 self.tokens
                                     .push(Token::Error(utils::LexerError::InvalidBinary(
                                         self.line,
                                         self.col - str.len(),
+                                        self.col,
                                         str,
                                     )));
                             } else {
@@ -255,7 +587,8 @@ impl<'a> Lexer<'a> {
             let mut is_float = false;
 
             if let Some(c) = self.current() {
-                if c == '.' {
+                // `0..10` is a range, not the float `0.` followed by `.10`.
+                if c == '.' && self.peek() != Some('.') {
                     is_float = true;
                     str.push(c);
                     self.advance();
@@ -270,7 +603,7 @@ impl<'a> Lexer<'a> {
                 }
 
                 if let Some(next_c) = self.current() {
-                    if next_c.to_ascii_lowercase() == 'e' {
+                    if next_c.eq_ignore_ascii_case(&'e') {
                         is_float = true;
                         str.push(next_c);
                         self.advance();
@@ -293,12 +626,13 @@ impl<'a> Lexer<'a> {
             }
 
             if is_float {
-                if let Err(_) = str.parse::<f64>() {
+                if str.parse::<f64>().is_err() {
                     self.has_error = true;
                     self.tokens
                         .push(Token::Error(utils::LexerError::InvalidFloat(
                             self.line,
                             self.col - str.len(),
+                            self.col,
                             str,
                         )));
                 } else {
@@ -306,12 +640,15 @@ impl<'a> Lexer<'a> {
                         .push(Token::FloatLiteral(self.line, self.col - str.len(), str));
                 }
             } else {
-                if let Err(_) = str.parse::<u64>() {
+                // A leading zero is rejected rather than read as decimal,
+                // since it is a common way to write octal elsewhere.
+                if str.parse::<u64>().is_err() || str.len() > 1 && str.starts_with('0') {
                     self.has_error = true;
                     self.tokens
                         .push(Token::Error(utils::LexerError::InvalidDecimal(
                             self.line,
                             self.col - str.len(),
+                            self.col,
                             str,
                         )));
                 } else {
@@ -322,8 +659,21 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Adds the decimal digits at the current position to `str`. After the
+    /// digits of a binary or octal number, these are digits out of its range,
+    /// which are kept in the number so that it is reported as a whole.
+    fn take_stray_digits(&mut self, str: &mut String) {
+        while let Some(c) = self.current() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            str.push(c);
+            self.advance();
+        }
+    }
+
     fn is_separator(&self, c: char) -> bool {
-        matches!(c, ';' | ',' | '{' | '}' | '[' | ']' | '(' | ')')
+        matches!(c, ';' | ',' | '{' | '}' | '[' | ']' | '(' | ')' | '@')
     }
 
     fn is_operator(&self, c: char) -> bool {
@@ -346,15 +696,29 @@ impl<'a> Lexer<'a> {
                 }
             }
 
+            let col = self.col;
             op.push(c);
-            self.tokens.push(Token::Operator(self.line, self.col, op));
             self.advance();
+            while let Some(next_c) = self.current() {
+                op.push(next_c);
+                if MULTI_CHAR_OPERATORS.contains(&op.as_str()) {
+                    self.advance();
+                } else {
+                    op.pop();
+                    break;
+                }
+            }
+            self.tokens.push(Token::Operator(self.line, col, op));
         }
     }
 
     fn handle_comment(&mut self) {
         let mut comment = String::new();
         comment.reserve(128);
+        let line = self.line;
+        let line_start = self.line_starts[line - 1];
+        let col = self.col - line_start + 1;
+        let is_trailing = !self.input[line_start..self.col].trim().is_empty();
 
         if let Some(c) = self.current() {
             if c == '/' {
@@ -371,7 +735,12 @@ impl<'a> Lexer<'a> {
                             comment.push(c);
                             self.advance();
                         }
-                        return;
+                        self.comments.push(Comment {
+                            line,
+                            col,
+                            is_trailing,
+                            text: comment,
+                        });
                     } else if next_c == '*' {
                         comment.push(next_c);
                         self.advance();
@@ -383,17 +752,26 @@ impl<'a> Lexer<'a> {
                                         comment.push(next_c);
                                         self.advance();
                                         self.advance();
+                                        self.comments.push(Comment {
+                                            line,
+                                            col,
+                                            is_trailing,
+                                            text: comment,
+                                        });
                                         return;
                                     }
                                 }
                             }
                             comment.push(c);
                             self.advance();
+                            if c == '\n' {
+                                self.next_line();
+                            }
                         }
                         self.has_error = true;
                         self.tokens
                             .push(Token::Error(LexerError::UnterminatedComment(
-                                self.line, self.col, comment,
+                                self.line, self.col, self.col, comment,
                             )));
                     }
                 }
@@ -403,52 +781,84 @@ impl<'a> Lexer<'a> {
 
     fn handle_string_literal(&mut self) {
         let mut literal = String::with_capacity(128);
+        let line = self.line;
 
         if let Some(c) = self.current() {
             literal.push(c);
             self.advance();
 
+            let mut escaped = false;
             while let Some(c) = self.current() {
-                if c == '"' {
-                    let x = literal
-                        .chars()
-                        .last()
-                        .expect("Unable to fetch last character from memory.");
-                    literal.push(c);
-                    self.advance();
-                    if x != '\\' {
-                        break;
-                    }
+                literal.push(c);
+                self.advance();
+                if c == '\n' {
+                    self.next_line();
                 }
-                if self.eof() {
-                    self.has_error = true;
-                    self.tokens.push(Token::Error(LexerError::UnexpectedEOF(
-                        self.line,
-                        self.col - literal.len(),
-                        literal,
-                    )));
+
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    let col = self.col - literal.len();
+                    let token = match decode_escapes(&literal[1..literal.len() - 1]) {
+                        Ok(_) => Token::StringLiteral(line, col, literal),
+                        Err(escape) => {
+                            self.has_error = true;
+                            Token::Error(LexerError::InvalidEscape(line, col, self.col, escape))
+                        }
+                    };
+                    self.tokens.push(token);
                     return;
                 }
-                literal.push(c);
-                self.advance();
             }
 
-            if literal.chars().last().expect("Unable to fetch character.") != '"' {
-                self.has_error = true;
-                self.tokens
-                    .push(Token::Error(LexerError::UnterminatedStringLiteral(
-                        self.line,
-                        self.col - literal.len(),
-                        literal,
-                    )));
-                return;
+            self.has_error = true;
+            let col = self.col - literal.len();
+            // A `\` right before the end of the input escapes nothing, which
+            // is why the string is never closed.
+            let err = if escaped {
+                LexerError::InvalidEscape(line, col, self.col, String::from("\\"))
+            } else {
+                LexerError::UnterminatedStringLiteral(line, col, self.col, literal)
+            };
+            self.tokens.push(Token::Error(err));
+        }
+    }
+
+    /// A `'` followed by an identifier-start character that is not immediately
+    /// closed by another `'` starts a loop label (`'outer`) rather than a
+    /// character literal (`'a'`).
+    fn is_label_start(&self) -> bool {
+        let mut chars = self
+            .input
+            .get(self.col..)
+            .unwrap_or_default()
+            .chars()
+            .skip(1);
+        match (chars.next(), chars.next()) {
+            (Some(c), next) => (c.is_alphabetic() || c == '_') && next != Some('\''),
+            _ => false,
+        }
+    }
+
+    fn handle_label(&mut self) {
+        let mut label = String::with_capacity(8);
+
+        if let Some(c) = self.current() {
+            label.push(c);
+            self.advance();
+
+            while let Some(c) = self.current() {
+                if self.is_operator(c) || self.is_separator(c) || c.is_whitespace() || c == '\'' {
+                    break;
+                }
+                label.push(c);
+                self.advance();
             }
 
-            self.tokens.push(Token::StringLiteral(
-                self.line,
-                self.col - literal.len(),
-                literal,
-            ));
+            self.tokens
+                .push(Token::Label(self.line, self.col - label.len(), label));
         }
     }
 
@@ -459,48 +869,44 @@ impl<'a> Lexer<'a> {
             literal.push(c);
             self.advance();
 
+            let mut escaped = false;
             while let Some(c) = self.current() {
-                if c == '\'' {
-                    let x = literal
-                        .chars()
-                        .last()
-                        .expect("Unable to fetch last character from memory.");
-                    literal.push(c);
-                    self.advance();
-                    println!("X: {}", x);
-                    if x != '\\' {
-                        break;
-                    }
-                }
-                if self.eof() {
-                    self.has_error = true;
-                    self.tokens.push(Token::Error(LexerError::UnexpectedEOF(
-                        self.line,
-                        self.col - literal.len(),
-                        literal,
-                    )));
-                    return;
+                // A character literal never spans lines, so stop at the end of
+                // the line rather than swallowing the rest of the file.
+                if c == '\n' {
+                    break;
                 }
                 literal.push(c);
                 self.advance();
-            }
 
-            if literal.chars().last().expect("Unable to fetch character.") != '\'' {
-                self.has_error = true;
-                self.tokens
-                    .push(Token::Error(LexerError::UnterminatedCharacterLiteral(
-                        self.line,
-                        self.col - literal.len(),
-                        literal,
-                    )));
-                return;
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '\'' {
+                    let col = self.col - literal.len();
+                    let token = match decode_escapes(&literal[1..literal.len() - 1]) {
+                        Ok(_) => Token::CharLiteral(self.line, col, literal),
+                        Err(escape) => {
+                            self.has_error = true;
+                            Token::Error(LexerError::InvalidEscape(
+                                self.line, col, self.col, escape,
+                            ))
+                        }
+                    };
+                    self.tokens.push(token);
+                    return;
+                }
             }
 
-            self.tokens.push(Token::CharLiteral(
-                self.line,
-                self.col - literal.len(),
-                literal,
-            ));
+            self.has_error = true;
+            self.tokens
+                .push(Token::Error(LexerError::UnterminatedCharacterLiteral(
+                    self.line,
+                    self.col - literal.len(),
+                    self.col,
+                    literal,
+                )));
         }
     }
 }
@@ -508,9 +914,12 @@ impl<'a> Lexer<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::symbol::Symbol;
     use rand::distributions::{Distribution, Uniform};
-    use rand::Rng;
-    use std::time::Instant;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::{HashMap, HashSet};
+    use std::panic;
 
     fn generate_random_number() -> String {
         let mut rng = rand::thread_rng();
@@ -541,26 +950,17 @@ mod tests {
         let mut input = String::new();
         for string in KEYWORDS {
             input.push_str(string);
-            input.push_str(" ");
+            input.push(' ');
         }
         let mut lexer = Lexer::new(&input);
 
         let tokens = lexer.lex();
         assert_eq!(tokens.len(), KEYWORDS.len() + 1);
 
-        for tok in tokens.iter().take(KEYWORDS.len()) {
+        for (tok, keyword) in tokens.iter().zip(KEYWORDS) {
             match tok {
-                Token::Keyword(_, _, word) => {
-                    if !KEYWORDS
-                        .iter()
-                        .position(|&s| s == word)
-                        .map(|pos| pos)
-                        .is_some()
-                    {
-                        panic!("Expected a keyword, got {:?}", tok);
-                    }
-                }
-                _ => panic!("Expected a keyword, got {:?}", tok),
+                Token::Keyword(_, _, word) if word == keyword => {}
+                _ => panic!("Expected the keyword `{}`, got {:?}", keyword, tok),
             }
         }
 
@@ -572,45 +972,291 @@ mod tests {
         let mut input = String::new();
         for string in DATA_TYPES {
             input.push_str(string);
-            input.push_str(" ");
+            input.push(' ');
         }
         let mut lexer = Lexer::new(&input);
         let tokens = lexer.lex();
 
         assert_eq!(tokens.len(), DATA_TYPES.len() + 1); // Ensure correct number of tokens
-        for token in tokens.iter().take(DATA_TYPES.len()) {
+        for (token, data_type) in tokens.iter().zip(DATA_TYPES) {
             match token {
-                Token::DataType(_, _, _) => {}
-                _ => panic!("Expected a data type, got {:?}", token),
+                Token::DataType(_, _, name) if name == data_type => {}
+                _ => panic!("Expected the data type `{}`, got {:?}", data_type, token),
             }
         }
         assert_eq!(tokens[DATA_TYPES.len()], Token::Eof);
+        assert_eq!(MAX_DATA_TYPE_LEN, "usize".len());
     }
 
     #[test]
     fn number_method_test() {
         let mut lexer = Lexer::new("0xAE 0x7E 0xe7 0b01 0o100 23.000535 1.05e+27 -100 100");
         let tokens = lexer.lex();
-        for tok in tokens.iter() {
-            match tok {
-                Token::FloatLiteral(_, _, _)
-                | Token::IntLiteral(_, _, _)
-                | Token::Identifier(_, _, _) => {}
-                _ => {}
-            }
+        assert!(!lexer.has_error());
+        assert_eq!(
+            tokens,
+            vec![
+                Token::IntLiteral(1, 0, String::from("0xAE")),
+                Token::IntLiteral(1, 5, String::from("0x7E")),
+                Token::IntLiteral(1, 10, String::from("0xe7")),
+                Token::IntLiteral(1, 15, String::from("0b01")),
+                Token::IntLiteral(1, 20, String::from("0o100")),
+                Token::FloatLiteral(1, 26, String::from("23.000535")),
+                Token::FloatLiteral(1, 36, String::from("1.05e+27")),
+                // The sign is an operator, so `-100` is a negation.
+                Token::Operator(1, 45, String::from("-")),
+                Token::IntLiteral(1, 46, String::from("100")),
+                Token::IntLiteral(1, 50, String::from("100")),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_integer_literals() {
+        let tokens = Lexer::new("08 0b102 0o78 0 10").lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Error(LexerError::InvalidDecimal(1, 0, 2, String::from("08"))),
+                Token::Error(LexerError::InvalidBinary(1, 3, 8, String::from("0b102"))),
+                Token::Error(LexerError::InvalidOctal(1, 9, 13, String::from("0o78"))),
+                Token::IntLiteral(1, 14, String::from("0")),
+                Token::IntLiteral(1, 16, String::from("10")),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_input_ending_in_multibyte_characters() {
+        let tokens = Lexer::new("x = 數據").lex();
+        assert_eq!(tokens[2], Token::Identifier(1, 4, Symbol::from("數據")));
+        assert_eq!(tokens[3], Token::Eof);
+
+        let mut lexer = Lexer::new("x = \"é");
+        let tokens = lexer.lex();
+        assert!(lexer.has_error());
+        assert_eq!(tokens.last(), Some(&Token::Eof));
+
+        let mut lexer = Lexer::new("/é");
+        assert_eq!(lexer.current(), Some('/'));
+        assert_eq!(lexer.peek(), Some('é'));
+        lexer.advance();
+        lexer.advance();
+        assert_eq!(lexer.col, 3);
+        lexer.advance();
+        assert_eq!(lexer.col, 3);
+        assert_eq!((lexer.current(), lexer.peek()), (None, None));
+        assert!(lexer.eof());
+    }
+
+    #[test]
+    fn test_identifier_sets() {
+        let tokens = Lexer::new("數據 _x1 é").lex();
+        assert_eq!(tokens[0], Token::Identifier(1, 0, Symbol::from("數據")));
+        assert_eq!(tokens[1], Token::Identifier(1, 7, Symbol::from("_x1")));
+        assert_eq!(tokens[2], Token::Identifier(1, 11, Symbol::from("é")));
+
+        let mut lexer = Lexer::new("smile😀 = 1;");
+        let tokens = lexer.lex();
+        assert!(lexer.has_error());
+        assert_eq!(
+            tokens[0],
+            Token::Error(LexerError::InvalidIdentifierCharacter(
+                1,
+                5,
+                9,
+                String::from("😀")
+            ))
+        );
+
+        let mut lexer = Lexer::new("snake_case2 = 數據;").identifier_set(IdentifierSet::Ascii);
+        let tokens = lexer.lex();
+        assert_eq!(
+            tokens[0],
+            Token::Identifier(1, 0, Symbol::from("snake_case2"))
+        );
+        assert_eq!(
+            tokens[2],
+            Token::Error(LexerError::InvalidIdentifierCharacter(
+                1,
+                14,
+                17,
+                String::from("數")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_mixed_indentation() {
+        let src = "fn main() {\n\t  i32 x = 1;\n\ti32 y = 2;\n    ret;\n \t\n}";
+        let mut lexer = Lexer::new(src).warn_mixed_indentation();
+        lexer.lex();
+        assert_eq!(lexer.warnings(), [SemanticWarning::MixedIndentation(2, 12)]);
+
+        let mut lexer = Lexer::new(src);
+        lexer.lex();
+        assert!(lexer.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut lexer = Lexer::new("'outer loop { break 'outer; }");
+        let tokens = lexer.lex();
+        assert!(!lexer.has_error());
+        assert_eq!(tokens[0], Token::Label(1, 0, String::from("'outer")));
+        assert_eq!(tokens[4], Token::Label(1, 20, String::from("'outer")));
+    }
+
+    #[test]
+    fn test_char_literals_and_labels() {
+        let tokens = Lexer::new("'a'").lex();
+        assert_eq!(tokens[0], Token::CharLiteral(1, 0, String::from("'a'")));
+
+        let tokens = Lexer::new("'abc").lex();
+        assert_eq!(tokens[0], Token::Label(1, 0, String::from("'abc")));
+
+        let tokens = Lexer::new("'a' + 1").lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::CharLiteral(1, 0, String::from("'a'")),
+                Token::Operator(1, 4, String::from("+")),
+                Token::IntLiteral(1, 6, String::from("1")),
+                Token::Eof,
+            ]
+        );
+
+        let tokens = Lexer::new(r"'\'' '\\' '數'").lex();
+        assert_eq!(tokens[0], Token::CharLiteral(1, 0, String::from(r"'\''")));
+        assert_eq!(tokens[1], Token::CharLiteral(1, 5, String::from(r"'\\'")));
+        assert_eq!(tokens[2], Token::CharLiteral(1, 10, String::from("'數'")));
+    }
+
+    #[test]
+    fn test_unterminated_char_literal() {
+        let mut lexer = Lexer::new("'+\nx");
+        let tokens = lexer.lex();
+        assert!(lexer.has_error());
+        assert_eq!(
+            tokens[0],
+            Token::Error(LexerError::UnterminatedCharacterLiteral(
+                1,
+                0,
+                2,
+                String::from("'+")
+            ))
+        );
+        assert_eq!(tokens[1], Token::Identifier(2, 3, Symbol::from("x")));
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let typed = Lexer::new("\"cafe\u{301}\"").lex();
+        let escaped = Lexer::new(r#""caf\u{65}\u{301}""#).lex();
+        assert_ne!(typed[0].get_lexeme(), escaped[0].get_lexeme());
+
+        let decoded = escaped[0].decoded_value().unwrap();
+        assert_eq!(typed[0].decoded_value().unwrap(), decoded);
+        assert_eq!(decoded, "cafe\u{301}");
+        assert_eq!(decoded.len(), 6);
+
+        let tokens = Lexer::new(r#""\x41\t\"\\" '\n'"#).lex();
+        assert_eq!(tokens[0].decoded_value().unwrap(), "A\t\"\\");
+        assert_eq!(tokens[1].decoded_value().unwrap(), "\n");
+        assert_eq!(Token::Eof.decoded_value(), None);
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        for (src, escape) in [
+            (r#""\q""#, r"\q"),
+            (r#""\xFF""#, r"\xFF"),
+            (r#""\u{110000}""#, r"\u{110000}"),
+            (r#""\u41""#, r"\u"),
+            (r#""abc\"#, r"\"),
+        ] {
+            let mut lexer = Lexer::new(src);
+            let tokens = lexer.lex();
+            assert!(lexer.has_error(), "{} should not lex", src);
+            assert_eq!(
+                tokens[0],
+                Token::Error(LexerError::InvalidEscape(
+                    1,
+                    0,
+                    src.len(),
+                    String::from(escape)
+                ))
+            );
         }
-        assert_eq!(tokens.len(), 11);
+    }
 
-        for tok in tokens.iter().take(9) {
-            match tok {
-                Token::IntLiteral(_, _, _)
-                | Token::FloatLiteral(_, _, _)
-                | Token::Operator(_, _, _) => {}
-                _ => panic!("Expected an integer or float, got {:?}", tok),
-            }
+    #[test]
+    fn test_spans() {
+        let inputs = [
+            "0x1F 0o17 0b101 42 3.14 6.02e23 0",
+            r#""tab\t quote\" hex\x41 smile\u{1F600}" 'a' '\n' 'outer"#,
+            "a<=b->c == d && !e || f >> 2",
+            "數據 = 無法訪問(x, \"數據\");",
+            "/* multi\nline */ fn main() {\n  // comment\n  ret \"two\nlines\";\n}",
+        ];
+        for input in inputs {
+            let tokens = Lexer::validate_spans(input).unwrap();
+            assert!(
+                tokens.iter().all(|token| !matches!(token, Token::Error(_))),
+                "unexpected errors in {:?}",
+                tokens
+            );
         }
 
-        assert_eq!(tokens[10], Token::Eof);
+        let mismatched = [Token::Identifier(1, 1, Symbol::from("ab"))];
+        assert!(span_mismatch("ab", &mismatched).is_some());
+    }
+
+    #[test]
+    fn test_multi_char_operators() {
+        let tokens = Lexer::new("a<=b->c = !d").lex();
+        let operators: Vec<&str> = tokens
+            .iter()
+            .filter(|tok| matches!(tok, Token::Operator(_, _, _)))
+            .map(|tok| tok.get_lexeme())
+            .collect();
+        assert_eq!(operators, vec!["<=", "->", "=", "!"]);
+    }
+
+    #[test]
+    fn test_range_operators() {
+        assert_eq!(
+            Lexer::new("0..10").lex(),
+            vec![
+                Token::IntLiteral(1, 0, String::from("0")),
+                Token::Operator(1, 1, String::from("..")),
+                Token::IntLiteral(1, 3, String::from("10")),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            Lexer::new("0..=10 1.5..2").lex(),
+            vec![
+                Token::IntLiteral(1, 0, String::from("0")),
+                Token::Operator(1, 1, String::from("..=")),
+                Token::IntLiteral(1, 4, String::from("10")),
+                Token::FloatLiteral(1, 7, String::from("1.5")),
+                Token::Operator(1, 10, String::from("..")),
+                Token::IntLiteral(1, 12, String::from("2")),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            Lexer::new("a.b").lex(),
+            vec![
+                Token::Identifier(1, 0, Symbol::from("a")),
+                Token::Operator(1, 1, String::from(".")),
+                Token::Identifier(1, 2, Symbol::from("b")),
+                Token::Eof,
+            ]
+        );
     }
 
     #[test]
@@ -621,36 +1267,21 @@ mod tests {
             large_input.push(' ');
         }
 
-        let mut lexer = Lexer::new(&large_input);
-
-        let start_time = Instant::now();
-        let tokens = lexer.lex();
-        let duration = start_time.elapsed();
+        let (tokens, result) = bench(&large_input);
 
-        for i in 0..tokens.len() - 1 {
-            match tokens[i] {
+        for tok in tokens.iter().take(tokens.len() - 1) {
+            match tok {
                 Token::FloatLiteral(_, _, _) | Token::IntLiteral(_, _, _) => {}
-                _ => panic!("Expected a float or integer literal, found {}", &tokens[i]),
+                _ => panic!("Expected a float or integer literal, found {}", tok),
             }
         }
 
         assert_eq!(tokens.last().unwrap(), &Token::Eof);
 
-        let input_size_bytes = large_input.len() as f64;
-        let input_size_mb = input_size_bytes / (1024.0 * 1024.0);
-
-        println!(
-            "Lexer took {} ms to lex random numbers.",
-            duration.as_millis()
-        );
         println!(
-            "Avg. characters/ms: {}",
-            input_size_bytes / duration.as_millis() as f64
+            "benchmark_number {}",
+            serde_json::to_string(&result).unwrap()
         );
-        println!("Input size: {} MB", input_size_mb);
-
-        let duration_seconds = duration.as_secs_f64();
-        println!("Throughput: {} MB/s", input_size_mb / duration_seconds); // MB/s
     }
 
     #[test]
@@ -661,50 +1292,341 @@ mod tests {
             large_input.push(' ');
         }
 
-        let mut lexer = Lexer::new(&large_input);
+        let (tokens, result) = bench(&large_input);
 
-        let start_time = Instant::now();
-        let tokens = lexer.lex();
-        let duration = start_time.elapsed();
-
-        for i in 0..tokens.len() - 1 {
-            match tokens.get(i).expect("Expected a token.") {
-                Token::Identifier(_, _, id) => {
-                    if KEYWORDS
-                        .iter()
-                        .position(|&s| s == id)
-                        .map(|pos| pos)
-                        .is_some()
-                        || DATA_TYPES
-                            .iter()
-                            .position(|&s| s == id)
-                            .map(|pos| pos)
-                            .is_some()
-                    {
-                        panic!("Expected a identifier, found keyword or data type.")
-                    }
-                    assert!(!id.is_empty());
+        for tok in tokens.iter().take(tokens.len() - 1) {
+            if let Token::Identifier(_, _, id) = tok {
+                if KEYWORDS.contains(&id.as_str()) || DATA_TYPES.contains(&id.as_str()) {
+                    panic!("Expected a identifier, found keyword or data type.")
                 }
-                _ => {}
+                assert!(!id.as_str().is_empty());
             }
         }
 
         assert_eq!(tokens.last().unwrap(), &Token::Eof); // Ensure the last token is EOF
 
-        let input_size_bytes = large_input.len() as f64;
-        let input_size_mb = input_size_bytes / (1024.0 * 1024.0); // Convert bytes to MB
-
         println!(
-            "Lexer took {} ms to lex random identifiers.",
-            duration.as_millis()
+            "benchmark_identifier {}",
+            serde_json::to_string(&result).unwrap()
         );
+    }
+
+    #[test]
+    fn benchmark_repeated_identifier() {
+        let names: Vec<String> = (0..100).map(|i| format!("identifier_{}", i)).collect();
+        let mut rng = rand::thread_rng();
+        let pick = Uniform::from(0..names.len());
+        let mut used = HashSet::new();
+        let mut large_input = String::new();
+        for _ in 0..200_000 {
+            let name = &names[pick.sample(&mut rng)];
+            used.insert(name.as_str());
+            large_input.push_str(name);
+            large_input.push(' ');
+        }
+
+        let (tokens, result) = bench(&large_input);
+
+        // Every occurrence of a name shares the string of the first one,
+        // where owned strings took an allocation per occurrence.
+        let mut first = HashMap::new();
+        for tok in tokens.iter().take(tokens.len() - 1) {
+            let Token::Identifier(_, _, symbol) = tok else {
+                panic!("Expected an identifier, found {}", tok);
+            };
+            let name = symbol.as_str();
+            assert_eq!(*first.entry(name).or_insert(name.as_ptr()), name.as_ptr());
+        }
+        assert_eq!(tokens.len() - 1, 200_000);
+        assert_eq!(first.len(), used.len());
+
+        let mut lexer = Lexer::new(&large_input);
+        lexer.lex();
+        assert_eq!(lexer.symbols().len(), used.len());
+
         println!(
-            "Avg. characters/ms: {}",
-            input_size_bytes / duration.as_millis() as f64
+            "benchmark_repeated_identifier {} identifiers: {}, symbols: {}",
+            serde_json::to_string(&result).unwrap(),
+            tokens.len() - 1,
+            lexer.symbols().len()
         );
-        println!("Input size: {} MB", input_size_mb);
+    }
+
+    #[test]
+    fn test_bench() {
+        let input = "fn main() { i32 x = 0x1F + 2; ret x; }\n".repeat(1000);
+        let (tokens, result) = bench(&input);
+        assert_eq!(tokens, Lexer::new(&input).lex());
+        assert_eq!(result.bytes, input.len());
+        assert!(result.throughput_mb_s > 0.0, "{:?}", result);
+
+        let baseline = BenchResult {
+            throughput_mb_s: 100.0,
+            ..result
+        };
+        let slower = |throughput_mb_s| BenchResult {
+            throughput_mb_s,
+            ..result
+        };
+        assert!(!slower(95.0).regressed_from(&baseline, 0.1));
+        assert!(slower(85.0).regressed_from(&baseline, 0.1));
+    }
+
+    #[test]
+    fn test_byte_order_mark() {
+        let src = "fn main(){}";
+        let tokens = Lexer::new(src).lex();
+        assert_eq!(Lexer::new(&format!("\u{FEFF}{}", src)).lex(), tokens);
+        assert_eq!(tokens[1], Token::Identifier(1, 3, Symbol::from("main")));
+        assert!(Lexer::validate_spans(&format!("\u{FEFF}{}", src)).is_ok());
+
+        let tokens = Lexer::new("\u{FEFF}#!/usr/bin/env zuroxc\nfn main(){}").lex();
+        assert_eq!(tokens[0], Token::Keyword(2, 22, String::from("fn")));
+
+        // Only a leading mark is ignored.
+        let mut lexer = Lexer::new("fn \u{FEFF}main(){}");
+        let tokens = lexer.lex();
+        assert!(lexer.has_error());
+        assert!(matches!(
+            tokens[1],
+            Token::Error(LexerError::InvalidIdentifierCharacter(1, 3, 6, _))
+        ));
+    }
 
-        let duration_seconds = duration.as_secs_f64();
-        println!("Throughput: {} MB/s", input_size_mb / duration_seconds); // MB/s
+    #[test]
+    fn test_shebang() {
+        let src = "#!/usr/bin/env zuroxc\nfn main() {}";
+        let tokens = Lexer::validate_spans(src).unwrap();
+        assert_eq!(tokens[0], Token::Keyword(2, 22, String::from("fn")));
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(Lexer::new("#!zuroxc").lex(), vec![Token::Eof]);
+
+        // `#!` anywhere but at the very start is not skipped.
+        let mut lexer = Lexer::new("\n#!/usr/bin/env zuroxc");
+        lexer.lex();
+        assert!(lexer.has_error());
+    }
+
+    #[test]
+    fn test_source_map() {
+        let src = "\u{feff}fn main() {\n    str s = \"a\nb\";\n\n}\n";
+        let mut lexer = Lexer::new(src);
+        let tokens = lexer.lex();
+        assert_eq!(lexer.line_starts(), [0, 12, 27, 31, 32, 34]);
+        let map = lexer.source_map();
+        let expected = SourceMap::new(strip_bom(src));
+        assert_eq!(map.src(), expected.src());
+        for offset in 0..=map.src().len() {
+            assert_eq!(map.line_col(offset), expected.line_col(offset));
+        }
+
+        for token in &tokens[..tokens.len() - 1] {
+            assert_eq!(
+                map.line_number_of(token.get_col()),
+                token.get_line(),
+                "{:?}",
+                token
+            );
+        }
+        assert_eq!(map.line_col(20), (2, 9));
+        assert_eq!(map.line_col(32), (5, 1));
+    }
+
+    #[test]
+    fn test_source_text() {
+        let src = "\u{feff}str s = \"a\\tb\\u{41}\"; u16 n = 0xFF;";
+        let mut lexer = Lexer::new(src);
+        let tokens = lexer.lex();
+
+        assert_eq!(lexer.source_text(src, &tokens[3]), "\"a\\tb\\u{41}\"");
+        assert_eq!(tokens[3].decoded_value().unwrap(), "a\tbA");
+        assert_eq!(lexer.source_text(src, &tokens[8]), "0xFF");
+        assert_eq!(lexer.source_text(src, &tokens[0]), "str");
+        assert_eq!(lexer.source_text(src, tokens.last().unwrap()), "");
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut lexer = Lexer::new("// one\nfn /* two\nlines */ main() {} // three");
+        let tokens = lexer.lex();
+        assert_eq!(
+            lexer.comments(),
+            [
+                Comment {
+                    line: 1,
+                    col: 1,
+                    is_trailing: false,
+                    text: String::from("// one"),
+                },
+                Comment {
+                    line: 2,
+                    col: 4,
+                    is_trailing: true,
+                    text: String::from("/* two\nlines */"),
+                },
+                Comment {
+                    line: 3,
+                    col: 20,
+                    is_trailing: true,
+                    text: String::from("// three"),
+                },
+            ]
+        );
+        assert_eq!(tokens[1], Token::Identifier(3, 26, Symbol::from("main")));
+    }
+
+    /// The number of random inputs each property is checked on.
+    const PROPERTY_CASES: usize = 500;
+
+    /// Characters that mean something to the lexer, or that it has gotten
+    /// wrong before, which random characters would seldom hit.
+    const INTERESTING_CHARS: &[char] = &[
+        'a', 'Z', '_', '0', '9', 'x', 'b', 'o', 'e', '.', '+', '-', '*', '/', '\\', '"', '\'', '=',
+        '<', '>', '!', '&', '|', '(', ')', '{', '}', '[', ']', ';', ',', '@', '#', ' ', '\t', '\n',
+        '\r', '\u{FEFF}', 'é', '數', '\u{301}', '😀',
+    ];
+
+    /// Pieces of valid source that stay the same tokens when separated by
+    /// whitespace.
+    const FRAGMENTS: &[&str] = &[
+        "fn",
+        "x",
+        "_tmp",
+        "數據",
+        "u8",
+        "loop",
+        "ret",
+        "true",
+        "42",
+        "0x1F",
+        "0b101",
+        "0o17",
+        "7u8",
+        "1.5",
+        "6.02e23",
+        "\"str\"",
+        r#""a\tb\u{301}""#,
+        "'c'",
+        r"'\n'",
+        "'outer",
+        "+",
+        "-",
+        "==",
+        "<=",
+        "->",
+        "..",
+        "..=",
+        "&&",
+        "(",
+        ")",
+        "{",
+        "}",
+        "[",
+        "]",
+        ";",
+        ",",
+        ".",
+        "@",
+        "// comment\n",
+        "/* block */",
+    ];
+
+    /// Any text: a mix of interesting characters and arbitrary ones.
+    fn arbitrary_text(rng: &mut StdRng) -> String {
+        let len = rng.gen_range(0..64);
+        (0..len)
+            .map(|_| match rng.gen_bool(0.5) {
+                true => INTERESTING_CHARS[rng.gen_range(0..INTERESTING_CHARS.len())],
+                false => rng.gen::<char>(),
+            })
+            .collect()
+    }
+
+    /// Valid fragments separated by whitespace.
+    fn token_soup(rng: &mut StdRng) -> String {
+        let len = rng.gen_range(0..24);
+        let mut src = String::new();
+        for _ in 0..len {
+            src.push_str(FRAGMENTS[rng.gen_range(0..FRAGMENTS.len())]);
+            src.push_str([" ", "  ", "\t", "\n"][rng.gen_range(0..4)]);
+        }
+        src
+    }
+
+    /// Checks `property` on [`PROPERTY_CASES`] inputs from `generate`. The
+    /// seed of the inputs is random but reported on failure, so that a failing
+    /// input can be made again.
+    fn check_property(
+        generate: impl Fn(&mut StdRng) -> String,
+        property: impl Fn(&str) -> Result<(), String>,
+    ) {
+        let seed = rand::thread_rng().gen();
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..PROPERTY_CASES {
+            let input = generate(&mut rng);
+            if let Err(err) = property(&input) {
+                panic!("{} for {:?} (seed {})", err, input, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lexing_never_panics() {
+        check_property(arbitrary_text, |input| {
+            let tokens = panic::catch_unwind(|| Lexer::new(input).lex())
+                .map_err(|_| String::from("Lexing panicked"))?;
+            match tokens.last() {
+                Some(Token::Eof) => Ok(()),
+                last => Err(format!("The tokens end with {:?}", last)),
+            }
+        });
+    }
+
+    #[test]
+    fn test_spans_increase() {
+        let property = |input: &str| {
+            let tokens = Lexer::new(input).lex();
+            let input = strip_bom(input);
+            if let Some(mismatch) = span_mismatch(input, &tokens) {
+                return Err(mismatch);
+            }
+            let mut end = 0;
+            for token in &tokens {
+                let span = match token {
+                    Token::Error(err) => err.span(),
+                    token => match token.span() {
+                        Some(span) => span,
+                        None => continue,
+                    },
+                };
+                if span.start < end || span.end < span.start || span.end > input.len() {
+                    return Err(format!(
+                        "{:?} spans {:?}, after a token ending at {}",
+                        token, span, end
+                    ));
+                }
+                end = span.end;
+            }
+            Ok(())
+        };
+        check_property(arbitrary_text, property);
+        check_property(token_soup, property);
+    }
+
+    #[test]
+    fn test_relexing_lexemes() {
+        check_property(token_soup, |input| {
+            let mut lexer = Lexer::new(input);
+            let tokens = lexer.lex();
+            if lexer.has_error() {
+                return Err(format!("Unexpected errors in {:?}", tokens));
+            }
+            let lexemes: Vec<&str> = tokens.iter().map(Token::get_lexeme).collect();
+            let relexed = Lexer::new(&lexemes.join(" ")).lex();
+            match token::tokens_equivalent(&tokens, &relexed) {
+                true => Ok(()),
+                false => Err(format!("{:?} relexed as {:?}", tokens, relexed)),
+            }
+        });
     }
 }
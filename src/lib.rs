@@ -2,8 +2,334 @@
 pub mod ast;
 pub mod cache;
 pub mod codegen;
+pub mod diagnostic;
+pub mod fold;
 pub mod lexer;
+pub mod node_id;
+pub mod outline;
 pub mod parser;
+pub mod pretty;
+pub mod program;
 pub mod semantic;
+pub mod source_map;
+pub mod stats;
+pub mod symbol;
+pub mod target;
 pub mod token;
 pub mod utils;
+pub mod visit;
+
+pub use semantic::analyze;
+
+use diagnostic::Diagnostic;
+use serde::Serialize;
+use source_map::SourceMap;
+use std::path::Path;
+use token::Token;
+use utils::{CompileError, LexerError};
+
+/// The outcome of compiling a source string: the syntax tree and everything
+/// reported along the way.
+#[derive(Debug)]
+pub struct Compilation {
+    pub ast: Box<ast::AST>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The byte offset at which each line of the source starts, as found by
+    /// the lexer, to show the diagnostics against with
+    /// [`SourceMap::from_line_starts`].
+    pub line_starts: Vec<usize>,
+}
+
+impl Compilation {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Optional checks that `compile_str_with` runs on top of the required ones.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Options {
+    /// Warn about lines whose indentation mixes tabs and spaces.
+    pub warn_mixed_indentation: bool,
+    /// The characters identifiers may be made of.
+    pub identifier_set: lexer::IdentifierSet,
+    /// Warn about names that do not follow these case styles.
+    pub naming: Option<semantic::NamingRules>,
+    /// The target to compile for, which is the host by default. It decides
+    /// the sizes of types, such as how large an array may be.
+    pub target: target::TargetTriple,
+}
+
+/// Lexes, parses and analyzes `src`. Each phase only runs if the previous
+/// one reported no errors, so the AST is empty when lexing fails.
+pub fn compile_str(src: &str) -> Compilation {
+    compile_str_with(src, Options::default())
+}
+
+/// Compiles `src` like [`compile_str`], along with the checks enabled in
+/// `options`.
+pub fn compile_str_with(src: &str, options: Options) -> Compilation {
+    compile_str_parsing(src, options, |tokens| {
+        parser::Parser::new(tokens).parse_with_diagnostics()
+    })
+}
+
+/// Compiles `src` like [`compile_str_with`], only parsing the declarations
+/// that are not in `declarations`, which is then updated for the next edit of
+/// the same source.
+pub fn compile_str_cached(
+    src: &str,
+    options: Options,
+    declarations: &mut cache::DeclarationCache,
+) -> Compilation {
+    compile_str_parsing(src, options, |tokens| declarations.parse(&tokens))
+}
+
+fn compile_str_parsing(
+    src: &str,
+    options: Options,
+    parse: impl FnOnce(Vec<Token>) -> (Box<ast::AST>, Vec<utils::ParserError>),
+) -> Compilation {
+    let mut lexer = lexer::Lexer::new(src).identifier_set(options.identifier_set);
+    if options.warn_mixed_indentation {
+        lexer = lexer.warn_mixed_indentation();
+    }
+    let tokens = lexer.lex();
+    let line_starts = lexer.line_starts().to_vec();
+    let warnings: Vec<Diagnostic> = lexer
+        .warnings()
+        .iter()
+        .cloned()
+        .map(Diagnostic::from)
+        .collect();
+    if lexer.has_error() {
+        let diagnostics = tokens
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Error(err) => Some(Diagnostic::from(err)),
+                _ => None,
+            })
+            .chain(warnings)
+            .collect();
+        return Compilation {
+            ast: Box::new(ast::AST {
+                declarations: Vec::new(),
+            }),
+            diagnostics,
+            line_starts,
+        };
+    }
+
+    let (ast, errors) = parse(tokens);
+    if !errors.is_empty() {
+        let diagnostics = errors
+            .into_iter()
+            .map(Diagnostic::from)
+            .chain(warnings)
+            .collect();
+        return Compilation {
+            ast,
+            diagnostics,
+            line_starts,
+        };
+    }
+
+    let mut diagnostics = analyze(&ast);
+    let codegen = codegen::Codegen::for_target(options.target);
+    diagnostics.extend(
+        semantic::check_array_fit(&ast, &codegen)
+            .into_iter()
+            .map(Diagnostic::from),
+    );
+    if let Some(rules) = &options.naming {
+        diagnostics.extend(
+            semantic::check_names(&ast, rules)
+                .into_iter()
+                .map(Diagnostic::from),
+        );
+    }
+    diagnostics.extend(warnings);
+    Compilation {
+        ast,
+        diagnostics,
+        line_starts,
+    }
+}
+
+/// Lexes `src`, separating the errors from the tokens. The tokens still end
+/// with [`Token::Eof`].
+pub fn lex_str(src: &str) -> (Vec<Token>, Vec<LexerError>) {
+    let mut errors = Vec::new();
+    let tokens = lexer::Lexer::new(src)
+        .lex()
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Error(err) => {
+                errors.push(err);
+                None
+            }
+            token => Some(token),
+        })
+        .collect();
+    (tokens, errors)
+}
+
+/// Lexes and parses `src` without analyzing it, returning the syntax tree
+/// along with a [`SourceMap`] of `src` without its byte order mark, to convert
+/// the positions it holds, and the diagnostics of both phases. Like
+/// [`compile_str`], the tree is empty when lexing fails.
+pub fn parse_program_str(src: &str) -> (Box<ast::AST>, SourceMap<'_>, Vec<Diagnostic>) {
+    let mut lexer = lexer::Lexer::new(src);
+    let tokens = lexer.lex();
+    let source = lexer.source_map();
+    if lexer.has_error() {
+        let ast = Box::new(ast::AST {
+            declarations: Vec::new(),
+        });
+        let diagnostics = tokens
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Error(err) => Some(Diagnostic::from(err)),
+                _ => None,
+            })
+            .collect();
+        return (ast, source, diagnostics);
+    }
+
+    let (ast, errors) = parser::Parser::new(tokens).parse_with_diagnostics();
+    (
+        ast,
+        source,
+        errors.into_iter().map(Diagnostic::from).collect(),
+    )
+}
+
+/// Lexes, parses and analyzes `src` like [`compile_str`], returning the
+/// syntax tree only if no phase reported an error.
+pub fn compile(src: &str) -> Result<Box<ast::AST>, Vec<CompileError>> {
+    let (tokens, errors) = lex_str(src);
+    if !errors.is_empty() {
+        return Err(errors.into_iter().map(CompileError::from).collect());
+    }
+
+    let (ast, errors) = parser::Parser::new(tokens).parse_with_diagnostics();
+    if !errors.is_empty() {
+        return Err(errors.into_iter().map(CompileError::from).collect());
+    }
+
+    let errors = semantic::check(&ast);
+    if !errors.is_empty() {
+        return Err(errors.into_iter().map(CompileError::from).collect());
+    }
+    Ok(ast)
+}
+
+/// Reads and compiles the file at `path`. See [`compile`].
+pub fn compile_file(path: impl AsRef<Path>) -> Result<Box<ast::AST>, Vec<CompileError>> {
+    let src = std::fs::read_to_string(path).map_err(|err| vec![CompileError::from(err)])?;
+    compile(&src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_input() {
+        for src in ["", "   \n\t", "\r\n\n  "] {
+            assert_eq!(lexer::Lexer::new(src).lex(), vec![Token::Eof]);
+
+            let compilation = compile_str(src);
+            assert!(compilation.ast.declarations.is_empty());
+            assert!(compilation.diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parser_without_tokens() {
+        let mut parser = parser::Parser::new(Vec::new());
+        assert!(parser.parse().declarations.is_empty());
+        assert!(!parser.has_error());
+    }
+
+    #[test]
+    fn test_lex_str() {
+        let (tokens, errors) = lex_str("i32 x = 0x1F;");
+        assert!(errors.is_empty());
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens.last(), Some(&Token::Eof));
+
+        let (tokens, errors) = lex_str("i32 x = 0xG;");
+        assert_eq!(
+            errors,
+            vec![LexerError::InvalidHexaDecimal(1, 8, 10, String::from("0x"))]
+        );
+        assert!(!tokens.iter().any(|token| matches!(token, Token::Error(_))));
+    }
+
+    #[test]
+    fn test_compile() {
+        assert_eq!(compile("fn main() { }").unwrap().declarations.len(), 1);
+
+        let errors = compile("fn main() { y = 1; }").unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [CompileError::Semantic(
+                utils::SemanticError::UndefinedVariable(1, 12, _)
+            )]
+        ));
+
+        let errors = compile_file("does/not/exist.zx").unwrap_err();
+        assert!(matches!(errors.as_slice(), [CompileError::Io(_)]));
+    }
+
+    #[test]
+    fn test_compile_str_stops_at_first_failing_phase() {
+        let compilation = compile_str("fn main() { i32 x = 0b2; y = 1; }");
+        assert!(compilation.ast.declarations.is_empty());
+        assert_eq!(
+            compilation.diagnostics,
+            vec![Diagnostic::error(1, 20, "Invalid binary number `0b2`.")
+                .with_note("`2` is not a binary digit. The binary digits are 0 and 1.")
+                .with_span(token::Span { start: 20, end: 23 })]
+        );
+
+        let compilation = compile_str("fn main() { y = 1; }");
+        assert_eq!(compilation.ast.declarations.len(), 1);
+        assert_eq!(
+            compilation.diagnostics,
+            vec![Diagnostic::error(1, 12, "Undefined variable `y`.")
+                .with_span(token::Span { start: 12, end: 13 })]
+        );
+    }
+
+    #[test]
+    fn test_parse_program_str() {
+        let src = "fn main() {\n    i32 count = 1;\n}\n";
+        let (ast, source, diagnostics) = parse_program_str(src);
+        assert!(diagnostics.is_empty());
+        let ast::Declaration::Function(main) = ast.declarations[0].as_ref() else {
+            panic!("Expected a function, found {:?}", ast.declarations[0]);
+        };
+        let ast::Statement::Var(declaration) = &main.block.statements[0] else {
+            panic!("Expected a variable, found {:?}", main.block.statements[0]);
+        };
+        let token = declaration.id.id.as_ref().unwrap();
+        let span = token.span().unwrap();
+        assert_eq!(source.line_number_of(span.start), token.get_line());
+        assert_eq!(source.line_of(span.start), "    i32 count = 1;");
+        assert_eq!(&source.src()[span.start..span.end], "count");
+
+        let (ast, _, diagnostics) = parse_program_str("fn main() { x = ; }");
+        assert_eq!(ast.declarations.len(), 1);
+        assert!(diagnostics.iter().all(Diagnostic::is_error));
+        assert!(!diagnostics.is_empty());
+
+        let (_, source, _) = parse_program_str("\u{feff}fn main() {}");
+        assert_eq!(&source.src()[3..7], "main");
+
+        let (ast, _, diagnostics) = parse_program_str("i32 x = 0b2;");
+        assert!(ast.declarations.is_empty());
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
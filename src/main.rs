@@ -1,24 +1,37 @@
+use clap::builder::RangedU64ValueParser;
 use clap::Parser;
 use clap_derive::{Parser, Subcommand, ValueEnum};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-
-mod ast;
-mod cache;
-mod lexer;
-mod parser;
-mod token;
-mod utils;
+use std::time::SystemTime;
+use zuroxc::diagnostic::{Diagnostic, LintLevels, LINTS};
+use zuroxc::lexer::{self, IdentifierSet};
+use zuroxc::source_map::SourceMap;
+use zuroxc::token::Span;
+use zuroxc::utils::SemanticWarning;
+use zuroxc::{
+    ast, cache, compile_str_cached, compile_str_with, diagnostic, fold, pretty,
+    semantic::NamingRules, stats::Stats, target, target::TargetTriple, Compilation, Options,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "zuroxc")]
 #[command(version = "0.1.0")]
 #[command(propagate_version = true)]
+#[command(subcommand_negates_reqs = true)]
 #[command(
     about = "Zurox Programming Language Compiler",
     long_about = "zuroxc is the compiler used to compile the new, simple and fast language Zuroxc. Check the documentation for more information."
 )]
 struct Cli {
+    /// Commands other than compiling do not take the compilation options below.
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// The files to operate on
     #[arg(short, long, value_name = "{FILES}")]
     files: Vec<PathBuf>,
@@ -27,20 +40,114 @@ struct Cli {
     #[arg(short, long, value_name = "[OUTPUT_FILE]")]
     output: Option<PathBuf>,
 
+    /// The directory to which outputs should be saved, mirroring the directory structure of the input files.
+    #[arg(long, value_name = "[OUT_DIR]", conflicts_with = "output")]
+    out_dir: Option<PathBuf>,
+
     /// The path to store the cache in.
     #[arg(short, long)]
     cache_dir: Option<PathBuf>,
 
+    /// Compile without reading or writing the cache, and without creating a cache directory.
+    #[arg(long)]
+    no_cache: bool,
+
     /// The level of optimization that should be performed.
-    #[arg(short, long, value_enum)]
+    #[arg(short = 'O', long, value_enum, default_value_t = Optimization::O2)]
     optimization: Optimization,
 
     /// Target CPU microarchitecture
+    #[arg(long, required_unless_present = "print_config")]
+    target_cpu: Option<String>,
+
+    /// The target triple to compile for, such as x86_64-unknown-linux-gnu. Defaults to the host.
+    #[arg(long, value_name = "TRIPLE", value_parser = TargetTriple::parse)]
+    target: Option<TargetTriple>,
+
+    /// Print the CPUs accepted by --target-cpu.
+    #[arg(long, exclusive = true)]
+    list_targets: bool,
+
+    /// Print the JSON Schema of the syntax tree written by `--emit ast`.
+    #[arg(long, exclusive = true)]
+    json_ast_schema: bool,
+
+    /// Print the configuration resolved from the options, the environment and the defaults as JSON, and exit.
+    #[arg(long)]
+    print_config: bool,
+
+    /// The width to which source lines shown with diagnostics are truncated. Defaults to the terminal width, or 100.
+    #[arg(long, value_name = "N")]
+    diagnostic_width: Option<usize>,
+
+    /// Emit an intermediate representation instead of the regular output, to the output path or the standard output.
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+
+    /// Warn about lines indented with both tabs and spaces.
     #[arg(long)]
-    target_cpu: String,
+    warn_mixed_indentation: bool,
+
+    /// Warn about names not in snake_case for functions and variables, SCREAMING_SNAKE_CASE for constants, or PascalCase for types and variants.
+    #[arg(long)]
+    warn_naming: bool,
+
+    /// Only accept ASCII letters, digits and underscores in identifiers, instead of any Unicode identifier character.
+    #[arg(long)]
+    ascii_identifiers: bool,
+
+    /// Print the number of declarations of each kind, statements and error nodes, the deepest block nesting and an estimate of the heap memory of the syntax tree of each file.
+    #[arg(long)]
+    stats: bool,
+
+    /// Keep compiling the remaining files after one fails, and list the files that failed at the end.
+    #[arg(long)]
+    keep_going: bool,
+
+    /// The number of files to compile at once. Defaults to the number of CPU
+    /// cores. Diagnostics are printed in the order of the files either way.
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    jobs: Option<usize>,
+
+    /// Report the warnings of a lint as errors, or of every lint for `all`. Warnings show their lint in brackets.
+    #[arg(long, value_name = "LINT")]
+    deny: Vec<String>,
+
+    /// Report the warnings of a lint, or of every lint for `all`, as warnings, even if they are denied.
+    #[arg(long, value_name = "LINT")]
+    warn: Vec<String>,
+
+    /// How diagnostics are printed.
+    #[arg(long, value_enum, default_value = "human")]
+    error_format: ErrorFormat,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Emit {
+    /// The syntax tree, as JSON.
+    Ast,
+    /// The path of each module the file imports, one per line, as written.
+    Deps,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ErrorFormat {
+    /// Messages with the source they refer to.
+    Human,
+    /// One JSON object per line, for tools.
+    Json,
+}
+
+/// Levels are written as they are passed to `-O`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
 enum Optimization {
     /// Enable very minimal optimizations (inlining).
     O0,
@@ -56,122 +163,820 @@ enum Optimization {
     Oz,
 }
 
-impl ToString for Optimization {
-    fn to_string(&self) -> String {
-        match self {
+impl Optimization {
+    /// Returns whether constant expressions are replaced with their values.
+    fn folds_constants(self) -> bool {
+        !matches!(self, Optimization::O0 | Optimization::Og)
+    }
+}
+
+impl fmt::Display for Optimization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
             Optimization::O0 => "O0",
             Optimization::O1 => "O1",
             Optimization::O2 => "O2",
             Optimization::O3 => "O3",
             Optimization::Og => "Og",
             Optimization::Oz => "Oz",
-        }
-        .to_string()
+        };
+        write!(f, "{}", level)
     }
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Debug)]
 enum Commands {
     Link {},
     Compile {},
-    Check {},
+    /// Report the diagnostics of the files without producing any output.
+    Check {
+        /// Skip the files that compiled without errors since they last changed, as recorded in the cache.
+        #[arg(long)]
+        only_changed: bool,
+    },
     EmitVMCode {},
     ClearCache {},
+    /// Print the number, total size and age of the cache entries.
+    CacheStats {},
+    /// Rewrite the cache entries written by older versions of the compiler in the current format, and delete those that cannot be.
+    MigrateCache {},
+    /// Rewrite the files in the canonical format, in place or to the output file. Files with errors are left as they are.
+    Fmt {},
+}
+
+/// Returns the cache directory set by `--cache-dir`, or the default one.
+fn cache_dir_path(cli_cache_dir: Option<PathBuf>) -> PathBuf {
+    // Default to "./.zuroxc/cache/" if no cache_dir is provided
+    cli_cache_dir.unwrap_or_else(|| [".", ".zuroxc", "cache"].iter().collect())
 }
 
-fn highlight(file: &str, line: usize, col: usize, value: &str) {}
+/// Returns the cache directory, creating it if needed. Returns `None` if it
+/// cannot be created, in which case files are compiled without a cache.
+fn get_cache_dir(cli_cache_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let path = cache_dir_path(cli_cache_dir);
 
-fn lexer_errors(tokens: &Vec<token::Token>) {
-    for tok in tokens {
-        match &tok {
-            token::Token::Error(e) => {
-                eprintln!("{}", e);
-                match e {
-                    _ => {}
-                }
-            }
-            _ => {}
+    if !path.exists() {
+        if let Err(e) = fs::create_dir_all(&path) {
+            eprintln!(
+                "Warning: Could not create the cache directory {}: {}. Continuing without a cache.",
+                path.display(),
+                e
+            );
+            return None;
+        }
+    }
+    Some(path)
+}
+
+/// Describes how long ago `time` was, e.g. `3 hours ago`.
+fn age(time: SystemTime) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(time)
+        .map_or(0, |age| age.as_secs());
+    let (amount, unit) = match seconds {
+        0..=59 => (seconds, "second"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{} {}{} ago", amount, unit, plural)
+}
+
+fn print_cache_stats(cache_dir: &Path) {
+    let cache_dir = cache_dir.to_str().expect("Invalid cache directory");
+    let stats = match cache::stats(cache_dir) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("Error reading cache directory {}: {}", cache_dir, e);
+            std::process::exit(1);
+        }
+    };
+    println!("Cache directory: {}", cache_dir);
+    println!("Entries: {}", stats.entries);
+    println!("Total size: {} bytes", stats.total_bytes);
+    if let (Some(oldest), Some(newest)) = (stats.oldest, stats.newest) {
+        println!("Oldest entry: {}", age(oldest));
+        println!("Newest entry: {}", age(newest));
+    }
+}
+
+fn migrate_cache(cache_dir: &Path) {
+    let cache_dir = cache_dir.to_str().expect("Invalid cache directory");
+    let report = match cache::migrate(cache_dir) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error migrating cache directory {}: {}", cache_dir, e);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Migrated {} cache entries, removed {} that could not be migrated, {} already up to date.",
+        report.migrated, report.removed, report.current
+    );
+}
+
+/// The width diagnostics are rendered at when neither `--diagnostic-width` nor
+/// the terminal width is known.
+const DEFAULT_DIAGNOSTIC_WIDTH: usize = 100;
+
+/// Returns the width diagnostics should be rendered at. The terminal width is
+/// taken from `COLUMNS`, which shells set for interactive sessions.
+fn diagnostic_width(cli: &Cli) -> usize {
+    cli.diagnostic_width
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(DEFAULT_DIAGNOSTIC_WIDTH)
+}
+
+/// The extension of output files. Until code generation exists, the output
+/// of a compilation is its serialized AST.
+const OUTPUT_EXTENSION: &str = "zxast";
+
+/// Returns the deepest directory containing every input file. Outputs are
+/// placed relative to it when mirroring the inputs into an output directory.
+fn common_base(files: &[PathBuf]) -> PathBuf {
+    let mut base: Option<PathBuf> = None;
+    for file in files {
+        let parent = file.parent().unwrap_or(Path::new(""));
+        base = Some(match base {
+            None => parent.to_path_buf(),
+            Some(base) => base
+                .components()
+                .zip(parent.components())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    base.unwrap_or_default()
+}
+
+/// Returns where the output for `file` should be saved, if anywhere.
+fn output_path(file: &Path, base: &Path, cli: &Cli) -> Option<PathBuf> {
+    match (&cli.out_dir, &cli.output) {
+        (Some(out_dir), _) => {
+            let relative = file
+                .strip_prefix(base)
+                .unwrap_or_else(|_| Path::new(file.file_name().unwrap_or_default()));
+            let extension = match cli.emit {
+                Some(Emit::Ast) => "json",
+                Some(Emit::Deps) => "deps",
+                None => OUTPUT_EXTENSION,
+            };
+            Some(out_dir.join(relative).with_extension(extension))
         }
+        (None, Some(output)) => Some(output.clone()),
+        (None, None) => None,
     }
 }
 
-fn parser_errors(ast: &Box<ast::AST>) {
-    for decl in &ast.declarations {
-        match decl.as_ref() {
-            ast::Declaration::Error(e) => {
-                eprintln!("{}", e);
+/// Returns the imports of `ast` as emitted by `--emit=deps`.
+fn deps(ast: &ast::AST) -> String {
+    ast.imports()
+        .into_iter()
+        .map(|path| path + "\n")
+        .collect()
+}
+
+fn save_output(ast: &ast::AST, path: &Path, emit: Option<Emit>) {
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            eprintln!("Error creating output directory {}: {}", parent.display(), e);
+            std::process::exit(1);
+        }
+    }
+    let path_str = path.to_str().expect("Invalid output path");
+    let result = match emit {
+        Some(Emit::Ast) => fs::write(path, ast::ast_to_json(ast)),
+        Some(Emit::Deps) => fs::write(path, deps(ast)),
+        None => cache::save_ast_to_file(ast, path_str),
+    };
+    if let Err(e) = result {
+        eprintln!("Error writing {}: {}", path_str, e);
+        std::process::exit(1);
+    }
+}
+
+/// A diagnostic as printed by `--error-format=json`.
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    diagnostic: &'a Diagnostic,
+}
+
+fn report(diagnostic: &Diagnostic, file_path_str: &str, source: &SourceMap, cli: &Cli) {
+    match cli.error_format {
+        ErrorFormat::Human => {
+            eprintln!("{}: {}", file_path_str, diagnostic);
+            let range = diagnostic.span.unwrap_or(Span {
+                start: diagnostic.col,
+                end: diagnostic.col,
+            });
+            let width = diagnostic_width(cli);
+            if let Some(highlighted) =
+                diagnostic::highlight(source, diagnostic.line, diagnostic.col, range, width)
+            {
+                eprintln!("{}", highlighted);
             }
-            _ => {}
         }
+        ErrorFormat::Json => {
+            let json = JsonDiagnostic {
+                file: file_path_str,
+                diagnostic,
+            };
+            eprintln!("{}", serde_json::to_string(&json).expect("Invalid diagnostic"));
+        }
+    }
+}
+
+/// Returns the lint levels set by `--deny` and `--warn`. Exits on an unknown
+/// lint.
+fn lint_levels(cli: &Cli) -> LintLevels {
+    let mut levels = LintLevels::default();
+    let result = cli
+        .deny
+        .iter()
+        .try_for_each(|lint| levels.deny(lint))
+        .and_then(|()| cli.warn.iter().try_for_each(|lint| levels.warn(lint)));
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
+    levels
 }
 
-fn get_cache_dir(cli_cache_dir: Option<PathBuf>) -> PathBuf {
-    match cli_cache_dir {
+/// Returns the options of the checks run on every file.
+fn options(cli: &Cli) -> Options {
+    Options {
+        warn_mixed_indentation: cli.warn_mixed_indentation,
+        identifier_set: if cli.ascii_identifiers {
+            IdentifierSet::Ascii
+        } else {
+            IdentifierSet::Xid
+        },
+        naming: cli.warn_naming.then(NamingRules::default),
+        target: cli.target.clone().unwrap_or_default(),
+    }
+}
+
+/// The configuration resolved from the options, the environment and the
+/// defaults, as printed by `--print-config`.
+#[derive(Serialize)]
+struct Config<'a> {
+    files: &'a [PathBuf],
+    output: Option<&'a PathBuf>,
+    out_dir: Option<&'a PathBuf>,
+    /// The cache directory, or `None` with `--no-cache`.
+    cache_dir: Option<PathBuf>,
+    optimization: Optimization,
+    target_cpu: Option<&'a str>,
+    diagnostic_width: usize,
+    emit: Option<Emit>,
+    error_format: ErrorFormat,
+    keep_going: bool,
+    jobs: usize,
+    stats: bool,
+    #[serde(flatten)]
+    options: Options,
+    /// The level of each lint, `deny` or `warn`.
+    lints: BTreeMap<&'static str, &'static str>,
+}
+
+fn print_config(cli: &Cli) {
+    let levels = lint_levels(cli);
+    let config = Config {
+        files: &cli.files,
+        output: cli.output.as_ref(),
+        out_dir: cli.out_dir.as_ref(),
+        cache_dir: (!cli.no_cache).then(|| cache_dir_path(cli.cache_dir.clone())),
+        optimization: cli.optimization,
+        target_cpu: cli.target_cpu.as_deref(),
+        diagnostic_width: diagnostic_width(cli),
+        emit: cli.emit,
+        error_format: cli.error_format,
+        keep_going: cli.keep_going,
+        jobs: jobs(cli),
+        stats: cli.stats,
+        options: options(cli),
+        lints: LINTS
+            .iter()
+            .map(|&lint| (lint, if levels.is_denied(lint) { "deny" } else { "warn" }))
+            .collect(),
+    };
+    println!("{}", serde_json::to_string_pretty(&config).expect("Invalid configuration"));
+}
+
+fn print_stats(file_path_str: &str, ast: &ast::AST) {
+    println!("{}:\n{}", file_path_str, Stats::of(ast));
+}
+
+/// Returns the number of files compiled at once, as set by `--jobs`.
+fn jobs(cli: &Cli) -> usize {
+    cli.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    })
+}
+
+/// Compiles each file with its declaration cache, if any, on the threads set
+/// up by `--jobs`. Returns the source of each file, without a byte order
+/// mark, and its compilation, or the error reading it, in the order of the
+/// files.
+fn compile_files(
+    files: &[(&Path, Option<PathBuf>)],
+    cli: &Cli,
+) -> Vec<std::io::Result<(String, Compilation)>> {
+    files
+        .par_iter()
+        .map(|(file, declarations)| compile_file(file, declarations.as_deref(), cli))
+        .collect()
+}
+
+/// Compiles `file`, returning its source along with the compilation.
+///
+/// With a `declarations` cache, only the declarations that changed since the
+/// file was last compiled are parsed. The error reading the file is returned
+/// rather than reported, so that it is reported in order with the others.
+fn compile_file(
+    file: &Path,
+    declarations: Option<&Path>,
+    cli: &Cli,
+) -> std::io::Result<(String, Compilation)> {
+    let src = fs::read_to_string(file)?;
+
+    // Lexer positions do not count a leading byte order mark, so neither may
+    // the source that diagnostics are shown against.
+    let src = lexer::strip_bom(&src);
+    let declarations_str =
+        declarations.map(|path| path.to_str().expect("Invalid cache directory"));
+    let compilation = match declarations_str {
         Some(path) => {
-            if !path.exists() {
-                // Attempt to create the directory if it doesn't exist
-                if let Err(e) = fs::create_dir_all(&path) {
-                    eprintln!("Error creating cache directory: {}", e);
-                    std::process::exit(1);
-                }
-            }
-            path
+            let mut cache = cache::load_declarations_from_file(path).unwrap_or_default();
+            let compilation = compile_str_cached(src, options(cli), &mut cache);
+            // Like the fingerprints, the declarations only save work on the
+            // next build, so failing to store them is not an error.
+            let _ = cache::save_declarations_to_file(&cache, path);
+            compilation
         }
+        None => compile_str_with(src, options(cli)),
+    };
+    Ok((src.to_string(), compilation))
+}
+
+/// Prints the diagnostics of `compilation`, the result of compiling `src`.
+/// Returns `None` if it has errors, and otherwise the AST along with whether
+/// there was nothing to report.
+fn report_compilation(
+    file_path_str: &str,
+    src: &str,
+    mut compilation: Compilation,
+    cli: &Cli,
+) -> Option<(ast::AST, bool)> {
+    let levels = lint_levels(cli);
+    for diagnostic in &mut compilation.diagnostics {
+        levels.apply(diagnostic);
+    }
+    let source = SourceMap::from_line_starts(src, std::mem::take(&mut compilation.line_starts));
+    for diagnostic in &compilation.diagnostics {
+        report(diagnostic, file_path_str, &source, cli);
+    }
+    // Also printed for files with errors, whose trees contain error nodes.
+    if cli.stats {
+        print_stats(file_path_str, &compilation.ast);
+    }
+    if compilation.has_errors() {
+        return None;
+    }
+    Some((*compilation.ast, compilation.diagnostics.is_empty()))
+}
+
+/// Reports the warnings of optimizing `file`, which has already compiled from
+/// `src` with the lines starting at the offsets given along with it, and
+/// returns whether none of them is denied. Files loaded from the cache have no
+/// source at hand, which is only read again to show where the warnings are.
+fn report_optimization(
+    file_path_str: &str,
+    file: &Path,
+    src: Option<(String, Vec<usize>)>,
+    warnings: Vec<SemanticWarning>,
+    cli: &Cli,
+) -> bool {
+    if warnings.is_empty() {
+        return true;
+    }
+    let read;
+    let source = match src {
+        Some((ref src, line_starts)) => SourceMap::from_line_starts(src, line_starts),
         None => {
-            // Default to "./.zuroxc/cache/" if no cache_dir is provided
-            let default_cache_dir: PathBuf = [".", ".zuroxc", "cache"].iter().collect();
+            read = fs::read_to_string(file).unwrap_or_default();
+            // Like the source the file was compiled from, which positions
+            // count from.
+            SourceMap::new(lexer::strip_bom(&read))
+        }
+    };
+    let levels = lint_levels(cli);
+    let mut denied = false;
+    for warning in warnings {
+        let mut diagnostic = Diagnostic::from(warning);
+        levels.apply(&mut diagnostic);
+        denied |= diagnostic.is_error();
+        report(&diagnostic, file_path_str, &source, cli);
+    }
+    !denied
+}
 
-            if !default_cache_dir.exists() {
-                if let Err(e) = fs::create_dir_all(&default_cache_dir) {
-                    eprintln!("Error creating default cache directory: {}", e);
-                    std::process::exit(1);
-                }
+/// Returns the settings a file is compiled with besides its contents, as
+/// distinguished by the cache: the options and the level of every lint.
+fn cache_settings(cli: &Cli) -> String {
+    let levels = lint_levels(cli);
+    let denied: Vec<&str> = LINTS
+        .iter()
+        .copied()
+        .filter(|lint| levels.is_denied(lint))
+        .collect();
+    serde_json::to_string(&(options(cli), denied)).expect("Invalid options")
+}
+
+/// Returns the cache entry of `file`, whether or not it exists.
+fn cache_entry(file: &Path, cache_dir: &Path, cli: &Cli) -> std::io::Result<PathBuf> {
+    let file_str = file.to_str().expect("Invalid input path");
+    let cache_dir_str = cache_dir.to_str().expect("Invalid cache directory");
+    let (hash, _) = cache::get_hash_fingerprinted(file_str, cache_dir_str)?;
+    Ok(cache::entry_path(
+        &hash,
+        &cache_settings(cli),
+        cache_dir_str,
+    ))
+}
+
+/// Returns the declaration cache of `file`.
+fn declarations_entry(file: &Path, cache_dir: &Path) -> PathBuf {
+    let file_str = file.to_str().expect("Invalid input path");
+    let cache_dir_str = cache_dir.to_str().expect("Invalid cache directory");
+    cache::declarations_path(file_str, cache_dir_str)
+}
+
+/// Checks every input file, or with `only_changed` only those without a cache
+/// entry for their current contents. Files that check without any diagnostics
+/// are added to the cache, so they are skipped until they change again.
+fn check_files(cli: &Cli, only_changed: bool) {
+    if cli.files.is_empty() {
+        eprintln!("Error: No input files specified.");
+        std::process::exit(1);
+    }
+    let cache_dir = if cli.no_cache {
+        None
+    } else {
+        get_cache_dir(cli.cache_dir.clone())
+    };
+    if only_changed && cache_dir.is_none() {
+        eprintln!("Error: --only-changed needs the cache to know which files changed.");
+        std::process::exit(1);
+    }
+
+    let (mut skipped, mut failed) = (0, 0);
+    let mut to_check = Vec::new();
+    let mut entries = Vec::new();
+    for file in &cli.files {
+        let file_path_str = file
+            .to_str()
+            .expect("Failed to convert file path to string.");
+        let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.clone());
+        let entry = match cache_dir
+            .as_ref()
+            .map(|dir| cache_entry(&canonical, dir, cli))
+        {
+            Some(Ok(entry)) => Some(entry),
+            Some(Err(e)) => {
+                eprintln!("Error reading {}: {}", file_path_str, e);
+                std::process::exit(1);
             }
+            None => None,
+        };
+        if only_changed && entry.as_ref().is_some_and(|entry| entry.exists()) {
+            skipped += 1;
+            continue;
+        }
 
-            default_cache_dir
+        let declarations = cache_dir
+            .as_ref()
+            .map(|dir| declarations_entry(&canonical, dir));
+        to_check.push((file.as_path(), declarations));
+        entries.push(entry);
+    }
+
+    let checked = to_check.len();
+    let compilations = compile_files(&to_check, cli);
+    for (((file, _), entry), compiled) in to_check.iter().zip(entries).zip(compilations) {
+        let file_path_str = file
+            .to_str()
+            .expect("Failed to convert file path to string.");
+        let (src, compilation) = match compiled {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file_path_str, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let Some((ast, clean)) = report_compilation(file_path_str, &src, compilation, cli) else {
+            failed += 1;
+            continue;
+        };
+        if let Some(entry) = entry.filter(|_| clean) {
+            let entry_str = entry.to_str().expect("Invalid cache directory");
+            if let Err(e) = cache::save_ast_to_file(&ast, entry_str) {
+                eprintln!(
+                    "Warning: Could not write to the cache at {}: {}.",
+                    entry_str, e
+                );
+            }
+        }
+    }
+
+    println!("Checked {} file(s), skipped {} unchanged.", checked, skipped);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Formats every input file in place, or the single input file into
+/// `--output`. Files that do not parse are reported and left unchanged.
+fn format_files(cli: &Cli) {
+    if cli.files.is_empty() {
+        eprintln!("Error: No input files specified.");
+        std::process::exit(1);
+    }
+    if cli.output.is_some() && cli.files.len() > 1 {
+        eprintln!("Error: --output takes a single input file.");
+        std::process::exit(1);
+    }
+
+    let mut failed = false;
+    for file in &cli.files {
+        let file_path_str = file
+            .to_str()
+            .expect("Failed to convert file path to string.");
+        let src = match fs::read_to_string(file) {
+            Ok(src) => src,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file_path_str, e);
+                std::process::exit(1);
+            }
+        };
+        let formatted = match pretty::format_source(&src) {
+            Ok(formatted) => formatted,
+            Err((diagnostics, source)) => {
+                for diagnostic in &diagnostics {
+                    report(diagnostic, file_path_str, &source, cli);
+                }
+                failed = true;
+                continue;
+            }
+        };
+        let path = cli.output.as_deref().unwrap_or(file);
+        if cli.output.is_none() && formatted == src {
+            continue;
+        }
+        if let Err(e) = fs::write(path, formatted) {
+            eprintln!("Error writing {}: {}", path.display(), e);
+            std::process::exit(1);
         }
     }
+    if failed {
+        std::process::exit(1);
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    if cli.list_targets {
+        println!("native");
+        for cpu in target::KNOWN_TARGETS {
+            println!("{}", cpu);
+        }
+        return;
+    }
+
+    if cli.json_ast_schema {
+        println!("{}", ast::ast_json_schema());
+        return;
+    }
+
+    if cli.print_config {
+        print_config(&cli);
+        return;
+    }
+
+    let jobs = jobs(&cli);
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+    {
+        eprintln!("Error: Could not start {} compilation thread(s): {}", jobs, e);
+        std::process::exit(1);
+    }
+
+    match &cli.command {
+        Some(Commands::CacheStats {}) => {
+            match get_cache_dir(cli.cache_dir.clone()) {
+                Some(cache_dir) => print_cache_stats(&cache_dir),
+                None => std::process::exit(1),
+            }
+            return;
+        }
+        Some(Commands::MigrateCache {}) => {
+            match get_cache_dir(cli.cache_dir.clone()) {
+                Some(cache_dir) => migrate_cache(&cache_dir),
+                None => std::process::exit(1),
+            }
+            return;
+        }
+        Some(Commands::Check { only_changed }) => {
+            check_files(&cli, *only_changed);
+            return;
+        }
+        Some(Commands::Fmt {}) => {
+            format_files(&cli);
+            return;
+        }
+        Some(Commands::Compile {}) | None => {}
+        Some(_) => {
+            eprintln!("Error: This command is not implemented yet.");
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(cpu) = cli.target_cpu.as_deref().filter(|cpu| !target::is_known(cpu)) {
+        match target::suggest(cpu) {
+            Some(suggestion) => eprintln!(
+                "Error: Unknown target CPU `{}`. Did you mean `{}`?",
+                cpu, suggestion
+            ),
+            None => eprintln!(
+                "Error: Unknown target CPU `{}`. Use --list-targets to see the known targets.",
+                cpu
+            ),
+        }
+        std::process::exit(1);
+    }
+
     if cli.files.is_empty() {
         eprintln!("Error: No input files specified.");
         std::process::exit(1);
     }
+    // Reports an unknown lint before any file is compiled.
+    lint_levels(&cli);
+
+    if cli.output.is_some() && cli.files.len() > 1 {
+        eprintln!("Error: --output takes a single input file, use --out-dir for several.");
+        std::process::exit(1);
+    }
+
+    // The cache directory is only created once it is known to be needed.
+    let mut cache_dir = if cli.no_cache {
+        None
+    } else {
+        get_cache_dir(cli.cache_dir.clone())
+    };
+    // A missing file is still made absolute, so that it does not move the
+    // base the outputs are placed relative to.
+    let files: Vec<PathBuf> = cli
+        .files
+        .iter()
+        .map(|file| {
+            fs::canonicalize(file)
+                .or_else(|_| std::path::absolute(file))
+                .unwrap_or_else(|_| file.clone())
+        })
+        .collect();
+    let base = common_base(&files);
+    let mut failed = Vec::new();
 
-    let cache_dir = get_cache_dir(cli.cache_dir);
+    // Only ASTs that compiled without any diagnostics are cached, keyed by the
+    // checks they were compiled with, so a cache hit has nothing to report.
+    let mut entries = Vec::new();
+    let mut cached = Vec::new();
+    let mut to_compile = Vec::new();
+    for (file, canonical) in cli.files.iter().zip(&files) {
+        // A file that cannot be read is reported in order with the others.
+        let entry = cache_dir
+            .as_ref()
+            .map(|dir| cache_entry(canonical, dir, &cli))
+            .transpose();
+        let ast = entry
+            .as_ref()
+            .ok()
+            .and_then(Option::as_ref)
+            .and_then(|entry| cache::load_ast_from_file(entry.to_str()?).ok());
+        if ast.is_none() && entry.is_ok() {
+            let declarations = cache_dir
+                .as_ref()
+                .map(|dir| declarations_entry(canonical, dir));
+            to_compile.push((file.as_path(), declarations));
+        }
+        entries.push(entry);
+        cached.push(ast);
+    }
+    let mut compilations = compile_files(&to_compile, &cli).into_iter();
 
-    for file in cli.files {
+    for (((file, canonical), entry), cached) in
+        cli.files.iter().zip(&files).zip(entries).zip(cached)
+    {
         let file_path_str = file
             .to_str()
             .expect("Failed to convert file path to string.");
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", file_path_str, e);
+                if !cli.keep_going {
+                    std::process::exit(1);
+                }
+                failed.push(file_path_str);
+                continue;
+            }
+        };
 
-        // Check if the file exists in the cache, using the cache directory
-        if !cache::file_exists_in_cache(
-            cache::get_hash(file_path_str).unwrap().as_str(),
-            cache_dir.to_str().expect("Invalid cache directory"),
-        ) {
-            // Lexer
-            let mut lexer = lexer::Lexer::new(
-                "\nif go then 數據無法訪問 run {+=x} \n \"數據無法訪問\\\"\" \n 數據無法訪問\"",
-            );
-
-            let tokens = lexer.lex();
-            if lexer.has_error() {
-                lexer_errors(&tokens);
-                return;
+        let (mut ast, src) = match cached {
+            Some(ast) => {
+                if cli.stats {
+                    print_stats(file_path_str, &ast);
+                }
+                (ast, None)
+            }
+            None => {
+                let compiled = compilations
+                    .next()
+                    .expect("Every file missing from the cache is compiled");
+                let (src, compilation) = match compiled {
+                    Ok(compiled) => compiled,
+                    Err(e) => {
+                        eprintln!("Error reading {}: {}", file_path_str, e);
+                        if !cli.keep_going {
+                            std::process::exit(1);
+                        }
+                        failed.push(file_path_str);
+                        continue;
+                    }
+                };
+                let line_starts = compilation.line_starts.clone();
+                let Some((ast, clean)) =
+                    report_compilation(file_path_str, &src, compilation, &cli)
+                else {
+                    if !cli.keep_going {
+                        std::process::exit(1);
+                    }
+                    failed.push(file_path_str);
+                    continue;
+                };
+                // The cache only saves work on later builds, so the build
+                // goes on without it if it cannot be written to.
+                let entry_str = entry
+                    .as_ref()
+                    .filter(|_| clean && cache_dir.is_some())
+                    .map(|entry| entry.to_str().expect("Invalid cache directory"));
+                if let Some(entry) = entry_str {
+                    if let Err(e) = cache::save_ast_to_file(&ast, entry) {
+                        eprintln!(
+                            "Warning: Could not write to the cache at {}: {}. Continuing without a cache.",
+                            entry, e
+                        );
+                        cache_dir = None;
+                    }
+                }
+                (ast, Some((src, line_starts)))
             }
+        };
 
-            // Parser
-            let mut parser = parser::Parser::new(tokens);
-            let ast = parser.parse();
-            if parser.has_error() {
-                parser_errors(&ast);
-                // TODO: Write error handler.
+        // The cache holds the tree as checked, since it does not depend on the
+        // optimization level.
+        if cli.optimization.folds_constants() {
+            let warnings = fold::eliminate_dead_branches(&mut ast);
+            if !report_optimization(file_path_str, file, src, warnings, &cli) {
+                if !cli.keep_going {
+                    std::process::exit(1);
+                }
+                failed.push(file_path_str);
+                continue;
             }
         }
+        match output_path(canonical, &base, &cli) {
+            Some(path) => save_output(&ast, &path, cli.emit),
+            None if cli.emit == Some(Emit::Ast) => println!("{}", ast::ast_to_json(&ast)),
+            None if cli.emit == Some(Emit::Deps) => print!("{}", deps(&ast)),
+            None => {}
+        }
+    }
+
+    if !failed.is_empty() {
+        eprintln!(
+            "Error: {} of {} file(s) failed to compile: {}",
+            failed.len(),
+            files.len(),
+            failed.join(", ")
+        );
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,246 @@
+use crate::ast::*;
+use crate::visit::{self, Visitor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+/// Identifies a node of the syntax tree. Ids are assigned in source order, so
+/// the same input always yields the same ids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId(pub u32);
+
+/// A node of a syntax tree that is given an id.
+///
+/// Nodes are the same if they are the same node of a tree, rather than equal
+/// ones, so that equal expressions in different places get ids of their own.
+#[derive(Clone, Copy, Debug)]
+pub enum Node<'ast> {
+    Declaration(&'ast Declaration),
+    Function(&'ast FunctionDeclaration),
+    Block(&'ast Block),
+    Statement(&'ast Statement),
+    Expression(&'ast Expression),
+    Identifier(&'ast Identifier),
+    Type(&'ast Type),
+}
+
+impl Node<'_> {
+    /// Returns the kind of the node along with where it is, since a node and
+    /// its first field can be in the same place.
+    fn identity(&self) -> (mem::Discriminant<Self>, *const ()) {
+        let node: *const () = match *self {
+            Node::Declaration(node) => (node as *const Declaration).cast(),
+            Node::Function(node) => (node as *const FunctionDeclaration).cast(),
+            Node::Block(node) => (node as *const Block).cast(),
+            Node::Statement(node) => (node as *const Statement).cast(),
+            Node::Expression(node) => (node as *const Expression).cast(),
+            Node::Identifier(node) => (node as *const Identifier).cast(),
+            Node::Type(node) => (node as *const Type).cast(),
+        };
+        (mem::discriminant(self), node)
+    }
+}
+
+impl PartialEq for Node<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for Node<'_> {}
+
+impl Hash for Node<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state)
+    }
+}
+
+macro_rules! node_from {
+    ($($variant:ident($node:ty)),* $(,)?) => {
+        $(
+            impl<'ast> From<&'ast $node> for Node<'ast> {
+                fn from(node: &'ast $node) -> Self {
+                    Node::$variant(node)
+                }
+            }
+        )*
+    };
+}
+
+node_from!(
+    Declaration(Declaration),
+    Function(FunctionDeclaration),
+    Block(Block),
+    Statement(Statement),
+    Expression(Expression),
+    Identifier(Identifier),
+    Type(Type),
+);
+
+/// A side table numbering the declarations, functions, blocks, statements,
+/// expressions, identifiers and types of one syntax tree.
+///
+/// The table borrows the tree, so the tree cannot be moved or modified while
+/// the ids are in use. The nodes of a clone of the tree are not in the table.
+#[derive(Debug, Default)]
+pub struct NodeIds<'ast> {
+    /// The nodes in the order of their ids.
+    nodes: Vec<Node<'ast>>,
+    ids: HashMap<Node<'ast>, NodeId>,
+}
+
+impl<'ast> NodeIds<'ast> {
+    /// Numbers the nodes of `ast` in a pre-order traversal, starting from 0.
+    pub fn assign(ast: &'ast AST) -> NodeIds<'ast> {
+        let mut table = NodeIds::default();
+        visit::walk_ast(&mut table, ast);
+        table
+    }
+
+    /// Returns the id of `node`, or `None` if it is not a node of the tree
+    /// this table was built from.
+    pub fn get(&self, node: impl Into<Node<'ast>>) -> Option<NodeId> {
+        self.ids.get(&node.into()).copied()
+    }
+
+    /// Returns the node with the id `id`, or `None` if the tree has no such
+    /// node.
+    pub fn node(&self, id: NodeId) -> Option<Node<'ast>> {
+        self.nodes.get(id.0 as usize).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn number(&mut self, node: impl Into<Node<'ast>>) {
+        let node = node.into();
+        self.ids.insert(node, NodeId(self.nodes.len() as u32));
+        self.nodes.push(node);
+    }
+}
+
+impl<'ast> Visitor<'ast> for NodeIds<'ast> {
+    fn visit_declaration(&mut self, decl: &'ast Declaration) {
+        self.number(decl);
+        visit::walk_declaration(self, decl);
+    }
+
+    fn visit_function(&mut self, function: &'ast FunctionDeclaration) {
+        self.number(function);
+        visit::walk_function(self, function);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.number(block);
+        visit::walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        self.number(statement);
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        self.number(expr);
+        visit::walk_expression(self, expr);
+    }
+
+    fn visit_type(&mut self, var_type: &'ast Type) {
+        self.number(var_type);
+        visit::walk_type_variant(self, &var_type.variant);
+    }
+
+    fn visit_identifier(&mut self, id: &'ast Identifier) {
+        self.number(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    /// Records the ids of the statements, expressions and identifiers of a
+    /// tree in traversal order.
+    struct Collector<'a, 'ast> {
+        table: &'a NodeIds<'ast>,
+        ids: Vec<Option<NodeId>>,
+    }
+
+    impl<'ast> Visitor<'ast> for Collector<'_, 'ast> {
+        fn visit_statement(&mut self, statement: &'ast Statement) {
+            self.ids.push(self.table.get(statement));
+            visit::walk_statement(self, statement);
+        }
+
+        fn visit_expression(&mut self, expr: &'ast Expression) {
+            self.ids.push(self.table.get(expr));
+            visit::walk_expression(self, expr);
+        }
+
+        fn visit_identifier(&mut self, id: &'ast Identifier) {
+            self.ids.push(self.table.get(id));
+        }
+    }
+
+    fn collect(src: &str) -> Vec<Option<NodeId>> {
+        let ast = Parser::new(Lexer::new(src).lex()).parse();
+        let table = NodeIds::assign(&ast);
+        let mut collector = Collector {
+            table: &table,
+            ids: Vec::new(),
+        };
+        visit::walk_ast(&mut collector, &ast);
+        collector.ids
+    }
+
+    #[test]
+    fn test_ids_are_deterministic() {
+        let src = "fn f(i32 x) -> i32 { i32 y = x * 2; if y > 3 { ret g(y); } ret y; } \
+                   fn g(i32 z) -> i32 { ret z; }";
+        let ids = collect(src);
+
+        assert!(ids.len() > 20);
+        assert!(ids.iter().all(Option::is_some));
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(ids, collect(src));
+    }
+
+    #[test]
+    fn test_nodes_by_id() {
+        let mut ast = Parser::new(Lexer::new("fn f() { ret 1; }").lex()).parse();
+        ast.declarations.push(ast.declarations[0].clone());
+        let table = NodeIds::assign(&ast);
+        let Declaration::Function(f) = ast.declarations[0].as_ref() else {
+            panic!("Expected a function");
+        };
+        let Declaration::Function(g) = ast.declarations[1].as_ref() else {
+            panic!("Expected a function");
+        };
+
+        let id = table.get(f.as_ref()).unwrap();
+        assert_eq!(table.node(id), Some(Node::Function(f)));
+        assert_eq!(table.get(ast.declarations[0].as_ref()), Some(NodeId(0)));
+        assert_eq!(
+            table.node(NodeId(0)),
+            Some(Node::Declaration(&ast.declarations[0]))
+        );
+        for id in 0..table.len() as u32 {
+            assert_eq!(table.get(table.node(NodeId(id)).unwrap()), Some(NodeId(id)));
+        }
+        assert_eq!(table.node(NodeId(table.len() as u32)), None);
+
+        // Equal blocks in different places are different nodes.
+        assert_eq!(f.block, g.block);
+        assert_ne!(table.get(f.block.as_ref()), table.get(g.block.as_ref()));
+        let copy = f.block.clone();
+        assert_eq!(table.get(copy.as_ref()), None);
+    }
+}
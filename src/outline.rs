@@ -0,0 +1,255 @@
+use crate::ast::*;
+use crate::token::Span;
+use crate::visit::{self, Visitor};
+use serde::Serialize;
+
+/// What an [`OutlineItem`] declares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ItemKind {
+    Import,
+    Enum,
+    Variant,
+    Struct,
+    Field,
+    Interface,
+    Implementation,
+    Function,
+    Method,
+    /// A local variable, only listed when the statements are.
+    Variable,
+}
+
+/// A named item of a syntax tree, as listed in an outline of its source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OutlineItem {
+    pub kind: ItemKind,
+    pub name: String,
+    /// The bytes of the name in the source.
+    pub span: Span,
+    /// The number of items the item is nested in, which is 0 for
+    /// declarations.
+    pub depth: usize,
+}
+
+/// Returns the items declared in `ast` in source order, each followed by the
+/// items nested in it: the variants of an enum, the fields of a struct and
+/// the methods of an interface or implementation. With `statements`, the
+/// local variables of every function and method are listed too.
+///
+/// An implementation is named after the interface it implements. Items whose
+/// name failed to parse are left out, along with the items nested in them.
+pub fn outline(ast: &AST, statements: bool) -> Vec<OutlineItem> {
+    let mut outliner = Outliner {
+        items: Vec::new(),
+        statements,
+        depth: 0,
+    };
+    for decl in &ast.declarations {
+        outliner.declaration(decl);
+    }
+    outliner.items
+}
+
+struct Outliner {
+    items: Vec<OutlineItem>,
+    statements: bool,
+    depth: usize,
+}
+
+impl Outliner {
+    /// Adds an item for `id`, returning whether it has a name.
+    fn item(&mut self, kind: ItemKind, id: &Identifier) -> bool {
+        let Some((token, span)) = id
+            .id
+            .as_ref()
+            .and_then(|token| Some((token, token.span()?)))
+        else {
+            return false;
+        };
+        self.items.push(OutlineItem {
+            kind,
+            name: token.get_lexeme().to_string(),
+            span,
+            depth: self.depth,
+        });
+        true
+    }
+
+    /// Visits the items nested in the item just added.
+    fn nested(&mut self, visit: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        visit(self);
+        self.depth -= 1;
+    }
+
+    fn declaration(&mut self, decl: &Declaration) {
+        match decl {
+            Declaration::Import(import) => self.import(import),
+            Declaration::Enum(enumeration) => {
+                if self.item(ItemKind::Enum, &enumeration.id) {
+                    self.nested(|outliner| {
+                        for variant in enumeration.variants.iter().flatten() {
+                            outliner.variant(ItemKind::Variant, variant);
+                        }
+                    });
+                }
+            }
+            Declaration::Struct(structure) => self.variant(ItemKind::Struct, &structure.variant),
+            Declaration::Function(function) => self.function(ItemKind::Function, function),
+            Declaration::Interface(interface) => {
+                if self.item(ItemKind::Interface, &interface.id) {
+                    self.nested(|outliner| {
+                        for method in &interface.methods {
+                            outliner.function(ItemKind::Method, method);
+                        }
+                    });
+                }
+            }
+            Declaration::Implementation(implementation) => {
+                if self.item(ItemKind::Implementation, &implementation.intf_id) {
+                    self.nested(|outliner| {
+                        for method in &implementation.methods {
+                            outliner.function(ItemKind::Method, method);
+                        }
+                    });
+                }
+            }
+            Declaration::Error(_) => {}
+        }
+    }
+
+    /// Adds an import named by its path, spanning every segment of it.
+    fn import(&mut self, import: &ImportDeclaration) {
+        let mut spans = import
+            .segments
+            .iter()
+            .filter_map(|segment| segment.id.as_ref()?.span());
+        let Some(first) = spans.next() else {
+            return;
+        };
+        let last = spans.next_back().unwrap_or(first);
+        self.items.push(OutlineItem {
+            kind: ItemKind::Import,
+            name: import.path(),
+            span: Span {
+                start: first.start,
+                end: last.end,
+            },
+            depth: self.depth,
+        });
+    }
+
+    /// Adds a struct or enum variant with its named fields.
+    fn variant(&mut self, kind: ItemKind, variant: &Variant) {
+        if !self.item(kind, variant.id()) {
+            return;
+        }
+        if let Variant::Named(_, fields) = variant {
+            self.nested(|outliner| {
+                for (_, id) in &fields.fields {
+                    outliner.item(ItemKind::Field, id);
+                }
+            });
+        }
+    }
+
+    fn function(&mut self, kind: ItemKind, function: &FunctionDeclaration) {
+        if !self.item(kind, &function.id) || !self.statements {
+            return;
+        }
+        self.nested(|outliner| visit::walk_block(outliner, &function.block));
+    }
+}
+
+impl<'ast> Visitor<'ast> for Outliner {
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        if let Statement::Var(declaration) = statement {
+            self.item(ItemKind::Variable, &declaration.id);
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn items(src: &str, statements: bool) -> Vec<(ItemKind, String, usize)> {
+        let ast = Parser::new(Lexer::new(src).lex()).parse();
+        outline(&ast, statements)
+            .into_iter()
+            .map(|item| {
+                assert_eq!(&src[item.span.start..item.span.end], item.name);
+                (item.kind, item.name, item.depth)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_outline() {
+        let src = "import std.io; fn first(i32 n) { i32 x = n; if n > 0 { i32 y = x; } } \
+                   fn second() { }";
+        let item = |kind, name: &str, depth| (kind, String::from(name), depth);
+        assert_eq!(
+            items(src, false),
+            [
+                item(ItemKind::Import, "std.io", 0),
+                item(ItemKind::Function, "first", 0),
+                item(ItemKind::Function, "second", 0),
+            ]
+        );
+        assert_eq!(
+            items(src, true)[1..4],
+            [
+                item(ItemKind::Function, "first", 0),
+                item(ItemKind::Variable, "x", 1),
+                item(ItemKind::Variable, "y", 1),
+            ]
+        );
+
+        let src = "enum Color { Red, Blue } intf Shape { fn area() -> i32 } \
+                   impl intf Shape for enum Color { fn area() -> i32 { ret 0; } }";
+        assert_eq!(
+            items(src, false),
+            [
+                item(ItemKind::Enum, "Color", 0),
+                item(ItemKind::Variant, "Red", 1),
+                item(ItemKind::Variant, "Blue", 1),
+                item(ItemKind::Interface, "Shape", 0),
+                item(ItemKind::Method, "area", 1),
+                item(ItemKind::Implementation, "Shape", 0),
+                item(ItemKind::Method, "area", 1),
+            ]
+        );
+
+        // Structs are not parsed yet, so the declaration is built by hand.
+        let fields = NamedFields {
+            fields: vec![
+                (Type::primitive("i32"), Identifier::named("x")),
+                (Type::primitive("i32"), Identifier::named("y")),
+            ],
+            error: None,
+        };
+        let ast = AST {
+            declarations: vec![Box::new(Declaration::Struct(Box::new(StructDeclaration {
+                variant: Box::new(Variant::Named(Identifier::named("Point"), Box::new(fields))),
+                attributes: Vec::new(),
+                error: None,
+            })))],
+        };
+        let items: Vec<_> = outline(&ast, false)
+            .into_iter()
+            .map(|item| (item.kind, item.name, item.depth))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                item(ItemKind::Struct, "Point", 0),
+                item(ItemKind::Field, "x", 1),
+                item(ItemKind::Field, "y", 1),
+            ]
+        );
+    }
+}
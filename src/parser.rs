@@ -1,9 +1,66 @@
-use crate::{ast::*, token::Token, utils::ParserError};
+use crate::{
+    ast::*,
+    lexer::Lexer,
+    token::{Span, Token, TokenKind},
+    utils::ParserError,
+};
 
 pub struct Parser {
     tokens: Vec<Token>, // Data from the lexer is to be moved here.
     index: usize,
-    has_error: bool,
+    errors: Vec<ParserError>,
+    /// Whether a `{` left open at the end of the input has been reported.
+    reported_unclosed: bool,
+    /// Whether function bodies are left unparsed.
+    lazy_bodies: bool,
+}
+
+/// How the statements of a block are terminated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SemicolonPolicy {
+    /// Every statement ends with a `;`.
+    Required,
+    /// A statement ends with a `;`, with the end of its line when the next
+    /// statement starts on a later one, or with the `}` closing its block.
+    NewlineOrSemicolon,
+}
+
+/// The terminator rule of the language. A `;` always ends a statement, and
+/// since statements may span lines, a line break only ends one that is
+/// complete. Two statements on the same line must still be separated by `;`,
+/// though the last one in a block may end at the `}` instead.
+pub const SEMICOLON_POLICY: SemicolonPolicy = SemicolonPolicy::NewlineOrSemicolon;
+
+/// The tokens a top-level declaration can start with, where parsing resumes
+/// after one that is not recognized.
+const DECLARATION_STARTS: &[TokenKind] = &[
+    TokenKind::Keyword("fn"),
+    TokenKind::Identifier("intf"),
+    TokenKind::Keyword("impl"),
+    TokenKind::Keyword("import"),
+    TokenKind::Keyword("enum"),
+    TokenKind::Keyword("struct"),
+    TokenKind::Keyword("pub"),
+    TokenKind::Keyword("const"),
+    TokenKind::Separator("@"),
+];
+
+/// Returns whether `err` reports a keyword or type name in place of a name,
+/// after which parsing goes on as if it were one.
+fn is_reserved_name(err: &ParserError) -> bool {
+    matches!(
+        err,
+        ParserError::ReservedKeyword(..) | ParserError::ReservedTypeName(..)
+    )
+}
+
+/// Returns the error that stopped parsing `function` early, if any. A
+/// reserved name does not, since the rest of the function is parsed anyway.
+fn stopping_error(function: &FunctionDeclaration) -> Option<ParserError> {
+    function
+        .error
+        .clone()
+        .filter(|err| !(is_reserved_name(err) && function.id.error.as_ref() == Some(err)))
 }
 
 /*
@@ -18,20 +75,60 @@ impl Parser {
         Parser {
             tokens,
             index: 0,
-            has_error: false,
+            errors: Vec::new(),
+            reported_unclosed: false,
+            lazy_bodies: false,
         }
     }
 
+    /// Leaves the body of every function unparsed, to be parsed on demand
+    /// with [`FunctionDeclaration::parse_body`]. Everything else, including
+    /// the signatures of the functions, is parsed as usual.
+    pub fn lazy_bodies(mut self) -> Self {
+        self.lazy_bodies = true;
+        self
+    }
+
+    /// Lexes and parses `src` as a standalone expression, returning the
+    /// expression along with every error encountered.
+    pub fn parse_expression_str(src: &str) -> (Box<Expression>, Vec<ParserError>) {
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let expression = parser.parse_expression();
+        parser.check_trailing_tokens();
+        (expression, parser.errors)
+    }
+
+    /// Lexes and parses `src` as a standalone type, returning the type along
+    /// with every error encountered.
+    pub fn parse_type_str(src: &str) -> (Box<Type>, Vec<ParserError>) {
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let parsed_type = parser.parse_type();
+        parser.check_trailing_tokens();
+        (parsed_type, parser.errors)
+    }
+
     pub fn has_error(&self) -> bool {
-        self.has_error
+        !self.errors.is_empty()
+    }
+
+    /// Returns every error encountered so far, in the order they were found.
+    pub fn errors(&self) -> &[ParserError] {
+        &self.errors
     }
 
     fn eof(&self) -> bool {
-        self.index >= self.tokens.len()
+        self.index >= self.tokens.len() || self.tokens[self.index] == Token::Eof
     }
 
     fn current(&self) -> Token {
-        self.tokens[self.index].clone()
+        self.tokens.get(self.index).cloned().unwrap_or(Token::Eof)
+    }
+
+    fn peek(&self) -> Token {
+        self.tokens
+            .get(self.index + 1)
+            .cloned()
+            .unwrap_or(Token::Eof)
     }
 
     fn check(&self, lexeme: &str) -> bool {
@@ -39,38 +136,190 @@ impl Parser {
     }
 
     fn advance(&mut self) {
-        self.index += 1
+        if !self.eof() {
+            self.index += 1
+        }
+    }
+
+    /// Consumes and returns the current token if it is `lexeme`. Otherwise
+    /// reports it as missing, with `context` saying what it is for, as in
+    /// "Expected '(' to open the parameter list, found '{'.".
+    fn expect(&mut self, lexeme: &str, context: &str) -> Result<Token, ParserError> {
+        let token = self.current();
+        if token.get_lexeme() == lexeme {
+            self.advance();
+            return Ok(token);
+        }
+        Err(self.error(ParserError::MissingToken(
+            token.get_line(),
+            token.get_col(),
+            format!(
+                "Expected '{}' {}, found '{}'.",
+                lexeme,
+                context,
+                token.get_lexeme()
+            ),
+        )))
+    }
+
+    /// Skips tokens until the current one is of one of `kinds`, or up to the
+    /// end of the input, so that parsing resumes at a token it can make sense
+    /// of.
+    fn recover_to(&mut self, kinds: &[TokenKind]) {
+        while !self.eof() && !kinds.iter().any(|&kind| self.current().is(kind)) {
+            self.advance();
+        }
+    }
+
+    /// Records that an error was encountered and hands it back so that it can
+    /// be stored in the node being parsed.
+    fn error(&mut self, err: ParserError) -> ParserError {
+        self.errors.push(err.clone());
+        err
+    }
+
+    /// Returns the first error recorded since the error count was `mark`.
+    fn error_since(&self, mark: usize) -> Option<ParserError> {
+        self.errors.get(mark).cloned()
+    }
+
+    /// Returns whether the statement being parsed ends before the current
+    /// token, following [`SEMICOLON_POLICY`].
+    fn at_statement_end(&self) -> bool {
+        self.check(";")
+            || SEMICOLON_POLICY == SemicolonPolicy::NewlineOrSemicolon
+                && (self.at_new_line() || self.check("}"))
+    }
+
+    /// Consumes the `;` terminating a statement, if it has one.
+    fn end_statement(&mut self) -> Option<ParserError> {
+        if self.at_statement_end() {
+            if self.check(";") {
+                self.advance();
+            }
+            return None;
+        }
+        Some(self.error(ParserError::MissingToken(
+            self.current().get_line(),
+            self.current().get_col(),
+            format!(
+                "Expected a separator ';', found '{}'.",
+                self.current().get_lexeme()
+            ),
+        )))
+    }
+
+    /// Returns whether the current token starts a later line than the one the
+    /// previous token is on. The end of the input counts as a new line.
+    fn at_new_line(&self) -> bool {
+        let Some(previous) = self.index.checked_sub(1).and_then(|i| self.tokens.get(i)) else {
+            return false;
+        };
+        self.eof() || self.current().get_line() > previous.get_line()
+    }
+
+    /// Returns the error for the `{` token `open`, which is still open at the
+    /// end of the input. Blocks nested in one another all end there when a
+    /// `}` is missing, so only the innermost one, which reaches the end first,
+    /// records its error.
+    fn unclosed(&mut self, open: &Token) -> ParserError {
+        let err = ParserError::UnclosedDelimiter(
+            open.get_line(),
+            open.get_col(),
+            open.get_lexeme().to_string(),
+        );
+        if self.reported_unclosed {
+            return err;
+        }
+        self.reported_unclosed = true;
+        self.error(err)
+    }
+
+    /// Reports anything left over after parsing a standalone fragment.
+    fn check_trailing_tokens(&mut self) {
+        if !self.eof() {
+            self.error(ParserError::UnexpectedToken(
+                self.current().get_line(),
+                self.current().get_col(),
+                self.current().get_lexeme().to_string(),
+            ));
+        }
+    }
+
+    /// Parses the comma-separated items of a list opened by `open` with `item`,
+    /// up to and including the `close` token. A trailing comma is allowed, but
+    /// an empty item, as in `a,,b`, is not. Returns the first error of the
+    /// list.
+    fn parse_comma_separated(
+        &mut self,
+        open: &Token,
+        close: &str,
+        mut item: impl FnMut(&mut Self),
+    ) -> Option<ParserError> {
+        while !self.check(close) {
+            if self.eof() {
+                return Some(self.unclosed(open));
+            }
+            let mark = self.errors.len();
+            item(self);
+            if let Some(err) = self.error_since(mark) {
+                return Some(err);
+            }
+
+            if self.check(",") {
+                self.advance();
+            } else if !self.check(close) {
+                return Some(self.error(ParserError::MissingToken(
+                    self.current().get_line(),
+                    self.current().get_col(),
+                    format!(
+                        "Expected a separator ',' or '{}', found '{}'.",
+                        close,
+                        self.current().get_lexeme()
+                    ),
+                )));
+            }
+        }
+        self.advance(); // skip the closing token
+        None
     }
 
     fn parse_identifier(&mut self) -> Box<Identifier> {
         if self.eof() {
-            let x = Box::new(Identifier {
+            let err = self.error(ParserError::UnexpectedEOF(
+                self.current().get_line(),
+                self.current().get_col(),
+                self.current().get_lexeme().to_string(),
+            ));
+            return Box::new(Identifier {
                 id: None,
-                error: Some(ParserError::UnexpectedEOF(
-                    self.current().get_line(),
-                    self.current().get_col(),
-                    self.current().get_lexeme().to_string(),
-                )),
+                error: Some(err),
             });
-            return x;
         }
-        match self.current() {
-            Token::Identifier(_, _, _) => {
-                self.advance();
-                Box::new(Identifier {
-                    id: Some(self.current()),
-                    error: None,
-                })
-            }
+        let token = self.current();
+        self.advance();
+        match token {
+            Token::Identifier(_, _, _) => Box::new(Identifier {
+                id: Some(token),
+                error: None,
+            }),
+            Token::Keyword(line, col, word) => Box::new(Identifier {
+                id: None,
+                error: Some(self.error(ParserError::ReservedKeyword(line, col, word))),
+            }),
+            Token::DataType(line, col, name) => Box::new(Identifier {
+                id: None,
+                error: Some(self.error(ParserError::ReservedTypeName(line, col, name))),
+            }),
             _ => {
-                self.advance();
+                let err = self.error(ParserError::InvalidSyntax(
+                    token.get_line(),
+                    token.get_col(),
+                    token.get_lexeme().to_string(),
+                ));
                 Box::new(Identifier {
                     id: None,
-                    error: Some(ParserError::InvalidSyntax(
-                        self.current().get_line(),
-                        self.current().get_col(),
-                        self.current().get_lexeme().to_string(),
-                    )),
+                    error: Some(err),
                 })
             }
         }
@@ -80,168 +329,2600 @@ impl Parser {
         if !self.check("<") {
             return None;
         }
-        let mut gp = Box::new(GenericParameters {
-            generics: Vec::new(),
-            error: None,
-        });
-        while !self.check(">") {
-            if !self.check("type") {
-                gp.error = Some(ParserError::InvalidSyntax(
-                    self.current().get_line(),
-                    self.current().get_line(),
-                    String::from(format!(
-                        "Expected a 'type' keyword, found '{}'.",
-                        self.current().get_lexeme()
-                    )),
-                ));
-                return Some(gp);
-            }
-
-            let id = self.parse_identifier();
-            let id2: Option<Box<Identifier>>;
-            if !self.check("impl") {
-                if !self.check(",") {
-                    gp.error = Some(ParserError::InvalidSyntax(
-                        self.current().get_line(),
-                        self.current().get_col(),
-                        String::from(format!(
-                            "Expected a keyword 'impl' or a separator ',', found '{}'.",
-                            self.current().get_lexeme()
-                        )),
-                    ));
-                    return Some(gp);
-                }
-            } else {
-                id2 = Some(self.parse_identifier());
+        let open = self.current();
+        self.advance(); // skip '<'
+
+        let mut generics = Vec::new();
+        let error = self.parse_comma_separated(&open, ">", |parser| {
+            if parser
+                .expect("type", "to declare a generic parameter")
+                .is_err()
+            {
+                return;
             }
 
-            if !self.check(",") {
-                gp.error = Some(ParserError::InvalidSyntax(
-                    self.current().get_line(),
-                    self.current().get_col(),
-                    format!(
-                        "Expected a separator ',' , found '{}'.",
-                        self.current().get_lexeme()
-                    ),
-                ));
+            let id = parser.parse_identifier();
+            if parser.check("impl") {
+                parser.advance();
+                let bound = parser.parse_identifier();
+                generics.push(Box::new(GenericVariants::Implements(id, bound)));
+            } else {
+                generics.push(Box::new(GenericVariants::Identifier(id)));
             }
+        });
+
+        Some(Box::new(GenericParameters { generics, error }))
+    }
+
+    /// Parses the types given for the generic parameters of a type where it
+    /// is used, such as the `<u8, type T>` of `enum Pair<u8, type T>`.
+    fn parse_generic_arguments(&mut self) -> Option<Box<GenericParameters>> {
+        if !self.check("<") {
+            return None;
         }
+        let open = self.current();
+        self.advance(); // skip '<'
 
-        return Some(gp);
+        let mut generics = Vec::new();
+        let error = self.parse_comma_separated(&open, ">", |parser| {
+            generics.push(Box::new(GenericVariants::Type(parser.parse_type())));
+            parser.split_closing_shift();
+        });
+        Some(Box::new(GenericParameters { generics, error }))
     }
 
-    fn parse_fn_parameters(&mut self) -> Option<Vec<(Box<Type>, Box<Identifier>)>> {
-        todo!();
+    /// Splits a `>>` closing two lists of generic arguments at once, as in
+    /// `enum A<enum B<u8>>`, into two `>`.
+    fn split_closing_shift(&mut self) {
+        if let Token::Operator(line, col, lexeme) = self.current() {
+            if lexeme == ">>" {
+                self.tokens[self.index] = Token::Operator(line, col, String::from(">"));
+                let second = Token::Operator(line, col + 1, String::from(">"));
+                self.tokens.insert(self.index + 1, second);
+            }
+        }
     }
 
-    fn parse_block(&mut self) -> Result<Box<Block>, ParserError> {
-        todo!();
+    fn parse_type(&mut self) -> Box<Type> {
+        let mark = self.errors.len();
+        let token = self.current();
+        let variant = match token {
+            Token::DataType(_, _, ref lexeme) => {
+                self.advance();
+                TypeVariant::Primitive(lexeme.clone())
+            }
+            _ if self.check("void") => {
+                self.advance();
+                TypeVariant::Primitive(String::from("void"))
+            }
+            _ if self.check("ref") => {
+                self.advance();
+                TypeVariant::Reference(self.parse_type().variant)
+            }
+            _ if self.check("[") => self.parse_array_type(),
+            _ if self.check("struct") => {
+                self.advance();
+                TypeVariant::Structure(self.parse_identifier(), self.parse_generic_arguments())
+            }
+            _ if self.check("enum") => {
+                self.advance();
+                TypeVariant::Enumeration(self.parse_identifier(), self.parse_generic_arguments())
+            }
+            _ if self.check("intf") => {
+                self.advance();
+                TypeVariant::Interface(self.parse_identifier(), self.parse_generic_arguments())
+            }
+            _ if self.check("type") => {
+                self.advance();
+                TypeVariant::Generic(self.parse_identifier())
+            }
+            _ => {
+                let err = self.error(ParserError::InvalidSyntax(
+                    token.get_line(),
+                    token.get_col(),
+                    format!("Expected a type, found '{}'.", token.get_lexeme()),
+                ));
+                self.advance();
+                TypeVariant::Error(err)
+            }
+        };
+        Box::new(Type {
+            variant: Box::new(variant),
+            error: self.error_since(mark),
+        })
     }
 
-    fn parse_fn(&mut self, is_pub: bool, is_const: bool) -> Box<FunctionDeclaration> {
-        self.advance(); // skip 'fn'
-        let id = self.parse_identifier();
-        if id.error.is_some() {
-            return Box::new(FunctionDeclaration {
-                id: id.clone(),
-                is_pub,
-                is_const,
-                generics: None,
-                parameters: None,
-                block: Box::new(Block {
-                    statements: Vec::new(),
-                }),
-                error: id.error,
-            });
+    /// Parses an array type of the form `[T; N]`.
+    fn parse_array_type(&mut self) -> TypeVariant {
+        self.advance(); // skip '['
+        let element = self.parse_type();
+        if element.error.is_some() {
+            return *element.variant;
         }
-        let generics = self.parse_generic_parameters();
-        if generics.is_some() && generics.as_ref().unwrap().error.is_some() {
-            return Box::new(FunctionDeclaration {
-                id,
-                is_pub,
-                is_const,
-                generics: None,
-                parameters: None,
-                block: Box::new(Block {
-                    statements: Vec::new(),
-                }),
-                error: generics.unwrap().error,
-            });
+
+        if !self.check(";") {
+            return TypeVariant::Error(self.error(ParserError::MissingToken(
+                self.current().get_line(),
+                self.current().get_col(),
+                format!(
+                    "Expected a separator ';' after the array element type, found '{}'.",
+                    self.current().get_lexeme()
+                ),
+            )));
         }
-        let parameters = self.parse_fn_parameters();
-        let block = self.parse_block();
-        Box::new(FunctionDeclaration {
-            id,
-            is_pub,
-            is_const,
-            generics,
-            parameters,
-            block: block.expect(""),
-            error: None,
-        })
+        self.advance(); // skip ';'
+
+        let size = self.parse_expression();
+        if !self.check("]") {
+            return TypeVariant::Error(self.error(ParserError::MissingToken(
+                self.current().get_line(),
+                self.current().get_col(),
+                format!(
+                    "Expected ']' to close the array type, found '{}'.",
+                    self.current().get_lexeme()
+                ),
+            )));
+        }
+        self.advance(); // skip ']'
+
+        TypeVariant::Array(element.variant, size)
     }
 
-    fn parse_declaration(&mut self) -> Box<Declaration> {
-        let is_pub = self.check("pub");
-        if is_pub {
+    /// Parses an expression, which may be a range. Ranges bind more loosely
+    /// than every operator and do not chain, so `0..n + 1` ends at `n + 1`.
+    fn parse_expression(&mut self) -> Box<Expression> {
+        let start = self.parse_binary_expression(1);
+        if !self.check("..") && !self.check("..=") {
+            return start;
+        }
+        let operator = self.current();
+        self.advance();
+        if let Some(err) = self.expect_operand(&operator) {
+            return Box::new(Expression::Error(err));
+        }
+        let end = self.parse_binary_expression(1);
+        Box::new(Expression::Primary(Box::new(Primary::Range(Box::new(
+            Range {
+                start,
+                end,
+                is_inclusive: operator.get_lexeme() == "..=",
+            },
+        )))))
+    }
+
+    /// Parses binary operations by precedence climbing: operators binding at
+    /// least as tightly as `min_precedence` are folded into the left operand,
+    /// which keeps every binary operator left-associative.
+    fn parse_binary_expression(&mut self, min_precedence: u8) -> Box<Expression> {
+        let mut lhs = self.parse_unary_expression();
+
+        while let Token::Operator(_, _, op) = self.current() {
+            let precedence = match Operator::precedence_of_token(&op) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            let operator = self.current();
             self.advance();
+            if let Some(err) = self.expect_operand(&operator) {
+                return Box::new(Expression::Error(err));
+            }
+            let rhs = self.parse_binary_expression(precedence + 1);
+            lhs = Box::new(Expression::Operation(Box::new(Operator::Binary(
+                op, lhs, rhs,
+            ))));
         }
 
-        let is_const = self.check("const");
+        lhs
+    }
 
-        if self.check("fn") {
-            return Box::new(Declaration::Function(self.parse_fn(is_pub, is_const)));
+    /// Returns whether `token` is a prefix operator.
+    fn is_unary_operator(token: &Token) -> bool {
+        match token {
+            Token::Operator(_, _, ref op) => matches!(op.as_str(), "+" | "-" | "!" | "~"),
+            Token::Keyword(_, _, ref keyword) => keyword == "ref" || keyword == "deref",
+            _ => false,
         }
+    }
 
-        if self.check("enum") {
-            if is_const {
-                return Box::new(Declaration::Error(ParserError::InvalidSyntax(
-                    self.current().get_line(),
-                    self.current().get_col(),
-                    String::from("The `const` keyword cannot be used with `enum` types."),
-                )));
+    /// Returns whether the current token can start an expression.
+    fn at_expression_start(&self) -> bool {
+        let token = self.current();
+        Parser::is_unary_operator(&token)
+            || Parser::literal(&token).is_some()
+            || matches!(token, Token::Identifier(_, _, _))
+            || self.check("(")
+            || self.check("match")
+            || self.at_loop()
+    }
+
+    /// Returns whether the current token starts a loop, which may be labeled.
+    fn at_loop(&self) -> bool {
+        matches!(self.current(), Token::Label(_, _, _)) || self.check("loop")
+    }
+
+    /// Reports an operator that is not followed by its operand, at the
+    /// operator. Binary operators in the way, as in `1 + * 2`, are reported
+    /// and skipped instead, leaving the operand after them to be parsed.
+    fn expect_operand(&mut self, operator: &Token) -> Option<ParserError> {
+        let mark = self.errors.len();
+        while let Token::Operator(line, col, op) = self.current() {
+            if Operator::precedence_of_token(&op).is_none() || self.at_expression_start() {
+                break;
+            }
+            if self.errors.len() == mark {
+                self.error(ParserError::InvalidSyntax(
+                    line,
+                    col,
+                    format!("Unexpected operator '{}' without a left operand.", op),
+                ));
             }
+            self.advance();
         }
+        if self.at_expression_start() {
+            return None;
+        }
+        Some(self.error(ParserError::MissingToken(
+            operator.get_line(),
+            operator.get_col(),
+            format!("Expected an expression after '{}'.", operator.get_lexeme()),
+        )))
+    }
 
-        if self.check("struct") {
-            if is_const {
-                return Box::new(Declaration::Error(ParserError::InvalidSyntax(
-                    self.current().get_line(),
-                    self.current().get_col(),
-                    String::from("The `const` keyword cannot be used with `struct` types."),
-                )));
+    fn parse_unary_expression(&mut self) -> Box<Expression> {
+        let token = self.current();
+        if !Parser::is_unary_operator(&token) {
+            // A binary operator here has no left operand, as in `* 2`.
+            if let Token::Operator(line, col, ref op) = token {
+                if Operator::precedence_of_token(op).is_some() {
+                    return Box::new(Expression::Error(self.error(ParserError::InvalidSyntax(
+                        line,
+                        col,
+                        format!("Unexpected operator '{}' without a left operand.", op),
+                    ))));
+                }
+            }
+            return Box::new(Expression::Primary(self.parse_primary()));
+        }
+
+        self.advance();
+        if let Some(err) = self.expect_operand(&token) {
+            return Box::new(Expression::Error(err));
+        }
+        let operand = self.parse_unary_expression();
+        Box::new(Expression::Operation(Box::new(Operator::Unary(
+            token.get_lexeme().to_string(),
+            operand,
+        ))))
+    }
+
+    /// Returns the literal `token` stands for, if it is one.
+    fn literal(token: &Token) -> Option<Literal> {
+        match token {
+            Token::IntLiteral(_, _, _) => Some(Literal::Integer(token.clone())),
+            Token::FloatLiteral(_, _, _) => Some(Literal::Float(token.clone())),
+            Token::StringLiteral(_, _, _) => Some(Literal::String(token.clone())),
+            Token::CharLiteral(_, _, _) => Some(Literal::Character(token.clone())),
+            Token::Keyword(_, _, ref keyword) if keyword == "true" || keyword == "false" => {
+                Some(Literal::Boolean(token.clone()))
+            }
+            Token::Keyword(_, _, ref keyword) if keyword == "null" => {
+                Some(Literal::Null(token.clone()))
             }
+            _ => None,
+        }
+    }
+
+    /// Returns a `-` followed by a numeric literal as a single literal that
+    /// starts at the `-`, for the places where only a literal is allowed. In
+    /// expressions the `-` stays a unary operator, so `a - 1` is a subtraction.
+    fn negative_literal(&self) -> Option<Literal> {
+        let minus = self.current();
+        if !matches!(minus, Token::Operator(_, _, ref op) if op == "-") {
+            return None;
+        }
+        match self.peek() {
+            Token::IntLiteral(_, _, value) => Some(Literal::Integer(Token::IntLiteral(
+                minus.get_line(),
+                minus.get_col(),
+                format!("-{}", value),
+            ))),
+            Token::FloatLiteral(_, _, value) => Some(Literal::Float(Token::FloatLiteral(
+                minus.get_line(),
+                minus.get_col(),
+                format!("-{}", value),
+            ))),
+            _ => None,
+        }
+    }
+
+    /// Parses a primary expression along with the field accesses following
+    /// it, which bind more tightly than every operator.
+    fn parse_primary(&mut self) -> Box<Primary> {
+        let mut primary = self.parse_operand();
+        while self.check(".") && !matches!(*primary, Primary::Error(_)) {
+            primary = self.parse_field_access(primary);
+        }
+        primary
+    }
+
+    /// Parses the member after a `.` and returns it as a member of `base`.
+    fn parse_field_access(&mut self, base: Box<Primary>) -> Box<Primary> {
+        let dot = self.current();
+        self.advance(); // skip '.'
+        if !matches!(self.current(), Token::Identifier(_, _, _)) {
+            return Box::new(Primary::Error(self.error(ParserError::MissingToken(
+                dot.get_line(),
+                dot.get_col(),
+                format!(
+                    "Expected a field name after '.', found '{}'.",
+                    self.current().get_lexeme()
+                ),
+            ))));
+        }
+        let member = self.parse_named_operand();
+        Box::new(Primary::FieldAccess(
+            Box::new(Expression::Primary(base)),
+            member,
+        ))
+    }
+
+    /// Parses a primary expression without the field accesses following it.
+    fn parse_operand(&mut self) -> Box<Primary> {
+        if self.check("match") {
+            return Box::new(Primary::Match(Box::new(self.parse_match_node(true))));
+        }
+
+        let token = self.current();
+        if self.at_loop() {
+            return match self.parse_loop_node() {
+                Ok(loop_statement) if loop_statement.iteration.is_some() => {
+                    Box::new(Primary::Error(self.error(ParserError::InvalidSyntax(
+                        token.get_line(),
+                        token.get_col(),
+                        String::from(
+                            "A loop over a range cannot be used as a value, since it can end without a `break`.",
+                        ),
+                    ))))
+                }
+                Ok(loop_statement) => Box::new(Primary::Loop(Box::new(loop_statement))),
+                Err(err) => Box::new(Primary::Error(err)),
+            };
         }
 
-        if self.check("intf") {
-            if is_const {
-                return Box::new(Declaration::Error(ParserError::InvalidSyntax(
+        if let Some(literal) = Parser::literal(&token) {
+            self.advance();
+            return Box::new(Primary::Literal(Box::new(literal)));
+        }
+
+        if let Token::Identifier(_, _, _) = token {
+            return self.parse_named_operand();
+        }
+
+        if self.check("(") {
+            self.advance(); // skip '('
+            let expression = self.parse_expression();
+            if !self.check(")") {
+                return Box::new(Primary::Error(self.error(ParserError::MissingToken(
                     self.current().get_line(),
                     self.current().get_col(),
-                    String::from("The `const` keyword cannot be used with `intf` types."),
-                )));
+                    format!(
+                        "Expected ')' to close the group, found '{}'.",
+                        self.current().get_lexeme()
+                    ),
+                ))));
             }
+            self.advance(); // skip ')'
+            return Box::new(Primary::Group(expression));
+        }
+
+        if self.eof() {
+            return Box::new(Primary::Error(self.error(ParserError::UnexpectedEOF(
+                token.get_line(),
+                token.get_col(),
+                String::from("Expected an expression."),
+            ))));
+        }
+
+        Box::new(Primary::Error(self.error(ParserError::UnexpectedToken(
+            token.get_line(),
+            token.get_col(),
+            token.get_lexeme().to_string(),
+        ))))
+    }
+
+    /// Parses an identifier, or the function call or array access it starts.
+    fn parse_named_operand(&mut self) -> Box<Primary> {
+        if self.peek().get_lexeme() == "(" {
+            return Box::new(Primary::FunctionCall(self.parse_function_call()));
         }
+        let id = self.parse_identifier();
+        if self.check("[") {
+            let access = self.parse_array_access(1);
+            debug_assert!(access.has_consistent_levels(1));
+            return Box::new(Primary::ArrayAccess(id, access));
+        }
+        Box::new(Primary::Identifier(id))
+    }
+
+    fn parse_function_call(&mut self) -> FunctionCall {
+        let id = self.parse_identifier();
+        let open = self.current();
+        self.advance(); // skip '('
+
+        let mut args = Vec::new();
+        let error =
+            self.parse_comma_separated(&open, ")", |parser| args.push(parser.parse_expression()));
+        FunctionCall { id, args, error }
+    }
+
+    /// Parses a chain of `[index]` accesses, where `level` is the position of
+    /// the access in the chain, starting from 1.
+    fn parse_array_access(&mut self, level: u32) -> Box<ArrayAccess> {
+        self.advance(); // skip '['
+        let mut access = Box::new(ArrayAccess {
+            level,
+            index: self.parse_expression(),
+            next: None,
+            error: None,
+        });
 
-        Box::new(Declaration::Error(
-            crate::utils::ParserError::UnexpectedToken(
+        if !self.check("]") {
+            access.error = Some(self.error(ParserError::MissingToken(
                 self.current().get_line(),
                 self.current().get_col(),
-                self.current().get_lexeme().to_string(),
-            ),
-        ))
+                format!(
+                    "Expected ']' to close the array access, found '{}'.",
+                    self.current().get_lexeme()
+                ),
+            )));
+            return access;
+        }
+        self.advance(); // skip ']'
+
+        if self.check("[") {
+            access.next = Some(self.parse_array_access(level + 1));
+        }
+        access
     }
 
-    pub fn parse(&mut self) -> Box<AST> {
-        let mut ast = Box::new(AST {
-            declarations: Vec::new(),
+    /// Parses the parameter list of `function`, which may start with the
+    /// `self` receiver of a method.
+    fn parse_fn_parameters(
+        &mut self,
+        function: &mut FunctionDeclaration,
+    ) -> Result<(), ParserError> {
+        let open = self.expect("(", "to open the parameter list")?;
+
+        let mut receiver = None;
+        let mut parameters = Vec::new();
+        let error = self.parse_comma_separated(&open, ")", |parser| {
+            if !parser.at_receiver() {
+                parameters.push((parser.parse_type(), parser.parse_identifier()));
+                return;
+            }
+            let token = parser.current();
+            let parsed = parser.parse_receiver();
+            if receiver.is_some() || !parameters.is_empty() {
+                parser.error(ParserError::InvalidSyntax(
+                    token.get_line(),
+                    token.get_col(),
+                    String::from("The receiver 'self' must be the first parameter."),
+                ));
+            }
+            receiver = Some(parsed);
         });
+        if let Some(err) = error {
+            return Err(err);
+        }
 
-        while !self.eof() {
-            ast.declarations.push(self.parse_declaration());
-            self.index += 1;
+        function.receiver = receiver;
+        if !parameters.is_empty() {
+            function.parameters = Some(parameters);
         }
+        Ok(())
+    }
 
-        ast
+    /// Returns whether the current token starts a `self` or `ref self`
+    /// receiver.
+    fn at_receiver(&self) -> bool {
+        self.check("self") || (self.check("ref") && self.peek().get_lexeme() == "self")
+    }
+
+    fn parse_receiver(&mut self) -> Box<Receiver> {
+        let is_ref = self.check("ref");
+        if is_ref {
+            self.advance(); // skip 'ref'
+        }
+        Box::new(Receiver {
+            id: self.parse_identifier(),
+            is_ref,
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<Box<Block>, ParserError> {
+        self.parse_block_with(false)
+    }
+
+    /// Parses a block. If `allows_value` is set, the block may end with an
+    /// expression without a `;`, which becomes the value of the block.
+    fn parse_block_with(&mut self, allows_value: bool) -> Result<Box<Block>, ParserError> {
+        let open = self.expect("{", "to open a block")?;
+
+        let mut block = Block::new(Vec::new());
+        while !self.check("}") {
+            if self.eof() {
+                return Err(self.unclosed(&open));
+            }
+            if self.check(";") {
+                self.advance(); // skip an empty statement
+                continue;
+            }
+            if !allows_value || !self.at_block_value() {
+                let statement = self.parse_statement();
+                // A loop the block ends with is its value, like a `match`.
+                match statement {
+                    Statement::Loop(loop_statement)
+                        if allows_value
+                            && loop_statement.iteration.is_none()
+                            && self.check("}") =>
+                    {
+                        block.value = Some(Box::new(Expression::Primary(Box::new(Primary::Loop(
+                            Box::new(loop_statement),
+                        )))));
+                    }
+                    statement => block.statements.push(statement),
+                }
+                continue;
+            }
+
+            let mark = self.errors.len();
+            let expr = self.parse_expression();
+            if self.check("}") {
+                block.value = Some(expr);
+            } else {
+                let statement = self.value_to_statement(*expr, mark);
+                block.statements.push(statement);
+            }
+        }
+        self.advance(); // skip '}'
+
+        Ok(block)
+    }
+
+    /// Returns whether the current token starts an expression rather than a
+    /// declaration or an assignment. Calls and `match` can be either, so they
+    /// are parsed as expressions and turned back into statements if they are
+    /// not the value of the block.
+    fn at_block_value(&self) -> bool {
+        let is_assignment =
+            matches!(self.current(), Token::Identifier(_, _, _)) && self.peek().get_lexeme() == "=";
+        self.at_expression_start()
+            && !self.at_var_declaration()
+            && !is_assignment
+            && !self.at_loop()
+    }
+
+    /// Turns an expression that is followed by more of its block into the
+    /// statement it starts. `mark` is the error count from before the
+    /// expression.
+    fn value_to_statement(&mut self, expr: Expression, mark: usize) -> Statement {
+        if let Some(err) = self.error_since(mark) {
+            return Statement::Error(err);
+        }
+        if let Expression::Primary(primary) = expr {
+            match *primary {
+                Primary::FunctionCall(call) => {
+                    return match self.end_statement() {
+                        Some(err) => Statement::Error(err),
+                        None => Statement::FunctionCall(call),
+                    };
+                }
+                Primary::Match(statement) => return Statement::Match(*statement),
+                _ => {}
+            }
+        }
+
+        let err = self.error(ParserError::MissingToken(
+            self.current().get_line(),
+            self.current().get_col(),
+            format!(
+                "Expected '}}' after the value of the block, found '{}'.",
+                self.current().get_lexeme()
+            ),
+        ));
+        self.advance();
+        Statement::Error(err)
+    }
+
+    fn parse_loop(&mut self) -> Statement {
+        match self.parse_loop_node() {
+            Ok(loop_statement) => Statement::Loop(loop_statement),
+            Err(err) => Statement::Error(err),
+        }
+    }
+
+    fn parse_loop_node(&mut self) -> Result<LoopStatement, ParserError> {
+        let label = match self.current() {
+            Token::Label(_, _, _) => {
+                let label = self.current();
+                self.advance();
+                Some(label)
+            }
+            _ => None,
+        };
+
+        self.expect("loop", "after a label")?;
+
+        let iteration = if self.check("{") {
+            None
+        } else {
+            Some(self.parse_range_iteration()?)
+        };
+
+        Ok(LoopStatement {
+            label,
+            iteration,
+            block: self.parse_block()?,
+        })
+    }
+
+    /// Parses the `i in 0..10` of a loop over a range.
+    fn parse_range_iteration(&mut self) -> Result<RangeIteration, ParserError> {
+        let variable = self.parse_identifier();
+        if let Some(err) = variable.error.clone() {
+            return Err(err);
+        }
+        self.expect("in", "after the loop variable")?;
+
+        let start = self.current();
+        let mark = self.errors.len();
+        let expr = self.parse_expression();
+        if let Some(err) = self.error_since(mark) {
+            return Err(err);
+        }
+        if let Expression::Primary(primary) = *expr {
+            if let Primary::Range(range) = *primary {
+                return Ok(RangeIteration { variable, range });
+            }
+        }
+        Err(self.error(ParserError::InvalidSyntax(
+            start.get_line(),
+            start.get_col(),
+            String::from("Expected a range to loop over, such as '0..10'."),
+        )))
+    }
+
+    /// Parses a `break` or `continue`, along with the label it targets and
+    /// the value a `break` gives its loop.
+    fn parse_jump(&mut self) -> Statement {
+        let is_break = self.check("break");
+        self.advance(); // skip 'break' or 'continue'
+
+        let label = match self.current() {
+            Token::Label(_, _, _) => {
+                let label = self.current();
+                self.advance();
+                Some(label)
+            }
+            _ => None,
+        };
+
+        let mark = self.errors.len();
+        let value = if is_break && !self.at_statement_end() && self.at_expression_start() {
+            Some(self.parse_expression())
+        } else {
+            None
+        };
+        if let Some(err) = self.error_since(mark).or_else(|| self.end_statement()) {
+            return Statement::Error(err);
+        }
+
+        if is_break {
+            Statement::Break(label, value)
+        } else {
+            Statement::Continue(label)
+        }
+    }
+
+    /// Parses a `ret` statement with its optional value.
+    fn parse_return(&mut self) -> Statement {
+        self.advance(); // skip 'ret'
+        let mark = self.errors.len();
+
+        let mut statement = ReturnStatement {
+            expr: None,
+            error: None,
+        };
+        if !self.at_statement_end() {
+            statement.expr = Some(self.parse_expression());
+        }
+        if self.error_since(mark).is_none() {
+            self.end_statement();
+        }
+        statement.error = self.error_since(mark);
+
+        Statement::Return(statement)
+    }
+
+    fn parse_if(&mut self) -> Statement {
+        self.advance(); // skip 'if'
+        let mark = self.errors.len();
+
+        let mut statement = IfStatement {
+            condition: self.parse_expression(),
+            if_block: Box::default(),
+            elif_statements: None,
+            else_block: None,
+            error: None,
+        };
+        if let Some(err) = self.error_since(mark) {
+            statement.error = Some(err);
+            return Statement::If(statement);
+        }
+        match self.parse_block() {
+            Ok(block) => statement.if_block = block,
+            Err(err) => {
+                statement.error = Some(err);
+                return Statement::If(statement);
+            }
+        }
+
+        let mut elif_statements = Vec::new();
+        while self.check("elif") {
+            self.advance(); // skip 'elif'
+            let mark = self.errors.len();
+
+            let condition = self.parse_expression();
+            let block = match self.error_since(mark) {
+                Some(_) => Box::default(),
+                None => self.parse_block().unwrap_or_else(|_| Box::default()),
+            };
+            let error = self.error_since(mark);
+            elif_statements.push(Box::new(ElifStatement {
+                condition,
+                block,
+                error: error.clone(),
+            }));
+
+            if error.is_some() {
+                statement.error = error;
+                break;
+            }
+        }
+        if !elif_statements.is_empty() {
+            statement.elif_statements = Some(elif_statements);
+        }
+
+        if statement.error.is_none() && self.check("else") {
+            self.advance(); // skip 'else'
+            match self.parse_block() {
+                Ok(block) => statement.else_block = Some(block),
+                Err(err) => statement.error = Some(err),
+            }
+        }
+
+        Statement::If(statement)
+    }
+
+    /// Reports an `elif` or `else` that does not follow an `if` and skips
+    /// the clause it starts, so that its condition and block are not parsed
+    /// as more errors. Returns `None` at any other token.
+    fn skip_dangling_clause(&mut self) -> Option<ParserError> {
+        let (line, col) = (self.current().get_line(), self.current().get_col());
+        let err = match self.current() {
+            Token::Keyword(_, _, keyword) if keyword == "elif" => {
+                ParserError::DanglingElif(line, col)
+            }
+            Token::Keyword(_, _, keyword) if keyword == "else" => {
+                ParserError::DanglingElse(line, col)
+            }
+            _ => return None,
+        };
+        let err = self.error(err);
+        self.advance();
+        while !self.eof() && !self.check("{") && !self.check(";") && !self.check("}") {
+            self.advance();
+        }
+        self.skip_body();
+        Some(err)
+    }
+
+    /// Parses `match value { case, case -> block ... default -> block }`, where
+    /// each case is a literal or the name of an enum variant.
+    fn parse_match(&mut self) -> Statement {
+        Statement::Match(self.parse_match_node(false))
+    }
+
+    /// Parses a `match`, whose arms end with a value if it `is_expression`.
+    fn parse_match_node(&mut self, is_expression: bool) -> MatchStatement {
+        self.advance(); // skip 'match'
+        let mark = self.errors.len();
+
+        let mut statement = MatchStatement {
+            expr: self.parse_expression(),
+            case_clauses: Vec::new(),
+            default_clause: None,
+            error: None,
+        };
+        statement.error = match self.error_since(mark) {
+            Some(err) => Some(err),
+            None => self.parse_match_body(&mut statement, is_expression).err(),
+        };
+
+        statement
+    }
+
+    fn parse_match_body(
+        &mut self,
+        statement: &mut MatchStatement,
+        is_expression: bool,
+    ) -> Result<(), ParserError> {
+        let open = self.current();
+        if let Some(err) = self.parse_body_start() {
+            return Err(err);
+        }
+        while !self.check("}") {
+            if self.eof() {
+                return Err(self.unclosed(&open));
+            }
+            if statement.default_clause.is_some() {
+                return Err(self.error(ParserError::MissingToken(
+                    self.current().get_line(),
+                    self.current().get_col(),
+                    format!(
+                        "Expected '}}' after the default clause, found '{}'.",
+                        self.current().get_lexeme()
+                    ),
+                )));
+            }
+
+            if self.check("default") {
+                self.advance(); // skip 'default'
+                self.parse_case_arrow()?;
+                statement.default_clause = Some(self.parse_block_with(is_expression)?);
+                continue;
+            }
+
+            if self.check("->") {
+                return Err(self.error(ParserError::UnexpectedToken(
+                    self.current().get_line(),
+                    self.current().get_col(),
+                    self.current().get_lexeme().to_string(),
+                )));
+            }
+            let mut cases = Vec::new();
+            let error = self.parse_comma_separated(&open, "->", |parser| {
+                if let Ok(case) = parser.parse_pattern() {
+                    cases.push(case);
+                }
+            });
+            if let Some(err) = error {
+                return Err(err);
+            }
+            statement.case_clauses.push(CaseClause {
+                cases,
+                case_block: self.parse_block_with(is_expression)?,
+                error: None,
+            });
+        }
+        self.advance(); // skip '}'
+
+        Ok(())
+    }
+
+    fn parse_case_arrow(&mut self) -> Result<(), ParserError> {
+        self.expect("->", "before the block of a case")?;
+        Ok(())
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, ParserError> {
+        let token = self.current();
+        if let Some(literal) = self.negative_literal() {
+            self.advance();
+            self.advance();
+            return Ok(Pattern::Literal(literal));
+        }
+        if let Some(literal) = Parser::literal(&token) {
+            self.advance();
+            return Ok(Pattern::Literal(literal));
+        }
+        if let Token::Identifier(_, _, _) = token {
+            return Ok(Pattern::Variant(self.parse_identifier()));
+        }
+        Err(self.error(ParserError::UnexpectedToken(
+            token.get_line(),
+            token.get_col(),
+            token.get_lexeme().to_string(),
+        )))
+    }
+
+    /// Parses a variable declaration such as `const i32 x = 5;`.
+    fn parse_var_declaration(&mut self) -> Statement {
+        let mark = self.errors.len();
+        let state = if self.check("volatile") {
+            VariableDeclaration::VOLATILE
+        } else if self.check("const") {
+            VariableDeclaration::CONST
+        } else {
+            VariableDeclaration::PLAIN
+        };
+        if state != VariableDeclaration::PLAIN {
+            self.advance();
+        }
+
+        let mut declaration = VariableDeclaration {
+            state,
+            var_type: self.parse_type(),
+            id: Box::default(),
+            init: None,
+            error: None,
+        };
+        // The rest of the declaration is still parsed after a reserved name.
+        let mut resume = mark;
+        if self.error_since(mark).is_none() {
+            declaration.id = self.parse_identifier();
+            if declaration.id.error.as_ref().is_some_and(is_reserved_name) {
+                resume = self.errors.len();
+            }
+        }
+        if self.error_since(resume).is_none() && self.check("=") {
+            self.advance(); // skip '='
+            declaration.init = Some(self.parse_expression());
+        }
+        if self.error_since(resume).is_none() {
+            self.end_statement();
+        }
+        declaration.error = self.error_since(mark);
+
+        Statement::Var(declaration)
+    }
+
+    fn parse_assignment(&mut self) -> Statement {
+        let mark = self.errors.len();
+        let id = self.parse_identifier();
+        self.advance(); // skip '='
+
+        let expr = self.parse_expression();
+        if self.error_since(mark).is_none() {
+            self.end_statement();
+        }
+        match self.error_since(mark) {
+            Some(err) => Statement::Error(err),
+            None => Statement::Assign(Assignment { id, expr }),
+        }
+    }
+
+    fn parse_call_statement(&mut self) -> Statement {
+        let mark = self.errors.len();
+        let call = self.parse_function_call();
+        if self.error_since(mark).is_none() {
+            self.end_statement();
+        }
+        match self.error_since(mark) {
+            Some(err) => Statement::Error(err),
+            None => Statement::FunctionCall(call),
+        }
+    }
+
+    /// Returns whether the current token can start a variable declaration.
+    fn at_var_declaration(&self) -> bool {
+        matches!(self.current(), Token::DataType(_, _, _))
+            || [
+                "volatile", "const", "ref", "[", "struct", "enum", "intf", "type", "void",
+            ]
+            .iter()
+            .any(|lexeme| self.check(lexeme))
+    }
+
+    fn parse_statement(&mut self) -> Statement {
+        if self.at_loop() {
+            return self.parse_loop();
+        }
+
+        if self.check("break") || self.check("continue") {
+            return self.parse_jump();
+        }
+
+        if self.check("ret") {
+            return self.parse_return();
+        }
+
+        if self.check("if") {
+            return self.parse_if();
+        }
+
+        if self.check("match") {
+            return self.parse_match();
+        }
+
+        if self.at_var_declaration() {
+            return self.parse_var_declaration();
+        }
+
+        if let Token::Identifier(_, _, _) = self.current() {
+            match self.peek().get_lexeme() {
+                "(" => return self.parse_call_statement(),
+                "=" => return self.parse_assignment(),
+                _ => {}
+            }
+        }
+
+        if let Some(err) = self.skip_dangling_clause() {
+            return Statement::Error(err);
+        }
+
+        let err = self.error(ParserError::UnexpectedToken(
+            self.current().get_line(),
+            self.current().get_col(),
+            self.current().get_lexeme().to_string(),
+        ));
+        self.advance();
+        Statement::Error(err)
+    }
+
+    fn parse_fn(&mut self, is_pub: bool, is_const: bool) -> Box<FunctionDeclaration> {
+        let mut function = self.parse_fn_signature(is_pub, is_const);
+        if stopping_error(&function).is_some() {
+            return function;
+        }
+        if self.lazy_bodies {
+            if let Some(body) = self.skip_body() {
+                function.lazy_body = Some(body);
+                return function;
+            }
+        }
+
+        match self.parse_block() {
+            Ok(block) => function.block = block,
+            Err(err) => function.error = Some(err),
+        }
+        function
+    }
+
+    /// Skips the block at the current token, returning its tokens. Returns
+    /// `None` without skipping anything if there is no block or it is never
+    /// closed, so that parsing it reports the error.
+    fn skip_body(&mut self) -> Option<Box<LazyBody>> {
+        if !self.check("{") {
+            return None;
+        }
+        let mut depth = 0usize;
+        let mut end = self.index;
+        loop {
+            match self.tokens.get(end)? {
+                Token::Eof => return None,
+                Token::Separator(_, _, lexeme) if lexeme == "{" => depth += 1,
+                Token::Separator(_, _, lexeme) if lexeme == "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            end += 1;
+        }
+
+        let tokens = self.tokens[self.index..=end].to_vec();
+        let span = Span {
+            start: tokens[0].get_col(),
+            end: tokens[tokens.len() - 1].get_col() + 1,
+        };
+        self.index = end + 1;
+        Some(Box::new(LazyBody { span, tokens }))
+    }
+
+    /// Parses everything of a function up to its body, which is left empty.
+    fn parse_fn_signature(&mut self, is_pub: bool, is_const: bool) -> Box<FunctionDeclaration> {
+        self.advance(); // skip 'fn'
+        let mut function = Box::new(FunctionDeclaration {
+            id: self.parse_identifier(),
+            is_pub,
+            is_const,
+            is_default: false,
+            generics: None,
+            receiver: None,
+            parameters: None,
+            return_type: None,
+            block: Box::default(),
+            lazy_body: None,
+            attributes: Vec::new(),
+            error: None,
+        });
+        if let Some(err) = function.id.error.clone() {
+            function.error = Some(err);
+            if stopping_error(&function).is_some() {
+                return function;
+            }
+        }
+
+        function.generics = self.parse_generic_parameters();
+        if let Some(err) = function.generics.as_ref().and_then(|g| g.error.clone()) {
+            function.generics = None;
+            function.error = Some(err);
+            return function;
+        }
+
+        if let Err(err) = self.parse_fn_parameters(&mut function) {
+            function.error = Some(err);
+            return function;
+        }
+
+        if self.check("->") {
+            self.advance(); // skip '->'
+            let return_type = self.parse_type();
+            if let Some(err) = return_type.error.clone() {
+                function.error = Some(err);
+                return function;
+            }
+            function.return_type = Some(return_type);
+        }
+
+        function
+    }
+
+    /// Parses the `{` opening the body of an interface or implementation.
+    fn parse_body_start(&mut self) -> Option<ParserError> {
+        self.expect("{", "to open the body").err()
+    }
+
+    /// Parses an interface, whose methods are signatures unless marked with
+    /// `default`, in which case they carry a body.
+    fn parse_interface(&mut self, is_pub: bool) -> Box<InterfaceDeclaration> {
+        self.advance(); // skip 'intf'
+        let mut interface = Box::new(InterfaceDeclaration {
+            id: self.parse_identifier(),
+            is_pub,
+            generics: None,
+            methods: Vec::new(),
+            attributes: Vec::new(),
+            error: None,
+        });
+        if interface.id.error.is_some() {
+            interface.error = interface.id.error.clone();
+            return interface;
+        }
+
+        interface.generics = self.parse_generic_parameters();
+        if let Some(err) = interface.generics.as_ref().and_then(|g| g.error.clone()) {
+            interface.generics = None;
+            interface.error = Some(err);
+            return interface;
+        }
+
+        let open = self.current();
+        if let Some(err) = self.parse_body_start() {
+            interface.error = Some(err);
+            return interface;
+        }
+        while !self.check("}") {
+            if self.eof() {
+                interface.error = Some(self.unclosed(&open));
+                return interface;
+            }
+
+            let attributes = self.parse_attributes();
+            let is_default = self.check("default");
+            if is_default {
+                self.advance();
+            }
+            if !self.check("fn") {
+                interface.error = Some(self.error(ParserError::MissingToken(
+                    self.current().get_line(),
+                    self.current().get_col(),
+                    format!(
+                        "Expected 'fn' to declare an interface method, found '{}'.",
+                        self.current().get_lexeme()
+                    ),
+                )));
+                return interface;
+            }
+
+            let mut method = if is_default {
+                self.parse_fn(false, false)
+            } else {
+                self.parse_fn_signature(false, false)
+            };
+            method.is_default = is_default;
+            method.attributes = attributes;
+            let error = stopping_error(&method);
+            interface.methods.push(method);
+            if error.is_some() {
+                interface.error = error;
+                return interface;
+            }
+        }
+        self.advance(); // skip '}'
+
+        interface
+    }
+
+    /// Parses `impl intf Name for type { methods }`.
+    fn parse_implementation(&mut self) -> Box<Declaration> {
+        self.advance(); // skip 'impl'
+        if let Err(err) = self.expect("intf", "after 'impl'") {
+            return Box::new(Declaration::Error(err));
+        }
+
+        let intf_id = self.parse_identifier();
+        if let Some(err) = intf_id.error.clone() {
+            return Box::new(Declaration::Error(err));
+        }
+        let generics = self.parse_generic_parameters();
+        if let Some(err) = generics.as_ref().and_then(|g| g.error.clone()) {
+            return Box::new(Declaration::Error(err));
+        }
+
+        if let Err(err) = self.expect("for", "after the interface") {
+            return Box::new(Declaration::Error(err));
+        }
+        let for_type = self.parse_type();
+        if let Some(err) = for_type.error.clone() {
+            return Box::new(Declaration::Error(err));
+        }
+
+        let mut implementation = Box::new(InterfaceImplementation {
+            intf_id,
+            for_type,
+            generics,
+            methods: Vec::new(),
+            attributes: Vec::new(),
+            error: None,
+        });
+        let open = self.current();
+        implementation.error = self.parse_body_start();
+        if implementation.error.is_some() {
+            return Box::new(Declaration::Implementation(implementation));
+        }
+        while !self.check("}") {
+            if self.eof() {
+                implementation.error = Some(self.unclosed(&open));
+                break;
+            }
+            let attributes = self.parse_attributes();
+            if !self.check("fn") {
+                implementation.error = Some(self.error(ParserError::MissingToken(
+                    self.current().get_line(),
+                    self.current().get_col(),
+                    format!(
+                        "Expected 'fn' to implement a method, found '{}'.",
+                        self.current().get_lexeme()
+                    ),
+                )));
+                break;
+            }
+
+            let mut method = self.parse_fn(false, false);
+            method.attributes = attributes;
+            let error = stopping_error(&method);
+            implementation.methods.push(method);
+            if error.is_some() {
+                implementation.error = error;
+                break;
+            }
+        }
+        if implementation.error.is_none() {
+            self.advance(); // skip '}'
+        }
+
+        Box::new(Declaration::Implementation(implementation))
+    }
+
+    /// Parses `enum Name { Variant, Variant(types), Variant { fields } }`.
+    fn parse_enum(&mut self) -> Box<EnumDeclaration> {
+        self.advance(); // skip 'enum'
+        let mut enumeration = Box::new(EnumDeclaration {
+            id: self.parse_identifier(),
+            generics: None,
+            variants: None,
+            attributes: Vec::new(),
+            error: None,
+        });
+        if enumeration.id.error.is_some() {
+            enumeration.error = enumeration.id.error.clone();
+            return enumeration;
+        }
+
+        enumeration.generics = self.parse_generic_parameters();
+        if let Some(err) = enumeration.generics.as_ref().and_then(|g| g.error.clone()) {
+            enumeration.generics = None;
+            enumeration.error = Some(err);
+            return enumeration;
+        }
+
+        let open = self.current();
+        if let Some(err) = self.parse_body_start() {
+            enumeration.error = Some(err);
+            return enumeration;
+        }
+        let mut variants = Vec::new();
+        enumeration.error =
+            self.parse_comma_separated(&open, "}", |parser| variants.push(parser.parse_variant()));
+        if !variants.is_empty() {
+            enumeration.variants = Some(variants);
+        }
+
+        enumeration
+    }
+
+    fn parse_variant(&mut self) -> Variant {
+        let id = self.parse_identifier();
+        let open = self.current();
+
+        if self.check("(") {
+            self.advance(); // skip '('
+            let mut fields = Vec::new();
+            let error =
+                self.parse_comma_separated(&open, ")", |parser| fields.push(parser.parse_type()));
+            return Variant::Tuple(id, Box::new(TupleFields { fields, error }));
+        }
+
+        if self.check("{") {
+            self.advance(); // skip '{'
+            let mut fields = Vec::new();
+            let error = self.parse_comma_separated(&open, "}", |parser| {
+                fields.push((parser.parse_type(), parser.parse_identifier()))
+            });
+            return Variant::Named(id, Box::new(NamedFields { fields, error }));
+        }
+
+        let discriminant = if self.check("=") {
+            self.advance(); // skip '='
+            Some(self.parse_expression())
+        } else {
+            None
+        };
+        Variant::Unit(id, discriminant)
+    }
+
+    /// Parses the attributes written before a declaration or a method, such
+    /// as `@inline` or `@deprecated("use g")`.
+    fn parse_attributes(&mut self) -> Vec<Attribute> {
+        let mut attributes = Vec::new();
+        while self.check("@") {
+            self.advance(); // skip '@'
+            let mut attribute = Attribute {
+                id: self.parse_identifier(),
+                args: Vec::new(),
+                error: None,
+            };
+            attribute.error = attribute.id.error.clone();
+            if attribute.error.is_none() && self.check("(") {
+                let open = self.current();
+                self.advance(); // skip '('
+                let mut args = Vec::new();
+                attribute.error = self.parse_comma_separated(&open, ")", |parser| {
+                    args.push(parser.parse_expression())
+                });
+                attribute.args = args;
+            }
+            attributes.push(attribute);
+        }
+        attributes
+    }
+
+    fn parse_declaration(&mut self) -> Box<Declaration> {
+        let at = self.current();
+        let attributes = self.parse_attributes();
+        let start = self.index;
+        let mut decl = self.parse_unattributed_declaration();
+        if attributes.is_empty() {
+            return decl;
+        }
+        match decl.as_mut() {
+            Declaration::Enum(enumeration) => enumeration.attributes = attributes,
+            Declaration::Struct(structure) => structure.attributes = attributes,
+            Declaration::Function(function) => function.attributes = attributes,
+            Declaration::Interface(interface) => interface.attributes = attributes,
+            Declaration::Implementation(implementation) => implementation.attributes = attributes,
+            Declaration::Import(_) => {
+                self.error(ParserError::InvalidSyntax(
+                    at.get_line(),
+                    at.get_col(),
+                    String::from("Attributes cannot be applied to an import."),
+                ));
+            }
+            // Skip the token that is not a declaration, as the caller only
+            // does when nothing was consumed.
+            Declaration::Error(_) if self.index == start => self.advance(),
+            Declaration::Error(_) => {}
+        }
+        decl
+    }
+
+    fn parse_unattributed_declaration(&mut self) -> Box<Declaration> {
+        let is_pub = self.check("pub");
+        if is_pub {
+            self.advance();
+        }
+
+        let is_const = self.check("const");
+        if is_const {
+            self.advance();
+        }
+
+        if self.check("fn") {
+            return Box::new(Declaration::Function(self.parse_fn(is_pub, is_const)));
+        }
+
+        if self.check("intf") && !is_const {
+            return Box::new(Declaration::Interface(self.parse_interface(is_pub)));
+        }
+
+        if self.check("impl") && !is_pub && !is_const {
+            return self.parse_implementation();
+        }
+
+        if self.check("import") && !is_pub && !is_const {
+            return Box::new(Declaration::Import(self.parse_import()));
+        }
+
+        if self.check("enum") && !is_const {
+            return Box::new(Declaration::Enum(self.parse_enum()));
+        }
+
+        if self.check("enum") && is_const {
+            return Box::new(Declaration::Error(self.error(ParserError::InvalidSyntax(
+                self.current().get_line(),
+                self.current().get_col(),
+                String::from("The `const` keyword cannot be used with `enum` types."),
+            ))));
+        }
+
+        if self.check("struct") && is_const {
+            return Box::new(Declaration::Error(self.error(ParserError::InvalidSyntax(
+                self.current().get_line(),
+                self.current().get_col(),
+                String::from("The `const` keyword cannot be used with `struct` types."),
+            ))));
+        }
+
+        if self.check("intf") && is_const {
+            return Box::new(Declaration::Error(self.error(ParserError::InvalidSyntax(
+                self.current().get_line(),
+                self.current().get_col(),
+                String::from("The `const` keyword cannot be used with `intf` types."),
+            ))));
+        }
+
+        if !is_pub && !is_const {
+            if let Some(err) = self.skip_dangling_clause() {
+                return Box::new(Declaration::Error(err));
+            }
+        }
+
+        Box::new(Declaration::Error(self.error(
+            ParserError::UnexpectedToken(
+                self.current().get_line(),
+                self.current().get_col(),
+                self.current().get_lexeme().to_string(),
+            ),
+        )))
+    }
+
+    /// Parses `import a.b.c;`. The path is only recorded, not resolved.
+    fn parse_import(&mut self) -> Box<ImportDeclaration> {
+        self.advance(); // skip 'import'
+        let mut import = ImportDeclaration {
+            segments: vec![self.parse_identifier()],
+            error: None,
+        };
+        while self.check(".") {
+            self.advance(); // skip '.'
+            import.segments.push(self.parse_identifier());
+        }
+        let end = self.end_statement();
+        import.error = import
+            .segments
+            .iter()
+            .find_map(|segment| segment.error.clone())
+            .or(end);
+        Box::new(import)
+    }
+
+    pub fn parse(&mut self) -> Box<AST> {
+        let mut ast = Box::new(AST {
+            declarations: Vec::new(),
+        });
+
+        while !self.eof() {
+            let start = self.index;
+            let declaration = self.parse_declaration();
+            if let Declaration::Error(err) = declaration.as_ref() {
+                // Skip at least the token that is not a declaration, so that
+                // the parser always makes progress, and after one that is not
+                // recognized at all, everything up to the next declaration.
+                if self.index == start {
+                    self.advance();
+                }
+                if let ParserError::UnexpectedToken(..) = err {
+                    self.recover_to(DECLARATION_STARTS);
+                }
+            }
+            ast.declarations.push(declaration);
+        }
+
+        ast
+    }
+
+    /// Parses the tokens into a syntax tree, which is partial if there were
+    /// errors, and returns it along with every error encountered.
+    pub fn parse_with_diagnostics(mut self) -> (Box<AST>, Vec<ParserError>) {
+        let ast = self.parse();
+        (ast, self.errors)
+    }
+}
+
+impl FunctionDeclaration {
+    /// Parses the body left unparsed by a parser with lazy bodies into
+    /// `block`, returning the errors found in it. Does nothing if the body
+    /// was already parsed.
+    pub fn parse_body(&mut self) -> Vec<ParserError> {
+        let Some(body) = self.lazy_body.take() else {
+            return Vec::new();
+        };
+        let mut tokens = body.tokens;
+        tokens.push(Token::Eof);
+        let mut parser = Parser::new(tokens);
+        match parser.parse_block() {
+            Ok(block) => self.block = block,
+            Err(err) => self.error = Some(err),
+        }
+        parser.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+    use crate::lexer::Lexer;
+    use crate::symbol::Symbol;
+
+    fn parse(src: &str) -> (Box<AST>, bool) {
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let ast = parser.parse();
+        (ast, parser.has_error())
+    }
+
+    fn function(ast: &AST, index: usize) -> &FunctionDeclaration {
+        match ast.declarations[index].as_ref() {
+            Declaration::Function(function) => function,
+            decl => panic!("Expected a function, got {:?}", decl),
+        }
+    }
+
+    fn int(expression: &Expression) -> &str {
+        match expression {
+            Expression::Primary(primary) => match primary.as_ref() {
+                Primary::Literal(literal) => match literal.as_ref() {
+                    Literal::Integer(token) => token.get_lexeme(),
+                    literal => panic!("Expected an integer, got {:?}", literal),
+                },
+                primary => panic!("Expected a literal, got {:?}", primary),
+            },
+            expression => panic!("Expected a primary, got {:?}", expression),
+        }
+    }
+
+    fn binary(expression: &Expression) -> (&str, &Expression, &Expression) {
+        match expression {
+            Expression::Operation(operator) => match operator.as_ref() {
+                Operator::Binary(op, lhs, rhs) => (op, lhs, rhs),
+                operator => panic!("Expected a binary operation, got {:?}", operator),
+            },
+            expression => panic!("Expected an operation, got {:?}", expression),
+        }
+    }
+
+    fn range(expression: &Expression) -> &Range {
+        match expression {
+            Expression::Primary(primary) => match primary.as_ref() {
+                Primary::Range(range) => range,
+                primary => panic!("Expected a range, got {:?}", primary),
+            },
+            expression => panic!("Expected a primary, got {:?}", expression),
+        }
+    }
+
+    #[test]
+    fn test_parse_type_str() {
+        let (parsed, errors) = Parser::parse_type_str("ref [u8; 4]");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        assert!(parsed.error.is_none());
+        match parsed.variant.as_ref() {
+            TypeVariant::Reference(inner) => match inner.as_ref() {
+                TypeVariant::Array(element, size) => {
+                    assert_eq!(
+                        element.as_ref(),
+                        &TypeVariant::Primitive(String::from("u8"))
+                    );
+                    assert_eq!(int(size), "4");
+                }
+                variant => panic!("Expected an array, got {:?}", variant),
+            },
+            variant => panic!("Expected a reference, got {:?}", variant),
+        }
+
+        let (parsed, errors) = Parser::parse_type_str("[u8;");
+        assert!(matches!(
+            errors.first(),
+            Some(ParserError::UnexpectedEOF(_, _, _))
+        ));
+        assert_eq!(parsed.error.as_ref(), errors.first());
+    }
+
+    /// Writes a primitive or struct type back with its generic arguments, as
+    /// in `List<u8>`.
+    fn generic_type(variant: &TypeVariant) -> String {
+        match variant {
+            TypeVariant::Primitive(name) => name.clone(),
+            TypeVariant::Structure(id, generics) => {
+                let name = id.id.as_ref().unwrap().get_lexeme().to_string();
+                let Some(generics) = generics else {
+                    return name;
+                };
+                let arguments: Vec<String> = generics
+                    .generics
+                    .iter()
+                    .map(|generic| match generic.as_ref() {
+                        GenericVariants::Type(argument) => generic_type(&argument.variant),
+                        generic => panic!("Expected a type argument, got {:?}", generic),
+                    })
+                    .collect();
+                format!("{}<{}>", name, arguments.join(", "))
+            }
+            variant => panic!("Expected a primitive or struct type, got {:?}", variant),
+        }
+    }
+
+    #[test]
+    fn test_nested_generic_types() {
+        for (src, expected) in [
+            ("struct List<u8>", "List<u8>"),
+            ("struct Map<u8, u16>", "Map<u8, u16>"),
+            ("struct List<struct List<u8>>", "List<List<u8>>"),
+            (
+                "struct Map<u8, struct List<struct List<u16>>>",
+                "Map<u8, List<List<u16>>>",
+            ),
+        ] {
+            let (parsed, errors) = Parser::parse_type_str(src);
+            assert!(
+                errors.is_empty(),
+                "unexpected errors in {}: {:?}",
+                src,
+                errors
+            );
+            assert_eq!(generic_type(&parsed.variant), expected);
+        }
+
+        let (_, errors) = Parser::parse_type_str("struct List<struct List<u8>");
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_expression_str() {
+        let (expression, errors) = Parser::parse_expression_str("1 - 2 - 3 * 4");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        let (op, lhs, rhs) = binary(&expression);
+        assert_eq!(op, "-");
+        let (inner_op, one, two) = binary(lhs);
+        assert_eq!((inner_op, int(one), int(two)), ("-", "1", "2"));
+        let (mul_op, three, four) = binary(rhs);
+        assert_eq!((mul_op, int(three), int(four)), ("*", "3", "4"));
+
+        let (_, errors) = Parser::parse_expression_str("f(a[0][1], (b)) c");
+        assert_eq!(
+            errors,
+            vec![ParserError::UnexpectedToken(1, 16, String::from("c"))]
+        );
+
+        let (expression, errors) = Parser::parse_expression_str("null");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        assert_eq!(
+            *expression,
+            *Expression::literal(Literal::Null(Token::Keyword(1, 0, String::from("null"))))
+        );
+    }
+
+    #[test]
+    fn test_ranges() {
+        let (expression, errors) = Parser::parse_expression_str("0..n + 1");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        let exclusive = range(&expression);
+        assert_eq!(int(&exclusive.start), "0");
+        assert_eq!(binary(&exclusive.end).0, "+");
+        assert!(!exclusive.is_inclusive);
+
+        let (expression, errors) = Parser::parse_expression_str("a ..= b");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        assert!(range(&expression).is_inclusive);
+
+        let (_, errors) = Parser::parse_expression_str("0..1..2");
+        assert_eq!(
+            errors,
+            vec![ParserError::UnexpectedToken(1, 4, String::from(".."))]
+        );
+    }
+
+    /// Returns the base and member of a field access.
+    fn field_access(expression: &Expression) -> (&Expression, &Primary) {
+        match expression {
+            Expression::Primary(primary) => match primary.as_ref() {
+                Primary::FieldAccess(base, member) => (base, member),
+                primary => panic!("Expected a field access, got {:?}", primary),
+            },
+            expression => panic!("Expected a primary, got {:?}", expression),
+        }
+    }
+
+    #[test]
+    fn test_field_accesses() {
+        let name = |primary: &Primary| match primary {
+            Primary::Identifier(id) => id.id.as_ref().unwrap().get_lexeme().to_string(),
+            primary => panic!("Expected an identifier, got {:?}", primary),
+        };
+
+        let (expression, errors) = Parser::parse_expression_str("a.b.c");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        let (base, c) = field_access(&expression);
+        assert_eq!(name(c), "c");
+        let (a, b) = field_access(base);
+        assert_eq!(name(b), "b");
+        assert!(matches!(a, Expression::Primary(a) if name(a) == "a"));
+
+        let (expression, errors) = Parser::parse_expression_str("-a.b(x) * a.c[0][1]");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        let (op, lhs, rhs) = binary(&expression);
+        assert_eq!(op, "*");
+        let Expression::Operation(negation) = lhs else {
+            panic!("Expected a negation, got {:?}", lhs);
+        };
+        let Operator::Unary(_, call) = negation.as_ref() else {
+            panic!("Expected a negation, got {:?}", negation);
+        };
+        match field_access(call).1 {
+            Primary::FunctionCall(call) => assert_eq!(call.args.len(), 1),
+            member => panic!("Expected a call, got {:?}", member),
+        }
+        match field_access(rhs).1 {
+            Primary::ArrayAccess(_, access) => assert_eq!(access.depth(), 2),
+            member => panic!("Expected an array access, got {:?}", member),
+        }
+
+        let (_, errors) = Parser::parse_expression_str("a.1");
+        assert_eq!(
+            errors[0],
+            ParserError::MissingToken(
+                1,
+                1,
+                String::from("Expected a field name after '.', found '1'.")
+            )
+        );
+    }
+
+    #[test]
+    fn test_statements() {
+        let (ast, has_error) = parse(
+            "fn add(i32 a, i32 b) -> i32 { const i32 c = a + b; c = c * 2; \
+             if c > 10 { ret c; } elif c < 0 { print(c); } else { ret; } ret 0; }",
+        );
+        assert!(!has_error, "unexpected parser error in {:?}", ast);
+
+        let function = function(&ast, 0);
+        assert_eq!(
+            function.return_type.as_ref().map(|t| t.variant.as_ref()),
+            Some(&TypeVariant::Primitive(String::from("i32")))
+        );
+
+        let statements = &function.block.statements;
+        assert_eq!(statements.len(), 4);
+        match &statements[0] {
+            Statement::Var(declaration) => {
+                assert!(declaration.is_const());
+                assert!(declaration.init.is_some());
+            }
+            statement => panic!("Expected a variable declaration, got {:?}", statement),
+        }
+        assert!(matches!(statements[1], Statement::Assign(_)));
+        match &statements[2] {
+            Statement::If(if_statement) => {
+                assert_eq!(if_statement.elif_statements.as_ref().map(Vec::len), Some(1));
+                assert!(matches!(
+                    if_statement.elif_statements.as_ref().unwrap()[0]
+                        .block
+                        .statements[0],
+                    Statement::FunctionCall(_)
+                ));
+                assert!(matches!(
+                    if_statement.else_block.as_ref().unwrap().statements[0],
+                    Statement::Return(ReturnStatement { expr: None, .. })
+                ));
+            }
+            statement => panic!("Expected an if statement, got {:?}", statement),
+        }
+        assert!(matches!(
+            statements[3],
+            Statement::Return(ReturnStatement { expr: Some(_), .. })
+        ));
+    }
+
+    #[test]
+    fn test_statement_terminators() {
+        for src in [
+            "fn main() { i32 x = 1; x = 2; }",
+            "fn main() {\n    i32 x = 1\n    x = 2\n}",
+            "fn main() {\n    i32 x = 1 +\n        2; x = 2\n}",
+            "fn main() { i32 a = 1\n i32 b = 2 }",
+            "fn main() { i32 a = 1; g(a) }",
+        ] {
+            let (ast, has_error) = parse(src);
+            assert!(!has_error, "unexpected parser error in {:?}", ast);
+            assert_eq!(function(&ast, 0).block.statements.len(), 2, "in {:?}", src);
+        }
+
+        let (ast, has_error) = parse("fn f() -> i32 {\n    ret\n}");
+        assert!(!has_error, "unexpected parser error in {:?}", ast);
+        assert!(matches!(
+            function(&ast, 0).block.statements[..],
+            [Statement::Return(ReturnStatement { expr: None, .. })]
+        ));
+
+        // The `}` closing the block ends its last statement.
+        for src in [
+            "fn f() { x = 2 }",
+            "fn f() { ret }",
+            "fn f() { if x { break } }",
+        ] {
+            let (ast, has_error) = parse(src);
+            assert!(!has_error, "unexpected parser error in {:?}", ast);
+            assert_eq!(function(&ast, 0).block.statements.len(), 1, "in {:?}", src);
+            assert!(function(&ast, 0).block.value.is_none(), "in {:?}", src);
+        }
+
+        let (_, has_error) = parse("fn main() { i32 x = 1 x = 2; }");
+        assert!(has_error);
+    }
+
+    #[test]
+    fn test_unclosed_block() {
+        let mut parser =
+            Parser::new(Lexer::new("fn main() {\n    i32 x = 1;\n    if x > 0 { ret; }\n").lex());
+        parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [ParserError::UnclosedDelimiter(1, 10, String::from("{"))]
+        );
+
+        let mut parser = Parser::new(Lexer::new("fn main() {\n    loop {\n        ret;\n").lex());
+        parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [ParserError::UnclosedDelimiter(2, 21, String::from("{"))]
+        );
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let (ast, has_error) = parse(
+            "enum Shape { Empty, Circle(f32), Rect { f32 w, f32 h }, } \
+             fn f(enum Shape s) { match s { Empty -> { } Circle, 1, 'c' -> { } default -> { } } }",
+        );
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+
+        let Declaration::Enum(enumeration) = ast.declarations[0].as_ref() else {
+            panic!("Expected an enum, got {:?}", ast.declarations[0]);
+        };
+        let variants = enumeration.variants.as_ref().unwrap();
+        assert!(matches!(variants[0], Variant::Unit(_, None)));
+        assert!(matches!(&variants[1], Variant::Tuple(_, fields) if fields.fields.len() == 1));
+        assert!(matches!(&variants[2], Variant::Named(_, fields) if fields.fields.len() == 2));
+
+        let Statement::Match(statement) = &function(&ast, 1).block.statements[0] else {
+            panic!("Expected a match statement");
+        };
+        assert_eq!(statement.case_clauses.len(), 2);
+        assert_eq!(statement.case_clauses[1].cases.len(), 3);
+        assert!(matches!(
+            statement.case_clauses[1].cases[1],
+            Pattern::Literal(_)
+        ));
+        assert!(statement.default_clause.is_some());
+
+        let (_, has_error) = parse("fn f(i32 x) { match x { default -> { } 1 -> { } } }");
+        assert!(has_error);
+    }
+
+    #[test]
+    fn test_negative_literal_patterns() {
+        let (ast, has_error) =
+            parse("fn f(i32 x) { match x { -1, 0 -> { } -2.5 -> { } default -> { } } }");
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+        let Statement::Match(statement) = &function(&ast, 0).block.statements[0] else {
+            panic!("Expected a match statement");
+        };
+        assert_eq!(
+            statement.case_clauses[0].cases[0],
+            Pattern::Literal(Literal::Integer(Token::IntLiteral(
+                1,
+                24,
+                String::from("-1")
+            )))
+        );
+        assert_eq!(
+            statement.case_clauses[1].cases[0],
+            Pattern::Literal(Literal::Float(Token::FloatLiteral(
+                1,
+                37,
+                String::from("-2.5")
+            )))
+        );
+
+        let (expr, errors) = Parser::parse_expression_str("a - 1");
+        assert!(errors.is_empty());
+        assert!(matches!(
+            expr.as_ref(),
+            Expression::Operation(operator) if matches!(operator.as_ref(), Operator::Binary(op, _, _) if op == "-")
+        ));
+    }
+
+    #[test]
+    fn test_match_expressions() {
+        let (ast, has_error) = parse(
+            "fn f(i32 y) -> i32 { i32 x = match y { 1 -> { g(); 1 } default -> { match y { 2 -> { 3 } default -> { 4 } } } }; \
+             ret match x { 0 -> { g(); match y { 1 -> { } } 5 } default -> { x } } + 1; }",
+        );
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+
+        let statements = &function(&ast, 0).block.statements;
+        let Statement::Var(declaration) = &statements[0] else {
+            panic!("Expected a declaration, got {:?}", statements[0]);
+        };
+        let Some(Expression::Primary(primary)) = declaration.init.as_deref() else {
+            panic!("Expected a primary, got {:?}", declaration.init);
+        };
+        let Primary::Match(match_expression) = primary.as_ref() else {
+            panic!("Expected a match expression, got {:?}", primary);
+        };
+        let arm = &match_expression.case_clauses[0].case_block;
+        assert!(matches!(arm.statements[..], [Statement::FunctionCall(_)]));
+        assert!(matches!(arm.value.as_deref(), Some(Expression::Primary(_))));
+        let default = match_expression.default_clause.as_ref().unwrap();
+        assert!(default.statements.is_empty());
+        assert!(default.value.is_some());
+
+        let Statement::Return(ret) = &statements[1] else {
+            panic!("Expected a return, got {:?}", statements[1]);
+        };
+        assert!(matches!(
+            ret.expr.as_deref(),
+            Some(Expression::Operation(_))
+        ));
+
+        for src in [
+            "fn f(i32 y) { i32 x = match y { default -> { 1 2 } }; }",
+            "fn f(i32 y) { match y { default -> { 1 } } }",
+        ] {
+            let (_, has_error) = parse(src);
+            assert!(has_error, "expected an error in {}", src);
+        }
+    }
+
+    #[test]
+    fn test_expect() {
+        let mut parser = Parser::new(Lexer::new("( x").lex());
+        assert_eq!(
+            parser.expect("(", "to open the list"),
+            Ok(Token::Separator(1, 0, String::from("(")))
+        );
+        let err = ParserError::MissingToken(
+            1,
+            2,
+            String::from("Expected ')' to close the list, found 'x'."),
+        );
+        assert_eq!(parser.expect(")", "to close the list"), Err(err.clone()));
+        assert_eq!(parser.errors(), [err]);
+        assert!(matches!(parser.current(), Token::Identifier(1, 2, _)));
+
+        let (_, errors) = Parser::new(Lexer::new("impl intf Shape enum Color { }").lex())
+            .parse_with_diagnostics();
+        assert_eq!(
+            errors,
+            [ParserError::MissingToken(
+                1,
+                16,
+                String::from("Expected 'for' after the interface, found 'enum'.")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_generic_parameter_error_position() {
+        let (_, errors) =
+            Parser::new(Lexer::new("fn f<type T,\n  i32 U>() { }").lex()).parse_with_diagnostics();
+        assert_eq!(
+            errors.first(),
+            Some(&ParserError::MissingToken(
+                2,
+                15,
+                String::from("Expected 'type' to declare a generic parameter, found 'i32'.")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        for src in [
+            "fn f(i32 x, i32 y,) { }",
+            "fn f<type T, type U impl Shape,>() { }",
+            "fn f() { g(1, 2,); }",
+            "enum E { A(u8, u16,), B { u8 x, u8 y, }, C, }",
+            "fn f(i32 x) { match x { 1, 2, -> { } default -> { } } }",
+        ] {
+            let (ast, has_error) = parse(src);
+            assert!(!has_error, "unexpected errors in {}: {:?}", src, ast);
+        }
+
+        for src in [
+            "fn f(i32 x,, i32 y) { }",
+            "fn f<type T,,>() { }",
+            "fn f() { g(1,, 2); }",
+            "enum E { A(u8,,), B }",
+            "enum E { A,, B }",
+            "fn f(i32 x) { match x { 1,, 2 -> { } } }",
+            "fn f(i32 x) { match x { , -> { } } }",
+        ] {
+            let (_, has_error) = parse(src);
+            assert!(has_error, "expected an error in {}", src);
+        }
+    }
+
+    #[test]
+    fn test_missing_operands() {
+        let errors = |src: &str| Parser::parse_expression_str(src).1;
+
+        assert_eq!(
+            errors("1 +"),
+            vec![ParserError::MissingToken(
+                1,
+                2,
+                String::from("Expected an expression after '+'.")
+            )]
+        );
+        assert_eq!(
+            errors("* 2"),
+            vec![ParserError::InvalidSyntax(
+                1,
+                0,
+                String::from("Unexpected operator '*' without a left operand.")
+            )]
+        );
+        let stray = |col| {
+            vec![ParserError::InvalidSyntax(
+                1,
+                col,
+                String::from("Unexpected operator '*' without a left operand."),
+            )]
+        };
+        assert_eq!(errors("1 + * 2"), stray(4));
+        assert_eq!(errors("1 * * * 2 + 3"), stray(4));
+        assert_eq!(errors("(1 + * 2) * 3"), stray(5));
+        assert_eq!(errors("0.. * 2"), stray(4));
+        let (expr, _) = Parser::parse_expression_str("1 + * 2");
+        assert_eq!(crate::pretty::to_source(&expr), "1 + 2");
+        assert_eq!(
+            errors("-)").first(),
+            Some(&ParserError::MissingToken(
+                1,
+                0,
+                String::from("Expected an expression after '-'.")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_with_diagnostics() {
+        let parser = Parser::new(Lexer::new("fn good() { } fn bad( { }").lex());
+        let (ast, errors) = parser.parse_with_diagnostics();
+
+        let good = function(&ast, 0);
+        assert_eq!(good.id.id.as_ref().unwrap().get_lexeme(), "good");
+        assert!(good.error.is_none());
+        assert!(function(&ast, 1).error.is_some());
+        assert!(!errors.is_empty());
+        assert_eq!(function(&ast, 1).error.as_ref(), errors.first());
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let (ast, has_error) = parse(
+            "pub intf Shape { fn area() -> i32 default fn sides() -> i32 { ret 0; } } \
+             impl intf Shape for struct Square { fn area() -> i32 { ret 4; } }",
+        );
+        assert!(!has_error, "unexpected parser error in {:?}", ast);
+
+        match ast.declarations[0].as_ref() {
+            Declaration::Interface(interface) => {
+                assert!(interface.is_pub);
+                let defaults: Vec<bool> = interface.methods.iter().map(|m| m.is_default).collect();
+                assert_eq!(defaults, vec![false, true]);
+                assert!(interface.methods[0].block.statements.is_empty());
+                assert_eq!(interface.methods[1].block.statements.len(), 1);
+            }
+            decl => panic!("Expected an interface, got {:?}", decl),
+        }
+        match ast.declarations[1].as_ref() {
+            Declaration::Implementation(implementation) => {
+                assert!(matches!(
+                    implementation.for_type.variant.as_ref(),
+                    TypeVariant::Structure(_, None)
+                ));
+                assert_eq!(implementation.methods.len(), 1);
+            }
+            decl => panic!("Expected an implementation, got {:?}", decl),
+        }
+    }
+
+    #[test]
+    fn test_reserved_keyword_as_name() {
+        // The rest of the declaration is parsed after the name, so nothing
+        // else is reported.
+        for (src, err) in [
+            (
+                "fn loop() {}",
+                ParserError::ReservedKeyword(1, 3, String::from("loop")),
+            ),
+            (
+                "fn if(i32 a) -> i32 { ret a; }",
+                ParserError::ReservedKeyword(1, 3, String::from("if")),
+            ),
+            (
+                "fn u8() {}",
+                ParserError::ReservedTypeName(1, 3, String::from("u8")),
+            ),
+        ] {
+            let mut parser = Parser::new(Lexer::new(&format!("{} fn main() {{}}", src)).lex());
+            let ast = parser.parse();
+            assert_eq!(parser.errors(), std::slice::from_ref(&err), "{}", src);
+            assert_eq!(ast.declarations.len(), 2, "{}", src);
+            assert_eq!(function(&ast, 0).error, Some(err), "{}", src);
+        }
+
+        let mut parser =
+            Parser::new(Lexer::new("intf I { fn loop() -> i32\n fn f() } fn main() {}").lex());
+        let ast = parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [ParserError::ReservedKeyword(1, 12, String::from("loop"))]
+        );
+        assert_eq!(ast.declarations.len(), 2);
+
+        for (src, err, statements) in [
+            (
+                "fn main() { u8 u8 = 1; }",
+                ParserError::ReservedTypeName(1, 15, String::from("u8")),
+                1,
+            ),
+            (
+                "fn main() { i32 i32 = 1\n i32 x = 2; }",
+                ParserError::ReservedTypeName(1, 16, String::from("i32")),
+                2,
+            ),
+        ] {
+            let mut parser = Parser::new(Lexer::new(src).lex());
+            let ast = parser.parse();
+            assert_eq!(parser.errors(), [err], "{}", src);
+            assert_eq!(ast.declarations.len(), 1, "{}", src);
+            assert_eq!(function(&ast, 0).block.statements.len(), statements);
+        }
+
+        let mut parser = Parser::new(Lexer::new("fn main() { u8 u8 = 1; }").lex());
+        parser.parse();
+        assert_eq!(
+            Diagnostic::from(parser.errors()[0].clone()).message,
+            "`u8` is a reserved type name and cannot be used as an identifier here."
+        );
+    }
+
+    #[test]
+    fn test_labeled_loops() {
+        let (ast, has_error) =
+            parse("fn main() { 'outer loop { loop { break 'outer; } continue 'outer; } }");
+        assert!(!has_error, "unexpected error in {:?}", ast);
+
+        let outer = match &function(&ast, 0).block.statements[0] {
+            Statement::Loop(outer) => outer,
+            stmt => panic!("Expected a loop, got {:?}", stmt),
+        };
+        assert_eq!(outer.label.as_ref().unwrap().get_lexeme(), "'outer");
+
+        let inner = match &outer.block.statements[0] {
+            Statement::Loop(inner) => inner,
+            stmt => panic!("Expected a loop, got {:?}", stmt),
+        };
+        assert!(inner.label.is_none());
+        match &inner.block.statements[0] {
+            Statement::Break(Some(label), None) => assert_eq!(label.get_lexeme(), "'outer"),
+            stmt => panic!("Expected a labeled break, got {:?}", stmt),
+        }
+        match &outer.block.statements[1] {
+            Statement::Continue(Some(label)) => assert_eq!(label.get_lexeme(), "'outer"),
+            stmt => panic!("Expected a labeled continue, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_range_loops() {
+        let (ast, has_error) = parse("fn main() { 'rows loop i in 0..=n - 1 { print(i); } }");
+        assert!(!has_error, "unexpected error in {:?}", ast);
+        let statement = match &function(&ast, 0).block.statements[0] {
+            Statement::Loop(statement) => statement,
+            stmt => panic!("Expected a loop, got {:?}", stmt),
+        };
+        assert_eq!(statement.label.as_ref().unwrap().get_lexeme(), "'rows");
+        let iteration = statement.iteration.as_ref().unwrap();
+        assert_eq!(iteration.variable.id.as_ref().unwrap().get_lexeme(), "i");
+        assert_eq!(int(&iteration.range.start), "0");
+        assert_eq!(binary(&iteration.range.end).0, "-");
+        assert!(iteration.range.is_inclusive);
+        assert_eq!(statement.block.statements.len(), 1);
+
+        let (ast, has_error) = parse("fn main() { loop i 0..10 { } }");
+        assert!(has_error);
+        assert_eq!(
+            function(&ast, 0).block.statements[0],
+            Statement::Error(ParserError::MissingToken(
+                1,
+                19,
+                String::from("Expected 'in' after the loop variable, found '0'.")
+            ))
+        );
+
+        let (ast, has_error) = parse("fn main() { loop i in n { } }");
+        assert!(has_error);
+        assert_eq!(
+            function(&ast, 0).block.statements[0],
+            Statement::Error(ParserError::InvalidSyntax(
+                1,
+                22,
+                String::from("Expected a range to loop over, such as '0..10'.")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_loop_values() {
+        let (ast, has_error) =
+            parse("fn main() { i32 x = 'a loop { break 'a 1 + 2; }; loop { break; } }");
+        assert!(!has_error, "unexpected error in {:?}", ast);
+        let statements = &function(&ast, 0).block.statements;
+        let Statement::Var(declaration) = &statements[0] else {
+            panic!("Expected a variable, got {:?}", statements[0]);
+        };
+        let Some(Expression::Primary(primary)) = declaration.init.as_deref() else {
+            panic!("Expected a loop, got {:?}", declaration.init);
+        };
+        let Primary::Loop(loop_statement) = primary.as_ref() else {
+            panic!("Expected a loop, got {:?}", primary);
+        };
+        assert_eq!(loop_statement.label.as_ref().unwrap().get_lexeme(), "'a");
+        match &loop_statement.block.statements[0] {
+            Statement::Break(Some(label), Some(value)) => {
+                assert_eq!(label.get_lexeme(), "'a");
+                assert_eq!(binary(value).0, "+");
+            }
+            stmt => panic!("Expected a break with a value, got {:?}", stmt),
+        }
+        match &statements[1] {
+            Statement::Loop(inner) => {
+                assert_eq!(inner.block.statements, [Statement::Break(None, None)]);
+            }
+            stmt => panic!("Expected a loop, got {:?}", stmt),
+        }
+
+        // A loop an arm ends with is the value of the arm.
+        let (ast, has_error) =
+            parse("fn main() { i32 x = match y { default -> { loop { break 1; } } }; }");
+        assert!(!has_error, "unexpected error in {:?}", ast);
+        let Statement::Var(declaration) = &function(&ast, 0).block.statements[0] else {
+            panic!("Expected a variable in {:?}", ast);
+        };
+        let Some(Expression::Primary(primary)) = declaration.init.as_deref() else {
+            panic!("Expected a match, got {:?}", declaration.init);
+        };
+        let Primary::Match(match_expression) = primary.as_ref() else {
+            panic!("Expected a match, got {:?}", primary);
+        };
+        let arm = match_expression.default_clause.as_ref().unwrap();
+        assert!(arm.statements.is_empty());
+        assert!(
+            matches!(arm.value.as_deref(), Some(Expression::Primary(primary))
+            if matches!(primary.as_ref(), Primary::Loop(_)))
+        );
+
+        let src = "fn main() { i32 x = loop i in 0..3 { break i; }; }";
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [ParserError::InvalidSyntax(
+                1,
+                src.find("loop").unwrap(),
+                String::from(
+                    "A loop over a range cannot be used as a value, since it can end without a `break`."
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn test_label_without_loop() {
+        let (ast, has_error) = parse("fn main() { 'outer break; }");
+        assert!(has_error);
+        assert!(matches!(
+            function(&ast, 0).block.statements[0],
+            Statement::Error(ParserError::MissingToken(_, _, _))
+        ));
+    }
+
+    #[test]
+    fn test_lazy_bodies() {
+        let src = "fn add(i32 a, i32 b) -> i32 { if a > b { ret a; } ret a + b; }\n\
+                   intf Shape { default fn area() -> i32 { ret 0; } }\n\
+                   fn main() { add(1, 2); }";
+        let eager = Parser::new(Lexer::new(src).lex()).parse();
+        let mut parser = Parser::new(Lexer::new(src).lex()).lazy_bodies();
+        let mut lazy = parser.parse();
+        assert!(!parser.has_error());
+
+        let add = function(&lazy, 0);
+        assert_eq!(add.parameters, function(&eager, 0).parameters);
+        assert_eq!(add.return_type, function(&eager, 0).return_type);
+        assert!(add.block.statements.is_empty());
+        let body = add.lazy_body.as_ref().unwrap();
+        assert_eq!(
+            &src[body.span.start..body.span.end],
+            "{ if a > b { ret a; } ret a + b; }"
+        );
+        assert_eq!(body.tokens.len(), 16);
+
+        for decl in lazy.declarations.iter_mut() {
+            match decl.as_mut() {
+                Declaration::Function(function) => {
+                    assert_eq!(function.parse_body(), []);
+                }
+                Declaration::Interface(interface) => {
+                    for method in &mut interface.methods {
+                        assert_eq!(method.parse_body(), []);
+                    }
+                }
+                decl => panic!("Unexpected declaration {:?}", decl),
+            }
+        }
+        assert_eq!(lazy, eager);
+
+        // An unclosed body is parsed right away to report it.
+        let mut parser = Parser::new(Lexer::new("fn f() { ret;").lex()).lazy_bodies();
+        let ast = parser.parse();
+        assert!(parser.has_error());
+        assert!(function(&ast, 0).lazy_body.is_none());
+    }
+
+    #[test]
+    fn test_receivers() {
+        let (ast, has_error) = parse(
+            "impl intf Shape for struct Point { fn area(ref self) -> i32 { ret 0; } \
+             fn scale(self, i32 by) { } fn origin() { } }",
+        );
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+        let Declaration::Implementation(implementation) = ast.declarations[0].as_ref() else {
+            panic!("Expected an implementation, got {:?}", ast.declarations[0]);
+        };
+        let methods = &implementation.methods;
+        let receiver = methods[0].receiver.as_ref().unwrap();
+        assert!(receiver.is_ref);
+        assert_eq!(
+            receiver.id.id,
+            Some(Token::Identifier(1, 47, Symbol::from("self")))
+        );
+        assert!(methods[0].parameters.is_none());
+        assert!(!methods[1].receiver.as_ref().unwrap().is_ref);
+        assert_eq!(methods[1].parameters.as_ref().unwrap().len(), 1);
+        assert!(methods[2].receiver.is_none());
+
+        let mut parser = Parser::new(Lexer::new("fn f(i32 x, ref self) { }").lex());
+        parser.parse();
+        assert_eq!(
+            parser.errors()[0],
+            ParserError::InvalidSyntax(
+                1,
+                12,
+                String::from("The receiver 'self' must be the first parameter.")
+            )
+        );
+    }
+
+    #[test]
+    fn test_reference_parameters() {
+        let (ast, has_error) = parse("fn f(ref u32 x, ref ref u8 y) { ref ref u8 z = ref y; }");
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+
+        let reference = |variant: TypeVariant| TypeVariant::Reference(Box::new(variant));
+        let u32 = TypeVariant::Primitive(String::from("u32"));
+        let u8 = TypeVariant::Primitive(String::from("u8"));
+        let parameters = function(&ast, 0).parameters.as_ref().unwrap();
+        assert_eq!(*parameters[0].0.variant, reference(u32));
+        assert_eq!(*parameters[1].0.variant, reference(reference(u8.clone())));
+        assert!(function(&ast, 0).receiver.is_none());
+
+        let Statement::Var(declaration) = &function(&ast, 0).block.statements[0] else {
+            panic!("Expected a variable declaration");
+        };
+        assert_eq!(*declaration.var_type.variant, reference(reference(u8)));
+    }
+
+    #[test]
+    fn test_empty_statements() {
+        let (ast, has_error) =
+            parse("fn f() { ; i32 x = 1;; ;;; x = 2;; if x == 2 { ; } f();; } fn g() { ;;; }");
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+
+        let statements = &function(&ast, 0).block.statements;
+        assert_eq!(statements.len(), 4);
+        assert!(matches!(statements[0], Statement::Var(_)));
+        assert!(matches!(statements[1], Statement::Assign(_)));
+        let Statement::If(statement) = &statements[2] else {
+            panic!("Expected an if statement");
+        };
+        assert!(statement.if_block.statements.is_empty());
+        assert!(matches!(statements[3], Statement::FunctionCall(_)));
+        assert!(function(&ast, 1).block.statements.is_empty());
+    }
+
+    #[test]
+    fn test_dangling_clauses() {
+        let errors = |src: &str| {
+            let mut parser = Parser::new(Lexer::new(src).lex());
+            let ast = parser.parse();
+            (ast, parser.errors().to_vec())
+        };
+
+        let (ast, errs) = errors("elif x > 1 { } else { } fn main() { }");
+        assert_eq!(
+            errs,
+            vec![
+                ParserError::DanglingElif(1, 0),
+                ParserError::DanglingElse(1, 15)
+            ]
+        );
+        assert_eq!(ast.declarations.len(), 3);
+        assert_eq!(
+            function(&ast, 2).id.id.as_ref().unwrap().get_lexeme(),
+            "main"
+        );
+
+        let (ast, errs) = errors("fn f() { i32 x = 1; elif x { x = 2; } else { } x = 3; }");
+        assert_eq!(
+            errs,
+            vec![
+                ParserError::DanglingElif(1, 20),
+                ParserError::DanglingElse(1, 38)
+            ]
+        );
+        let statements = &function(&ast, 0).block.statements;
+        assert_eq!(statements.len(), 4);
+        assert!(matches!(statements[3], Statement::Assign(_)));
+
+        let (_, errs) = errors("fn f() { if true { } elif false { } else { } }");
+        assert!(errs.is_empty(), "unexpected errors {:?}", errs);
+    }
+
+    #[test]
+    fn test_imports() {
+        let (ast, has_error) = parse("import std.io;\nimport geometry\nfn main() { }");
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+        assert_eq!(ast.imports(), ["std.io", "geometry"]);
+        let Declaration::Import(import) = ast.declarations[0].as_ref() else {
+            panic!("Expected an import");
+        };
+        assert_eq!(
+            import.segments[1].id,
+            Some(Token::Identifier(1, 11, Symbol::from("io")))
+        );
+
+        let mut parser = Parser::new(Lexer::new("import std.loop;").lex());
+        parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [ParserError::ReservedKeyword(1, 11, String::from("loop"))]
+        );
+    }
+
+    #[test]
+    fn test_attributes() {
+        let (ast, has_error) = parse(
+            "@inline fn f() {} @deprecated(\"use g\", 2) @cold pub fn g() {} \
+             intf Shape { @inline fn area() -> i32 } \
+             impl intf Shape for i32 { @noinline fn area() -> i32 { ret 0; } }",
+        );
+        assert!(!has_error, "unexpected errors in {:?}", ast);
+        let attributes = &function(&ast, 0).attributes;
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(
+            attributes[0].id.id,
+            Some(Token::Identifier(1, 1, Symbol::from("inline")))
+        );
+        assert!(attributes[0].args.is_empty());
+
+        let attributes = &function(&ast, 1).attributes;
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].args.len(), 2);
+        assert!(function(&ast, 1).is_pub);
+
+        let Declaration::Interface(interface) = ast.declarations[2].as_ref() else {
+            panic!("Expected an interface");
+        };
+        assert_eq!(interface.methods[0].attributes.len(), 1);
+        let Declaration::Implementation(implementation) = ast.declarations[3].as_ref() else {
+            panic!("Expected an implementation");
+        };
+        assert_eq!(implementation.methods[0].attributes.len(), 1);
+
+        let mut parser = Parser::new(Lexer::new("@inline import std; @cold 42 fn h() {}").lex());
+        let ast = parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [
+                ParserError::InvalidSyntax(
+                    1,
+                    0,
+                    String::from("Attributes cannot be applied to an import.")
+                ),
+                ParserError::UnexpectedToken(1, 26, String::from("42")),
+            ]
+        );
+        assert!(matches!(
+            ast.declarations.last().map(Box::as_ref),
+            Some(Declaration::Function(_))
+        ));
+    }
+
+    #[test]
+    fn test_recover_to() {
+        let src = "fn first() { } 42 x = 1; ) ] } \"junk\" + - * ; ; 7.5 fn second() { }";
+        let mut parser = Parser::new(Lexer::new(src).lex());
+        let ast = parser.parse();
+        assert_eq!(
+            parser.errors(),
+            [ParserError::UnexpectedToken(
+                1,
+                src.find("42").unwrap(),
+                String::from("42")
+            )]
+        );
+        assert_eq!(ast.declarations.len(), 3);
+        assert_eq!(
+            function(&ast, 0).id.id.as_ref().unwrap().get_lexeme(),
+            "first"
+        );
+        assert!(matches!(
+            ast.declarations[1].as_ref(),
+            Declaration::Error(_)
+        ));
+        assert_eq!(
+            function(&ast, 2).id.id.as_ref().unwrap().get_lexeme(),
+            "second"
+        );
+
+        let mut parser = Parser::new(Lexer::new("x y z").lex());
+        parser.recover_to(DECLARATION_STARTS);
+        assert!(parser.eof());
+
+        // Only the keywords that start a declaration end the skipped tokens.
+        let mut parser = Parser::new(Lexer::new("x if { fn_name intf S { }").lex());
+        parser.recover_to(DECLARATION_STARTS);
+        assert_eq!(parser.current().get_lexeme(), "intf");
     }
 }
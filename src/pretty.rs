@@ -0,0 +1,1175 @@
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Comment, Lexer};
+use crate::parser::Parser;
+use crate::source_map::SourceMap;
+use crate::token::Token;
+use crate::visit::{self, Visitor, Walk};
+
+/// The indentation of each level of nesting in formatted source.
+const INDENT: &str = "    ";
+
+/// Prints `expr` back as source code. Groups are dropped and parentheses are
+/// only inserted where the precedence or associativity of the operators
+/// requires them, so the output parses back to the same operations. Prefix
+/// operators in a row are kept apart by a space, as in `- -1`.
+pub fn to_source(expr: &Expression) -> String {
+    let mut printer = Printer::default();
+    printer.expression(expr);
+    printer.out
+}
+
+/// Formats `src` canonically: declarations separated by a blank line, one
+/// statement per line indented by four spaces, and single spaces around
+/// operators. Comments on lines of their own are kept before the declaration
+/// or statement they precede, or at the end of their block, and those that
+/// follow code stay at the end of the line that code is printed on.
+///
+/// Returns the diagnostics instead if `src` has lexer or parser errors, since
+/// whatever failed to parse is missing from the tree it is printed from, along
+/// with the map of `src` without its byte order mark to show them against.
+pub fn format_source(src: &str) -> Result<String, (Vec<Diagnostic>, SourceMap<'_>)> {
+    let mut lexer = Lexer::new(src);
+    let tokens = lexer.lex();
+    if lexer.has_error() {
+        let diagnostics = tokens
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Error(err) => Some(Diagnostic::from(err)),
+                _ => None,
+            })
+            .collect();
+        return Err((diagnostics, lexer.source_map()));
+    }
+    let braces = closing_lines(&tokens);
+    let keywords = keyword_lines(&tokens);
+
+    let (ast, errors) = Parser::new(tokens).parse_with_diagnostics();
+    if !errors.is_empty() {
+        let diagnostics = errors.into_iter().map(Diagnostic::from).collect();
+        return Err((diagnostics, lexer.source_map()));
+    }
+
+    let mut printer = Printer {
+        comments: lexer.comments(),
+        braces: braces.iter(),
+        keywords: keywords.iter(),
+        ..Printer::default()
+    };
+    printer.declarations(&ast);
+    Ok(printer.out)
+}
+
+#[derive(Default)]
+struct Printer<'c> {
+    out: String,
+    /// The current level of nesting.
+    indent: usize,
+    /// The comments that are not printed yet, in source order.
+    comments: &'c [Comment],
+    /// The line of the `}` closing each `{` of the source that is not
+    /// printed yet, in source order.
+    braces: std::slice::Iter<'c, Option<usize>>,
+    /// The line of each `ret`, `break` and `continue` of the source that is
+    /// not printed yet, in source order.
+    keywords: std::slice::Iter<'c, usize>,
+}
+
+/// Returns the line of the `}` closing each `{` in `tokens`, in the order of
+/// the `{`.
+fn closing_lines(tokens: &[Token]) -> Vec<Option<usize>> {
+    let mut lines = Vec::new();
+    let mut open = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Separator(_, _, lexeme) if lexeme == "{" => {
+                open.push(lines.len());
+                lines.push(None);
+            }
+            Token::Separator(line, _, lexeme) if lexeme == "}" => {
+                if let Some(i) = open.pop() {
+                    lines[i] = Some(*line);
+                }
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+/// Returns the line of each `ret`, `break` and `continue` in `tokens`, which
+/// start the statements that may keep no token of their own.
+fn keyword_lines(tokens: &[Token]) -> Vec<usize> {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Keyword(line, _, lexeme)
+                if matches!(lexeme.as_str(), "ret" | "break" | "continue") =>
+            {
+                Some(*line)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns whether `comment` comes before a node starting on `line`. A
+/// comment following code on that line may follow the node as well.
+fn is_before(comment: &Comment, line: usize) -> bool {
+    comment.line < line || comment.line == line && !comment.is_trailing
+}
+
+/// Returns `text` moved from column `from` to column `to`, shifting the lines
+/// after the first of a block comment along with it.
+fn reindented(text: &str, from: usize, to: usize) -> String {
+    let mut lines = text.split('\n');
+    let mut out = String::from(lines.next().unwrap_or_default());
+    for line in lines {
+        let rest = line.trim_start();
+        out.push('\n');
+        if !rest.is_empty() {
+            let indent = (line.len() - rest.len() + to).saturating_sub(from);
+            out.push_str(&" ".repeat(indent));
+            out.push_str(rest);
+        }
+    }
+    out
+}
+
+impl Printer<'_> {
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Prints the comments before a node starting on `line`. Nothing is
+    /// printed for an unknown line, leaving the comments for the next node.
+    fn comments_through(&mut self, line: Option<usize>) {
+        let Some(line) = line else {
+            return;
+        };
+        while let Some((comment, rest)) = self.comments.split_first() {
+            if !is_before(comment, line) {
+                break;
+            }
+            self.comment(comment);
+            self.comments = rest;
+        }
+    }
+
+    /// Prints `comment` at the end of the last line printed if it followed
+    /// code, which is printed by then, and on a line of its own otherwise.
+    fn comment(&mut self, comment: &Comment) {
+        let end = self.out.trim_end_matches('\n').len();
+        let start = self.out[..end].rfind('\n').map_or(0, |i| i + 1);
+        // Anything after a line comment would be part of it.
+        if comment.is_trailing && end > 0 && !self.out[start..end].contains("//") {
+            let text = reindented(&comment.text, comment.col, end - start + 2);
+            self.out.insert_str(end, &format!(" {}", text));
+        } else {
+            self.write_indent();
+            let text = reindented(&comment.text, comment.col, self.indent * INDENT.len() + 1);
+            self.out.push_str(&text);
+            self.out.push('\n');
+        }
+    }
+
+    /// Prints a `{` that was in the source, returning the line of the `}`
+    /// closing it there. Trees that were not parsed from source, or with
+    /// fewer braces than printed, have no lines.
+    fn open_brace(&mut self) -> Option<usize> {
+        self.out.push('{');
+        self.braces.next().copied().flatten()
+    }
+
+    /// Prints the comments left before the `}` on `close`, at the end of the
+    /// body it closes.
+    fn close_brace(&mut self, close: Option<usize>) {
+        self.indent += 1;
+        self.comments_through(close);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    /// Returns whether there are comments before the `}` on `close`.
+    fn has_comments_before(&self, close: Option<usize>) -> bool {
+        match (self.comments.first(), close) {
+            (Some(comment), Some(close)) => is_before(comment, close),
+            _ => false,
+        }
+    }
+
+    fn declarations(&mut self, ast: &AST) {
+        let lines: Vec<Option<usize>> = ast
+            .declarations
+            .iter()
+            .map(|decl| declaration_line(decl))
+            .collect();
+        for (i, decl) in ast.declarations.iter().enumerate() {
+            let is_import = |decl: &Declaration| matches!(decl, Declaration::Import(_));
+            if i > 0 && !(is_import(decl) && is_import(&ast.declarations[i - 1])) {
+                self.out.push('\n');
+            }
+            self.comments_through(lines[i]);
+            self.declaration(decl);
+        }
+        self.comments_through(Some(usize::MAX));
+    }
+
+    fn declaration(&mut self, decl: &Declaration) {
+        match decl {
+            Declaration::Enum(enumeration) => {
+                self.attributes(&enumeration.attributes);
+                self.write_indent();
+                self.out.push_str("enum ");
+                self.identifier(&enumeration.id);
+                self.generics(enumeration.generics.as_deref());
+                self.out.push(' ');
+                let close = self.open_brace();
+                let variants = enumeration.variants.as_deref().unwrap_or_default();
+                if variants.is_empty() && !self.has_comments_before(close) {
+                    self.out.push_str("}\n");
+                    return;
+                }
+                self.out.push('\n');
+                self.indent += 1;
+                for variant in variants {
+                    self.comments_through(variant.id().id.as_ref().map(Token::get_line));
+                    self.write_indent();
+                    self.variant(variant);
+                    self.out.push_str(",\n");
+                }
+                self.indent -= 1;
+                self.close_brace(close);
+                self.out.push('\n');
+            }
+            Declaration::Struct(structure) => {
+                self.attributes(&structure.attributes);
+                self.write_indent();
+                self.out.push_str("struct ");
+                self.variant(&structure.variant);
+                self.out.push_str(";\n");
+            }
+            Declaration::Function(function) => self.function(function, true),
+            Declaration::Interface(interface) => {
+                self.attributes(&interface.attributes);
+                self.write_indent();
+                if interface.is_pub {
+                    self.out.push_str("pub ");
+                }
+                self.out.push_str("intf ");
+                self.identifier(&interface.id);
+                self.generics(interface.generics.as_deref());
+                self.out.push(' ');
+                self.methods(&interface.methods, |method| method.is_default);
+            }
+            Declaration::Implementation(implementation) => {
+                self.attributes(&implementation.attributes);
+                self.write_indent();
+                self.out.push_str("impl intf ");
+                self.identifier(&implementation.intf_id);
+                self.generics(implementation.generics.as_deref());
+                self.out.push_str(" for ");
+                self.type_variant(&implementation.for_type.variant);
+                self.out.push(' ');
+                self.methods(&implementation.methods, |_| true);
+            }
+            Declaration::Import(import) => {
+                self.write_indent();
+                self.out.push_str("import ");
+                for (i, segment) in import.segments.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push('.');
+                    }
+                    self.identifier(segment);
+                }
+                self.out.push_str(";\n");
+            }
+            Declaration::Error(_) => self.out.push_str("<error>\n"),
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        for attribute in attributes {
+            self.write_indent();
+            self.out.push('@');
+            self.identifier(&attribute.id);
+            if !attribute.args.is_empty() {
+                self.arguments(&attribute.args);
+            }
+            self.out.push('\n');
+        }
+    }
+
+    /// Prints the body of an interface or implementation. Methods with a
+    /// body are set apart from the others by a blank line.
+    fn methods(
+        &mut self,
+        methods: &[Box<FunctionDeclaration>],
+        has_body: impl Fn(&FunctionDeclaration) -> bool,
+    ) {
+        let close = self.open_brace();
+        if methods.is_empty() && !self.has_comments_before(close) {
+            self.out.push_str("}\n");
+            return;
+        }
+        self.out.push('\n');
+        self.indent += 1;
+        for (i, method) in methods.iter().enumerate() {
+            if i > 0 && (has_body(method) || has_body(&methods[i - 1])) {
+                self.out.push('\n');
+            }
+            self.comments_through(function_line(method));
+            self.function(method, has_body(method));
+        }
+        self.indent -= 1;
+        self.close_brace(close);
+        self.out.push('\n');
+    }
+
+    /// Prints `function`, or only its signature unless it `has_body`.
+    fn function(&mut self, function: &FunctionDeclaration, has_body: bool) {
+        self.attributes(&function.attributes);
+        self.write_indent();
+        if function.is_pub {
+            self.out.push_str("pub ");
+        }
+        if function.is_const {
+            self.out.push_str("const ");
+        }
+        if function.is_default {
+            self.out.push_str("default ");
+        }
+        self.out.push_str("fn ");
+        self.identifier(&function.id);
+        self.generics(function.generics.as_deref());
+        self.out.push('(');
+        if let Some(receiver) = &function.receiver {
+            if receiver.is_ref {
+                self.out.push_str("ref ");
+            }
+            self.identifier(&receiver.id);
+        }
+        for (i, (param_type, id)) in function.parameters.iter().flatten().enumerate() {
+            if i > 0 || function.receiver.is_some() {
+                self.out.push_str(", ");
+            }
+            self.type_variant(&param_type.variant);
+            self.out.push(' ');
+            self.identifier(id);
+        }
+        self.out.push(')');
+        if let Some(return_type) = &function.return_type {
+            self.out.push_str(" -> ");
+            self.type_variant(&return_type.variant);
+        }
+        if has_body {
+            self.out.push(' ');
+            self.block(&function.block);
+        }
+        self.out.push('\n');
+    }
+
+    fn generics(&mut self, generics: Option<&GenericParameters>) {
+        let Some(generics) = generics else {
+            return;
+        };
+        self.out.push('<');
+        for (i, generic) in generics.generics.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            match generic.as_ref() {
+                GenericVariants::Identifier(id) => {
+                    self.out.push_str("type ");
+                    self.identifier(id);
+                }
+                GenericVariants::Implements(id, bound) => {
+                    self.out.push_str("type ");
+                    self.identifier(id);
+                    self.out.push_str(" impl ");
+                    self.identifier(bound);
+                }
+                GenericVariants::Type(argument) => self.type_variant(&argument.variant),
+                GenericVariants::Error(_) => self.out.push_str("<error>"),
+            }
+        }
+        self.out.push('>');
+    }
+
+    /// Prints an enum variant or the body of a struct, starting at its name.
+    fn variant(&mut self, variant: &Variant) {
+        match variant {
+            Variant::Named(id, fields) => {
+                self.identifier(id);
+                self.out.push(' ');
+                self.open_brace();
+                for (i, (field_type, field_id)) in fields.fields.iter().enumerate() {
+                    self.out.push_str(if i > 0 { ", " } else { " " });
+                    self.type_variant(&field_type.variant);
+                    self.out.push(' ');
+                    self.identifier(field_id);
+                }
+                self.out
+                    .push_str(if fields.fields.is_empty() { "}" } else { " }" });
+            }
+            Variant::Tuple(id, fields) => {
+                self.identifier(id);
+                self.out.push('(');
+                for (i, field_type) in fields.fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.type_variant(&field_type.variant);
+                }
+                self.out.push(')');
+            }
+            Variant::Unit(id, discriminant) => {
+                self.identifier(id);
+                if let Some(discriminant) = discriminant {
+                    self.out.push_str(" = ");
+                    self.expression(discriminant);
+                }
+            }
+        }
+    }
+
+    fn type_variant(&mut self, variant: &TypeVariant) {
+        match variant {
+            TypeVariant::Primitive(name) => self.out.push_str(name),
+            TypeVariant::Structure(id, generics) => {
+                self.out.push_str("struct ");
+                self.identifier(id);
+                self.generics(generics.as_deref());
+            }
+            TypeVariant::Enumeration(id, generics) => {
+                self.out.push_str("enum ");
+                self.identifier(id);
+                self.generics(generics.as_deref());
+            }
+            TypeVariant::Interface(id, generics) => {
+                self.out.push_str("intf ");
+                self.identifier(id);
+                self.generics(generics.as_deref());
+            }
+            TypeVariant::Array(element, size) => {
+                self.out.push('[');
+                self.type_variant(element);
+                self.out.push_str("; ");
+                self.expression(size);
+                self.out.push(']');
+            }
+            TypeVariant::Reference(inner) => {
+                self.out.push_str("ref ");
+                self.type_variant(inner);
+            }
+            TypeVariant::Generic(id) => {
+                self.out.push_str("type ");
+                self.identifier(id);
+            }
+            TypeVariant::Error(_) => self.out.push_str("<error>"),
+        }
+    }
+
+    /// Prints `block` from its `{` to its `}`.
+    fn block(&mut self, block: &Block) {
+        let close = self.open_brace();
+        if block.statements.is_empty() && block.value.is_none() && !self.has_comments_before(close)
+        {
+            self.out.push('}');
+            return;
+        }
+        self.out.push('\n');
+        self.indent += 1;
+
+        // Statements that kept no token, such as `llvm {}`, take the line
+        // of what follows them, so that the comments before them are not
+        // printed after them. Those starting with a keyword take its line.
+        let value_line = block.value.as_deref().and_then(first_line);
+        let mut next = value_line.or(close);
+        let mut lines: Vec<Option<usize>> = block.statements.iter().map(first_line).collect();
+        for line in lines.iter_mut().rev() {
+            match line {
+                Some(line) => next = Some(*line),
+                None => *line = next,
+            }
+        }
+        for (statement, line) in block.statements.iter().zip(lines) {
+            let line = match statement {
+                Statement::Return(_) | Statement::Break(..) | Statement::Continue(_) => {
+                    self.keywords.clone().next().copied()
+                }
+                _ => line,
+            };
+            self.comments_through(line);
+            self.statement(statement);
+        }
+        if let Some(value) = &block.value {
+            self.comments_through(value_line);
+            self.write_indent();
+            self.expression(value);
+            self.out.push('\n');
+        }
+
+        self.indent -= 1;
+        self.close_brace(close);
+    }
+
+    /// Prints `statement` on lines of its own.
+    fn statement(&mut self, statement: &Statement) {
+        self.write_indent();
+        match statement {
+            Statement::If(if_statement) => {
+                self.out.push_str("if ");
+                self.expression(&if_statement.condition);
+                self.out.push(' ');
+                self.block(&if_statement.if_block);
+                for elif in if_statement.elif_statements.iter().flatten() {
+                    self.out.push_str(" elif ");
+                    self.expression(&elif.condition);
+                    self.out.push(' ');
+                    self.block(&elif.block);
+                }
+                if let Some(else_block) = &if_statement.else_block {
+                    self.out.push_str(" else ");
+                    self.block(else_block);
+                }
+            }
+            Statement::Loop(loop_statement) => self.loop_statement(loop_statement),
+            Statement::Assign(assignment) => {
+                self.identifier(&assignment.id);
+                self.out.push_str(" = ");
+                self.expression(&assignment.expr);
+                self.out.push(';');
+            }
+            Statement::Var(declaration) => {
+                match declaration.state {
+                    VariableDeclaration::VOLATILE => self.out.push_str("volatile "),
+                    VariableDeclaration::CONST => self.out.push_str("const "),
+                    _ => {}
+                }
+                self.type_variant(&declaration.var_type.variant);
+                self.out.push(' ');
+                self.identifier(&declaration.id);
+                if let Some(init) = &declaration.init {
+                    self.out.push_str(" = ");
+                    self.expression(init);
+                }
+                self.out.push(';');
+            }
+            Statement::Match(match_statement) => self.match_arms(match_statement),
+            Statement::Return(ret) => {
+                self.keywords.next();
+                self.out.push_str("ret");
+                if let Some(expr) = &ret.expr {
+                    self.out.push(' ');
+                    self.expression(expr);
+                }
+                self.out.push(';');
+            }
+            Statement::Break(label, value) => {
+                self.keywords.next();
+                self.out.push_str("break");
+                if let Some(label) = label {
+                    self.out.push(' ');
+                    self.out.push_str(label.get_lexeme());
+                }
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expression(value);
+                }
+                self.out.push(';');
+            }
+            Statement::Continue(label) => {
+                self.keywords.next();
+                self.out.push_str("continue");
+                if let Some(label) = label {
+                    self.out.push(' ');
+                    self.out.push_str(label.get_lexeme());
+                }
+                self.out.push(';');
+            }
+            Statement::FunctionCall(call) => {
+                self.function_call(call);
+                self.out.push(';');
+            }
+            Statement::LLVM(block) => {
+                self.out.push_str("llvm ");
+                self.string_block(&block.statements);
+            }
+            Statement::ASM(block) => {
+                self.out.push_str("asm ");
+                self.string_block(&block.statements);
+            }
+            Statement::Error(_) => self.out.push_str("<error>"),
+        }
+        self.out.push('\n');
+    }
+
+    fn string_block(&mut self, statements: &[BlockStringLiteralVariant]) {
+        self.out.push('{');
+        for (i, statement) in statements.iter().enumerate() {
+            self.out.push_str(if i > 0 { ", " } else { " " });
+            match statement {
+                BlockStringLiteralVariant::StringLiteral(literal) => self.literal(literal),
+                BlockStringLiteralVariant::Identifier(id) => self.identifier(id),
+            }
+        }
+        self.out
+            .push_str(if statements.is_empty() { "}" } else { " }" });
+    }
+
+    /// Prints a `match` with each arm on lines of its own.
+    fn match_arms(&mut self, match_statement: &MatchStatement) {
+        self.out.push_str("match ");
+        self.expression(&match_statement.expr);
+        self.out.push(' ');
+        let close = self.open_brace();
+        self.out.push('\n');
+        self.indent += 1;
+        for clause in &match_statement.case_clauses {
+            self.comments_through(clause_line(clause));
+            self.write_indent();
+            self.patterns(&clause.cases);
+            self.out.push_str(" -> ");
+            self.block(&clause.case_block);
+            self.out.push('\n');
+        }
+        if let Some(default) = &match_statement.default_clause {
+            self.write_indent();
+            self.out.push_str("default -> ");
+            self.block(default);
+            self.out.push('\n');
+        }
+        self.indent -= 1;
+        self.close_brace(close);
+    }
+
+    fn patterns(&mut self, cases: &[Pattern]) {
+        for (i, case) in cases.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            match case {
+                Pattern::Literal(literal) => self.literal(literal),
+                Pattern::Variant(id) => self.identifier(id),
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Operation(operator) => self.operator(operator),
+            Expression::Primary(primary) => self.primary(primary),
+            Expression::Error(_) => self.out.push_str("<error>"),
+        }
+    }
+
+    fn operator(&mut self, operator: &Operator) {
+        match operator {
+            Operator::Binary(op, lhs, rhs) => {
+                self.operand(operator, lhs, false);
+                self.out.push(' ');
+                self.out.push_str(op);
+                self.out.push(' ');
+                self.operand(operator, rhs, true);
+            }
+            Operator::Unary(op, operand) => {
+                self.out.push_str(op);
+                let start = self.out.len();
+                self.operand(operator, operand, false);
+                // `ref` and `deref` are keywords, and would run into an
+                // identifier, and prefix operators in a row would read as
+                // one, as `--1` does.
+                let next = self.out[start..].chars().next();
+                if op.starts_with(char::is_alphabetic)
+                    || next.is_some_and(|c| matches!(c, '+' | '-' | '!' | '~'))
+                {
+                    self.out.insert(start, ' ');
+                }
+            }
+            Operator::Error(_) => self.out.push_str("<error>"),
+        }
+    }
+
+    /// Prints an operand of `parent`, in parentheses if it would not parse
+    /// back as that operand otherwise.
+    fn operand(&mut self, parent: &Operator, operand: &Expression, is_rhs: bool) {
+        let operand = ungrouped(operand);
+        match operand {
+            Expression::Operation(child) if Operator::needs_parens(parent, child, is_rhs) => {
+                self.out.push('(');
+                self.operator(child);
+                self.out.push(')');
+            }
+            _ if is_range(operand) => {
+                self.out.push('(');
+                self.expression(operand);
+                self.out.push(')');
+            }
+            _ => self.expression(operand),
+        }
+    }
+
+    /// Prints a bound of a range, in parentheses if it is a range itself
+    /// since ranges do not chain.
+    fn range_bound(&mut self, bound: &Expression) {
+        let bound = ungrouped(bound);
+        if is_range(bound) {
+            self.out.push('(');
+            self.expression(bound);
+            self.out.push(')');
+        } else {
+            self.expression(bound);
+        }
+    }
+
+    fn loop_statement(&mut self, loop_statement: &LoopStatement) {
+        if let Some(label) = &loop_statement.label {
+            self.out.push_str(label.get_lexeme());
+            self.out.push(' ');
+        }
+        self.out.push_str("loop ");
+        if let Some(iteration) = &loop_statement.iteration {
+            self.identifier(&iteration.variable);
+            self.out.push_str(" in ");
+            self.range(&iteration.range);
+            self.out.push(' ');
+        }
+        self.block(&loop_statement.block);
+    }
+
+    fn range(&mut self, range: &Range) {
+        self.range_bound(&range.start);
+        self.out
+            .push_str(if range.is_inclusive { "..=" } else { ".." });
+        self.range_bound(&range.end);
+    }
+
+    fn primary(&mut self, primary: &Primary) {
+        match primary {
+            Primary::Literal(literal) => self.literal(literal),
+            Primary::Identifier(id) => self.identifier(id),
+            Primary::Group(inner) => self.expression(ungrouped(inner)),
+            Primary::ArrayAccess(id, access) => {
+                self.identifier(id);
+                let mut access = Some(access);
+                while let Some(current) = access {
+                    self.out.push('[');
+                    self.expression(&current.index);
+                    self.out.push(']');
+                    access = current.next.as_ref();
+                }
+            }
+            Primary::FunctionCall(call) => self.function_call(call),
+            Primary::Match(match_statement) => self.match_expression(match_statement),
+            Primary::Loop(loop_statement) => self.loop_statement(loop_statement),
+            Primary::FieldAccess(base, member) => {
+                let base = ungrouped(base);
+                if matches!(base, Expression::Operation(_)) || is_range(base) {
+                    self.out.push('(');
+                    self.expression(base);
+                    self.out.push(')');
+                } else {
+                    self.expression(base);
+                }
+                self.out.push('.');
+                self.primary(member);
+            }
+            Primary::Range(range) => self.range(range),
+            Primary::Error(_) => self.out.push_str("<error>"),
+        }
+    }
+
+    /// Prints a `match` expression on a single line if its arms only yield a
+    /// value, and with each arm on lines of its own otherwise.
+    fn match_expression(&mut self, match_statement: &MatchStatement) {
+        let arms = match_statement
+            .case_clauses
+            .iter()
+            .map(|clause| &clause.case_block)
+            .chain(&match_statement.default_clause);
+        if arms.into_iter().any(|arm| !arm.statements.is_empty()) {
+            self.match_arms(match_statement);
+            return;
+        }
+
+        self.out.push_str("match ");
+        self.expression(&match_statement.expr);
+        self.out.push(' ');
+        self.open_brace();
+        for clause in &match_statement.case_clauses {
+            self.out.push(' ');
+            self.patterns(&clause.cases);
+            self.out.push_str(" -> ");
+            self.value_block(&clause.case_block);
+        }
+        if let Some(default) = &match_statement.default_clause {
+            self.out.push_str(" default -> ");
+            self.value_block(default);
+        }
+        self.out.push_str(" }");
+    }
+
+    /// Prints an arm of a `match` expression that only yields a value.
+    fn value_block(&mut self, block: &Block) {
+        self.open_brace();
+        match &block.value {
+            Some(value) => {
+                self.out.push(' ');
+                self.expression(value);
+                self.out.push_str(" }");
+            }
+            None => self.out.push('}'),
+        }
+    }
+
+    fn function_call(&mut self, call: &FunctionCall) {
+        self.identifier(&call.id);
+        self.arguments(&call.args);
+    }
+
+    fn arguments(&mut self, args: &[Box<Expression>]) {
+        self.out.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expression(arg);
+        }
+        self.out.push(')');
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal_token(literal) {
+            Some(token) => self.out.push_str(token.get_lexeme()),
+            None => self.out.push_str("<error>"),
+        }
+    }
+
+    fn identifier(&mut self, id: &Identifier) {
+        match &id.id {
+            Some(token) => self.out.push_str(token.get_lexeme()),
+            None => self.out.push_str("<error>"),
+        }
+    }
+}
+
+/// Returns whether `expr` is a range, which binds more loosely than every
+/// operator.
+fn is_range(expr: &Expression) -> bool {
+    matches!(expr, Expression::Primary(primary) if matches!(primary.as_ref(), Primary::Range(_)))
+}
+
+/// Returns the expression inside any number of groups.
+fn ungrouped(mut expr: &Expression) -> &Expression {
+    while let Expression::Primary(primary) = expr {
+        match primary.as_ref() {
+            Primary::Group(inner) => expr = inner,
+            _ => break,
+        }
+    }
+    expr
+}
+
+fn literal_token(literal: &Literal) -> Option<&Token> {
+    match literal {
+        Literal::Integer(token)
+        | Literal::Float(token)
+        | Literal::String(token)
+        | Literal::Character(token)
+        | Literal::Boolean(token)
+        | Literal::Null(token) => Some(token),
+        Literal::Error(_) => None,
+    }
+}
+
+/// Finds the first line of a node from the tokens kept in it. Keywords are
+/// not kept, so a node may start on an earlier line than the one found.
+#[derive(Default)]
+struct FirstLine(Option<usize>);
+
+impl FirstLine {
+    fn record(&mut self, token: &Token) {
+        let line = token.get_line();
+        self.0 = Some(self.0.map_or(line, |first| first.min(line)));
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        for attribute in attributes {
+            self.visit_identifier(&attribute.id);
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for FirstLine {
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        match statement {
+            Statement::Loop(LoopStatement {
+                label: Some(label), ..
+            })
+            | Statement::Break(Some(label), _)
+            | Statement::Continue(Some(label)) => self.record(label),
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        if let Expression::Primary(primary) = expr {
+            match primary.as_ref() {
+                Primary::Literal(literal) => {
+                    if let Some(token) = literal_token(literal) {
+                        self.record(token);
+                    }
+                }
+                Primary::Loop(loop_statement) => {
+                    if let Some(label) = &loop_statement.label {
+                        self.record(label);
+                    }
+                }
+                _ => {}
+            }
+        }
+        visit::walk_expression(self, expr);
+    }
+
+    fn visit_identifier(&mut self, id: &'ast Identifier) {
+        if let Some(token) = &id.id {
+            self.record(token);
+        }
+    }
+}
+
+fn first_line<N: Walk>(node: &N) -> Option<usize> {
+    let mut finder = FirstLine::default();
+    node.accept(&mut finder);
+    finder.0
+}
+
+fn declaration_line(decl: &Declaration) -> Option<usize> {
+    let mut finder = FirstLine::default();
+    match decl {
+        Declaration::Enum(enumeration) => finder.attributes(&enumeration.attributes),
+        Declaration::Struct(structure) => finder.attributes(&structure.attributes),
+        Declaration::Function(function) => finder.attributes(&function.attributes),
+        Declaration::Interface(interface) => finder.attributes(&interface.attributes),
+        Declaration::Implementation(implementation) => {
+            finder.attributes(&implementation.attributes)
+        }
+        Declaration::Import(_) | Declaration::Error(_) => {}
+    }
+    finder.visit_declaration(decl);
+    finder.0
+}
+
+fn function_line(function: &FunctionDeclaration) -> Option<usize> {
+    let mut finder = FirstLine::default();
+    finder.attributes(&function.attributes);
+    finder.visit_function(function);
+    finder.0
+}
+
+fn clause_line(clause: &CaseClause) -> Option<usize> {
+    let mut finder = FirstLine::default();
+    for case in &clause.cases {
+        match case {
+            Pattern::Literal(literal) => {
+                if let Some(token) = literal_token(literal) {
+                    finder.record(token);
+                }
+            }
+            Pattern::Variant(id) => finder.visit_identifier(id),
+        }
+    }
+    finder.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn round_trip(src: &str) -> String {
+        let (expr, errors) = Parser::parse_expression_str(src);
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+        to_source(&expr)
+    }
+
+    #[test]
+    fn test_to_source_parenthesizes_by_precedence() {
+        for src in [
+            "(1 + 2) * 3",
+            "1 + 2 * 3",
+            "a - (b - c)",
+            "-(x + 1) << f(y, z[0])",
+            "match y { 1, -2 -> { a + 1 } default -> { 0 } } * 2",
+            "0..n + 1",
+            "(0..1)..=2",
+            "-(a..b) + (0..1)",
+            "-a.b.c(x.y) + (a + b).c + f().d[e.f]",
+        ] {
+            let printed = round_trip(src);
+            assert_eq!(printed, src);
+            assert_eq!(
+                Parser::parse_expression_str(&printed),
+                Parser::parse_expression_str(src)
+            );
+        }
+
+        assert_eq!(round_trip("((1 * 2)) + 3"), "1 * 2 + 3");
+        assert_eq!(round_trip("(a - b) - c"), "a - b - c");
+        assert_eq!(round_trip("ref x || !(a && b)"), "ref x || !(a && b)");
+        assert_eq!(round_trip("-(-1)"), "- -1");
+        assert_eq!(round_trip("!(!a) && -(+b)"), "! !a && - +b");
+        assert_eq!(round_trip("- - -x"), "- - -x");
+        let negation = Expression::unary("-", Expression::int("-1"));
+        assert_eq!(to_source(&negation), "- -1");
+        assert_eq!(round_trip(&round_trip("-(-1)")), "- -1");
+
+        let tree = Expression::binary(
+            "*",
+            Expression::binary("+", Expression::int("1"), Expression::int("2")),
+            Expression::int("3"),
+        );
+        assert_eq!(to_source(&tree), "(1 + 2) * 3");
+    }
+
+    const MESSY: &str = "import std . io;import std.fmt;
+// Adds two numbers.
+@inline pub fn add( i32 a,i32 b )->i32{ret a+(b*2);}
+enum Color{Red,Green=2,Rgb(u8,u8,u8)}
+intf Shape{fn area(ref self)->i32
+default fn name(self)->i32{ret 0;}}
+impl intf Shape for enum Color{fn area(ref self)->i32{ret match self{Red->{1}default->{0}};}}
+fn main(){i32 x=add(1,2);const i32 y=  3
+'outer loop i in 0..x{if i>y{break 'outer;}elif i==1{continue;}else{x=x+(i*2);}}
+match x{1,2->{ret;} default->{}}   // done
+  /* nothing
+     left */ }
+fn empty() {
+// TODO
+}
+";
+
+    const CANONICAL: &str = "import std.io;
+import std.fmt;
+
+// Adds two numbers.
+@inline
+pub fn add(i32 a, i32 b) -> i32 {
+    ret a + b * 2;
+}
+
+enum Color {
+    Red,
+    Green = 2,
+    Rgb(u8, u8, u8),
+}
+
+intf Shape {
+    fn area(ref self) -> i32
+
+    default fn name(self) -> i32 {
+        ret 0;
+    }
+}
+
+impl intf Shape for enum Color {
+    fn area(ref self) -> i32 {
+        ret match self { Red -> { 1 } default -> { 0 } };
+    }
+}
+
+fn main() {
+    i32 x = add(1, 2);
+    const i32 y = 3;
+    'outer loop i in 0..x {
+        if i > y {
+            break 'outer;
+        } elif i == 1 {
+            continue;
+        } else {
+            x = x + i * 2;
+        }
+    }
+    match x {
+        1, 2 -> {
+            ret;
+        }
+        default -> {}
+    } // done
+    /* nothing
+       left */
+}
+
+fn empty() {
+    // TODO
+}
+";
+
+    #[test]
+    fn test_format_source() {
+        assert_eq!(format_source(MESSY).unwrap(), CANONICAL);
+        assert_eq!(format_source(CANONICAL).unwrap(), CANONICAL);
+
+        let src = "fn f() { i32 x = match y { 1 -> { g(); 2 } default -> { 0 } }; }";
+        let formatted = format_source(src).unwrap();
+        assert_eq!(
+            formatted,
+            "fn f() {\n    i32 x = match y {\n        1 -> {\n            g();\n            2\n        }\n        default -> {\n            0\n        }\n    };\n}\n"
+        );
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+
+        let src = "fn f() { i32 x = 'a loop { break 'a g(); }; }";
+        let formatted = format_source(src).unwrap();
+        assert_eq!(
+            formatted,
+            "fn f() {\n    i32 x = 'a loop {\n        break 'a g();\n    };\n}\n"
+        );
+
+        let src = "fn f() -> i32 { // start
+    g(); // call
+    i32 x = 1 + // one
+        2;
+    ret 1 /* c */ + x;
+}
+enum E { A, // a
+B }
+fn h() {
+        /* block
+           comment */
+    ret; // done
+}
+";
+        let formatted = format_source(src).unwrap();
+        assert_eq!(
+            formatted,
+            "fn f() -> i32 { // start
+    g(); // call
+    i32 x = 1 + 2; // one
+    ret 1 + x; /* c */
+}
+
+enum E {
+    A, // a
+    B,
+}
+
+fn h() {
+    /* block
+       comment */
+    ret; // done
+}
+"
+        );
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+
+        let (errors, source) = format_source("\u{feff}fn f() { x = ; }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(source.src(), "fn f() { x = ; }");
+        assert!(format_source("fn f() { \"x }").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use crate::ast::*;
+use crate::token::Token;
+use crate::utils::SemanticError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Identifies a file of a program by its position in the list of syntax
+/// trees given to [`Program::merge`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FileId(pub u32);
+
+/// The declarations of every file of a program.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Program {
+    /// The top-level declarations in file order, each with the file it
+    /// comes from.
+    pub declarations: Vec<(FileId, Box<Declaration>)>,
+}
+
+impl Program {
+    /// Combines the syntax trees of the files of a program, the first
+    /// having id 0. Public names declared by more than one file are
+    /// reported at each later declaration, along with the file declaring
+    /// it. Names declared twice in the same file are left to the semantic
+    /// analysis of that file.
+    pub fn merge(asts: Vec<AST>) -> (Program, Vec<(FileId, SemanticError)>) {
+        let mut declarations = Vec::new();
+        let mut errors = Vec::new();
+        let mut owners: HashMap<String, FileId> = HashMap::new();
+
+        for (index, ast) in asts.into_iter().enumerate() {
+            let file = FileId(index as u32);
+            for decl in ast.declarations {
+                if let Some(token) = public_name(&decl) {
+                    let owner = *owners.entry(token.get_lexeme().to_string()).or_insert(file);
+                    if owner != file {
+                        errors.push((
+                            file,
+                            SemanticError::ModuleCollision(
+                                token.get_line(),
+                                token.get_col(),
+                                token.get_lexeme().to_string(),
+                            ),
+                        ));
+                    }
+                }
+                declarations.push((file, decl));
+            }
+        }
+
+        (Program { declarations }, errors)
+    }
+
+    /// Returns the declarations of one file.
+    pub fn declarations_of(&self, file: FileId) -> impl Iterator<Item = &Declaration> {
+        self.declarations
+            .iter()
+            .filter(move |(id, _)| *id == file)
+            .map(|(_, decl)| decl.as_ref())
+    }
+
+    /// Returns the first public declaration named `name`, along with the
+    /// file declaring it.
+    pub fn lookup(&self, name: &str) -> Option<(FileId, &Declaration)> {
+        self.declarations
+            .iter()
+            .find(|(_, decl)| public_name(decl).is_some_and(|token| token.get_lexeme() == name))
+            .map(|(file, decl)| (*file, decl.as_ref()))
+    }
+}
+
+/// Checks that no module imports itself, directly or through other modules.
+/// `modules` pairs the path of each file of a program, such as
+/// `geometry.shapes`, with its syntax tree, in the order given to
+/// [`Program::merge`]. Each cycle is reported once, at the import closing it,
+/// as the paths along it, such as `a -> b -> a`. Imports of modules that are
+/// not part of the program are ignored.
+pub fn check_imports(modules: &[(&str, &AST)]) -> Vec<(FileId, SemanticError)> {
+    let index: HashMap<&str, usize> = modules
+        .iter()
+        .enumerate()
+        .map(|(i, (path, _))| (*path, i))
+        .collect();
+    let graph: Vec<Vec<(usize, &ImportDeclaration)>> = modules
+        .iter()
+        .map(|(_, ast)| {
+            ast.declarations
+                .iter()
+                .filter_map(|decl| match decl.as_ref() {
+                    Declaration::Import(import) => {
+                        Some((*index.get(import.path().as_str())?, import.as_ref()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut checker = ImportChecker {
+        modules,
+        graph: &graph,
+        visited: vec![false; modules.len()],
+        stack: Vec::new(),
+        errors: Vec::new(),
+    };
+    for module in 0..modules.len() {
+        checker.visit(module);
+    }
+    checker.errors
+}
+
+/// Searches the import graph depth first, where an import of a module that
+/// is still being searched closes a cycle.
+struct ImportChecker<'a, 'ast> {
+    modules: &'a [(&'a str, &'ast AST)],
+    graph: &'a [Vec<(usize, &'ast ImportDeclaration)>],
+    visited: Vec<bool>,
+    stack: Vec<usize>,
+    errors: Vec<(FileId, SemanticError)>,
+}
+
+impl ImportChecker<'_, '_> {
+    fn visit(&mut self, module: usize) {
+        if self.visited[module] {
+            return;
+        }
+        self.visited[module] = true;
+        self.stack.push(module);
+        for &(imported, import) in &self.graph[module] {
+            if let Some(start) = self.stack.iter().position(|&m| m == imported) {
+                let cycle: Vec<&str> = self.stack[start..]
+                    .iter()
+                    .chain([&imported])
+                    .map(|&m| self.modules[m].0)
+                    .collect();
+                let (line, col) = import
+                    .segments
+                    .first()
+                    .and_then(|segment| segment.id.as_ref())
+                    .map_or((0, 0), |token| (token.get_line(), token.get_col()));
+                self.errors.push((
+                    FileId(module as u32),
+                    SemanticError::CircularImport(line, col, cycle.join(" -> ")),
+                ));
+            } else {
+                self.visit(imported);
+            }
+        }
+        self.stack.pop();
+    }
+}
+
+/// Returns the name a declaration makes visible to other files. Functions
+/// and interfaces are visible when marked `pub`; structs and enums have no
+/// visibility and are always visible.
+fn public_name(decl: &Declaration) -> Option<&Token> {
+    match decl {
+        Declaration::Function(function) if function.is_pub => function.id.id.as_ref(),
+        Declaration::Interface(interface) if interface.is_pub => interface.id.id.as_ref(),
+        Declaration::Struct(structure) => structure.variant.id().id.as_ref(),
+        Declaration::Enum(enumeration) => enumeration.id.id.as_ref(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn merge(sources: &[&str]) -> (Program, Vec<(FileId, SemanticError)>) {
+        Program::merge(
+            sources
+                .iter()
+                .map(|src| *Parser::new(Lexer::new(src).lex()).parse())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_merge() {
+        let (program, errors) = merge(&[
+            "pub fn f() {} fn helper() {}",
+            "enum Color { Red } pub fn g() {}",
+        ]);
+        assert!(errors.is_empty());
+        assert_eq!(program.declarations.len(), 4);
+        assert_eq!(program.declarations_of(FileId(0)).count(), 2);
+        assert_eq!(program.declarations_of(FileId(1)).count(), 2);
+        assert!(matches!(
+            program.lookup("g"),
+            Some((FileId(1), Declaration::Function(_)))
+        ));
+        assert_eq!(program.lookup("helper"), None);
+    }
+
+    #[test]
+    fn test_merge_collisions() {
+        let (program, errors) = merge(&[
+            "pub fn f() {} fn helper() {}",
+            "fn helper() {}\npub fn f() {}",
+            "pub fn f() {}",
+        ]);
+        assert_eq!(program.declarations.len(), 5);
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    FileId(1),
+                    SemanticError::ModuleCollision(2, 22, String::from("f"))
+                ),
+                (
+                    FileId(2),
+                    SemanticError::ModuleCollision(1, 7, String::from("f"))
+                ),
+            ]
+        );
+        assert!(matches!(program.lookup("f"), Some((FileId(0), _))));
+    }
+
+    fn check(modules: &[(&str, &str)]) -> Vec<(FileId, SemanticError)> {
+        let asts: Vec<(&str, Box<AST>)> = modules
+            .iter()
+            .map(|(path, src)| (*path, Parser::new(Lexer::new(src).lex()).parse()))
+            .collect();
+        let modules: Vec<(&str, &AST)> = asts
+            .iter()
+            .map(|(path, ast)| (*path, ast.as_ref()))
+            .collect();
+        check_imports(&modules)
+    }
+
+    #[test]
+    fn test_circular_imports() {
+        assert_eq!(
+            check(&[("main", "fn main() { }"), ("util", "import util;")]),
+            vec![(
+                FileId(1),
+                SemanticError::CircularImport(1, 7, String::from("util -> util"))
+            )]
+        );
+
+        assert_eq!(
+            check(&[
+                ("geometry.a", "import geometry.b;"),
+                ("geometry.b", "import std.io;\nimport geometry.a;"),
+            ]),
+            vec![(
+                FileId(1),
+                SemanticError::CircularImport(
+                    2,
+                    22,
+                    String::from("geometry.a -> geometry.b -> geometry.a")
+                )
+            )]
+        );
+
+        let errors = check(&[("a", "import b;"), ("b", "import c;"), ("c", "fn f() { }")]);
+        assert!(errors.is_empty(), "unexpected errors {:?}", errors);
+    }
+}
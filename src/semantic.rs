@@ -0,0 +1,2825 @@
+use crate::ast::*;
+use crate::codegen::Codegen;
+use crate::diagnostic::Diagnostic;
+use crate::fold;
+use crate::pretty;
+use crate::token::Token;
+use crate::utils::{CaseStyle, SemanticError, SemanticWarning};
+use crate::visit::{self, Visitor};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Runs every semantic pass over `ast` in order and returns all of their
+/// diagnostics, errors first.
+pub fn analyze(ast: &AST) -> Vec<Diagnostic> {
+    let errors = check(ast).into_iter().map(Diagnostic::from);
+    let warnings = check_unused(ast)
+        .into_iter()
+        .chain(check_dead_code(ast))
+        .chain(check_attributes(ast))
+        .chain(check_empty_arrays(ast))
+        .map(Diagnostic::from);
+    errors.chain(warnings).collect()
+}
+
+/// Runs every semantic pass over `ast` in order and returns all of their
+/// errors.
+///
+/// Function signatures are collected first. If that fails, e.g. because a
+/// function is declared twice, type checking is skipped since it would check
+/// calls against the wrong signature. Resolution and label checks do not
+/// depend on signatures and always run.
+pub fn check(ast: &AST) -> Vec<SemanticError> {
+    let mut errors = Vec::new();
+
+    let (functions, collection_errors) = collect_functions(ast);
+    let collection_failed = !collection_errors.is_empty();
+    errors.extend(collection_errors);
+
+    errors.extend(check_parameters(ast));
+    errors.extend(check_generics(ast));
+    errors.extend(check_generic_arity(ast));
+    errors.extend(check_discriminants(ast));
+    errors.extend(check_array_sizes(ast));
+    errors.extend(resolve(ast, &functions));
+    errors.extend(check_labels(ast));
+    errors.extend(check_implementations(ast));
+    if !collection_failed {
+        errors.extend(check_types(ast, &functions));
+    }
+
+    errors
+}
+
+/// The type of a value as seen by the type checker.
+#[derive(Clone, Debug, PartialEq)]
+enum Ty {
+    Primitive(String),
+    /// An integer literal without a suffix, which fits any integer type.
+    IntLiteral,
+    /// A float literal without a suffix, which fits any float type.
+    FloatLiteral,
+    Str,
+    Reference(Box<Ty>),
+    /// The type of `null`, which only fits references.
+    Null,
+    Array(Box<Ty>),
+    /// A struct, enum or interface type, by name.
+    Named(String),
+    /// A generic type parameter of the function being checked, by name.
+    Generic(String),
+    Void,
+    /// The type of an expression that could not be checked, e.g. because it
+    /// refers to an undefined name. It is compatible with every type so that
+    /// a single mistake is only reported once.
+    Unknown,
+}
+
+impl Ty {
+    fn from_variant(variant: &TypeVariant) -> Ty {
+        match variant {
+            TypeVariant::Primitive(name) if name == "void" => Ty::Void,
+            TypeVariant::Primitive(name) => Ty::Primitive(name.clone()),
+            TypeVariant::Array(element, _) => Ty::Array(Box::new(Ty::from_variant(element))),
+            TypeVariant::Reference(inner) => Ty::Reference(Box::new(Ty::from_variant(inner))),
+            TypeVariant::Structure(id, _)
+            | TypeVariant::Enumeration(id, _)
+            | TypeVariant::Interface(id, _) => match &id.id {
+                Some(token) => Ty::Named(token.get_lexeme().to_string()),
+                None => Ty::Unknown,
+            },
+            TypeVariant::Generic(id) => match &id.id {
+                Some(token) => Ty::Generic(token.get_lexeme().to_string()),
+                None => Ty::Unknown,
+            },
+            TypeVariant::Error(_) => Ty::Unknown,
+        }
+    }
+
+    fn from_type(var_type: &Option<Box<Type>>) -> Ty {
+        match var_type {
+            Some(var_type) => Ty::from_variant(&var_type.variant),
+            None => Ty::Void,
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        match self {
+            Ty::Primitive(name) => name.starts_with('i') || name.starts_with('u'),
+            Ty::IntLiteral | Ty::Unknown => true,
+            _ => false,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        match self {
+            Ty::Primitive(name) => name != "bool" && name != "char",
+            Ty::IntLiteral | Ty::FloatLiteral | Ty::Unknown => true,
+            _ => false,
+        }
+    }
+
+    fn is_bool(&self) -> bool {
+        matches!(self, Ty::Unknown) || self == &Ty::Primitive(String::from("bool"))
+    }
+
+    /// Returns whether a value of type `found` can be used where `self` is
+    /// expected.
+    fn accepts(&self, found: &Ty) -> bool {
+        match (self, found) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+            (Ty::Primitive(name), Ty::IntLiteral) => name.starts_with('i') || name.starts_with('u'),
+            (Ty::Primitive(name), Ty::FloatLiteral) => name.starts_with('f'),
+            (Ty::Reference(_), Ty::Null) => true,
+            (Ty::Array(expected), Ty::Array(found))
+            | (Ty::Reference(expected), Ty::Reference(found)) => expected.accepts(found),
+            (expected, found) => expected == found,
+        }
+    }
+
+    /// Replaces the generic parameters in the type by the types they were
+    /// inferred to be. Parameters that were not inferred become unknown.
+    fn substitute(&self, inferred: &HashMap<String, Ty>) -> Ty {
+        match self {
+            Ty::Generic(name) => inferred.get(name).cloned().unwrap_or(Ty::Unknown),
+            Ty::Reference(inner) => Ty::Reference(Box::new(inner.substitute(inferred))),
+            Ty::Array(element) => Ty::Array(Box::new(element.substitute(inferred))),
+            other => other.clone(),
+        }
+    }
+
+    /// Returns the type of a binary arithmetic operation on two compatible
+    /// operands, preferring a concrete type over a literal one.
+    fn unify(self, other: Ty) -> Ty {
+        match self {
+            Ty::IntLiteral | Ty::FloatLiteral | Ty::Unknown => other,
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Primitive(name) | Ty::Named(name) | Ty::Generic(name) => write!(f, "{}", name),
+            Ty::IntLiteral => write!(f, "{{integer}}"),
+            Ty::FloatLiteral => write!(f, "{{float}}"),
+            Ty::Str => write!(f, "string"),
+            Ty::Reference(inner) => write!(f, "ref {}", inner),
+            Ty::Null => write!(f, "null"),
+            Ty::Array(element) => write!(f, "[{}]", element),
+            Ty::Void => write!(f, "void"),
+            Ty::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+/// Returns every function that has a body: top-level functions, default
+/// interface methods and the methods of implementations. Methods come with
+/// the type of `self` in them, which is the interface or the implementing
+/// type.
+fn function_bodies(ast: &AST) -> impl Iterator<Item = (&FunctionDeclaration, Option<Ty>)> {
+    ast.declarations
+        .iter()
+        .flat_map(|decl| -> Vec<(&FunctionDeclaration, Option<Ty>)> {
+            match decl.as_ref() {
+                Declaration::Function(function) => vec![(function, None)],
+                Declaration::Interface(interface) => {
+                    let self_type = match &interface.id.id {
+                        Some(token) => Ty::Named(token.get_lexeme().to_string()),
+                        None => Ty::Unknown,
+                    };
+                    interface
+                        .methods
+                        .iter()
+                        .filter(|method| method.is_default)
+                        .map(|method| (method.as_ref(), Some(self_type.clone())))
+                        .collect()
+                }
+                Declaration::Implementation(implementation) => {
+                    let self_type = Ty::from_variant(&implementation.for_type.variant);
+                    implementation
+                        .methods
+                        .iter()
+                        .map(|method| (method.as_ref(), Some(self_type.clone())))
+                        .collect()
+                }
+                _ => Vec::new(),
+            }
+        })
+}
+
+/// Checks that no function, interface method or implementation method names
+/// two of its parameters the same. Each repeated name is reported at its
+/// second occurrence.
+pub fn check_parameters(ast: &AST) -> Vec<SemanticError> {
+    let mut errors = Vec::new();
+
+    for decl in &ast.declarations {
+        let functions: Vec<&FunctionDeclaration> = match decl.as_ref() {
+            Declaration::Function(function) => vec![function],
+            Declaration::Interface(interface) => interface
+                .methods
+                .iter()
+                .map(|method| method.as_ref())
+                .collect(),
+            Declaration::Implementation(implementation) => implementation
+                .methods
+                .iter()
+                .map(|method| method.as_ref())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        for function in functions {
+            let mut names = HashSet::new();
+            for (_, id) in function.parameters.iter().flatten() {
+                let Some(token) = &id.id else {
+                    continue;
+                };
+                if !names.insert(token.get_lexeme()) {
+                    errors.push(SemanticError::DuplicateParameter(
+                        token.get_line(),
+                        token.get_col(),
+                        token.get_lexeme().to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/// Checks that every implementation names a declared interface and provides
+/// each of its methods that has no default body.
+pub fn check_implementations(ast: &AST) -> Vec<SemanticError> {
+    let interfaces: HashMap<&str, &InterfaceDeclaration> = ast
+        .declarations
+        .iter()
+        .filter_map(|decl| match decl.as_ref() {
+            Declaration::Interface(interface) => interface
+                .id
+                .id
+                .as_ref()
+                .map(|token| (token.get_lexeme(), interface.as_ref())),
+            _ => None,
+        })
+        .collect();
+    let mut errors = Vec::new();
+
+    for decl in &ast.declarations {
+        let Declaration::Implementation(implementation) = decl.as_ref() else {
+            continue;
+        };
+        let Some(token) = &implementation.intf_id.id else {
+            continue;
+        };
+        let Some(interface) = interfaces.get(token.get_lexeme()) else {
+            errors.push(SemanticError::UndefinedInterface(
+                token.get_line(),
+                token.get_col(),
+                token.get_lexeme().to_string(),
+            ));
+            continue;
+        };
+
+        let implemented: Vec<&str> = implementation
+            .methods
+            .iter()
+            .filter_map(|method| method.id.id.as_ref().map(Token::get_lexeme))
+            .collect();
+        for method in interface.methods.iter().filter(|method| !method.is_default) {
+            let Some(name) = method.id.id.as_ref().map(Token::get_lexeme) else {
+                continue;
+            };
+            if !implemented.contains(&name) {
+                errors.push(SemanticError::MissingInterfaceMethod(
+                    token.get_line(),
+                    token.get_col(),
+                    name.to_string(),
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+/// The parameter and return types of a function.
+struct Signature {
+    parameters: Vec<Ty>,
+    return_type: Ty,
+    /// The interface bounding each generic parameter, if any.
+    bounds: Bounds,
+}
+
+impl Signature {
+    fn of(function: &FunctionDeclaration) -> Signature {
+        Signature {
+            parameters: function
+                .parameters
+                .iter()
+                .flatten()
+                .map(|(param_type, _)| Ty::from_variant(&param_type.variant))
+                .collect(),
+            return_type: Ty::from_type(&function.return_type),
+            bounds: bounds(&function.generics),
+        }
+    }
+}
+
+type Functions = HashMap<String, Signature>;
+
+type Bounds = HashMap<String, Option<String>>;
+
+/// Returns the generic parameters of a declaration, along with the interface
+/// each one must implement if it is written as `type T impl Interface`.
+fn bounds(generics: &Option<Box<GenericParameters>>) -> Bounds {
+    let lexeme = |id: &Identifier| id.id.as_ref().map(|token| token.get_lexeme().to_string());
+    generics
+        .iter()
+        .flat_map(|generics| &generics.generics)
+        .filter_map(|generic| match generic.as_ref() {
+            GenericVariants::Identifier(id) => Some((lexeme(id)?, None)),
+            GenericVariants::Implements(id, bound) => Some((lexeme(id)?, lexeme(bound))),
+            GenericVariants::Type(_) | GenericVariants::Error(_) => None,
+        })
+        .collect()
+}
+
+/// The method signatures of every interface, and the types implementing it.
+#[derive(Default)]
+struct Interfaces {
+    methods: HashMap<String, Functions>,
+    implementors: HashMap<String, Vec<Ty>>,
+}
+
+impl Interfaces {
+    fn collect(ast: &AST) -> Interfaces {
+        let mut interfaces = Interfaces::default();
+        for decl in &ast.declarations {
+            match decl.as_ref() {
+                Declaration::Interface(interface) => {
+                    let Some(token) = &interface.id.id else {
+                        continue;
+                    };
+                    let methods = interface
+                        .methods
+                        .iter()
+                        .filter_map(|method| {
+                            let name = method.id.id.as_ref()?.get_lexeme().to_string();
+                            Some((name, Signature::of(method)))
+                        })
+                        .collect();
+                    interfaces
+                        .methods
+                        .insert(token.get_lexeme().to_string(), methods);
+                }
+                Declaration::Implementation(implementation) => {
+                    let Some(token) = &implementation.intf_id.id else {
+                        continue;
+                    };
+                    interfaces
+                        .implementors
+                        .entry(token.get_lexeme().to_string())
+                        .or_default()
+                        .push(Ty::from_variant(&implementation.for_type.variant));
+                }
+                _ => {}
+            }
+        }
+        interfaces
+    }
+
+    /// Returns whether a value of type `found` implements `interface`.
+    fn implemented_by(&self, interface: &str, found: &Ty) -> bool {
+        self.implementors
+            .get(interface)
+            .is_some_and(|types| types.iter().any(|ty| ty.accepts(found)))
+    }
+}
+
+/// The names of the variants of every enum, in declaration order.
+type Enums = HashMap<String, Vec<String>>;
+
+fn collect_enums(ast: &AST) -> Enums {
+    let lexeme = |id: &Identifier| id.id.as_ref().map(|token| token.get_lexeme().to_string());
+    ast.declarations
+        .iter()
+        .filter_map(|decl| match decl.as_ref() {
+            Declaration::Enum(enumeration) => {
+                let variants = enumeration
+                    .variants
+                    .iter()
+                    .flatten()
+                    .filter_map(|variant| match variant {
+                        Variant::Named(id, _) | Variant::Tuple(id, _) | Variant::Unit(id, _) => {
+                            lexeme(id)
+                        }
+                    })
+                    .collect();
+                Some((lexeme(&enumeration.id)?, variants))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collects the signature of every top-level function, reporting functions
+/// that are declared more than once.
+fn collect_functions(ast: &AST) -> (Functions, Vec<SemanticError>) {
+    let mut functions = Functions::new();
+    let mut errors = Vec::new();
+
+    for decl in &ast.declarations {
+        let Declaration::Function(function) = decl.as_ref() else {
+            continue;
+        };
+        let Some(token) = &function.id.id else {
+            continue;
+        };
+
+        if functions
+            .insert(token.get_lexeme().to_string(), Signature::of(function))
+            .is_some()
+        {
+            errors.push(SemanticError::Redefinition(
+                token.get_line(),
+                token.get_col(),
+                token.get_lexeme().to_string(),
+            ));
+        }
+    }
+
+    (functions, errors)
+}
+
+/// A stack of lexical scopes mapping names to what is known about them,
+/// innermost last.
+struct Scopes<T> {
+    frames: Vec<HashMap<String, T>>,
+}
+
+impl<T> Scopes<T> {
+    fn new() -> Self {
+        Scopes {
+            frames: vec![HashMap::new()],
+        }
+    }
+
+    fn push(&mut self) {
+        self.frames.push(HashMap::new());
+    }
+
+    /// Leaves the innermost scope and returns what it defined.
+    fn pop(&mut self) -> Option<HashMap<String, T>> {
+        self.frames.pop()
+    }
+
+    fn define(&mut self, name: &str, value: T) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.insert(name.to_string(), value);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&T> {
+        self.frames.iter().rev().find_map(|frame| frame.get(name))
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut T> {
+        self.frames
+            .iter_mut()
+            .rev()
+            .find_map(|frame| frame.get_mut(name))
+    }
+}
+
+/// Returns the position of the first token of an expression, if it has one.
+pub fn expression_position(expr: &Expression) -> Option<(usize, usize)> {
+    let token = match expr {
+        Expression::Operation(operator) => {
+            return match operator.as_ref() {
+                Operator::Binary(_, lhs, _) => expression_position(lhs),
+                Operator::Unary(_, operand) => expression_position(operand),
+                Operator::Error(_) => None,
+            }
+        }
+        Expression::Primary(primary) => match primary.as_ref() {
+            Primary::Literal(literal) => match literal.as_ref() {
+                Literal::Integer(token)
+                | Literal::Float(token)
+                | Literal::String(token)
+                | Literal::Character(token)
+                | Literal::Boolean(token)
+                | Literal::Null(token) => token.clone(),
+                Literal::Error(_) => return None,
+            },
+            Primary::Identifier(id) | Primary::ArrayAccess(id, _) => id.id.clone()?,
+            Primary::FunctionCall(call) => call.id.id.clone()?,
+            Primary::Group(inner) => return expression_position(inner),
+            Primary::Match(match_statement) => return expression_position(&match_statement.expr),
+            Primary::Loop(loop_statement) => return loop_position(loop_statement),
+            Primary::Range(range) => return expression_position(&range.start),
+            Primary::FieldAccess(base, _) => return expression_position(base),
+            Primary::Error(_) => return None,
+        },
+        Expression::Error(_) => return None,
+    };
+    Some((token.get_line(), token.get_col()))
+}
+
+/// Returns the arguments of a member call such as `a.b(x)`, or the indices of
+/// a member array access such as `a.b[i]`. The name of the member is not an
+/// operand since it is looked up in the type of `a`.
+fn member_operands(member: &Primary) -> Vec<&Expression> {
+    match member {
+        Primary::FunctionCall(call) => call.args.iter().map(|arg| arg.as_ref()).collect(),
+        Primary::ArrayAccess(_, access) => {
+            let mut operands = Vec::new();
+            let mut access = Some(access);
+            while let Some(current) = access {
+                operands.push(current.index.as_ref());
+                access = current.next.as_ref();
+            }
+            operands
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Reports every use of a variable or function that is not in scope.
+fn resolve(ast: &AST, functions: &Functions) -> Vec<SemanticError> {
+    let mut resolver = Resolver {
+        functions,
+        scopes: Scopes::new(),
+        errors: Vec::new(),
+    };
+
+    for (function, self_type) in function_bodies(ast) {
+        resolver.function(function, self_type.is_some());
+    }
+
+    resolver.errors
+}
+
+struct Resolver<'a> {
+    functions: &'a Functions,
+    /// Whether each variable in scope has a generic type, in which case it
+    /// can be the receiver of a call to an interface method.
+    scopes: Scopes<bool>,
+    errors: Vec<SemanticError>,
+}
+
+impl Resolver<'_> {
+    fn function(&mut self, function: &FunctionDeclaration, is_method: bool) {
+        self.scopes.push();
+        if let Some(token) = function
+            .receiver
+            .as_ref()
+            .and_then(|receiver| receiver.id.id.as_ref())
+        {
+            if is_method {
+                self.scopes.define(token.get_lexeme(), false);
+            } else {
+                self.errors.push(SemanticError::SelfOutsideMethod(
+                    token.get_line(),
+                    token.get_col(),
+                    token.get_lexeme().to_string(),
+                ));
+            }
+        }
+        for (param_type, id) in function.parameters.iter().flatten() {
+            if let Some(token) = &id.id {
+                let is_generic = matches!(*param_type.variant, TypeVariant::Generic(_));
+                self.scopes.define(token.get_lexeme(), is_generic);
+            }
+        }
+        self.block(&function.block);
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push();
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+        if let Some(value) = &block.value {
+            self.expression(value);
+        }
+        self.scopes.pop();
+    }
+
+    fn match_statement(&mut self, match_statement: &MatchStatement) {
+        self.expression(&match_statement.expr);
+        for clause in &match_statement.case_clauses {
+            self.block(&clause.case_block);
+        }
+        if let Some(default) = &match_statement.default_clause {
+            self.block(default);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Var(declaration) => {
+                if let Some(init) = &declaration.init {
+                    self.expression(init);
+                }
+                if let Some(token) = &declaration.id.id {
+                    let is_generic =
+                        matches!(*declaration.var_type.variant, TypeVariant::Generic(_));
+                    self.scopes.define(token.get_lexeme(), is_generic);
+                }
+            }
+            Statement::Assign(assignment) => {
+                self.variable(&assignment.id);
+                self.expression(&assignment.expr);
+            }
+            Statement::Return(ret) => {
+                if let Some(expr) = &ret.expr {
+                    self.expression(expr);
+                }
+            }
+            Statement::FunctionCall(call) => self.call(call),
+            Statement::If(if_statement) => {
+                self.expression(&if_statement.condition);
+                self.block(&if_statement.if_block);
+                for elif in if_statement.elif_statements.iter().flatten() {
+                    self.expression(&elif.condition);
+                    self.block(&elif.block);
+                }
+                if let Some(else_block) = &if_statement.else_block {
+                    self.block(else_block);
+                }
+            }
+            Statement::Loop(loop_statement) => self.loop_statement(loop_statement),
+            Statement::Match(match_statement) => self.match_statement(match_statement),
+            Statement::Break(_, Some(value)) => self.expression(value),
+            _ => {}
+        }
+    }
+
+    fn loop_statement(&mut self, loop_statement: &LoopStatement) {
+        // The loop variable is only in scope in the body of the loop.
+        self.scopes.push();
+        if let Some(iteration) = &loop_statement.iteration {
+            self.expression(&iteration.range.start);
+            self.expression(&iteration.range.end);
+            if let Some(token) = &iteration.variable.id {
+                self.scopes.define(token.get_lexeme(), false);
+            }
+        }
+        self.block(&loop_statement.block);
+        self.scopes.pop();
+    }
+
+    fn variable(&mut self, id: &Identifier) {
+        if let Some(token) = &id.id {
+            // Methods with a receiver define `self`, so it is only undefined
+            // where there is no receiver to refer to.
+            if token.get_lexeme() == "self" && self.scopes.lookup("self").is_none() {
+                self.errors.push(SemanticError::SelfOutsideMethod(
+                    token.get_line(),
+                    token.get_col(),
+                    token.get_lexeme().to_string(),
+                ));
+            } else if self.scopes.lookup(token.get_lexeme()).is_none() {
+                self.errors.push(SemanticError::UndefinedVariable(
+                    token.get_line(),
+                    token.get_col(),
+                    token.get_lexeme().to_string(),
+                ));
+            }
+        }
+    }
+
+    /// Returns whether the first argument of `call` is a variable of generic
+    /// type. The type checker resolves such calls against its bound.
+    fn is_method_call(&self, call: &FunctionCall) -> bool {
+        let Some(Expression::Primary(primary)) = call.args.first().map(|arg| arg.as_ref()) else {
+            return false;
+        };
+        let Primary::Identifier(id) = primary.as_ref() else {
+            return false;
+        };
+        id.id
+            .as_ref()
+            .and_then(|token| self.scopes.lookup(token.get_lexeme()))
+            .is_some_and(|is_generic| *is_generic)
+    }
+
+    fn call(&mut self, call: &FunctionCall) {
+        if let Some(token) = &call.id.id {
+            if !self.functions.contains_key(token.get_lexeme()) && !self.is_method_call(call) {
+                self.errors.push(SemanticError::UndefinedFunction(
+                    token.get_line(),
+                    token.get_col(),
+                    token.get_lexeme().to_string(),
+                ));
+            }
+        }
+        for arg in &call.args {
+            self.expression(arg);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Operation(operator) => match operator.as_ref() {
+                Operator::Binary(_, lhs, rhs) => {
+                    self.expression(lhs);
+                    self.expression(rhs);
+                }
+                Operator::Unary(_, operand) => self.expression(operand),
+                Operator::Error(_) => {}
+            },
+            Expression::Primary(primary) => match primary.as_ref() {
+                Primary::Identifier(id) => self.variable(id),
+                Primary::ArrayAccess(id, access) => {
+                    self.variable(id);
+                    let mut access = Some(access);
+                    while let Some(current) = access {
+                        self.expression(&current.index);
+                        access = current.next.as_ref();
+                    }
+                }
+                Primary::FunctionCall(call) => self.call(call),
+                Primary::Group(inner) => self.expression(inner),
+                Primary::Match(match_statement) => self.match_statement(match_statement),
+                Primary::Loop(loop_statement) => self.loop_statement(loop_statement),
+                Primary::Range(range) => {
+                    self.expression(&range.start);
+                    self.expression(&range.end);
+                }
+                Primary::FieldAccess(base, member) => {
+                    self.expression(base);
+                    for operand in member_operands(member) {
+                        self.expression(operand);
+                    }
+                }
+                Primary::Literal(_) | Primary::Error(_) => {}
+            },
+            Expression::Error(_) => {}
+        }
+    }
+}
+
+/// Returns the position of the label of a loop used as a value or, as the
+/// `loop` keyword is not kept, of the first value a `break` gives it.
+fn loop_position(loop_statement: &LoopStatement) -> Option<(usize, usize)> {
+    if let Some(label) = &loop_statement.label {
+        return Some((label.get_line(), label.get_col()));
+    }
+    let mut finder = FirstBreakValue(None);
+    finder.visit_block(&loop_statement.block);
+    finder.0.and_then(expression_position)
+}
+
+struct FirstBreakValue<'ast>(Option<&'ast Expression>);
+
+impl<'ast> Visitor<'ast> for FirstBreakValue<'ast> {
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        if let Statement::Break(_, Some(value)) = statement {
+            self.0.get_or_insert(value);
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+/// Checks that every value is used where its type is expected.
+fn check_types(ast: &AST, functions: &Functions) -> Vec<SemanticError> {
+    let interfaces = Interfaces::collect(ast);
+    let enums = collect_enums(ast);
+    let mut checker = TypeChecker {
+        functions,
+        interfaces: &interfaces,
+        enums: &enums,
+        scopes: Scopes::new(),
+        function_id: None,
+        return_type: Ty::Void,
+        bounds: Bounds::new(),
+        loops: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for (function, self_type) in function_bodies(ast) {
+        checker.function(function, self_type);
+    }
+
+    checker.errors
+}
+
+/// What the type checker knows about a variable in scope.
+struct Variable {
+    ty: Ty,
+    is_const: bool,
+    /// The value of a constant, if it is known.
+    value: Option<fold::Value>,
+}
+
+struct TypeChecker<'a> {
+    functions: &'a Functions,
+    interfaces: &'a Interfaces,
+    enums: &'a Enums,
+    scopes: Scopes<Variable>,
+    /// The name of the function being checked, which locates a missing
+    /// return value.
+    function_id: Option<Token>,
+    /// The return type of the function being checked.
+    return_type: Ty,
+    /// The generic parameters of the function being checked.
+    bounds: Bounds,
+    /// The loops enclosing the statement being checked, innermost last.
+    loops: Vec<LoopFrame>,
+    errors: Vec<SemanticError>,
+}
+
+/// What the type checker knows about a loop enclosing a `break`.
+struct LoopFrame {
+    label: Option<String>,
+    is_value: bool,
+    /// The position of a loop used as a value, see [`loop_position`].
+    position: Option<(usize, usize)>,
+    /// The type of the values the `break`s leaving the loop give it so far.
+    ty: Option<Ty>,
+}
+
+impl TypeChecker<'_> {
+    fn function(&mut self, function: &FunctionDeclaration, self_type: Option<Ty>) {
+        self.function_id = function.id.id.clone();
+        self.return_type = Ty::from_type(&function.return_type);
+        self.bounds = bounds(&function.generics);
+        self.scopes.push();
+        if let (Some(receiver), Some(self_type)) = (&function.receiver, self_type) {
+            let ty = if receiver.is_ref {
+                Ty::Reference(Box::new(self_type))
+            } else {
+                self_type
+            };
+            let variable = Variable {
+                ty,
+                is_const: false,
+                value: None,
+            };
+            self.scopes.define("self", variable);
+        }
+        for (param_type, id) in function.parameters.iter().flatten() {
+            if let Some(token) = &id.id {
+                let variable = Variable {
+                    ty: Ty::from_variant(&param_type.variant),
+                    is_const: false,
+                    value: None,
+                };
+                self.scopes.define(token.get_lexeme(), variable);
+            }
+        }
+        for statement in &function.block.statements {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    /// Checks a block and returns the type of its value, which is `void` if
+    /// it has none.
+    fn block(&mut self, block: &Block) -> Ty {
+        self.scopes.push();
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+        let ty = match &block.value {
+            Some(value) => self.expression(value),
+            None => Ty::Void,
+        };
+        self.scopes.pop();
+        ty
+    }
+
+    fn mismatch(&mut self, position: Option<(usize, usize)>, message: String) {
+        if let Some((line, col)) = position {
+            self.errors
+                .push(SemanticError::TypeMismatch(line, col, message));
+        }
+    }
+
+    /// Checks that `expr` has a type accepted where `expected` is required.
+    fn expect(&mut self, expected: &Ty, expr: &Expression) {
+        let found = self.expression(expr);
+        if found == Ty::Null && !expected.accepts(&found) {
+            if let Some((line, col)) = expression_position(expr) {
+                self.errors.push(SemanticError::NullForNonReference(
+                    line,
+                    col,
+                    expected.to_string(),
+                ));
+            }
+        } else if !expected.accepts(&found) {
+            self.mismatch(
+                expression_position(expr),
+                format!("expected `{}`, found `{}`", expected, found),
+            );
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Var(declaration) => {
+                let declared = Ty::from_variant(&declaration.var_type.variant);
+                if declared == Ty::Void {
+                    if let Some(token) = &declaration.id.id {
+                        self.errors.push(SemanticError::VoidVariable(
+                            token.get_line(),
+                            token.get_col(),
+                            token.get_lexeme().to_string(),
+                        ));
+                    }
+                }
+                if let Some(init) = &declaration.init {
+                    self.expect(&declared, init);
+                }
+                let Some(token) = &declaration.id.id else {
+                    return;
+                };
+                // A constant without an initializer has no value to fold. The
+                // initializer may read the constants declared before it.
+                let folded = declaration.init.as_deref().and_then(|init| {
+                    fold::fold_with(init, &|name| self.scopes.lookup(name)?.value.clone())
+                });
+                if declaration.is_const() && folded.is_none() {
+                    self.errors.push(SemanticError::NonConstantInitializer(
+                        token.get_line(),
+                        token.get_col(),
+                        token.get_lexeme().to_string(),
+                    ));
+                }
+                let variable = Variable {
+                    ty: declared,
+                    is_const: declaration.is_const(),
+                    value: folded.filter(|_| declaration.is_const()),
+                };
+                self.scopes.define(token.get_lexeme(), variable);
+            }
+            Statement::Assign(assignment) => {
+                let target = assignment
+                    .id
+                    .id
+                    .as_ref()
+                    .and_then(|token| Some((token, self.scopes.lookup(token.get_lexeme())?)));
+                let expected = match target {
+                    Some((token, variable)) => {
+                        if variable.is_const {
+                            self.errors.push(SemanticError::AssignToConst(
+                                token.get_line(),
+                                token.get_col(),
+                                token.get_lexeme().to_string(),
+                            ));
+                        }
+                        variable.ty.clone()
+                    }
+                    None => Ty::Unknown,
+                };
+                self.expect(&expected, &assignment.expr);
+            }
+            Statement::Return(ret) => {
+                let expected = self.return_type.clone();
+                match &ret.expr {
+                    Some(expr) if expected == Ty::Void => {
+                        let found = self.expression(expr);
+                        self.mismatch(
+                            expression_position(expr),
+                            format!("expected no return value, found `{}`", found),
+                        );
+                    }
+                    Some(expr) => self.expect(&expected, expr),
+                    None if expected != Ty::Void => self.mismatch(
+                        self.function_id
+                            .as_ref()
+                            .map(|t| (t.get_line(), t.get_col())),
+                        format!("expected `{}`, found `void`", expected),
+                    ),
+                    None => {}
+                }
+            }
+            Statement::FunctionCall(call) => {
+                self.call(call);
+            }
+            Statement::If(if_statement) => {
+                self.condition(&if_statement.condition);
+                self.block(&if_statement.if_block);
+                for elif in if_statement.elif_statements.iter().flatten() {
+                    self.condition(&elif.condition);
+                    self.block(&elif.block);
+                }
+                if let Some(else_block) = &if_statement.else_block {
+                    self.block(else_block);
+                }
+            }
+            Statement::Loop(loop_statement) => {
+                self.loop_statement(loop_statement, false);
+            }
+            Statement::Break(label, value) => {
+                self.break_statement(label.as_ref(), value.as_deref())
+            }
+            Statement::Match(match_statement) => {
+                let matched = self.expression(&match_statement.expr);
+                if match_statement.default_clause.is_none() {
+                    self.exhaustive(&matched, match_statement, false);
+                }
+                for clause in &match_statement.case_clauses {
+                    self.block(&clause.case_block);
+                }
+                if let Some(default) = &match_statement.default_clause {
+                    self.block(default);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks a loop and returns the type of the values the `break`s leaving
+    /// it give it.
+    fn loop_statement(&mut self, loop_statement: &LoopStatement, is_value: bool) -> Option<Ty> {
+        self.loops.push(LoopFrame {
+            label: loop_statement
+                .label
+                .as_ref()
+                .map(|label| label.get_lexeme().to_string()),
+            is_value,
+            position: is_value.then(|| loop_position(loop_statement)).flatten(),
+            ty: None,
+        });
+        self.scopes.push();
+        if let Some(iteration) = &loop_statement.iteration {
+            let ty = self.range(&iteration.range);
+            if let Some(token) = &iteration.variable.id {
+                let variable = Variable {
+                    ty,
+                    is_const: false,
+                    value: None,
+                };
+                self.scopes.define(token.get_lexeme(), variable);
+            }
+        }
+        self.block(&loop_statement.block);
+        self.scopes.pop();
+        self.loops.pop().and_then(|frame| frame.ty)
+    }
+
+    /// Checks the value of a `break` against the loop it leaves. Every
+    /// `break` leaving a loop used as a value must give it a value of the
+    /// same type, and no other `break` may give one.
+    fn break_statement(&mut self, label: Option<&Token>, value: Option<&Expression>) {
+        let found = value.map(|value| self.expression(value));
+        let target = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|frame| frame.label.as_deref() == Some(label.get_lexeme())),
+            None => self.loops.len().checked_sub(1),
+        };
+        // A `break` outside of the loop it names is reported by the label
+        // checks.
+        let Some(target) = target else {
+            return;
+        };
+        let frame = &self.loops[target];
+
+        match (value, found, frame.is_value) {
+            (Some(value), Some(found), false) => self.mismatch(
+                expression_position(value),
+                format!("expected no `break` value, found `{}`", found),
+            ),
+            // Without a label, the `break` is reported where the loop is,
+            // or where the function is if the loop has no position either.
+            (None, _, true) => {
+                let position = label
+                    .map(|label| (label.get_line(), label.get_col()))
+                    .or(frame.position)
+                    .or_else(|| {
+                        self.function_id
+                            .as_ref()
+                            .map(|t| (t.get_line(), t.get_col()))
+                    });
+                if let Some((line, col)) = position {
+                    self.errors
+                        .push(SemanticError::MissingBreakValue(line, col));
+                }
+            }
+            (Some(value), Some(found), true) => match frame.ty.clone() {
+                None => self.loops[target].ty = Some(found),
+                Some(expected) if found.accepts(&expected) || expected.accepts(&found) => {
+                    self.loops[target].ty = Some(expected.unify(found));
+                }
+                Some(expected) => self.mismatch(
+                    expression_position(value),
+                    format!(
+                        "`break` values have incompatible types: expected `{}`, found `{}`",
+                        expected, found
+                    ),
+                ),
+            },
+            _ => {}
+        }
+    }
+
+    /// Checks that a `match` without a default clause on a value of enum type
+    /// `matched` has a case for every variant of the enum. A match expression
+    /// must yield a value whatever it matches, so one on a value of any other
+    /// type is never exhaustive without a default clause.
+    fn exhaustive(&mut self, matched: &Ty, match_statement: &MatchStatement, is_expression: bool) {
+        let variants = match matched {
+            Ty::Named(name) => self.enums.get(name),
+            _ => None,
+        };
+        let Some(variants) = variants else {
+            if is_expression && matched != &Ty::Unknown {
+                if let Some((line, col)) = expression_position(&match_statement.expr) {
+                    self.errors.push(SemanticError::NonExhaustiveMatch(
+                        line,
+                        col,
+                        String::from("`_`"),
+                    ));
+                }
+            }
+            return;
+        };
+        let covered: HashSet<&str> = match_statement
+            .case_clauses
+            .iter()
+            .flat_map(|clause| &clause.cases)
+            .filter_map(|case| match case {
+                Pattern::Variant(id) => id.id.as_ref().map(|token| token.get_lexeme()),
+                Pattern::Literal(_) => None,
+            })
+            .collect();
+        let missing: Vec<String> = variants
+            .iter()
+            .filter(|variant| !covered.contains(variant.as_str()))
+            .map(|variant| format!("`{}`", variant))
+            .collect();
+
+        if missing.is_empty() {
+            return;
+        }
+        if let Some((line, col)) = expression_position(&match_statement.expr) {
+            self.errors.push(SemanticError::NonExhaustiveMatch(
+                line,
+                col,
+                missing.join(", "),
+            ));
+        }
+    }
+
+    /// Checks a `match` expression and returns the type of its value, which
+    /// every arm must yield.
+    fn match_expression(&mut self, match_statement: &MatchStatement) -> Ty {
+        let matched = self.expression(&match_statement.expr);
+        if match_statement.default_clause.is_none() {
+            self.exhaustive(&matched, match_statement, true);
+        }
+
+        let arms = match_statement
+            .case_clauses
+            .iter()
+            .map(|clause| &clause.case_block)
+            .chain(match_statement.default_clause.as_ref());
+        let mut result: Option<Ty> = None;
+        for arm in arms {
+            let found = self.block(arm);
+            let Some(expected) = result.take() else {
+                result = Some(found);
+                continue;
+            };
+            if found.accepts(&expected) || expected.accepts(&found) {
+                result = Some(expected.unify(found));
+                continue;
+            }
+            let position = arm
+                .value
+                .as_deref()
+                .and_then(expression_position)
+                .or_else(|| expression_position(&match_statement.expr));
+            self.mismatch(
+                position,
+                format!(
+                    "match arms have incompatible types: expected `{}`, found `{}`",
+                    expected, found
+                ),
+            );
+            result = Some(expected);
+        }
+        result.unwrap_or(Ty::Void)
+    }
+
+    fn condition(&mut self, condition: &Expression) {
+        self.expect(&Ty::Primitive(String::from("bool")), condition);
+    }
+
+    fn call(&mut self, call: &FunctionCall) -> Ty {
+        let arg_types: Vec<Ty> = call.args.iter().map(|arg| self.expression(arg)).collect();
+        let Some(token) = &call.id.id else {
+            return Ty::Unknown;
+        };
+        let functions = self.functions;
+        let Some(signature) = functions.get(token.get_lexeme()) else {
+            return match arg_types.first() {
+                Some(Ty::Generic(generic)) => {
+                    let generic = generic.clone();
+                    self.method_call(token, &generic, &arg_types[1..], &call.args[1..])
+                }
+                _ => Ty::Unknown,
+            };
+        };
+        self.arguments(token, signature, &arg_types, &call.args)
+    }
+
+    /// Checks a call to the interface method named by `token` on a value of
+    /// the generic type `generic`, which must be bounded by an interface
+    /// declaring the method.
+    fn method_call(
+        &mut self,
+        token: &Token,
+        generic: &str,
+        arg_types: &[Ty],
+        args: &[Box<Expression>],
+    ) -> Ty {
+        let name = token.get_lexeme();
+        let bound = self.bounds.get(generic).cloned().flatten();
+        let interfaces = self.interfaces;
+        let method = bound
+            .as_ref()
+            .and_then(|bound| interfaces.methods.get(bound))
+            .and_then(|methods| methods.get(name));
+
+        match (method, bound) {
+            (Some(signature), _) => self.arguments(token, signature, arg_types, args),
+            (None, bound) => {
+                let message = match bound {
+                    Some(bound) => format!(
+                        "`{}` is not declared by `{}`, the bound of `{}`",
+                        name, bound, generic
+                    ),
+                    None => format!("`{}` has no bound declaring `{}`", generic, name),
+                };
+                self.errors.push(SemanticError::MethodNotInBound(
+                    token.get_line(),
+                    token.get_col(),
+                    message,
+                ));
+                Ty::Unknown
+            }
+        }
+    }
+
+    /// Checks the arguments of a call to the function named by `token` and
+    /// returns the type of the call.
+    fn arguments(
+        &mut self,
+        token: &Token,
+        signature: &Signature,
+        arg_types: &[Ty],
+        args: &[Box<Expression>],
+    ) -> Ty {
+        if signature.parameters.len() != arg_types.len() {
+            self.errors.push(SemanticError::ArgumentCountMismatch(
+                token.get_line(),
+                token.get_col(),
+                format!(
+                    "`{}` takes {} argument(s) but {} were supplied",
+                    token.get_lexeme(),
+                    signature.parameters.len(),
+                    arg_types.len()
+                ),
+            ));
+            return signature.return_type.substitute(&HashMap::new());
+        }
+
+        let mut inferred = HashMap::new();
+        for ((expected, found), arg) in signature.parameters.iter().zip(arg_types).zip(args) {
+            if let Ty::Generic(generic) = expected {
+                if let Some(Some(bound)) = signature.bounds.get(generic) {
+                    self.bound(bound, found, arg);
+                }
+                inferred.insert(generic.clone(), found.clone());
+            } else if !expected.accepts(found) {
+                self.mismatch(
+                    expression_position(arg),
+                    format!("expected `{}`, found `{}`", expected, found),
+                );
+            }
+        }
+        signature.return_type.substitute(&inferred)
+    }
+
+    /// Checks that `found`, the type of the argument `arg` passed for a
+    /// generic parameter, implements the interface `bound`.
+    fn bound(&mut self, bound: &str, found: &Ty, arg: &Expression) {
+        let satisfied = match found {
+            Ty::Unknown => true,
+            Ty::Generic(generic) => self.bounds.get(generic) == Some(&Some(bound.to_string())),
+            _ => self.interfaces.implemented_by(bound, found),
+        };
+        if !satisfied {
+            if let Some((line, col)) = expression_position(arg) {
+                self.errors.push(SemanticError::UnsatisfiedBound(
+                    line,
+                    col,
+                    format!("`{}` does not implement `{}`", found, bound),
+                ));
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Ty {
+        match expr {
+            Expression::Operation(operator) => match operator.as_ref() {
+                Operator::Binary(op, lhs, rhs) => self.binary(op, lhs, rhs),
+                Operator::Unary(op, operand) => self.unary(op, operand),
+                Operator::Error(_) => Ty::Unknown,
+            },
+            Expression::Primary(primary) => self.primary(primary),
+            Expression::Error(_) => Ty::Unknown,
+        }
+    }
+
+    /// Checks that the bounds of `range` are integers of the same type, and
+    /// returns that type.
+    fn range(&mut self, range: &Range) -> Ty {
+        let start_type = self.expression(&range.start);
+        let end_type = self.expression(&range.end);
+        let valid = start_type.is_integer()
+            && (start_type.accepts(&end_type) || end_type.accepts(&start_type));
+        if !valid {
+            let op = if range.is_inclusive { "..=" } else { ".." };
+            self.mismatch(
+                expression_position(&range.start),
+                format!(
+                    "cannot apply `{}` to `{}` and `{}`",
+                    op, start_type, end_type
+                ),
+            );
+            return Ty::Unknown;
+        }
+        start_type.unify(end_type)
+    }
+
+    fn binary(&mut self, op: &str, lhs: &Expression, rhs: &Expression) -> Ty {
+        let lhs_type = self.expression(lhs);
+        let rhs_type = self.expression(rhs);
+        let bool_type = Ty::Primitive(String::from("bool"));
+
+        let valid = match op {
+            "&&" | "||" => lhs_type.is_bool() && rhs_type.is_bool(),
+            "==" | "!=" => lhs_type.accepts(&rhs_type) || rhs_type.accepts(&lhs_type),
+            "<" | "<=" | ">" | ">=" | "+" | "-" | "*" | "/" | "%" => {
+                lhs_type.is_numeric()
+                    && (lhs_type.accepts(&rhs_type) || rhs_type.accepts(&lhs_type))
+            }
+            _ => {
+                lhs_type.is_integer()
+                    && (lhs_type.accepts(&rhs_type) || rhs_type.accepts(&lhs_type))
+            }
+        };
+        if !valid {
+            self.mismatch(
+                expression_position(lhs),
+                format!("cannot apply `{}` to `{}` and `{}`", op, lhs_type, rhs_type),
+            );
+            return Ty::Unknown;
+        }
+
+        match op {
+            "&&" | "||" | "==" | "!=" | "<" | "<=" | ">" | ">=" => bool_type,
+            _ => lhs_type.unify(rhs_type),
+        }
+    }
+
+    fn unary(&mut self, op: &str, operand: &Expression) -> Ty {
+        let operand_type = self.expression(operand);
+        let result = match op {
+            "ref" => Some(Ty::Reference(Box::new(operand_type.clone()))),
+            "deref" => match &operand_type {
+                Ty::Reference(inner) => Some(*inner.clone()),
+                Ty::Unknown => Some(Ty::Unknown),
+                _ => None,
+            },
+            "!" if operand_type.is_bool() || operand_type.is_integer() => {
+                Some(operand_type.clone())
+            }
+            "~" if operand_type.is_integer() => Some(operand_type.clone()),
+            "+" | "-" if operand_type.is_numeric() => Some(operand_type.clone()),
+            _ => None,
+        };
+
+        result.unwrap_or_else(|| {
+            self.mismatch(
+                expression_position(operand),
+                format!("cannot apply `{}` to `{}`", op, operand_type),
+            );
+            Ty::Unknown
+        })
+    }
+
+    fn primary(&mut self, primary: &Primary) -> Ty {
+        match primary {
+            Primary::Literal(literal) => literal_type(literal),
+            Primary::Identifier(id) => self.variable(id),
+            Primary::Group(inner) => self.expression(inner),
+            Primary::FunctionCall(call) => self.call(call),
+            Primary::Match(match_statement) => self.match_expression(match_statement),
+            // A loop that never breaks never ends, so its value can be of any
+            // type.
+            Primary::Loop(loop_statement) => self
+                .loop_statement(loop_statement, true)
+                .unwrap_or(Ty::Unknown),
+            // Struct fields have no types yet.
+            Primary::FieldAccess(base, member) => {
+                self.expression(base);
+                for operand in member_operands(member) {
+                    self.expression(operand);
+                }
+                Ty::Unknown
+            }
+            // Ranges have no type of their own yet.
+            Primary::Range(range) => {
+                self.range(range);
+                Ty::Unknown
+            }
+            Primary::ArrayAccess(id, access) => {
+                let mut current_type = self.variable(id);
+                let mut access = Some(access);
+                while let Some(current) = access {
+                    let index_type = self.expression(&current.index);
+                    if !index_type.is_integer() {
+                        self.mismatch(
+                            expression_position(&current.index),
+                            format!("expected an integer index, found `{}`", index_type),
+                        );
+                    }
+                    current_type = match current_type {
+                        Ty::Array(element) => *element,
+                        Ty::Unknown => Ty::Unknown,
+                        other => {
+                            self.mismatch(
+                                id.id.as_ref().map(|t| (t.get_line(), t.get_col())),
+                                format!("cannot index into `{}`", other),
+                            );
+                            Ty::Unknown
+                        }
+                    };
+                    access = current.next.as_ref();
+                }
+                current_type
+            }
+            Primary::Error(_) => Ty::Unknown,
+        }
+    }
+
+    fn variable(&self, id: &Identifier) -> Ty {
+        id.id
+            .as_ref()
+            .and_then(|token| self.scopes.lookup(token.get_lexeme()))
+            .map(|variable| variable.ty.clone())
+            .unwrap_or(Ty::Unknown)
+    }
+}
+
+/// Returns the type of a literal, taking its suffix into account.
+fn literal_type(literal: &Literal) -> Ty {
+    match literal {
+        Literal::Integer(token) => {
+            let lexeme = token.get_lexeme();
+            match lexeme.find(['i', 'u']) {
+                Some(suffix) => Ty::Primitive(lexeme[suffix..].to_string()),
+                None => Ty::IntLiteral,
+            }
+        }
+        Literal::Float(token) => {
+            let lexeme = token.get_lexeme();
+            let suffix = ["f32", "f64", "f80", "f128"]
+                .iter()
+                .find(|suffix| !lexeme.starts_with("0x") && lexeme.ends_with(*suffix));
+            match suffix {
+                Some(suffix) => Ty::Primitive(suffix.to_string()),
+                None => Ty::FloatLiteral,
+            }
+        }
+        Literal::String(_) => Ty::Str,
+        Literal::Character(_) => Ty::Primitive(String::from("char")),
+        Literal::Boolean(_) => Ty::Primitive(String::from("bool")),
+        Literal::Null(_) => Ty::Null,
+        Literal::Error(_) => Ty::Unknown,
+    }
+}
+
+/// Warns about the local variables that are never read. Assigning to a
+/// variable does not count as reading it, and names starting with `_` are
+/// never reported.
+pub fn check_unused(ast: &AST) -> Vec<SemanticWarning> {
+    let mut checker = UnusedChecker {
+        scopes: Scopes::new(),
+        unused: Vec::new(),
+    };
+    for (function, _) in function_bodies(ast) {
+        checker.visit_block(&function.block);
+    }
+
+    checker.unused.sort_by_key(Token::get_col);
+    checker
+        .unused
+        .into_iter()
+        .map(|token| {
+            SemanticWarning::UnusedVariable(
+                token.get_line(),
+                token.get_col(),
+                token.get_lexeme().to_string(),
+            )
+        })
+        .collect()
+}
+
+struct UnusedChecker {
+    /// The declaration of each variable in scope, and whether it was read.
+    scopes: Scopes<(Token, bool)>,
+    unused: Vec<Token>,
+}
+
+impl<'ast> Visitor<'ast> for UnusedChecker {
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        if let Expression::Primary(primary) = expr {
+            if let Primary::FieldAccess(base, member) = primary.as_ref() {
+                self.visit_expression(base);
+                for operand in member_operands(member) {
+                    self.visit_expression(operand);
+                }
+                return;
+            }
+        }
+        visit::walk_expression(self, expr);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.scopes.push();
+        visit::walk_block(self, block);
+        let frame = self.scopes.pop().unwrap_or_default();
+        self.unused.extend(
+            frame
+                .into_values()
+                .filter(|(token, read)| !read && !token.get_lexeme().starts_with('_'))
+                .map(|(token, _)| token),
+        );
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        match statement {
+            Statement::Var(declaration) => {
+                if let Some(init) = &declaration.init {
+                    self.visit_expression(init);
+                }
+                if let Some(token) = &declaration.id.id {
+                    self.scopes
+                        .define(token.get_lexeme(), (token.clone(), false));
+                }
+            }
+            Statement::Assign(assignment) => self.visit_expression(&assignment.expr),
+            Statement::Loop(loop_statement) => {
+                // Loop variables are never reported, but they shadow the
+                // variables of the enclosing scopes.
+                self.scopes.push();
+                if let Some(iteration) = &loop_statement.iteration {
+                    self.visit_expression(&iteration.range.start);
+                    self.visit_expression(&iteration.range.end);
+                    if let Some(token) = &iteration.variable.id {
+                        self.scopes
+                            .define(token.get_lexeme(), (token.clone(), true));
+                    }
+                }
+                self.visit_block(&loop_statement.block);
+                self.scopes.pop();
+            }
+            _ => visit::walk_statement(self, statement),
+        }
+    }
+
+    fn visit_function_call(&mut self, call: &'ast FunctionCall) {
+        for arg in &call.args {
+            self.visit_expression(arg);
+        }
+    }
+
+    fn visit_identifier(&mut self, id: &'ast Identifier) {
+        let Some(token) = &id.id else {
+            return;
+        };
+        if let Some((_, read)) = self.scopes.lookup_mut(token.get_lexeme()) {
+            *read = true;
+        }
+    }
+}
+
+/// Warns about the functions that can never be called. Public functions,
+/// `main` and methods are entry points, and a function is used if an entry
+/// point calls it, directly or through other functions. A function that only
+/// calls itself is still unused.
+pub fn check_dead_code(ast: &AST) -> Vec<SemanticWarning> {
+    let mut callees: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut functions = Vec::new();
+    let mut reachable = Vec::new();
+    for decl in &ast.declarations {
+        match decl.as_ref() {
+            Declaration::Function(function) => {
+                let Some(token) = &function.id.id else {
+                    continue;
+                };
+                let name = token.get_lexeme();
+                callees.insert(name, calls(function));
+                if function.is_pub || name == "main" {
+                    reachable.push(name);
+                } else {
+                    functions.push(token);
+                }
+            }
+            // Methods are called through their interface, so whatever they
+            // call is reachable.
+            Declaration::Interface(interface) => {
+                for method in &interface.methods {
+                    reachable.extend(calls(method));
+                }
+            }
+            Declaration::Implementation(implementation) => {
+                for method in &implementation.methods {
+                    reachable.extend(calls(method));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut used = HashSet::new();
+    while let Some(name) = reachable.pop() {
+        if used.insert(name) {
+            reachable.extend(callees.get(name).into_iter().flatten());
+        }
+    }
+
+    functions
+        .into_iter()
+        .filter(|token| !used.contains(token.get_lexeme()))
+        .map(|token| {
+            SemanticWarning::UnusedFunction(
+                token.get_line(),
+                token.get_col(),
+                token.get_lexeme().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Returns the names of the functions called in the body of `function`.
+fn calls(function: &FunctionDeclaration) -> Vec<&str> {
+    let mut collector = CallCollector { calls: Vec::new() };
+    collector.visit_block(&function.block);
+    collector.calls
+}
+
+struct CallCollector<'ast> {
+    calls: Vec<&'ast str>,
+}
+
+impl<'ast> Visitor<'ast> for CallCollector<'ast> {
+    fn visit_function_call(&mut self, call: &'ast FunctionCall) {
+        if let Some(token) = &call.id.id {
+            self.calls.push(token.get_lexeme());
+        }
+        visit::walk_function_call(self, call);
+    }
+}
+
+/// The attributes the compiler knows. Others are accepted with a warning.
+pub const KNOWN_ATTRIBUTES: &[&str] = &["cold", "deprecated", "inline", "noinline"];
+
+/// Warns about the attributes of declarations and methods that are not
+/// [`KNOWN_ATTRIBUTES`].
+pub fn check_attributes(ast: &AST) -> Vec<SemanticWarning> {
+    let mut lists = Vec::new();
+    for decl in &ast.declarations {
+        match decl.as_ref() {
+            Declaration::Enum(enumeration) => lists.push(&enumeration.attributes),
+            Declaration::Struct(structure) => lists.push(&structure.attributes),
+            Declaration::Function(function) => lists.push(&function.attributes),
+            Declaration::Interface(interface) => {
+                lists.push(&interface.attributes);
+                lists.extend(interface.methods.iter().map(|method| &method.attributes));
+            }
+            Declaration::Implementation(implementation) => {
+                lists.push(&implementation.attributes);
+                lists.extend(
+                    implementation
+                        .methods
+                        .iter()
+                        .map(|method| &method.attributes),
+                );
+            }
+            Declaration::Import(_) | Declaration::Error(_) => {}
+        }
+    }
+
+    lists
+        .into_iter()
+        .flatten()
+        .filter_map(|attribute| attribute.id.id.as_ref())
+        .filter(|token| !KNOWN_ATTRIBUTES.contains(&token.get_lexeme()))
+        .map(|token| {
+            SemanticWarning::UnknownAttribute(
+                token.get_line(),
+                token.get_col(),
+                token.get_lexeme().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// The case style expected of each kind of name by [`check_names`]. A kind
+/// without a style is not checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct NamingRules {
+    /// Functions and the methods of interfaces.
+    pub functions: Option<CaseStyle>,
+    /// Parameters, local variables and loop variables.
+    pub variables: Option<CaseStyle>,
+    /// Variables declared `const`.
+    pub constants: Option<CaseStyle>,
+    /// Structs, enums, their variants and interfaces.
+    pub types: Option<CaseStyle>,
+}
+
+impl Default for NamingRules {
+    fn default() -> Self {
+        NamingRules {
+            functions: Some(CaseStyle::Snake),
+            variables: Some(CaseStyle::Snake),
+            constants: Some(CaseStyle::ScreamingSnake),
+            types: Some(CaseStyle::Pascal),
+        }
+    }
+}
+
+/// Warns about the names that do not follow the style `rules` expects of
+/// their kind. The methods of implementations are named by their interface,
+/// so only their parameters and bodies are checked.
+pub fn check_names(ast: &AST, rules: &NamingRules) -> Vec<SemanticWarning> {
+    let mut checker = NamingChecker {
+        rules: *rules,
+        warnings: Vec::new(),
+    };
+    visit::walk_ast(&mut checker, ast);
+    checker.warnings
+}
+
+struct NamingChecker {
+    rules: NamingRules,
+    warnings: Vec<SemanticWarning>,
+}
+
+impl NamingChecker {
+    fn check(&mut self, id: &Identifier, style: Option<CaseStyle>) {
+        let (Some(token), Some(style)) = (&id.id, style) else {
+            return;
+        };
+        if !style.matches(token.get_lexeme()) {
+            self.warnings.push(SemanticWarning::NonConventionalName(
+                token.get_line(),
+                token.get_col(),
+                token.get_lexeme().to_string(),
+                style,
+            ));
+        }
+    }
+
+    fn parameters(&mut self, function: &FunctionDeclaration) {
+        for (_, id) in function.parameters.iter().flatten() {
+            self.check(id, self.rules.variables);
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for NamingChecker {
+    fn visit_declaration(&mut self, decl: &'ast Declaration) {
+        match decl {
+            Declaration::Enum(enumeration) => {
+                self.check(&enumeration.id, self.rules.types);
+                for variant in enumeration.variants.iter().flatten() {
+                    self.check(variant.id(), self.rules.types);
+                }
+            }
+            Declaration::Struct(structure) => self.check(structure.variant.id(), self.rules.types),
+            Declaration::Interface(interface) => self.check(&interface.id, self.rules.types),
+            _ => {}
+        }
+        visit::walk_declaration(self, decl);
+    }
+
+    fn visit_function(&mut self, function: &'ast FunctionDeclaration) {
+        self.check(&function.id, self.rules.functions);
+        self.parameters(function);
+        self.visit_block(&function.block);
+    }
+
+    fn visit_implementation(&mut self, implementation: &'ast InterfaceImplementation) {
+        for method in &implementation.methods {
+            self.parameters(method);
+            self.visit_block(&method.block);
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        match statement {
+            Statement::Var(declaration) if declaration.is_const() => {
+                self.check(&declaration.id, self.rules.constants)
+            }
+            Statement::Var(declaration) => self.check(&declaration.id, self.rules.variables),
+            Statement::Loop(loop_statement) => {
+                if let Some(iteration) = &loop_statement.iteration {
+                    self.check(&iteration.variable, self.rules.variables);
+                }
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+}
+
+/// Checks that no declaration names two of its generic parameters the same.
+/// Each repeated name is reported at its second occurrence.
+pub fn check_generics(ast: &AST) -> Vec<SemanticError> {
+    let mut lists = Vec::new();
+    for decl in &ast.declarations {
+        match decl.as_ref() {
+            Declaration::Enum(enumeration) => lists.push(&enumeration.generics),
+            Declaration::Function(function) => lists.push(&function.generics),
+            Declaration::Interface(interface) => {
+                lists.push(&interface.generics);
+                lists.extend(interface.methods.iter().map(|method| &method.generics));
+            }
+            Declaration::Implementation(implementation) => {
+                lists.push(&implementation.generics);
+                lists.extend(implementation.methods.iter().map(|method| &method.generics));
+            }
+            _ => {}
+        }
+    }
+
+    let mut errors = Vec::new();
+    for generics in lists.into_iter().flatten() {
+        let mut names = HashSet::new();
+        for generic in &generics.generics {
+            let id = match generic.as_ref() {
+                GenericVariants::Identifier(id) | GenericVariants::Implements(id, _) => id,
+                GenericVariants::Type(_) | GenericVariants::Error(_) => continue,
+            };
+            let Some(token) = &id.id else {
+                continue;
+            };
+            if !names.insert(token.get_lexeme()) {
+                errors.push(SemanticError::DuplicateGeneric(
+                    token.get_line(),
+                    token.get_col(),
+                    token.get_lexeme().to_string(),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Checks that every use of a struct, enum or interface declared in `ast`
+/// with generic arguments, such as `enum Pair<u8, i32>`, gives one for each of
+/// its generic parameters. A use without any arguments is left for them to be
+/// inferred.
+pub fn check_generic_arity(ast: &AST) -> Vec<SemanticError> {
+    let mut checker = ArityChecker {
+        arities: HashMap::new(),
+        errors: Vec::new(),
+    };
+    for decl in &ast.declarations {
+        let (id, generics) = match decl.as_ref() {
+            Declaration::Enum(enumeration) => (enumeration.id.as_ref(), &enumeration.generics),
+            Declaration::Interface(interface) => (interface.id.as_ref(), &interface.generics),
+            Declaration::Struct(structure) => (structure.variant.id(), &None),
+            _ => continue,
+        };
+        if let Some(token) = &id.id {
+            let arity = generics
+                .as_ref()
+                .map_or(0, |generics| generics.generics.len());
+            checker.arities.insert(token.get_lexeme(), arity);
+        }
+    }
+    visit::walk_ast(&mut checker, ast);
+    checker.errors
+}
+
+/// Reports the uses of generic types with the wrong number of arguments.
+struct ArityChecker<'ast> {
+    /// The number of generic parameters of each type, by name.
+    arities: HashMap<&'ast str, usize>,
+    errors: Vec<SemanticError>,
+}
+
+impl<'ast> Visitor<'ast> for ArityChecker<'ast> {
+    fn visit_type(&mut self, var_type: &'ast Type) {
+        if let TypeVariant::Structure(id, Some(generics))
+        | TypeVariant::Enumeration(id, Some(generics))
+        | TypeVariant::Interface(id, Some(generics)) = var_type.variant.as_ref()
+        {
+            if let Some(token) = &id.id {
+                let found = generics.generics.len();
+                match self.arities.get(token.get_lexeme()) {
+                    Some(&expected) if expected != found => {
+                        self.errors.push(SemanticError::GenericArityMismatch(
+                            token.get_line(),
+                            token.get_col(),
+                            token.get_lexeme().to_string(),
+                            expected,
+                            found,
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        visit::walk_type_variant(self, &var_type.variant);
+    }
+}
+
+/// Checks the discriminants of the variants of every enum. An explicit one
+/// must be a constant integer, and a variant without one takes the next value
+/// after the variant before it, starting from 0. The values must be distinct
+/// and fit in `i32`, which represents enums.
+pub fn check_discriminants(ast: &AST) -> Vec<SemanticError> {
+    let mut errors = Vec::new();
+    for decl in &ast.declarations {
+        let Declaration::Enum(enumeration) = decl.as_ref() else {
+            continue;
+        };
+        let mut used = HashSet::new();
+        let mut next = Some(0);
+        for variant in enumeration.variants.iter().flatten() {
+            let (id, discriminant) = match variant {
+                Variant::Named(id, _) | Variant::Tuple(id, _) => (id, None),
+                Variant::Unit(id, discriminant) => (id, discriminant.as_deref()),
+            };
+            let Some(token) = &id.id else {
+                continue;
+            };
+            let (line, col, name) = (token.get_line(), token.get_col(), token.get_lexeme());
+
+            let value = match discriminant.map(fold::fold) {
+                Some(Some(fold::Value::Int(value))) => Some(value),
+                Some(_) => {
+                    errors.push(SemanticError::NonConstantDiscriminant(
+                        line,
+                        col,
+                        name.to_string(),
+                    ));
+                    None
+                }
+                None => next,
+            };
+            // The values after one that could not be computed are unknown,
+            // and are not checked.
+            let Some(value) = value else {
+                next = None;
+                continue;
+            };
+            next = value.checked_add(1);
+            if i32::try_from(value).is_err() {
+                errors.push(SemanticError::DiscriminantOverflow(
+                    line,
+                    col,
+                    name.to_string(),
+                ));
+            } else if !used.insert(value) {
+                errors.push(SemanticError::DuplicateDiscriminant(
+                    line,
+                    col,
+                    name.to_string(),
+                ));
+            }
+        }
+    }
+    errors
+}
+
+/// Checks that the size of every array type whose size is constant is a
+/// non-negative integer. Sizes that are not constant are left to be checked
+/// when they are known.
+pub fn check_array_sizes(ast: &AST) -> Vec<SemanticError> {
+    array_sizes(ast)
+        .into_iter()
+        .filter(|size| match fold::fold(size) {
+            Some(fold::Value::Int(value)) => value < 0,
+            Some(_) => true,
+            None => false,
+        })
+        .filter_map(|size| {
+            let (line, col) = expression_position(size)?;
+            Some(SemanticError::InvalidArraySize(
+                line,
+                col,
+                pretty::to_source(size),
+            ))
+        })
+        .collect()
+}
+
+/// Warns about the array types with a constant size of 0, which are allowed
+/// but can hold no elements.
+pub fn check_empty_arrays(ast: &AST) -> Vec<SemanticWarning> {
+    array_sizes(ast)
+        .into_iter()
+        .filter(|size| fold::fold(size) == Some(fold::Value::Int(0)))
+        .filter_map(|size| {
+            let (line, col) = expression_position(size)?;
+            Some(SemanticWarning::EmptyArray(line, col))
+        })
+        .collect()
+}
+
+/// Checks that every array type whose size is known on the target of
+/// `codegen` takes up no more bytes than a value can there. An array of
+/// arrays that are too large is left to the error about its elements.
+pub fn check_array_fit(ast: &AST, codegen: &Codegen) -> Vec<SemanticError> {
+    let target = codegen.target();
+    let max = target.max_object_size();
+    arrays(ast)
+        .into_iter()
+        .filter(|(element, size)| {
+            let Some(fold::Value::Int(len)) = fold::fold(size) else {
+                return false;
+            };
+            let (Some(element), Ok(len)) = (codegen.size_of(element), u64::try_from(len)) else {
+                return false;
+            };
+            element <= max && element.checked_mul(len).is_none_or(|bytes| bytes > max)
+        })
+        .filter_map(|(_, size)| {
+            let (line, col) = expression_position(size)?;
+            Some(SemanticError::ArrayTooLarge(
+                line,
+                col,
+                target.to_string(),
+                max,
+            ))
+        })
+        .collect()
+}
+
+/// Returns the size of every array type in `ast`, in source order. The size
+/// of an array comes before the sizes of its elements.
+fn array_sizes(ast: &AST) -> Vec<&Expression> {
+    arrays(ast).into_iter().map(|(_, size)| size).collect()
+}
+
+/// Returns the element type and size of every array type in `ast`, in source
+/// order. An array comes before its elements.
+fn arrays(ast: &AST) -> Vec<(&TypeVariant, &Expression)> {
+    let mut collector = Arrays { arrays: Vec::new() };
+    visit::walk_ast(&mut collector, ast);
+    collector.arrays
+}
+
+struct Arrays<'ast> {
+    arrays: Vec<(&'ast TypeVariant, &'ast Expression)>,
+}
+
+impl<'ast> Arrays<'ast> {
+    fn variant(&mut self, variant: &'ast TypeVariant) {
+        match variant {
+            TypeVariant::Array(element, size) => {
+                self.arrays.push((element, size));
+                self.variant(element);
+            }
+            TypeVariant::Reference(inner) => self.variant(inner),
+            _ => {}
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for Arrays<'ast> {
+    fn visit_type(&mut self, var_type: &'ast Type) {
+        self.variant(&var_type.variant);
+        visit::walk_type_variant(self, &var_type.variant);
+    }
+}
+
+/// Checks that every labeled `break` and `continue` targets the label of an
+/// enclosing `loop`.
+pub fn check_labels(ast: &AST) -> Vec<SemanticError> {
+    let mut checker = LabelChecker {
+        labels: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for (function, _) in function_bodies(ast) {
+        checker.visit_block(&function.block);
+    }
+
+    checker.errors
+}
+
+struct LabelChecker {
+    /// Labels of the loops enclosing the statement being checked, innermost last.
+    labels: Vec<String>,
+    errors: Vec<SemanticError>,
+}
+
+impl LabelChecker {
+    fn loop_statement(&mut self, loop_statement: &LoopStatement) {
+        match &loop_statement.label {
+            Some(label) => {
+                self.labels.push(label.get_lexeme().to_string());
+                visit::walk_loop(self, loop_statement);
+                self.labels.pop();
+            }
+            None => visit::walk_loop(self, loop_statement),
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for LabelChecker {
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        match statement {
+            Statement::Loop(loop_statement) => return self.loop_statement(loop_statement),
+            Statement::Break(Some(label), _) | Statement::Continue(Some(label))
+                if !self.labels.iter().any(|l| l == label.get_lexeme()) =>
+            {
+                self.errors.push(SemanticError::UndefinedLabel(
+                    label.get_line(),
+                    label.get_col(),
+                    label.get_lexeme().to_string(),
+                ));
+            }
+            _ => {}
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        if let Expression::Primary(primary) = expr {
+            if let Primary::Loop(loop_statement) = primary.as_ref() {
+                return self.loop_statement(loop_statement);
+            }
+        }
+        visit::walk_expression(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::target::TargetTriple;
+
+    fn parse(src: &str) -> Box<AST> {
+        let tokens = Lexer::new(src).lex();
+        let mut parser = Parser::new(tokens);
+        let ast = parser.parse();
+        assert!(!parser.has_error(), "unexpected parser error in {:?}", ast);
+        ast
+    }
+
+    fn check(src: &str) -> Vec<SemanticError> {
+        check_labels(&parse(src))
+    }
+
+    /// Returns the messages of the errors `analyze` reports for `src`.
+    fn messages(src: &str) -> Vec<String> {
+        analyze(&parse(src))
+            .into_iter()
+            .filter(Diagnostic::is_error)
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_defined_labels() {
+        let errors = check("fn main() { 'outer loop { loop { break 'outer; continue 'outer; } } }");
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+    }
+
+    #[test]
+    fn test_undefined_label() {
+        let errors = check("fn main() { 'outer loop { break 'inner; } loop { continue 'outer; } }");
+        assert_eq!(
+            errors,
+            vec![
+                SemanticError::UndefinedLabel(1, 32, String::from("'inner")),
+                SemanticError::UndefinedLabel(1, 58, String::from("'outer")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_reports_independent_errors() {
+        let messages = messages(
+            "fn square(i32 x) -> i32 { ret x * x; } \
+             fn main() { i32 a = true; b = square(a); bool c = square(2); }",
+        );
+        assert_eq!(
+            messages,
+            vec![
+                "Undefined variable `b`.",
+                "Mismatched types: expected `i32`, found `bool`.",
+                "Mismatched types: expected `bool`, found `i32`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_loops() {
+        let messages = messages(
+            "fn main(i64 n) { loop i in 0..n { i64 x = i; } loop j in 0..10 { bool b = j; } \
+             i64 y = i; }",
+        );
+        assert_eq!(
+            messages,
+            vec![
+                "Undefined variable `i`.",
+                "Mismatched types: expected `bool`, found `{integer}`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_accesses() {
+        let messages = messages("fn f(i32 p) { i32 x = p.x + p.g(p, y, p.z[0]); }");
+        assert_eq!(messages, vec!["Undefined variable `y`."]);
+    }
+
+    #[test]
+    fn test_analyze_skips_type_checks_after_redefinition() {
+        let messages = messages("fn f() { ret 1; } fn f() -> bool { i32 x = f(); ret x; }");
+        assert_eq!(messages, vec!["`f` is defined more than once."]);
+    }
+
+    #[test]
+    fn test_block_scoped_constants() {
+        let errors = messages("fn main() { const i32 x = 2 * 3; x = 7; }");
+        assert_eq!(errors, vec!["Cannot assign twice to constant `x`."]);
+
+        let errors = messages("fn f(i32 n) { const i32 x = n + 1; const i32 y; }");
+        assert_eq!(
+            errors,
+            vec![
+                "Constant `x` must be initialized with a constant expression.",
+                "Constant `y` must be initialized with a constant expression.",
+            ]
+        );
+
+        let errors = messages(
+            "fn main() -> i32 { if true { const i64 x = -(1 << 4) % 3; i64 y = x; ret 0; } ret 1; }",
+        );
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        let errors = messages(
+            "fn f() -> i32 { const i32 a = 2; const i32 b = a + 3; i32 c = b; \
+             const i32 d = c + 1; ret d; }",
+        );
+        assert_eq!(
+            errors,
+            vec!["Constant `d` must be initialized with a constant expression."]
+        );
+    }
+
+    #[test]
+    fn test_generic_bounds() {
+        let interface = "intf Shape { fn area() -> i32 fn scale(i32 factor) } \
+                         impl intf Shape for struct Square { fn area() -> i32 { ret 4; } \
+                         fn scale(i32 factor) { } }";
+
+        let errors = messages(&format!(
+            "{} fn total<type T impl Shape>(type T shape) -> i32 {{ scale(shape, 2); \
+             ret area(shape) + 1; }} \
+             fn main() {{ struct Square square; i32 a = total(square); }}",
+            interface
+        ));
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        let errors = messages(&format!(
+            "{} fn total<type T impl Shape, type U>(type T shape, type U other) -> i32 {{ \
+             ret perimeter(shape) + area(other); }} \
+             fn main() {{ struct Circle circle; i32 a = total(circle, 1); }}",
+            interface
+        ));
+        assert_eq!(
+            errors,
+            vec![
+                "Method not available through the generic bound: \
+                 `perimeter` is not declared by `Shape`, the bound of `T`.",
+                "Method not available through the generic bound: \
+                 `U` has no bound declaring `area`.",
+                "Unsatisfied generic bound: `Circle` does not implement `Shape`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_expressions() {
+        let errors = check(
+            "enum Color { Red, Blue } \
+             fn f(i32 y, enum Color c, i64 n) -> i32 { \
+             const i32 x = match 2 { 1 -> { 10 } default -> { 0 } }; \
+             i32 z = match y { 1 -> { x } default -> { 0 } }; \
+             i64 w = match c { Red -> { 1 } Blue -> { n } }; \
+             ret z; }",
+        );
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        let errors = messages(
+            "enum Color { Red, Blue } \
+             fn f(i32 y, enum Color c) { \
+             i32 a = match y { 1 -> { 1 } default -> { true } }; \
+             i32 b = match y { 1 -> { 1 } }; \
+             i32 d = match c { Red -> { 1 } }; }",
+        );
+        assert_eq!(
+            errors,
+            [
+                "Mismatched types: match arms have incompatible types: expected `{integer}`, found `bool`.",
+                "Non-exhaustive match: `_` not covered.",
+                "Non-exhaustive match: `Blue` not covered.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        let warnings = check_unused(&parse(
+            "fn f(i32 p) -> i32 { i32 a = 1; i32 b = a; i32 _c = 2; i32 d; d = 3; \
+             if true { i32 e = p; ret e; } i32 g = 4; ret g; }",
+        ));
+        assert_eq!(
+            warnings,
+            [
+                SemanticWarning::UnusedVariable(1, 36, String::from("b")),
+                SemanticWarning::UnusedVariable(1, 59, String::from("d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let errors = super::check(&parse(
+            "fn f(i64 n, bool b) { i64 x = 0..n; i64 y = 0..=b; }",
+        ));
+        assert_eq!(
+            errors,
+            vec![SemanticError::TypeMismatch(
+                1,
+                44,
+                String::from("cannot apply `..=` to `{integer}` and `bool`")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_dead_code() {
+        let warnings = check_dead_code(&parse(
+            "fn unused() { unused(); } pub fn api() { even(); } \
+             fn even() { odd(); } fn odd() { even(); } fn main() { }",
+        ));
+        assert_eq!(
+            warnings,
+            [SemanticWarning::UnusedFunction(
+                1,
+                3,
+                String::from("unused")
+            )]
+        );
+
+        let warnings = check_dead_code(&parse(
+            "pub fn api() { } fn helper() { } \
+             impl intf Shape for i32 { fn area() -> i32 { ret helper(); } }",
+        ));
+        assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+    }
+
+    #[test]
+    fn test_duplicate_parameters() {
+        let errors = check_parameters(&parse("fn f(u8 x, u8 x) { }"));
+        assert_eq!(
+            errors,
+            vec![SemanticError::DuplicateParameter(1, 14, String::from("x"))]
+        );
+
+        let errors = check_parameters(&parse("fn f(u8 x, u8 y) { }"));
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+    }
+
+    #[test]
+    fn test_void() {
+        let errors = super::check(&parse("fn f() -> void { ret; } fn g() { f(); }"));
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        assert_eq!(
+            super::check(&parse("fn f() { void x; }")),
+            vec![SemanticError::VoidVariable(1, 14, String::from("x"))]
+        );
+        assert_eq!(
+            super::check(&parse("fn f() -> void { ret 1; }")),
+            vec![SemanticError::TypeMismatch(
+                1,
+                21,
+                String::from("expected no return value, found `{integer}`")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_generics() {
+        let errors = check_generics(&parse(
+            "fn f<type T, type U impl Shape, type T>(type T x) { } \
+             intf Shape<type S> { fn area<type A, type A impl Shape>() -> i32 }",
+        ));
+        assert_eq!(
+            errors,
+            vec![
+                SemanticError::DuplicateGeneric(1, 37, String::from("T")),
+                SemanticError::DuplicateGeneric(1, 96, String::from("A")),
+            ]
+        );
+
+        let errors = check_generics(&parse("fn f<type T, type U>(type T x, type U y) { }"));
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+    }
+
+    #[test]
+    fn test_generic_arity() {
+        let src = "enum Pair<type A, type B> { Both(type A, type B) } \
+                   fn f(enum Pair<u8> p, enum Pair<u8, [i32; 2]> q) -> enum Pair<u8, enum Pair<i32>> { }";
+        assert_eq!(
+            check_generic_arity(&parse(src)),
+            vec![
+                SemanticError::GenericArityMismatch(1, 61, String::from("Pair"), 2, 1),
+                SemanticError::GenericArityMismatch(1, 122, String::from("Pair"), 2, 1),
+            ]
+        );
+        assert_eq!(
+            messages("enum Unit { A } fn f(enum Unit<u8> u) { }"),
+            vec!["`Unit` takes 0 generic arguments, found 1."]
+        );
+
+        let src = "enum Pair<type A, type B> { Both(type A, type B) } \
+                   fn f<type T>(enum Pair<u8, type T> p, enum Pair q, enum Other<u8> r) { }";
+        assert!(check_generic_arity(&parse(src)).is_empty());
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        let enumeration = "enum Color { Red, Green, Blue(u8) }";
+        let check = |cases: &str| {
+            messages(&format!(
+                "{} fn f(enum Color c) -> i32 {{ match c {{ {} }} ret 0; }}",
+                enumeration, cases
+            ))
+        };
+
+        assert_eq!(
+            check("Red, Green -> { }"),
+            vec!["Non-exhaustive match: `Blue` not covered."]
+        );
+        assert!(check("Red, Green -> { } default -> { }").is_empty());
+        assert!(check("Red -> { } Green, Blue -> { }").is_empty());
+        assert!(messages("fn f(i32 x) { match x { 1, 2 -> { } } }").is_empty());
+    }
+
+    #[test]
+    fn test_default_interface_methods() {
+        let interface = "intf Shape { fn area() -> i32 default fn sides() -> i32 { ret 0; } }";
+
+        let errors = check_implementations(&parse(&format!(
+            "{} impl intf Shape for struct Square {{ fn area() -> i32 {{ ret 4; }} }}",
+            interface
+        )));
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        let errors = check_implementations(&parse(&format!(
+            "{} impl intf Shape for struct Square {{ fn sides() -> i32 {{ ret 4; }} }} \
+             impl intf Area for i32 {{ }}",
+            interface
+        )));
+        assert_eq!(
+            errors,
+            vec![
+                SemanticError::MissingInterfaceMethod(1, 79, String::from("area")),
+                SemanticError::UndefinedInterface(1, 146, String::from("Area")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discriminants() {
+        let errors = check_discriminants(&parse(
+            "enum E { A = 0, B = 5, C, D = -(1 << 3), Named { i32 x }, F = 1 + 1 }",
+        ));
+        assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+        let errors = check_discriminants(&parse(
+            "enum E { A = 1, B = 5, C = 1 } enum F { X, Y = 0, Z = 2147483647, W }",
+        ));
+        assert_eq!(
+            errors,
+            vec![
+                SemanticError::DuplicateDiscriminant(1, 23, String::from("C")),
+                SemanticError::DuplicateDiscriminant(1, 43, String::from("Y")),
+                SemanticError::DiscriminantOverflow(1, 66, String::from("W")),
+            ]
+        );
+
+        assert_eq!(
+            messages("enum E { A = f(), B } fn f() -> i32 { ret 1; }"),
+            ["The discriminant of `A` must be a constant integer."]
+        );
+        assert_eq!(
+            messages("enum E { A = true }"),
+            ["The discriminant of `A` must be a constant integer."]
+        );
+    }
+
+    #[test]
+    fn test_receivers() {
+        let src = "intf Shape { fn area(ref self) -> i32 } \
+                   impl intf Shape for struct Point { fn area(ref self) -> i32 { ref struct Point p = self; ret 0; } }";
+        assert!(super::check(&parse(src)).is_empty());
+
+        assert_eq!(
+            messages(
+                "intf Shape { fn area() -> i32 } \
+                 impl intf Shape for struct Point { fn area() -> i32 { struct Point p = self; ret 0; } }"
+            ),
+            ["`self` is only available in methods that take `self` or `ref self`."]
+        );
+        assert_eq!(
+            super::check(&parse("fn f(self) { } fn g() { i32 x = self; }")),
+            vec![
+                SemanticError::SelfOutsideMethod(1, 5, String::from("self")),
+                SemanticError::SelfOutsideMethod(1, 32, String::from("self")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reference_parameters() {
+        let src = "fn f(ref u32 x, ref ref u8 y) { u32 a = deref x; ref u8 b = deref y; } \
+                   fn g(u32 v, ref u8 w) { f(ref v, ref w); }";
+        assert!(super::check(&parse(src)).is_empty());
+
+        assert_eq!(
+            messages("fn f(ref ref u8 y) { } fn g(u8 w) { f(ref w); }"),
+            ["Mismatched types: expected `ref ref u8`, found `ref u8`."]
+        );
+    }
+
+    #[test]
+    fn test_naming() {
+        let warnings = check_names(
+            &parse(
+                "fn MyFunc(i32 Count) { const i32 limit = 1; i32 _ok = 2; loop I in 0..limit { } } \
+                 enum color { Red, dark_blue } intf shape { fn Area() -> i32 } \
+                 impl intf shape for i32 { fn Area() -> i32 { const i32 MAX = 1; ret MAX; } }",
+            ),
+            &NamingRules::default(),
+        );
+        let names: Vec<(&str, CaseStyle)> = warnings
+            .iter()
+            .map(|warning| match warning {
+                SemanticWarning::NonConventionalName(_, _, name, style) => (name.as_str(), *style),
+                warning => panic!("Unexpected warning {:?}", warning),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("MyFunc", CaseStyle::Snake),
+                ("Count", CaseStyle::Snake),
+                ("limit", CaseStyle::ScreamingSnake),
+                ("I", CaseStyle::Snake),
+                ("color", CaseStyle::Pascal),
+                ("dark_blue", CaseStyle::Pascal),
+                ("shape", CaseStyle::Pascal),
+                ("Area", CaseStyle::Snake),
+            ]
+        );
+        assert_eq!(
+            warnings[0],
+            SemanticWarning::NonConventionalName(1, 3, String::from("MyFunc"), CaseStyle::Snake)
+        );
+
+        // Structs are not parsed yet, so the declaration is built by hand.
+        let ast = AST {
+            declarations: vec![Box::new(Declaration::Struct(Box::new(StructDeclaration {
+                variant: Box::new(Variant::Unit(Identifier::named("my_struct"), None)),
+                attributes: Vec::new(),
+                error: None,
+            })))],
+        };
+        assert_eq!(
+            check_names(&ast, &NamingRules::default()),
+            [SemanticWarning::NonConventionalName(
+                0,
+                0,
+                String::from("my_struct"),
+                CaseStyle::Pascal
+            )]
+        );
+
+        let rules = NamingRules {
+            functions: None,
+            ..NamingRules::default()
+        };
+        assert!(check_names(&parse("fn MyFunc() { }"), &rules).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_attributes() {
+        let warnings = check_attributes(&parse(
+            "@inline fn f() {} @fast fn g() {} intf Shape { @pure fn area() -> i32 }",
+        ));
+        assert_eq!(
+            warnings,
+            [
+                SemanticWarning::UnknownAttribute(1, 19, String::from("fast")),
+                SemanticWarning::UnknownAttribute(1, 48, String::from("pure")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_array_sizes() {
+        let src = "fn f([u8; 0] a, [[i32; 2 - 2]; 3] b) -> [u8; 1 + 1] { }";
+        let ast = parse(src);
+        assert!(check_array_sizes(&ast).is_empty());
+        assert_eq!(
+            check_empty_arrays(&ast),
+            vec![
+                SemanticWarning::EmptyArray(1, 10),
+                SemanticWarning::EmptyArray(1, 23),
+            ]
+        );
+
+        let ast = parse("fn f([u8; -1] a, [u8; 1.5] b, [u8; n] c) { }");
+        assert_eq!(
+            check_array_sizes(&ast),
+            vec![
+                SemanticError::InvalidArraySize(1, 11, String::from("-1")),
+                SemanticError::InvalidArraySize(1, 22, String::from("1.5")),
+            ]
+        );
+        assert!(check_empty_arrays(&ast).is_empty());
+        assert_eq!(
+            messages("fn f([u8; 1.5] a) { }"),
+            ["The size of an array must be a non-negative integer, found `1.5`."]
+        );
+
+        let diagnostics = analyze(&parse("pub fn f() -> [u8; 0] { }"));
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert!(!diagnostics[0].is_error());
+        assert_eq!(diagnostics[0].code.as_deref(), Some("empty-array"));
+    }
+
+    #[test]
+    fn test_array_fit() {
+        let wide = Codegen::for_target(TargetTriple::parse("x86_64-unknown-linux-gnu").unwrap());
+        let narrow = Codegen::for_target(TargetTriple::parse("i686-unknown-linux-gnu").unwrap());
+
+        let ast = parse("fn f([u8; 3000000000] a, [u16; 1 << 29] b, [i64; n] c) { }");
+        assert!(check_array_fit(&ast, &wide).is_empty());
+        let target = String::from("i686-unknown-linux-gnu");
+        assert_eq!(
+            check_array_fit(&ast, &narrow),
+            vec![SemanticError::ArrayTooLarge(
+                1,
+                10,
+                target.clone(),
+                2147483647
+            )]
+        );
+
+        // Only the innermost array that is too large is reported, and sizes
+        // too large for the compiler to count still are.
+        let ast = parse("fn f([[u8; 1 << 40]; 1 << 40] a, [[u8; 1 << 31]; 2] b) { }");
+        let max = i64::MAX as u64;
+        assert_eq!(
+            check_array_fit(&ast, &wide),
+            vec![SemanticError::ArrayTooLarge(
+                1,
+                21,
+                String::from("x86_64-unknown-linux-gnu"),
+                max
+            )]
+        );
+        assert_eq!(
+            check_array_fit(&ast, &narrow),
+            vec![
+                SemanticError::ArrayTooLarge(1, 11, target.clone(), 2147483647),
+                SemanticError::ArrayTooLarge(1, 39, target.clone(), 2147483647),
+            ]
+        );
+        assert_eq!(
+            Diagnostic::from(SemanticError::ArrayTooLarge(1, 0, target, 2147483647)).message,
+            "The array is too large for `i686-unknown-linux-gnu`, where a value can take up at most 2147483647 bytes."
+        );
+    }
+
+    #[test]
+    fn test_null() {
+        assert!(super::check(&parse(
+            "fn f<type T>(ref u32 a, ref type T b) { a = null; b = null; ref u8 c = null; }"
+        ))
+        .is_empty());
+
+        assert_eq!(
+            super::check(&parse("fn f() { u32 x = null; }")),
+            vec![SemanticError::NullForNonReference(
+                1,
+                17,
+                String::from("u32")
+            )]
+        );
+        assert_eq!(
+            messages("fn f(u32 x) { x = null; }"),
+            ["`null` can only be used for references, but `u32` is expected."]
+        );
+    }
+
+    #[test]
+    fn test_break_values() {
+        assert!(super::check(&parse(
+            "fn f(i32 n) -> i32 { const i32 x = loop { break 5; }; \
+             i32 y = 'outer loop { loop { if n > x { break 'outer n; } break; } break 0; }; \
+             ret loop { break x + y; }; }"
+        ))
+        .is_empty());
+
+        let src = "fn f(bool b) { i32 x = loop { if b { break 1; } break true; }; }";
+        assert_eq!(
+            super::check(&parse(src)),
+            vec![SemanticError::TypeMismatch(
+                1,
+                src.find("true").unwrap(),
+                String::from(
+                    "`break` values have incompatible types: expected `{integer}`, found `bool`"
+                )
+            )]
+        );
+        let src = "fn f() { i32 x = loop { break false; }; }";
+        assert_eq!(
+            super::check(&parse(src)),
+            vec![SemanticError::TypeMismatch(
+                1,
+                src.find("false").unwrap(),
+                String::from("expected `i32`, found `bool`")
+            )]
+        );
+
+        let src = "fn f(bool b) { i32 x = loop { if b { break; } break 1; }; }";
+        assert_eq!(
+            super::check(&parse(src)),
+            vec![SemanticError::MissingBreakValue(1, src.find('1').unwrap())]
+        );
+        let src = "fn f() { i32 x = 'a loop { break 'a; }; }";
+        assert_eq!(
+            messages(src),
+            ["The `loop` is used as a value, so every `break` leaving it must give it one."]
+        );
+        assert_eq!(
+            super::check(&parse(src)),
+            vec![SemanticError::MissingBreakValue(
+                1,
+                src.rfind("'a").unwrap()
+            )]
+        );
+
+        let src = "fn f() { loop { break 1; } }";
+        assert_eq!(
+            super::check(&parse(src)),
+            vec![SemanticError::TypeMismatch(
+                1,
+                src.find('1').unwrap(),
+                String::from("expected no `break` value, found `{integer}`")
+            )]
+        );
+        let src = "fn f() { i32 x = loop { break 'missing 1; }; }";
+        assert_eq!(
+            super::check(&parse(src)),
+            vec![SemanticError::UndefinedLabel(
+                1,
+                src.find("'missing").unwrap(),
+                String::from("'missing")
+            )]
+        );
+    }
+}
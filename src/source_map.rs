@@ -0,0 +1,90 @@
+/// An index of where each line of a source text starts, to look up the line
+/// containing a byte offset in logarithmic time.
+#[derive(Debug)]
+pub struct SourceMap<'a> {
+    src: &'a str,
+    /// The byte offset of the first character of each line.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(src: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceMap { src, line_starts }
+    }
+
+    /// Creates the map of `src` from the offsets its lines start at, as
+    /// found by a lexer that has gone through all of it, see
+    /// [`Lexer::line_starts`](crate::lexer::Lexer::line_starts).
+    pub fn from_line_starts(src: &'a str, line_starts: Vec<usize>) -> Self {
+        SourceMap { src, line_starts }
+    }
+
+    pub fn src(&self) -> &'a str {
+        self.src
+    }
+
+    /// Returns the 1-based number of the line containing `offset`. A newline
+    /// belongs to the line it ends, and offsets past the end of the source to
+    /// the last line.
+    pub fn line_number_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    /// Returns the 1-based line and column of the byte at `offset`, where the
+    /// column counts bytes from the start of the line.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_number_of(offset);
+        (line, offset - self.line_starts[line - 1] + 1)
+    }
+
+    /// Returns the byte offsets at which the line containing `offset` starts
+    /// and ends, excluding its newline.
+    pub fn line_bounds(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_number_of(offset);
+        let start = self.line_starts[line - 1];
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.src.len(),
+        };
+        (start, end)
+    }
+
+    /// Returns the text of the line containing `offset`, without its newline.
+    pub fn line_of(&self, offset: usize) -> &'a str {
+        let (start, end) = self.line_bounds(offset);
+        &self.src[start..end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_of() {
+        let src = "fn main() {\n    i32 x = 1\n\n}";
+        let map = SourceMap::new(src);
+
+        for (offset, line, text) in [
+            (0, 1, "fn main() {"),
+            (5, 1, "fn main() {"),
+            (11, 1, "fn main() {"),
+            (12, 2, "    i32 x = 1"),
+            (20, 2, "    i32 x = 1"),
+            (25, 2, "    i32 x = 1"),
+            (26, 3, ""),
+            (27, 4, "}"),
+            (28, 4, "}"),
+        ] {
+            assert_eq!(map.line_number_of(offset), line, "at {}", offset);
+            assert_eq!(map.line_of(offset), text, "at {}", offset);
+        }
+        assert_eq!(map.line_col(0), (1, 1));
+        assert_eq!(map.line_col(11), (1, 12));
+        assert_eq!(map.line_col(20), (2, 9));
+        assert_eq!(map.line_col(28), (4, 2));
+    }
+}
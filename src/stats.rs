@@ -0,0 +1,465 @@
+use crate::ast::*;
+use crate::token::Token;
+use crate::utils::{LexerError, ParserError};
+use crate::visit::{self, Visitor};
+use std::{fmt, mem};
+
+/// Counts describing the shape of a syntax tree, as printed by `--stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub functions: usize,
+    pub structs: usize,
+    pub enums: usize,
+    pub interfaces: usize,
+    pub implementations: usize,
+    /// Every statement, including those nested in other statements.
+    pub statements: usize,
+    /// The deepest nesting of blocks, where the body of a function is 1.
+    pub max_depth: usize,
+    /// The nodes standing in for something that failed to parse.
+    pub errors: usize,
+    /// An estimate of the heap memory the tree takes up, see
+    /// [`ast_heap_size`].
+    pub heap_bytes: usize,
+}
+
+impl Stats {
+    pub fn of(ast: &AST) -> Stats {
+        let mut counter = Counter {
+            stats: Stats::default(),
+            depth: 0,
+        };
+        visit::walk_ast(&mut counter, ast);
+        Stats {
+            heap_bytes: ast_heap_size(ast),
+            ..counter.stats
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("Functions", self.functions),
+            ("Structs", self.structs),
+            ("Enums", self.enums),
+            ("Interfaces", self.interfaces),
+            ("Implementations", self.implementations),
+            ("Statements", self.statements),
+            ("Max block depth", self.max_depth),
+            ("Error nodes", self.errors),
+            ("Heap bytes", self.heap_bytes),
+        ];
+        for (i, (name, count)) in rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:<16}{:>6}", name, count)?;
+        }
+        Ok(())
+    }
+}
+
+struct Counter {
+    stats: Stats,
+    depth: usize,
+}
+
+impl<'ast> Visitor<'ast> for Counter {
+    fn visit_declaration(&mut self, decl: &'ast Declaration) {
+        match decl {
+            Declaration::Enum(_) => self.stats.enums += 1,
+            Declaration::Struct(_) => self.stats.structs += 1,
+            Declaration::Function(_) => self.stats.functions += 1,
+            Declaration::Interface(_) => self.stats.interfaces += 1,
+            Declaration::Implementation(_) => self.stats.implementations += 1,
+            Declaration::Import(_) => {}
+            Declaration::Error(_) => self.stats.errors += 1,
+        }
+        visit::walk_declaration(self, decl);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        visit::walk_block(self, block);
+        self.depth -= 1;
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        self.stats.statements += 1;
+        if let Statement::Error(_) = statement {
+            self.stats.errors += 1;
+        }
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        let is_error = match expr {
+            Expression::Operation(operator) => matches!(operator.as_ref(), Operator::Error(_)),
+            Expression::Primary(primary) => match primary.as_ref() {
+                Primary::Literal(literal) => matches!(literal.as_ref(), Literal::Error(_)),
+                primary => matches!(primary, Primary::Error(_)),
+            },
+            Expression::Error(_) => true,
+        };
+        if is_error {
+            self.stats.errors += 1;
+        }
+        visit::walk_expression(self, expr);
+    }
+
+    fn visit_type(&mut self, var_type: &'ast Type) {
+        if let TypeVariant::Error(_) = var_type.variant.as_ref() {
+            self.stats.errors += 1;
+        }
+        visit::walk_type_variant(self, &var_type.variant);
+    }
+}
+
+/// Estimates the bytes of heap memory `ast` takes up, counting every boxed
+/// node, the spare capacity of its vectors and strings, and the strings of
+/// its tokens and errors. Interned names are shared, so they are left out.
+pub fn ast_heap_size(ast: &AST) -> usize {
+    ast.heap_size()
+}
+
+/// A value that owns memory on the heap.
+trait HeapSize {
+    /// Returns the bytes of heap memory owned by the value, not counting the
+    /// value itself.
+    fn heap_size(&self) -> usize;
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        mem::size_of::<T>() + self.as_ref().heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * mem::size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl HeapSize for Token {
+    fn heap_size(&self) -> usize {
+        match self {
+            Token::Identifier(..) | Token::Eof => 0,
+            Token::Error(error) => error.heap_size(),
+            Token::DataType(_, _, lexeme)
+            | Token::Separator(_, _, lexeme)
+            | Token::Operator(_, _, lexeme)
+            | Token::Keyword(_, _, lexeme)
+            | Token::Label(_, _, lexeme)
+            | Token::IntLiteral(_, _, lexeme)
+            | Token::FloatLiteral(_, _, lexeme)
+            | Token::StringLiteral(_, _, lexeme)
+            | Token::CharLiteral(_, _, lexeme) => lexeme.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for LexerError {
+    fn heap_size(&self) -> usize {
+        match self {
+            LexerError::UnexpectedEOF(_, _, _, text)
+            | LexerError::InvalidBinary(_, _, _, text)
+            | LexerError::InvalidOctal(_, _, _, text)
+            | LexerError::InvalidDecimal(_, _, _, text)
+            | LexerError::InvalidHexaDecimal(_, _, _, text)
+            | LexerError::InvalidFloat(_, _, _, text)
+            | LexerError::UnterminatedStringLiteral(_, _, _, text)
+            | LexerError::UnterminatedCharacterLiteral(_, _, _, text)
+            | LexerError::UnterminatedComment(_, _, _, text)
+            | LexerError::InvalidEscape(_, _, _, text)
+            | LexerError::InvalidIdentifierCharacter(_, _, _, text) => text.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for ParserError {
+    fn heap_size(&self) -> usize {
+        match self {
+            ParserError::UnexpectedToken(_, _, text)
+            | ParserError::MissingToken(_, _, text)
+            | ParserError::InvalidSyntax(_, _, text)
+            | ParserError::UnexpectedEOF(_, _, text)
+            | ParserError::ReservedKeyword(_, _, text)
+            | ParserError::ReservedTypeName(_, _, text)
+            | ParserError::UnclosedDelimiter(_, _, text) => text.heap_size(),
+            ParserError::DanglingElif(..) | ParserError::DanglingElse(..) => 0,
+        }
+    }
+}
+
+/// Implements [`HeapSize`] for structs as the sum over the listed fields,
+/// which must be every field owning heap memory.
+macro_rules! heap_size_of_fields {
+    ($($node:ty { $($field:ident),* })*) => {
+        $(impl HeapSize for $node {
+            fn heap_size(&self) -> usize {
+                0 $(+ self.$field.heap_size())*
+            }
+        })*
+    };
+}
+
+heap_size_of_fields! {
+    AST { declarations }
+    Identifier { id, error }
+    ArrayAccess { index, next, error }
+    FunctionCall { id, args, error }
+    Range { start, end }
+    GenericParameters { generics, error }
+    Type { variant, error }
+    Block { statements, value }
+    Assignment { id, expr }
+    IfStatement { condition, if_block, elif_statements, else_block, error }
+    ElifStatement { condition, block, error }
+    VariableDeclaration { var_type, id, init, error }
+    LoopStatement { label, iteration, block }
+    RangeIteration { variable, range }
+    MatchStatement { expr, case_clauses, default_clause, error }
+    CaseClause { cases, case_block, error }
+    ReturnStatement { expr, error }
+    LLVMBlock { statements, error }
+    ASMBlock { statements, error }
+    NamedFields { fields, error }
+    TupleFields { fields, error }
+    EnumDeclaration { id, generics, variants, attributes, error }
+    Attribute { id, args, error }
+    StructDeclaration { variant, attributes, error }
+    FunctionDeclaration {
+        id, generics, receiver, parameters, return_type, block, lazy_body, attributes, error
+    }
+    Receiver { id }
+    LazyBody { tokens }
+    InterfaceDeclaration { id, generics, methods, attributes, error }
+    InterfaceImplementation { intf_id, for_type, generics, methods, attributes, error }
+    ImportDeclaration { segments, error }
+}
+
+impl HeapSize for Declaration {
+    fn heap_size(&self) -> usize {
+        match self {
+            Declaration::Enum(enumeration) => enumeration.heap_size(),
+            Declaration::Struct(structure) => structure.heap_size(),
+            Declaration::Function(function) => function.heap_size(),
+            Declaration::Interface(interface) => interface.heap_size(),
+            Declaration::Implementation(implementation) => implementation.heap_size(),
+            Declaration::Import(import) => import.heap_size(),
+            Declaration::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Variant {
+    fn heap_size(&self) -> usize {
+        match self {
+            Variant::Named(id, fields) => id.heap_size() + fields.heap_size(),
+            Variant::Tuple(id, fields) => id.heap_size() + fields.heap_size(),
+            Variant::Unit(id, value) => id.heap_size() + value.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Statement {
+    fn heap_size(&self) -> usize {
+        match self {
+            Statement::If(statement) => statement.heap_size(),
+            Statement::Loop(statement) => statement.heap_size(),
+            Statement::Assign(assignment) => assignment.heap_size(),
+            Statement::Var(declaration) => declaration.heap_size(),
+            Statement::Match(statement) => statement.heap_size(),
+            Statement::Return(statement) => statement.heap_size(),
+            Statement::Break(label, value) => label.heap_size() + value.heap_size(),
+            Statement::Continue(label) => label.heap_size(),
+            Statement::FunctionCall(call) => call.heap_size(),
+            Statement::LLVM(block) => block.heap_size(),
+            Statement::ASM(block) => block.heap_size(),
+            Statement::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for BlockStringLiteralVariant {
+    fn heap_size(&self) -> usize {
+        match self {
+            BlockStringLiteralVariant::StringLiteral(literal) => literal.heap_size(),
+            BlockStringLiteralVariant::Identifier(id) => id.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Pattern {
+    fn heap_size(&self) -> usize {
+        match self {
+            Pattern::Literal(literal) => literal.heap_size(),
+            Pattern::Variant(id) => id.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for TypeVariant {
+    fn heap_size(&self) -> usize {
+        match self {
+            TypeVariant::Primitive(name) => name.heap_size(),
+            TypeVariant::Structure(id, generics)
+            | TypeVariant::Enumeration(id, generics)
+            | TypeVariant::Interface(id, generics) => id.heap_size() + generics.heap_size(),
+            TypeVariant::Array(element, size) => element.heap_size() + size.heap_size(),
+            TypeVariant::Reference(referenced) => referenced.heap_size(),
+            TypeVariant::Generic(id) => id.heap_size(),
+            TypeVariant::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for GenericVariants {
+    fn heap_size(&self) -> usize {
+        match self {
+            GenericVariants::Identifier(id) => id.heap_size(),
+            GenericVariants::Implements(id, intf) => id.heap_size() + intf.heap_size(),
+            GenericVariants::Type(generic_type) => generic_type.heap_size(),
+            GenericVariants::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Expression {
+    fn heap_size(&self) -> usize {
+        match self {
+            Expression::Operation(operator) => operator.heap_size(),
+            Expression::Primary(primary) => primary.heap_size(),
+            Expression::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Operator {
+    fn heap_size(&self) -> usize {
+        match self {
+            Operator::Binary(op, left, right) => {
+                op.heap_size() + left.heap_size() + right.heap_size()
+            }
+            Operator::Unary(op, operand) => op.heap_size() + operand.heap_size(),
+            Operator::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Primary {
+    fn heap_size(&self) -> usize {
+        match self {
+            Primary::Literal(literal) => literal.heap_size(),
+            Primary::Identifier(id) => id.heap_size(),
+            Primary::Group(expr) => expr.heap_size(),
+            Primary::ArrayAccess(id, access) => id.heap_size() + access.heap_size(),
+            Primary::FunctionCall(call) => call.heap_size(),
+            Primary::Match(statement) => statement.heap_size(),
+            Primary::Loop(statement) => statement.heap_size(),
+            Primary::Range(range) => range.heap_size(),
+            Primary::FieldAccess(expr, member) => expr.heap_size() + member.heap_size(),
+            Primary::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Literal {
+    fn heap_size(&self) -> usize {
+        match self {
+            Literal::Integer(token)
+            | Literal::Float(token)
+            | Literal::String(token)
+            | Literal::Character(token)
+            | Literal::Boolean(token)
+            | Literal::Null(token) => token.heap_size(),
+            Literal::Error(error) => error.heap_size(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn count(src: &str) -> Stats {
+        Stats::of(&Parser::new(Lexer::new(src).lex()).parse())
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = count(
+            "enum Color { Red, Green } intf Shape { fn area() -> i32 } \
+             impl intf Shape for enum Color { fn area() -> i32 { ret 0; } } \
+             fn main(i32 n) { i32 x = 1; loop { if x > n { break; } x = x + 1; } } \
+             fn f() { ret; }",
+        );
+        assert_eq!(
+            stats,
+            Stats {
+                functions: 2,
+                structs: 0,
+                enums: 1,
+                interfaces: 1,
+                implementations: 1,
+                statements: 7,
+                max_depth: 3,
+                errors: 0,
+                heap_bytes: stats.heap_bytes,
+            }
+        );
+        assert_eq!(
+            stats.to_string().lines().nth(5),
+            Some("Statements           7")
+        );
+
+        let stats = count("fn f() { x = ; } 42");
+        assert_eq!((stats.functions, stats.errors), (1, 2));
+    }
+
+    #[test]
+    fn test_ast_heap_size() {
+        let size = |src: &str| ast_heap_size(&Parser::new(Lexer::new(src).lex()).parse());
+        assert_eq!(size(""), 0);
+
+        let ast = AST {
+            declarations: vec![Box::new(Declaration::Error(ParserError::DanglingElse(
+                0, 0,
+            )))],
+        };
+        assert_eq!(
+            ast_heap_size(&ast),
+            mem::size_of::<Box<Declaration>>() + mem::size_of::<Declaration>()
+        );
+
+        let one = size("fn f() { i32 x = 1; }");
+        let two = size("fn f() { i32 x = 1; } fn g() { i32 x = 1; }");
+        let longer = size("fn f() { i32 x = 1; i32 y = x + 2; ret y; }");
+        assert!(0 < one && one < two, "{} {}", one, two);
+        assert!(one < longer, "{} {}", one, longer);
+        assert_eq!(count("fn f() { i32 x = 1; }").heap_bytes, one);
+    }
+}
@@ -0,0 +1,203 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::sync::Arc;
+
+/// A string interned by a [`Symbols`] table, such as the name of an
+/// identifier. The symbols a table gives out for the same string share one
+/// allocation, so cloning one never allocates and comparing two only compares
+/// their addresses. Symbols from different tables, such as a lexed name and
+/// one loaded from the cache, are equal if their strings are.
+///
+/// A string is freed along with the last symbol and table holding it.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// Returns the string this symbol was interned from.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Creates a symbol of its own, sharing storage with no other. Use a
+/// [`Symbols`] table for names that repeat.
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Self {
+        Symbol(Arc::from(string))
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+/// Hashes the string, since symbols of different tables can be equal.
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+/// The table interning the names of one compilation session, such as those
+/// lexed from one source or loaded from one cache entry. It only lives as long
+/// as the session does, and taking a string out of it needs no lock.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    strings: HashSet<Arc<str>>,
+}
+
+thread_local! {
+    /// The table interning the symbols deserialized on this thread, if any,
+    /// see [`Symbols::deserializing`].
+    static DESERIALIZING: RefCell<Option<Symbols>> = const { RefCell::new(None) };
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the symbol for `string`, storing the string if it is the first
+    /// time the table sees it.
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(string) = self.strings.get(string) {
+            return Symbol(Arc::clone(string));
+        }
+        let string: Arc<str> = Arc::from(string);
+        self.strings.insert(Arc::clone(&string));
+        Symbol(string)
+    }
+
+    /// Returns the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Runs `f`, interning the symbols it deserializes on the current thread in
+    /// this table, so that the names repeated in a loaded tree share storage
+    /// like those of a lexed one. Symbols deserialized outside of it each get
+    /// a string of their own.
+    pub fn deserializing<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let outer = DESERIALIZING.with(|table| table.replace(Some(mem::take(self))));
+        let result = f();
+        *self = DESERIALIZING
+            .with(|table| table.replace(outer))
+            .unwrap_or_default();
+        result
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Symbols are written as their strings, since sharing storage is only
+/// meaningful within the program that interned them.
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        let symbol = DESERIALIZING.with(|table| match table.borrow_mut().as_mut() {
+            Some(symbols) => symbols.intern(&string),
+            None => Symbol::from(string.as_str()),
+        });
+        Ok(symbol)
+    }
+}
+
+impl JsonSchema for Symbol {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        String::from("Symbol")
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut symbols = Symbols::new();
+        let a = symbols.intern("a");
+        let again = symbols.intern(&String::from("a"));
+        assert_eq!(a, again);
+        assert!(Arc::ptr_eq(&a.0, &again.0));
+        assert_ne!(a, symbols.intern("b"));
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(a.as_str(), "a");
+        assert_eq!(a.to_string(), "a");
+        assert_eq!(format!("{:?}", a), "\"a\"");
+
+        // Symbols of different tables are compared by their strings.
+        let other = Symbols::new().intern("a");
+        assert!(!Arc::ptr_eq(&a.0, &other.0));
+        assert_eq!(a, other);
+        assert_eq!(a, Symbol::from("a"));
+        assert_eq!(
+            HashSet::from([a.clone(), other]),
+            HashSet::from([Symbol::from("a")])
+        );
+
+        // Each string is freed along with the last symbol holding it.
+        let weak = Arc::downgrade(&a.0);
+        drop((symbols, again));
+        assert!(weak.upgrade().is_some());
+        drop(a);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_serde() {
+        let symbol = Symbol::from("symbol_test_serde");
+        let json = serde_json::to_string(&symbol).unwrap();
+        assert_eq!(json, "\"symbol_test_serde\"");
+        assert_eq!(serde_json::from_str::<Symbol>(&json).unwrap(), symbol);
+
+        let bytes = bincode::serialize(&[&symbol, &symbol]).unwrap();
+        let [a, b] = bincode::deserialize::<[Symbol; 2]>(&bytes).unwrap();
+        assert!(!Arc::ptr_eq(&a.0, &b.0));
+
+        let mut symbols = Symbols::new();
+        let [a, b]: [Symbol; 2] = symbols.deserializing(|| bincode::deserialize(&bytes).unwrap());
+        assert_eq!(a, symbol);
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!(symbols.len(), 1);
+        assert!(DESERIALIZING.with(|table| table.borrow().is_none()));
+    }
+}
@@ -0,0 +1,363 @@
+use serde::{Serialize, Serializer};
+use std::env::consts;
+use std::fmt;
+
+/// The CPUs that can be passed to `--target-cpu`, besides `native`, which
+/// selects the CPU of the host.
+pub const KNOWN_TARGETS: &[&str] = &[
+    "generic",
+    "x86-64",
+    "x86-64-v2",
+    "x86-64-v3",
+    "x86-64-v4",
+    "nehalem",
+    "sandybridge",
+    "haswell",
+    "skylake",
+    "skylake-avx512",
+    "icelake-server",
+    "alderlake",
+    "sapphirerapids",
+    "znver1",
+    "znver2",
+    "znver3",
+    "znver4",
+    "cortex-a53",
+    "cortex-a72",
+    "cortex-a76",
+    "neoverse-n1",
+    "neoverse-v1",
+    "apple-m1",
+    "apple-m2",
+    "apple-m3",
+];
+
+pub fn is_known(cpu: &str) -> bool {
+    cpu == "native" || KNOWN_TARGETS.contains(&cpu)
+}
+
+/// Returns the known target closest to `cpu`, if one is close enough to be
+/// what was meant.
+pub fn suggest(cpu: &str) -> Option<&'static str> {
+    let max_distance = (cpu.chars().count() / 3).max(2);
+    std::iter::once("native")
+        .chain(KNOWN_TARGETS.iter().copied())
+        .map(|target| (edit_distance(cpu, target), target))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, target)| target)
+}
+
+/// The architectures a [`TargetTriple`] can name, with the width of their
+/// pointers in bits, including those the compiler itself can be built for.
+pub const KNOWN_ARCHS: &[(&str, u32)] = &[
+    ("x86_64", 64),
+    ("i386", 32),
+    ("i586", 32),
+    ("i686", 32),
+    ("aarch64", 64),
+    ("arm", 32),
+    ("armv7", 32),
+    ("riscv32", 32),
+    ("riscv64", 64),
+    ("wasm32", 32),
+    ("wasm64", 64),
+    ("powerpc", 32),
+    ("powerpc64", 64),
+    ("mips", 32),
+    ("mips64", 64),
+    ("mipsisa32r6", 32),
+    ("mipsisa64r6", 64),
+    ("s390x", 64),
+    ("sparc", 32),
+    ("sparc64", 64),
+    ("loongarch64", 64),
+    ("m68k", 32),
+    ("csky", 32),
+    ("hexagon", 32),
+];
+
+/// Returns the architecture of a triple for the architecture `arch` as named
+/// by [`consts::ARCH`].
+fn triple_arch(arch: &str) -> &str {
+    match arch {
+        "x86" => "i686",
+        "mips32r6" => "mipsisa32r6",
+        "mips64r6" => "mipsisa64r6",
+        arch => arch,
+    }
+}
+
+/// A target triple such as `x86_64-unknown-linux-gnu`, as passed to
+/// `--target`: the architecture, the vendor, the operating system and, if
+/// given, the environment or ABI.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TargetTriple {
+    pub arch: String,
+    pub vendor: String,
+    pub os: String,
+    pub env: Option<String>,
+}
+
+impl TargetTriple {
+    /// Parses a triple of three or four components separated by `-`, whose
+    /// architecture is one of [`KNOWN_ARCHS`] or that of the host.
+    pub fn parse(triple: &str) -> Result<TargetTriple, String> {
+        let components: Vec<&str> = triple.split('-').collect();
+        let is_component = |component: &&str| {
+            !component.is_empty()
+                && component
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        };
+        if !(3..=4).contains(&components.len()) || !components.iter().all(is_component) {
+            return Err(format!(
+                "Invalid target triple `{}`. Expected `<arch>-<vendor>-<os>` or `<arch>-<vendor>-<os>-<env>`, such as `x86_64-unknown-linux-gnu`.",
+                triple
+            ));
+        }
+        if arch_pointer_width(components[0]).is_none() {
+            return Err(format!(
+                "Unknown architecture `{}` in the target triple `{}`. The known architectures are `{}`.",
+                components[0],
+                triple,
+                KNOWN_ARCHS
+                    .iter()
+                    .map(|(arch, _)| *arch)
+                    .collect::<Vec<_>>()
+                    .join("`, `")
+            ));
+        }
+        Ok(TargetTriple {
+            arch: String::from(components[0]),
+            vendor: String::from(components[1]),
+            os: String::from(components[2]),
+            env: components.get(3).map(|env| env.to_string()),
+        })
+    }
+
+    /// Returns the triple of the host the compiler runs on. `parse` accepts
+    /// the architecture of the host even if it is not one of [`KNOWN_ARCHS`],
+    /// with pointers as wide as those of the host, so that the triple always
+    /// parses back the same.
+    pub fn host() -> TargetTriple {
+        let arch = triple_arch(consts::ARCH);
+        let (vendor, os) = match consts::OS {
+            "macos" => ("apple", "darwin"),
+            "windows" => ("pc", "windows"),
+            os => ("unknown", os),
+        };
+        let env = if cfg!(target_env = "gnu") {
+            Some("gnu")
+        } else if cfg!(target_env = "musl") {
+            Some("musl")
+        } else if cfg!(target_env = "msvc") {
+            Some("msvc")
+        } else {
+            None
+        };
+        TargetTriple {
+            arch: String::from(arch),
+            vendor: String::from(vendor),
+            os: String::from(os),
+            env: env.map(String::from),
+        }
+    }
+
+    /// Returns the width of a pointer on the target in bits, which is 64 for
+    /// a triple built with an architecture that is not known.
+    pub fn pointer_width(&self) -> u32 {
+        arch_pointer_width(&self.arch).unwrap_or(64)
+    }
+
+    /// Returns the most bytes a single value can take up on the target, which
+    /// is the largest signed integer as wide as a pointer, so that the
+    /// distance between any two of its bytes fits in one.
+    pub fn max_object_size(&self) -> u64 {
+        (1 << (self.pointer_width() - 1)) - 1
+    }
+
+    /// Returns the bytes a value of the primitive type `name` takes up on the
+    /// target, or `None` if `name` is not a sized primitive type. `usize` and
+    /// `isize` are as wide as a pointer, and `f80` is padded to 16 bytes.
+    pub fn size_of_primitive(&self, name: &str) -> Option<u32> {
+        let size = match name {
+            "u8" | "i8" | "bool" => 1,
+            "u16" | "i16" => 2,
+            "u32" | "i32" | "f32" | "char" => 4,
+            "u64" | "i64" | "f64" => 8,
+            "u128" | "i128" | "f80" | "f128" => 16,
+            "usize" | "isize" => self.pointer_width() / 8,
+            _ => return None,
+        };
+        Some(size)
+    }
+}
+
+impl Default for TargetTriple {
+    fn default() -> Self {
+        TargetTriple::host()
+    }
+}
+
+impl fmt::Display for TargetTriple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.arch, self.vendor, self.os)?;
+        if let Some(env) = &self.env {
+            write!(f, "-{}", env)?;
+        }
+        Ok(())
+    }
+}
+
+/// Triples are written as they would be passed to `--target`.
+impl Serialize for TargetTriple {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Returns the width of a pointer in bits on the architecture `arch`, if it is
+/// known or is that of the host.
+fn arch_pointer_width(arch: &str) -> Option<u32> {
+    KNOWN_ARCHS
+        .iter()
+        .find(|(known, _)| *known == arch)
+        .map(|(_, width)| *width)
+        .or_else(|| (arch == triple_arch(consts::ARCH)).then_some(usize::BITS))
+}
+
+/// Returns the Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        assert!(is_known("native"));
+        assert!(is_known("znver3"));
+        assert!(!is_known("skylak"));
+
+        assert_eq!(suggest("skylak"), Some("skylake"));
+        assert_eq!(suggest("x86_64"), Some("x86-64"));
+        assert_eq!(suggest("nativ"), Some("native"));
+        assert_eq!(suggest("pentium-pro-max"), None);
+    }
+
+    #[test]
+    fn test_target_triple() {
+        let triple = TargetTriple::parse("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            (
+                triple.arch.as_str(),
+                triple.vendor.as_str(),
+                triple.os.as_str()
+            ),
+            ("x86_64", "unknown", "linux")
+        );
+        assert_eq!(triple.env.as_deref(), Some("gnu"));
+        assert_eq!(triple.pointer_width(), 64);
+        assert_eq!(triple.to_string(), "x86_64-unknown-linux-gnu");
+        assert_eq!(
+            serde_json::to_string(&triple).unwrap(),
+            "\"x86_64-unknown-linux-gnu\""
+        );
+
+        let triple = TargetTriple::parse("wasm32-unknown-unknown").unwrap();
+        assert_eq!(triple.env, None);
+        assert_eq!(triple.pointer_width(), 32);
+        assert_eq!(triple.to_string(), "wasm32-unknown-unknown");
+
+        for malformed in [
+            "",
+            "x86_64",
+            "x86_64-linux",
+            "x86_64--linux",
+            "a-b-c-d-e",
+            "x86 64-pc-linux",
+        ] {
+            let err = TargetTriple::parse(malformed).unwrap_err();
+            assert!(err.starts_with("Invalid target triple"), "{}", err);
+        }
+        assert_eq!(
+            TargetTriple::parse("z80-unknown-none").unwrap_err(),
+            format!(
+                "Unknown architecture `z80` in the target triple `z80-unknown-none`. The known architectures are `{}`.",
+                KNOWN_ARCHS.iter().map(|(arch, _)| *arch).collect::<Vec<_>>().join("`, `")
+            )
+        );
+
+        let host = TargetTriple::host();
+        assert_eq!(host.pointer_width(), usize::BITS);
+        assert_eq!(TargetTriple::parse(&host.to_string()).ok(), Some(host));
+
+        // Each architecture the compiler can be built for names a known one.
+        for arch in [
+            "x86",
+            "x86_64",
+            "arm",
+            "aarch64",
+            "m68k",
+            "csky",
+            "mips",
+            "mips32r6",
+            "mips64",
+            "mips64r6",
+            "powerpc",
+            "powerpc64",
+            "riscv32",
+            "riscv64",
+            "s390x",
+            "sparc",
+            "sparc64",
+            "hexagon",
+            "loongarch64",
+            "wasm32",
+            "wasm64",
+        ] {
+            let triple = format!("{}-unknown-linux-gnu", triple_arch(arch));
+            assert!(TargetTriple::parse(&triple).is_ok(), "{}", triple);
+        }
+        let powerpc = TargetTriple::parse("powerpc64-unknown-linux-gnu").unwrap();
+        assert_eq!(powerpc.pointer_width(), 64);
+        assert_eq!(
+            TargetTriple::parse("i686-pc-windows-msvc")
+                .unwrap()
+                .pointer_width(),
+            32
+        );
+    }
+
+    #[test]
+    fn test_size_of_primitive() {
+        let wide = TargetTriple::parse("x86_64-unknown-linux-gnu").unwrap();
+        let narrow = TargetTriple::parse("i686-pc-windows-msvc").unwrap();
+        for name in ["usize", "isize"] {
+            assert_eq!(wide.size_of_primitive(name), Some(8));
+            assert_eq!(narrow.size_of_primitive(name), Some(4));
+        }
+        for (name, size) in [("u8", 1), ("i16", 2), ("char", 4), ("f64", 8), ("i128", 16)] {
+            assert_eq!(wide.size_of_primitive(name), Some(size));
+            assert_eq!(narrow.size_of_primitive(name), Some(size));
+        }
+        assert_eq!(wide.size_of_primitive("void"), None);
+        assert_eq!(wide.size_of_primitive("Point"), None);
+
+        assert_eq!(wide.max_object_size(), i64::MAX as u64);
+        assert_eq!(narrow.max_object_size(), i32::MAX as u64);
+    }
+}
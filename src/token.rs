@@ -1,20 +1,33 @@
-use crate::utils;
-use serde::{Deserialize, Serialize};
+use crate::symbol::Symbol;
+use crate::{lexer, utils};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cell::Cell;
+use std::{fmt, mem};
 
-/// Represents a token in the lexical analysis phase. 
+/// A range of byte offsets into the lexed input, end-exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Represents a token in the lexical analysis phase.
 /// Each token stores its line, column, and lexeme value.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, JsonSchema)]
 pub enum Token {
     /// Data type token: (line, column, value)
     DataType(usize, usize, String),
     /// Identifier token: (line, column, value)
-    Identifier(usize, usize, String),
+    Identifier(usize, usize, Symbol),
     /// Separator token: (line, column, value)
     Separator(usize, usize, String),
     /// Operator token: (line, column, value)
     Operator(usize, usize, String),
     /// Keyword token: (line, column, value)
     Keyword(usize, usize, String),
+    /// Loop label token such as `'outer`: (line, column, value)
+    Label(usize, usize, String),
 
     /// Integer literal token: (line, column, value)
     IntLiteral(usize, usize, String),
@@ -32,6 +45,26 @@ pub enum Token {
     Eof,
 }
 
+/// The kind of a token, regardless of where it is. Keywords, separators and
+/// operators are only of the kind spelled with the same lexeme, and so are
+/// identifiers, for the words that are only keywords in some places, such as
+/// `intf`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    DataType,
+    Identifier(&'static str),
+    Separator(&'static str),
+    Operator(&'static str),
+    Keyword(&'static str),
+    Label,
+    IntLiteral,
+    FloatLiteral,
+    StringLiteral,
+    CharLiteral,
+    Error,
+    Eof,
+}
+
 impl Token {
     /// Returns the line number where the token occurs.
     pub fn get_line(&self) -> usize {
@@ -41,6 +74,7 @@ impl Token {
             | Self::Separator(line, _, _)
             | Self::Operator(line, _, _)
             | Self::Keyword(line, _, _)
+            | Self::Label(line, _, _)
             | Self::IntLiteral(line, _, _)
             | Self::CharLiteral(line, _, _)
             | Self::FloatLiteral(line, _, _)
@@ -57,6 +91,7 @@ impl Token {
             | Self::Separator(_, col, _)
             | Self::Operator(_, col, _)
             | Self::Keyword(_, col, _)
+            | Self::Label(_, col, _)
             | Self::IntLiteral(_, col, _)
             | Self::CharLiteral(_, col, _)
             | Self::FloatLiteral(_, col, _)
@@ -65,14 +100,28 @@ impl Token {
         }
     }
 
+    /// Returns the bytes of the input the token was lexed from. The column of a
+    /// token is the byte offset at which it starts, and its lexeme is the
+    /// source text it covers. Errors and EOF have no span.
+    pub fn span(&self) -> Option<Span> {
+        match &self {
+            Self::Error(_) | Self::Eof => None,
+            _ => Some(Span {
+                start: self.get_col(),
+                end: self.get_col() + self.get_lexeme().len(),
+            }),
+        }
+    }
+
     /// Returns the lexeme (value) of the token as a string slice.
     pub fn get_lexeme(&self) -> &str {
         match &self {
+            Self::Identifier(_, _, symbol) => symbol.as_str(),
             Self::DataType(_, _, lexeme)
-            | Self::Identifier(_, _, lexeme)
             | Self::Separator(_, _, lexeme)
             | Self::Operator(_, _, lexeme)
             | Self::Keyword(_, _, lexeme)
+            | Self::Label(_, _, lexeme)
             | Self::IntLiteral(_, _, lexeme)
             | Self::CharLiteral(_, _, lexeme)
             | Self::FloatLiteral(_, _, lexeme)
@@ -80,11 +129,154 @@ impl Token {
             _ => "", // Return empty string if token type does not contain a lexeme
         }
     }
+
+    /// Returns the value of a string or character literal with its quotes
+    /// removed and escape sequences decoded, or `None` for any other token.
+    pub fn decoded_value(&self) -> Option<String> {
+        match &self {
+            Self::StringLiteral(_, _, lexeme) | Self::CharLiteral(_, _, lexeme) => {
+                lexer::decode_escapes(&lexeme[1..lexeme.len() - 1]).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the radix an integer literal is written in, from the prefix of
+    /// its lexeme: 16 for `0x`, 8 for `0o`, 2 for `0b` and 10 otherwise.
+    /// Returns `None` for any other token.
+    pub fn numeric_radix(&self) -> Option<u32> {
+        let Self::IntLiteral(_, _, lexeme) = &self else {
+            return None;
+        };
+        match lexeme.get(..2) {
+            Some("0x") | Some("0X") => Some(16),
+            Some("0o") | Some("0O") => Some(8),
+            Some("0b") | Some("0B") => Some(2),
+            _ => Some(10),
+        }
+    }
+
+    /// Returns whether the token is the same as `other` wherever it occurs:
+    /// both are of the same kind with the same lexeme, or the same kind of
+    /// error.
+    pub fn same_as(&self, other: &Token) -> bool {
+        match (self, other) {
+            (Self::Error(a), Self::Error(b)) => mem::discriminant(a) == mem::discriminant(b),
+            _ => {
+                mem::discriminant(self) == mem::discriminant(other)
+                    && self.get_lexeme() == other.get_lexeme()
+            }
+        }
+    }
+
+    /// Returns whether the token is of the kind `kind`.
+    pub fn is(&self, kind: TokenKind) -> bool {
+        match (self, kind) {
+            (Self::Identifier(_, _, symbol), TokenKind::Identifier(name)) => {
+                symbol.as_str() == name
+            }
+            (Self::Separator(_, _, lexeme), TokenKind::Separator(expected))
+            | (Self::Operator(_, _, lexeme), TokenKind::Operator(expected))
+            | (Self::Keyword(_, _, lexeme), TokenKind::Keyword(expected)) => lexeme == expected,
+            (Self::DataType(..), TokenKind::DataType)
+            | (Self::Label(..), TokenKind::Label)
+            | (Self::IntLiteral(..), TokenKind::IntLiteral)
+            | (Self::FloatLiteral(..), TokenKind::FloatLiteral)
+            | (Self::StringLiteral(..), TokenKind::StringLiteral)
+            | (Self::CharLiteral(..), TokenKind::CharLiteral)
+            | (Self::Error(_), TokenKind::Error)
+            | (Self::Eof, TokenKind::Eof) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the token moved down by `lines` lines and right by `cols`
+    /// bytes. Errors and EOF have no position and are returned as they are.
+    pub fn moved(mut self, lines: isize, cols: isize) -> Token {
+        match &mut self {
+            Self::DataType(line, col, _)
+            | Self::Identifier(line, col, _)
+            | Self::Separator(line, col, _)
+            | Self::Operator(line, col, _)
+            | Self::Keyword(line, col, _)
+            | Self::Label(line, col, _)
+            | Self::IntLiteral(line, col, _)
+            | Self::CharLiteral(line, col, _)
+            | Self::FloatLiteral(line, col, _)
+            | Self::StringLiteral(line, col, _) => {
+                *line = line.saturating_add_signed(lines);
+                *col = col.saturating_add_signed(cols);
+            }
+            Self::Error(_) | Self::Eof => {}
+        }
+        self
+    }
+
+    /// Runs `f`, moving the tokens it deserializes on the current thread like
+    /// [`Token::moved`], so that a tree loaded from where it was parsed can
+    /// be placed elsewhere in the input.
+    pub fn moving<T>(lines: isize, cols: isize, f: impl FnOnce() -> T) -> T {
+        let outer = MOVING.with(|moving| moving.replace((lines, cols)));
+        let result = f();
+        MOVING.with(|moving| moving.set(outer));
+        result
+    }
+}
+
+thread_local! {
+    /// How far the tokens deserialized on this thread are moved, see
+    /// [`Token::moving`].
+    static MOVING: Cell<(isize, isize)> = const { Cell::new((0, 0)) };
+}
+
+/// A token as it is serialized, before it is moved.
+#[derive(Deserialize)]
+#[serde(rename = "Token")]
+enum Serialized {
+    DataType(usize, usize, String),
+    Identifier(usize, usize, Symbol),
+    Separator(usize, usize, String),
+    Operator(usize, usize, String),
+    Keyword(usize, usize, String),
+    Label(usize, usize, String),
+    IntLiteral(usize, usize, String),
+    FloatLiteral(usize, usize, String),
+    StringLiteral(usize, usize, String),
+    CharLiteral(usize, usize, String),
+    Error(utils::LexerError),
+    Eof,
+}
+
+impl<'de> Deserialize<'de> for Token {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = match Serialized::deserialize(deserializer)? {
+            Serialized::DataType(line, col, value) => Token::DataType(line, col, value),
+            Serialized::Identifier(line, col, value) => Token::Identifier(line, col, value),
+            Serialized::Separator(line, col, value) => Token::Separator(line, col, value),
+            Serialized::Operator(line, col, value) => Token::Operator(line, col, value),
+            Serialized::Keyword(line, col, value) => Token::Keyword(line, col, value),
+            Serialized::Label(line, col, value) => Token::Label(line, col, value),
+            Serialized::IntLiteral(line, col, value) => Token::IntLiteral(line, col, value),
+            Serialized::FloatLiteral(line, col, value) => Token::FloatLiteral(line, col, value),
+            Serialized::StringLiteral(line, col, value) => Token::StringLiteral(line, col, value),
+            Serialized::CharLiteral(line, col, value) => Token::CharLiteral(line, col, value),
+            Serialized::Error(err) => Token::Error(err),
+            Serialized::Eof => Token::Eof,
+        };
+        let (lines, cols) = MOVING.with(Cell::get);
+        Ok(token.moved(lines, cols))
+    }
 }
 
-use std::fmt;
+/// Returns whether two token streams are the same program, ignoring where
+/// the tokens are. Whitespace and comments are not tokens, so streams lexed
+/// from sources that only differ in them are equivalent. A formatter must
+/// keep the tokens of its input equivalent to those of its output.
+pub fn tokens_equivalent(a: &[Token], b: &[Token]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same_as(b))
+}
 
-/// Implements the `Display` trait for `Token`, providing a human-readable 
+/// Implements the `Display` trait for `Token`, providing a human-readable
 /// string representation of each token. This is especially useful for debugging.
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -109,11 +301,12 @@ impl fmt::Display for Token {
                 "Operator(line: {}, col: {}, value: {})",
                 line, col, value
             ),
-            Token::Keyword(line, col, ref value) => write!(
-                f,
-                "Keyword(line: {}, col: {}, value: {})",
-                line, col, value
-            ),
+            Token::Keyword(line, col, ref value) => {
+                write!(f, "Keyword(line: {}, col: {}, value: {})", line, col, value)
+            }
+            Token::Label(line, col, ref value) => {
+                write!(f, "Label(line: {}, col: {}, value: {})", line, col, value)
+            }
             Token::IntLiteral(line, col, ref value) => write!(
                 f,
                 "IntLiteral(line: {}, col: {}, value: {})",
@@ -124,18 +317,146 @@ impl fmt::Display for Token {
                 "FloatLiteral(line: {}, col: {}, value: {})",
                 line, col, value
             ),
+            // Literals are escaped so that each token prints on a single line.
             Token::StringLiteral(line, col, ref value) => write!(
                 f,
                 "StringLiteral(line: {}, col: {}, value: {})",
-                line, col, value
+                line,
+                col,
+                value.escape_debug()
             ),
             Token::CharLiteral(line, col, ref value) => write!(
                 f,
                 "CharLiteral(line: {}, col: {}, value: {})",
-                line, col, value
+                line,
+                col,
+                value.escape_debug()
             ),
             Token::Error(ref err) => write!(f, "Error: {}", err),
             Token::Eof => write!(f, "End of File"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn test_display_escapes_literals() {
+        let token = Token::StringLiteral(1, 0, String::from("\"a\nb\""));
+        assert_eq!(
+            token.to_string(),
+            r#"StringLiteral(line: 1, col: 0, value: \"a\nb\")"#
+        );
+
+        let token = Token::CharLiteral(1, 0, String::from("'\t'"));
+        assert_eq!(
+            token.to_string(),
+            r#"CharLiteral(line: 1, col: 0, value: \'\t\')"#
+        );
+    }
+    #[test]
+    fn test_tokens_as_keys() {
+        let tokens = lexer::Lexer::new("x = x + 0xZ;").lex();
+        let unique: HashSet<&Token> = tokens.iter().collect();
+        assert_eq!(unique.len(), tokens.len());
+        assert!(unique.contains(&Token::Identifier(1, 0, Symbol::from("x"))));
+        assert!(!unique.contains(&Token::Identifier(1, 1, Symbol::from("x"))));
+
+        let mut frequency: HashMap<&str, usize> = HashMap::new();
+        for token in &tokens {
+            *frequency.entry(token.get_lexeme()).or_default() += 1;
+        }
+        assert_eq!(frequency["x"], 2);
+
+        let mut errors: HashMap<Token, usize> = HashMap::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if let Token::Error(_) = token {
+                errors.insert(token.clone(), i);
+            }
+        }
+        let error = Token::Error(utils::LexerError::InvalidHexaDecimal(
+            1,
+            8,
+            10,
+            String::from("0x"),
+        ));
+        assert_eq!(errors.get(&error), Some(&4));
+    }
+
+    #[test]
+    fn test_tokens_equivalent() {
+        let lex = |src: &str| lexer::Lexer::new(src).lex();
+        let compact = lex("fn main() {\n  i32 x = 1 + 2;\n}");
+        let spread = lex("fn main()\n{\n\ti32 x =\n\t\t1 + 2; // three\n}\n");
+        assert_ne!(compact, spread);
+        assert!(tokens_equivalent(&compact, &spread));
+
+        assert!(!tokens_equivalent(
+            &compact,
+            &lex("fn main() { i32 x = 1 - 2; }")
+        ));
+        assert!(!tokens_equivalent(
+            &compact,
+            &lex("fn main() { i32 x = 1 + 2; };")
+        ));
+        assert!(tokens_equivalent(&lex("0xZ"), &lex("  0xZ")));
+    }
+
+    #[test]
+    fn test_numeric_radix() {
+        let radix = |src: &str| lexer::Lexer::new(src).lex()[0].numeric_radix();
+        assert_eq!(radix("0xFFu8"), Some(16));
+        assert_eq!(radix("0o17"), Some(8));
+        assert_eq!(radix("0b101"), Some(2));
+        assert_eq!(radix("42"), Some(10));
+        assert_eq!(radix("0"), Some(10));
+        assert_eq!(radix("1.5"), None);
+        assert_eq!(radix("x"), None);
+    }
+
+    #[test]
+    fn test_kinds() {
+        let tokens = lexer::Lexer::new("intf fn { + i32 x 1 'a'").lex();
+        assert!(tokens[0].is(TokenKind::Identifier("intf")));
+        assert!(!tokens[0].is(TokenKind::Keyword("intf")));
+        assert!(tokens[1].is(TokenKind::Keyword("fn")));
+        assert!(!tokens[1].is(TokenKind::Keyword("if")));
+        assert!(tokens[2].is(TokenKind::Separator("{")));
+        assert!(!tokens[2].is(TokenKind::Operator("{")));
+        assert!(tokens[3].is(TokenKind::Operator("+")));
+        assert!(tokens[4].is(TokenKind::DataType));
+        assert!(!tokens[5].is(TokenKind::Identifier("intf")));
+        assert!(tokens[6].is(TokenKind::IntLiteral));
+        assert!(tokens[7].is(TokenKind::CharLiteral));
+        assert!(Token::Eof.is(TokenKind::Eof));
+    }
+
+    #[test]
+    fn test_moving() {
+        let tokens = lexer::Lexer::new("x = 1;\ny = 0xZ;").lex();
+        assert_eq!(
+            tokens[4].clone().moved(2, 3),
+            Token::Identifier(4, 10, Symbol::from("y"))
+        );
+        assert_eq!(
+            tokens[5].clone().moved(-1, -7),
+            Token::Operator(1, 2, String::from("="))
+        );
+        assert_eq!(tokens[6].clone().moved(1, 1), tokens[6]);
+        assert_eq!(Token::Eof.moved(1, 1), Token::Eof);
+
+        let bytes = bincode::serialize(&tokens).unwrap();
+        let moved: Vec<Token> = Token::moving(2, 3, || bincode::deserialize(&bytes).unwrap());
+        let expected: Vec<Token> = tokens
+            .iter()
+            .map(|token| token.clone().moved(2, 3))
+            .collect();
+        assert_eq!(moved, expected);
+        assert_eq!(bincode::deserialize::<Vec<Token>>(&bytes).unwrap(), tokens);
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Token>>(&json).unwrap(), tokens);
+    }
+}
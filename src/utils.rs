@@ -1,24 +1,50 @@
+use crate::codegen::CodegenError;
+use crate::token::Span;
 use colored::*;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// An error in the input of the lexer: (line, column, end column, value). The
+/// columns are the byte offsets bounding the text at fault.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub enum LexerError {
-    UnexpectedEOF(usize, usize, String),
-    InvalidBinary(usize, usize, String),
-    InvalidOctal(usize, usize, String),
-    InvalidDecimal(usize, usize, String),
-    InvalidHexaDecimal(usize, usize, String),
-    InvalidFloat(usize, usize, String),
-    UnterminatedStringLiteral(usize, usize, String),
-    UnterminatedCharacterLiteral(usize, usize, String),
-    UnterminatedComment(usize, usize, String),
+    UnexpectedEOF(usize, usize, usize, String),
+    InvalidBinary(usize, usize, usize, String),
+    InvalidOctal(usize, usize, usize, String),
+    InvalidDecimal(usize, usize, usize, String),
+    InvalidHexaDecimal(usize, usize, usize, String),
+    InvalidFloat(usize, usize, usize, String),
+    UnterminatedStringLiteral(usize, usize, usize, String),
+    UnterminatedCharacterLiteral(usize, usize, usize, String),
+    UnterminatedComment(usize, usize, usize, String),
+    InvalidEscape(usize, usize, usize, String),
+    InvalidIdentifierCharacter(usize, usize, usize, String),
+}
+
+impl LexerError {
+    /// Returns the bytes of the input the error is about.
+    pub fn span(&self) -> Span {
+        match *self {
+            LexerError::UnexpectedEOF(_, start, end, _)
+            | LexerError::InvalidBinary(_, start, end, _)
+            | LexerError::InvalidOctal(_, start, end, _)
+            | LexerError::InvalidDecimal(_, start, end, _)
+            | LexerError::InvalidHexaDecimal(_, start, end, _)
+            | LexerError::InvalidFloat(_, start, end, _)
+            | LexerError::UnterminatedStringLiteral(_, start, end, _)
+            | LexerError::UnterminatedCharacterLiteral(_, start, end, _)
+            | LexerError::UnterminatedComment(_, start, end, _)
+            | LexerError::InvalidEscape(_, start, end, _)
+            | LexerError::InvalidIdentifierCharacter(_, start, end, _) => Span { start, end },
+        }
+    }
 }
 
 impl fmt::Display for LexerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            LexerError::UnexpectedEOF(line, col, value) => {
+            LexerError::UnexpectedEOF(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -28,7 +54,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::InvalidBinary(line, col, value) => {
+            LexerError::InvalidBinary(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -38,7 +64,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::InvalidOctal(line, col, value) => {
+            LexerError::InvalidOctal(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -48,7 +74,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::InvalidDecimal(line, col, value) => {
+            LexerError::InvalidDecimal(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -58,7 +84,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::InvalidHexaDecimal(line, col, value) => {
+            LexerError::InvalidHexaDecimal(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -68,7 +94,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::InvalidFloat(line, col, value) => {
+            LexerError::InvalidFloat(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -78,7 +104,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::UnterminatedStringLiteral(line, col, value) => {
+            LexerError::UnterminatedStringLiteral(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -88,7 +114,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::UnterminatedCharacterLiteral(line, col, value) => {
+            LexerError::UnterminatedCharacterLiteral(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -98,7 +124,7 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
-            LexerError::UnterminatedComment(line, col, value) => {
+            LexerError::UnterminatedComment(line, col, _, value) => {
                 write!(
                     f,
                     "{} {} {} {}",
@@ -108,16 +134,42 @@ impl fmt::Display for LexerError {
                     value.blue()
                 )
             }
+            LexerError::InvalidEscape(line, col, _, value) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Invalid escape sequence at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    value.blue()
+                )
+            }
+            LexerError::InvalidIdentifierCharacter(line, col, _, value) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Invalid character in an identifier at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    value.blue()
+                )
+            }
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum ParserError {
     UnexpectedToken(usize, usize, String),
     MissingToken(usize, usize, String),
     InvalidSyntax(usize, usize, String),
     UnexpectedEOF(usize, usize, String),
+    ReservedKeyword(usize, usize, String),
+    /// A data type such as `u8` where a name is expected.
+    ReservedTypeName(usize, usize, String),
+    UnclosedDelimiter(usize, usize, String),
+    DanglingElif(usize, usize),
+    DanglingElse(usize, usize),
 }
 
 impl fmt::Display for ParserError {
@@ -163,6 +215,605 @@ impl fmt::Display for ParserError {
                     message.blue()
                 )
             }
+            ParserError::ReservedKeyword(line, col, word) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Reserved keyword used as a name at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    format!(
+                        "`{}` is a reserved keyword and cannot be used as a name",
+                        word
+                    )
+                    .blue()
+                )
+            }
+            ParserError::ReservedTypeName(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Reserved type name used as a name at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    format!(
+                        "`{}` is a reserved type name and cannot be used as an identifier here",
+                        name
+                    )
+                    .blue()
+                )
+            }
+            ParserError::UnclosedDelimiter(line, col, delimiter) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unclosed delimiter at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    delimiter.blue()
+                )
+            }
+            ParserError::DanglingElif(line, col) => {
+                write!(
+                    f,
+                    "{} {}",
+                    "`elif` without a preceding `if` at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow()
+                )
+            }
+            ParserError::DanglingElse(line, col) => {
+                write!(
+                    f,
+                    "{} {}",
+                    "`else` without a preceding `if` at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow()
+                )
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SemanticError {
+    UndefinedLabel(usize, usize, String),
+    UndefinedVariable(usize, usize, String),
+    UndefinedFunction(usize, usize, String),
+    Redefinition(usize, usize, String),
+    TypeMismatch(usize, usize, String),
+    ArgumentCountMismatch(usize, usize, String),
+    UndefinedInterface(usize, usize, String),
+    MissingInterfaceMethod(usize, usize, String),
+    AssignToConst(usize, usize, String),
+    NonConstantInitializer(usize, usize, String),
+    MethodNotInBound(usize, usize, String),
+    UnsatisfiedBound(usize, usize, String),
+    DuplicateParameter(usize, usize, String),
+    DuplicateGeneric(usize, usize, String),
+    NonExhaustiveMatch(usize, usize, String),
+    VoidVariable(usize, usize, String),
+    NonConstantDiscriminant(usize, usize, String),
+    DuplicateDiscriminant(usize, usize, String),
+    DiscriminantOverflow(usize, usize, String),
+    SelfOutsideMethod(usize, usize, String),
+    ModuleCollision(usize, usize, String),
+    CircularImport(usize, usize, String),
+    /// A generic type used with the wrong number of generic arguments: its
+    /// name, the number of parameters it declares, and the number given.
+    GenericArityMismatch(usize, usize, String, usize, usize),
+    /// An array type whose constant size is negative or not an integer: the
+    /// size as written.
+    InvalidArraySize(usize, usize, String),
+    /// A `null` where a value that is not a reference is expected: the
+    /// expected type.
+    NullForNonReference(usize, usize, String),
+    /// A `break` without a value leaving a `loop` used as a value.
+    MissingBreakValue(usize, usize),
+    /// An array type taking up more bytes than a value can on the target:
+    /// the target, and the most bytes a value can take up there.
+    ArrayTooLarge(usize, usize, String, u64),
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::UndefinedLabel(line, col, label) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Undefined loop label at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    label.blue()
+                )
+            }
+            SemanticError::UndefinedVariable(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Undefined variable at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::UndefinedFunction(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Undefined function at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::Redefinition(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Redefinition of a name at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::TypeMismatch(line, col, message) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Mismatched types at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    message.blue()
+                )
+            }
+            SemanticError::ArgumentCountMismatch(line, col, message) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Wrong number of arguments at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    message.blue()
+                )
+            }
+            SemanticError::UndefinedInterface(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Undefined interface at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::MissingInterfaceMethod(line, col, method) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Missing interface method at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    method.blue()
+                )
+            }
+            SemanticError::AssignToConst(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Assignment to a constant at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::NonConstantInitializer(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Non-constant initializer at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::MethodNotInBound(line, col, message) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Method not in generic bound at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    message.blue()
+                )
+            }
+            SemanticError::UnsatisfiedBound(line, col, message) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unsatisfied generic bound at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    message.blue()
+                )
+            }
+            SemanticError::DuplicateParameter(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Duplicate parameter at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::DuplicateGeneric(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Duplicate generic parameter at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::NonExhaustiveMatch(line, col, missing) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Non-exhaustive match at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    missing.blue()
+                )
+            }
+            SemanticError::VoidVariable(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Void variable at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::NonConstantDiscriminant(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Non-constant discriminant at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::DuplicateDiscriminant(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Duplicate discriminant at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::DiscriminantOverflow(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Discriminant overflow at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::SelfOutsideMethod(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Receiver outside of a method at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::ModuleCollision(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Name declared by another file at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticError::CircularImport(line, col, cycle) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Circular import at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    cycle.blue()
+                )
+            }
+            SemanticError::GenericArityMismatch(line, col, name, expected, found) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Wrong number of generic arguments at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    format!("{} expects {}, found {}", name, expected, found).blue()
+                )
+            }
+            SemanticError::InvalidArraySize(line, col, size) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Invalid array size at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    size.blue()
+                )
+            }
+            SemanticError::NullForNonReference(line, col, expected) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Null for a value that is not a reference at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    expected.blue()
+                )
+            }
+            SemanticError::MissingBreakValue(line, col) => {
+                write!(
+                    f,
+                    "{} {}",
+                    "`break` without a value for a loop used as a value at"
+                        .red()
+                        .bold(),
+                    format!("line {}, col {}", line, col).yellow()
+                )
+            }
+            SemanticError::ArrayTooLarge(line, col, target, _) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Array too large for the target at".red().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    target.blue()
+                )
+            }
+        }
+    }
+}
+
+/// A style problem that does not prevent compilation. Style checks are off
+/// unless enabled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SemanticWarning {
+    /// A line indented with both tabs and spaces: (line, start of the line)
+    MixedIndentation(usize, usize),
+    /// A local variable that is never read: (line, column, name)
+    UnusedVariable(usize, usize, String),
+    /// A private function that no entry point calls: (line, column, name)
+    UnusedFunction(usize, usize, String),
+    /// A name that does not follow the case style of its kind: (line,
+    /// column, name, expected style)
+    NonConventionalName(usize, usize, String, CaseStyle),
+    /// An attribute the compiler does not know: (line, column, name)
+    UnknownAttribute(usize, usize, String),
+    /// An array type with a constant size of 0: (line, column of the size)
+    EmptyArray(usize, usize),
+    /// A constant condition or loop range that makes code unreachable, which
+    /// optimization removes: (line, column, the condition or range)
+    DeadBranch(usize, usize, String),
+}
+
+/// A convention for writing names made of several words.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseStyle {
+    /// `lower_case_words`
+    Snake,
+    /// `UPPER_CASE_WORDS`
+    ScreamingSnake,
+    /// `CapitalizedWords`
+    Pascal,
+}
+
+impl CaseStyle {
+    /// Returns whether `name` follows the style. Leading and trailing
+    /// underscores are ignored, so `_unused` is in snake case.
+    pub fn matches(self, name: &str) -> bool {
+        let name = name.trim_matches('_');
+        match self {
+            CaseStyle::Snake => !name.chars().any(char::is_uppercase),
+            CaseStyle::ScreamingSnake => !name.chars().any(char::is_lowercase),
+            CaseStyle::Pascal => {
+                !name.contains('_') && !name.chars().next().is_some_and(char::is_lowercase)
+            }
+        }
+    }
+}
+
+impl fmt::Display for CaseStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaseStyle::Snake => write!(f, "snake_case"),
+            CaseStyle::ScreamingSnake => write!(f, "SCREAMING_SNAKE_CASE"),
+            CaseStyle::Pascal => write!(f, "PascalCase"),
         }
     }
 }
+
+impl fmt::Display for SemanticWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticWarning::MixedIndentation(line, col) => {
+                write!(
+                    f,
+                    "{} {}",
+                    "Mixed tabs and spaces in the indentation at"
+                        .yellow()
+                        .bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                )
+            }
+            SemanticWarning::UnusedVariable(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unused variable at".yellow().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticWarning::UnusedFunction(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unused function at".yellow().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticWarning::NonConventionalName(line, col, name, style) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    format!("Name not in {} at", style).yellow().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticWarning::UnknownAttribute(line, col, name) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Unknown attribute at".yellow().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    name.blue()
+                )
+            }
+            SemanticWarning::EmptyArray(line, col) => {
+                write!(
+                    f,
+                    "{} {}",
+                    "Empty array at".yellow().bold(),
+                    format!("line {}, col {}", line, col).yellow()
+                )
+            }
+            SemanticWarning::DeadBranch(line, col, condition) => {
+                write!(
+                    f,
+                    "{} {} {} {}",
+                    "Dead branch at".yellow().bold(),
+                    format!("line {}, col {}", line, col).yellow(),
+                    "->".cyan(),
+                    condition.blue()
+                )
+            }
+        }
+    }
+}
+
+/// An error from any phase of compiling a file, for library users that
+/// handle them all the same way.
+#[derive(Debug)]
+pub enum CompileError {
+    Lex(LexerError),
+    Parse(ParserError),
+    Semantic(SemanticError),
+    Codegen(CodegenError),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Lex(err) => write!(f, "{}", err),
+            CompileError::Parse(err) => write!(f, "{}", err),
+            CompileError::Semantic(err) => write!(f, "{}", err),
+            CompileError::Codegen(err) => write!(f, "{}", err),
+            CompileError::Io(err) => write!(f, "{} {}", "I/O error:".red().bold(), err),
+        }
+    }
+}
+
+impl From<LexerError> for CompileError {
+    fn from(err: LexerError) -> Self {
+        CompileError::Lex(err)
+    }
+}
+
+impl From<ParserError> for CompileError {
+    fn from(err: ParserError) -> Self {
+        CompileError::Parse(err)
+    }
+}
+
+impl From<SemanticError> for CompileError {
+    fn from(err: SemanticError) -> Self {
+        CompileError::Semantic(err)
+    }
+}
+
+impl From<CodegenError> for CompileError {
+    fn from(err: CodegenError) -> Self {
+        CompileError::Codegen(err)
+    }
+}
+
+impl From<std::io::Error> for CompileError {
+    fn from(err: std::io::Error) -> Self {
+        CompileError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex() -> Result<(), CompileError> {
+        Err(LexerError::InvalidOctal(1, 2, 5, String::from("0o9")))?
+    }
+
+    fn parse() -> Result<(), CompileError> {
+        Err(ParserError::UnexpectedToken(3, 4, String::from("}")))?
+    }
+
+    fn check() -> Result<(), CompileError> {
+        Err(SemanticError::UndefinedVariable(5, 6, String::from("x")))?
+    }
+
+    fn read() -> Result<String, CompileError> {
+        Ok(std::fs::read_to_string("does/not/exist.zx")?)
+    }
+
+    #[test]
+    fn test_compile_error_conversions() {
+        colored::control::set_override(false);
+
+        let err = lex().unwrap_err();
+        assert!(matches!(err, CompileError::Lex(_)));
+        assert!(err.to_string().ends_with("line 1, col 2 -> 0o9"), "{}", err);
+
+        let err = parse().unwrap_err();
+        assert!(matches!(err, CompileError::Parse(_)));
+        assert!(err.to_string().ends_with("line 3, col 4 -> }"), "{}", err);
+
+        let err = check().unwrap_err();
+        assert!(matches!(err, CompileError::Semantic(_)));
+        assert_eq!(err.to_string(), "Undefined variable at line 5, col 6 -> x");
+
+        let err = read().unwrap_err();
+        assert!(matches!(err, CompileError::Io(_)));
+        assert!(err.to_string().starts_with("I/O error: "), "{}", err);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::ast::*;
+use crate::token::Span;
+
+/*
+ * A read-only traversal of the syntax tree. Each `visit_*` method defaults to
+ * the matching `walk_*` function, which visits the children of the node in
+ * source order. Implementors override the methods for the nodes they care about
+ * and call the `walk_*` function to keep descending.
+ */
+
+pub trait Visitor<'ast>: Sized {
+    fn visit_declaration(&mut self, decl: &'ast Declaration) {
+        walk_declaration(self, decl);
+    }
+
+    fn visit_function(&mut self, function: &'ast FunctionDeclaration) {
+        walk_function(self, function);
+    }
+
+    fn visit_interface(&mut self, interface: &'ast InterfaceDeclaration) {
+        walk_interface(self, interface);
+    }
+
+    fn visit_implementation(&mut self, implementation: &'ast InterfaceImplementation) {
+        walk_implementation(self, implementation);
+    }
+
+    fn visit_variant(&mut self, variant: &'ast Variant) {
+        walk_variant(self, variant);
+    }
+
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: &'ast Expression) {
+        walk_expression(self, expr);
+    }
+
+    fn visit_function_call(&mut self, call: &'ast FunctionCall) {
+        walk_function_call(self, call);
+    }
+
+    fn visit_type(&mut self, var_type: &'ast Type) {
+        walk_type_variant(self, &var_type.variant);
+    }
+
+    fn visit_identifier(&mut self, _id: &'ast Identifier) {}
+}
+
+pub fn walk_ast<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast AST) {
+    for decl in &ast.declarations {
+        visitor.visit_declaration(decl);
+    }
+}
+
+pub fn walk_declaration<'ast, V: Visitor<'ast>>(visitor: &mut V, decl: &'ast Declaration) {
+    match decl {
+        Declaration::Enum(enumeration) => {
+            visitor.visit_identifier(&enumeration.id);
+            for variant in enumeration.variants.iter().flatten() {
+                visitor.visit_variant(variant);
+            }
+        }
+        Declaration::Struct(structure) => visitor.visit_variant(&structure.variant),
+        Declaration::Function(function) => visitor.visit_function(function),
+        Declaration::Interface(interface) => visitor.visit_interface(interface),
+        Declaration::Implementation(implementation) => visitor.visit_implementation(implementation),
+        Declaration::Import(import) => {
+            for segment in &import.segments {
+                visitor.visit_identifier(segment);
+            }
+        }
+        Declaration::Error(_) => {}
+    }
+}
+
+pub fn walk_function<'ast, V: Visitor<'ast>>(visitor: &mut V, function: &'ast FunctionDeclaration) {
+    visitor.visit_identifier(&function.id);
+    if let Some(receiver) = &function.receiver {
+        visitor.visit_identifier(&receiver.id);
+    }
+    for (param_type, id) in function.parameters.iter().flatten() {
+        visitor.visit_type(param_type);
+        visitor.visit_identifier(id);
+    }
+    if let Some(return_type) = &function.return_type {
+        visitor.visit_type(return_type);
+    }
+    visitor.visit_block(&function.block);
+}
+
+pub fn walk_interface<'ast, V: Visitor<'ast>>(
+    visitor: &mut V,
+    interface: &'ast InterfaceDeclaration,
+) {
+    visitor.visit_identifier(&interface.id);
+    for method in &interface.methods {
+        visitor.visit_function(method);
+    }
+}
+
+pub fn walk_implementation<'ast, V: Visitor<'ast>>(
+    visitor: &mut V,
+    implementation: &'ast InterfaceImplementation,
+) {
+    visitor.visit_identifier(&implementation.intf_id);
+    visitor.visit_type(&implementation.for_type);
+    for method in &implementation.methods {
+        visitor.visit_function(method);
+    }
+}
+
+pub fn walk_variant<'ast, V: Visitor<'ast>>(visitor: &mut V, variant: &'ast Variant) {
+    match variant {
+        Variant::Named(id, fields) => {
+            visitor.visit_identifier(id);
+            for (field_type, field_id) in &fields.fields {
+                visitor.visit_type(field_type);
+                visitor.visit_identifier(field_id);
+            }
+        }
+        Variant::Tuple(id, fields) => {
+            visitor.visit_identifier(id);
+            for field_type in &fields.fields {
+                visitor.visit_type(field_type);
+            }
+        }
+        Variant::Unit(id, discriminant) => {
+            visitor.visit_identifier(id);
+            if let Some(discriminant) = discriminant {
+                visitor.visit_expression(discriminant);
+            }
+        }
+    }
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast>>(visitor: &mut V, block: &'ast Block) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+    if let Some(value) = &block.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast>>(visitor: &mut V, statement: &'ast Statement) {
+    match statement {
+        Statement::If(if_statement) => {
+            visitor.visit_expression(&if_statement.condition);
+            visitor.visit_block(&if_statement.if_block);
+            for elif in if_statement.elif_statements.iter().flatten() {
+                visitor.visit_expression(&elif.condition);
+                visitor.visit_block(&elif.block);
+            }
+            if let Some(else_block) = &if_statement.else_block {
+                visitor.visit_block(else_block);
+            }
+        }
+        Statement::Loop(loop_statement) => walk_loop(visitor, loop_statement),
+        Statement::Assign(assignment) => {
+            visitor.visit_identifier(&assignment.id);
+            visitor.visit_expression(&assignment.expr);
+        }
+        Statement::Var(declaration) => {
+            visitor.visit_type(&declaration.var_type);
+            visitor.visit_identifier(&declaration.id);
+            if let Some(init) = &declaration.init {
+                visitor.visit_expression(init);
+            }
+        }
+        Statement::Match(match_statement) => walk_match(visitor, match_statement),
+        Statement::Return(ret) => {
+            if let Some(expr) = &ret.expr {
+                visitor.visit_expression(expr);
+            }
+        }
+        Statement::FunctionCall(call) => visitor.visit_function_call(call),
+        Statement::Break(_, value) => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+        Statement::Continue(_) | Statement::LLVM(_) | Statement::ASM(_) | Statement::Error(_) => {}
+    }
+}
+
+/// Visits the range and the body of a loop, used as a statement or a value.
+pub fn walk_loop<'ast, V: Visitor<'ast>>(visitor: &mut V, loop_statement: &'ast LoopStatement) {
+    if let Some(iteration) = &loop_statement.iteration {
+        visitor.visit_identifier(&iteration.variable);
+        visitor.visit_expression(&iteration.range.start);
+        visitor.visit_expression(&iteration.range.end);
+    }
+    visitor.visit_block(&loop_statement.block);
+}
+
+/// Visits the scrutinee, the variant patterns and the arms of a `match`
+/// statement or expression.
+pub fn walk_match<'ast, V: Visitor<'ast>>(visitor: &mut V, match_statement: &'ast MatchStatement) {
+    visitor.visit_expression(&match_statement.expr);
+    for clause in &match_statement.case_clauses {
+        for case in &clause.cases {
+            if let Pattern::Variant(id) = case {
+                visitor.visit_identifier(id);
+            }
+        }
+        visitor.visit_block(&clause.case_block);
+    }
+    if let Some(default) = &match_statement.default_clause {
+        visitor.visit_block(default);
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast>>(visitor: &mut V, expr: &'ast Expression) {
+    match expr {
+        Expression::Operation(operator) => match operator.as_ref() {
+            Operator::Binary(_, lhs, rhs) => {
+                visitor.visit_expression(lhs);
+                visitor.visit_expression(rhs);
+            }
+            Operator::Unary(_, operand) => visitor.visit_expression(operand),
+            Operator::Error(_) => {}
+        },
+        Expression::Primary(primary) => walk_primary(visitor, primary),
+        Expression::Error(_) => {}
+    }
+}
+
+/// Visits the children of a primary expression. Field access members are
+/// primaries without an expression of their own, and are walked through here.
+pub fn walk_primary<'ast, V: Visitor<'ast>>(visitor: &mut V, primary: &'ast Primary) {
+    match primary {
+        Primary::Identifier(id) => visitor.visit_identifier(id),
+        Primary::Group(inner) => visitor.visit_expression(inner),
+        Primary::ArrayAccess(id, access) => {
+            visitor.visit_identifier(id);
+            let mut access = Some(access);
+            while let Some(current) = access {
+                visitor.visit_expression(&current.index);
+                access = current.next.as_ref();
+            }
+        }
+        Primary::FunctionCall(call) => visitor.visit_function_call(call),
+        Primary::Match(match_statement) => walk_match(visitor, match_statement),
+        Primary::Loop(loop_statement) => walk_loop(visitor, loop_statement),
+        Primary::Range(range) => {
+            visitor.visit_expression(&range.start);
+            visitor.visit_expression(&range.end);
+        }
+        Primary::FieldAccess(base, member) => {
+            visitor.visit_expression(base);
+            walk_primary(visitor, member);
+        }
+        Primary::Literal(_) | Primary::Error(_) => {}
+    }
+}
+
+pub fn walk_function_call<'ast, V: Visitor<'ast>>(visitor: &mut V, call: &'ast FunctionCall) {
+    visitor.visit_identifier(&call.id);
+    for arg in &call.args {
+        visitor.visit_expression(arg);
+    }
+}
+
+pub fn walk_type_variant<'ast, V: Visitor<'ast>>(visitor: &mut V, variant: &'ast TypeVariant) {
+    match variant {
+        TypeVariant::Structure(id, generics)
+        | TypeVariant::Enumeration(id, generics)
+        | TypeVariant::Interface(id, generics) => {
+            visitor.visit_identifier(id);
+            for generic in generics.iter().flat_map(|generics| &generics.generics) {
+                if let GenericVariants::Type(argument) = generic.as_ref() {
+                    visitor.visit_type(argument);
+                }
+            }
+        }
+        TypeVariant::Generic(id) => visitor.visit_identifier(id),
+        TypeVariant::Array(element, size) => {
+            walk_type_variant(visitor, element);
+            visitor.visit_expression(size);
+        }
+        TypeVariant::Reference(inner) => walk_type_variant(visitor, inner),
+        TypeVariant::Primitive(_) | TypeVariant::Error(_) => {}
+    }
+}
+
+/// A node a visitor can be started from.
+pub trait Walk {
+    fn accept<'ast, V: Visitor<'ast>>(&'ast self, visitor: &mut V);
+}
+
+impl Walk for Expression {
+    fn accept<'ast, V: Visitor<'ast>>(&'ast self, visitor: &mut V) {
+        visitor.visit_expression(self);
+    }
+}
+
+impl Walk for Statement {
+    fn accept<'ast, V: Visitor<'ast>>(&'ast self, visitor: &mut V) {
+        visitor.visit_statement(self);
+    }
+}
+
+impl Walk for Block {
+    fn accept<'ast, V: Visitor<'ast>>(&'ast self, visitor: &mut V) {
+        visitor.visit_block(self);
+    }
+}
+
+impl<T: Walk> Walk for Box<T> {
+    fn accept<'ast, V: Visitor<'ast>>(&'ast self, visitor: &mut V) {
+        self.as_ref().accept(visitor);
+    }
+}
+
+/// Returns every identifier `node` refers to, in source order, along with its
+/// span. The names introduced by variable declarations and range loops are
+/// not references, and identifiers that failed to parse have no span, so
+/// neither is returned.
+pub fn referenced_identifiers<N: Walk>(node: &N) -> Vec<(&Identifier, Span)> {
+    let mut collector = IdentifierCollector {
+        identifiers: Vec::new(),
+    };
+    node.accept(&mut collector);
+    collector.identifiers
+}
+
+struct IdentifierCollector<'ast> {
+    identifiers: Vec<(&'ast Identifier, Span)>,
+}
+
+impl<'ast> Visitor<'ast> for IdentifierCollector<'ast> {
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        match statement {
+            Statement::Var(declaration) => {
+                self.visit_type(&declaration.var_type);
+                if let Some(init) = &declaration.init {
+                    self.visit_expression(init);
+                }
+            }
+            Statement::Loop(loop_statement) => {
+                if let Some(iteration) = &loop_statement.iteration {
+                    self.visit_expression(&iteration.range.start);
+                    self.visit_expression(&iteration.range.end);
+                }
+                self.visit_block(&loop_statement.block);
+            }
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_identifier(&mut self, id: &'ast Identifier) {
+        if let Some(span) = id.id.as_ref().and_then(|token| token.span()) {
+            self.identifiers.push((id, span));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn names<N: Walk>(node: &N) -> Vec<(&str, usize)> {
+        referenced_identifiers(node)
+            .into_iter()
+            .map(|(id, span)| (id.id.as_ref().unwrap().get_lexeme(), span.start))
+            .collect()
+    }
+
+    #[test]
+    fn test_referenced_identifiers() {
+        let (expr, _) = Parser::parse_expression_str("a + f(b, c)");
+        let identifiers = referenced_identifiers(&expr);
+        let spans: Vec<Span> = identifiers.iter().map(|(_, span)| *span).collect();
+        assert_eq!(
+            spans,
+            [(0, 1), (4, 5), (6, 7), (9, 10)].map(|(start, end)| Span { start, end })
+        );
+        assert_eq!(names(&expr), [("a", 0), ("f", 4), ("b", 6), ("c", 9)]);
+
+        let src = "fn f() { i32 x = y; loop i in 0..n { x = x + i; } }";
+        let ast = Parser::new(Lexer::new(src).lex()).parse();
+        let Declaration::Function(function) = ast.declarations[0].as_ref() else {
+            panic!("Expected a function");
+        };
+        assert_eq!(
+            names(&function.block),
+            [("y", 17), ("n", 33), ("x", 37), ("x", 41), ("i", 45)]
+        );
+        assert_eq!(names(&function.block.statements[0]), [("y", 17)]);
+    }
+}
@@ -0,0 +1,828 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Creates an empty scratch directory, unique to the calling test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zuroxc-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Returns a compiler invocation running inside `dir`, with the required
+/// options already set.
+fn zuroxc(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_zuroxc"));
+    command
+        .current_dir(dir)
+        .args(["-O", "o2", "--target-cpu", "native", "-c", "cache"]);
+    command
+}
+
+#[test]
+fn test_out_dir_mirrors_inputs() {
+    let dir = scratch_dir("out-dir");
+    fs::create_dir_all(dir.join("src/a")).unwrap();
+    fs::write(dir.join("src/a/one.zx"), "fn one() {}").unwrap();
+    fs::write(dir.join("src/two.zx"), "fn two() {}").unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "src/a/one.zx", "-f", "src/two.zx", "--out-dir", "out"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("out/a/one.zxast").is_file());
+    assert!(dir.join("out/two.zxast").is_file());
+
+    let output = zuroxc(&dir)
+        .args(["-f", "src/two.zx", "-o", "two.out"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("two.out").is_file());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_diagnostic_width_truncates_source() {
+    let dir = scratch_dir("diagnostic-width");
+    let src = format!(
+        "fn main() {{ i32 x = {}y{}; }}",
+        "1 + ".repeat(50),
+        " + 1".repeat(50)
+    );
+    fs::write(dir.join("long.zx"), &src).unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "long.zx", "--diagnostic-width", "40"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    let source = lines
+        .iter()
+        .position(|line| line.starts_with("1 | "))
+        .unwrap();
+    assert_eq!(lines[source].chars().count(), 40);
+    let caret = lines[source + 1].find('^').unwrap();
+    assert_eq!(lines[source].chars().nth(caret), Some('y'));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_target_cpu_is_validated() {
+    let dir = scratch_dir("target-cpu");
+    fs::write(dir.join("main.zx"), "fn main() {}").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .current_dir(&dir)
+        .args(["-O", "o2", "--target-cpu", "skylak", "-f", "main.zx"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Did you mean `skylake`?"), "{}", stderr);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .current_dir(&dir)
+        .args(["-O", "o2", "--target-cpu", "skylake", "-f", "main.zx"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .arg("--list-targets")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line == "skylake"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .current_dir(&dir)
+        .args([
+            "-O",
+            "o2",
+            "--target-cpu",
+            "native",
+            "--target",
+            "x86_64-linux",
+        ])
+        .args(["-f", "main.zx"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Invalid target triple `x86_64-linux`"),
+        "{}",
+        stderr
+    );
+
+    // The target decides how large an array may be, also for a file that
+    // was cached for another target.
+    fs::write(dir.join("big.zx"), "pub fn f([u8; 3000000000] a) { }").unwrap();
+    let compile = |target: &str| {
+        zuroxc(&dir)
+            .args(["--target", target, "-f", "big.zx"])
+            .output()
+            .unwrap()
+    };
+    let output = compile("x86_64-unknown-linux-gnu");
+    assert!(output.status.success(), "{:?}", output);
+    let output = compile("i686-unknown-linux-gnu");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("The array is too large for `i686-unknown-linux-gnu`"),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_emit_ast_and_json_errors() {
+    let dir = scratch_dir("emit-ast");
+    fs::write(dir.join("good.zx"), "fn main() { i32 x = 1; }").unwrap();
+    fs::write(dir.join("bad.zx"), "fn main() { y = 1; }").unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "good.zx", "--emit", "ast"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let ast = zuroxc::ast::ast_from_json(&String::from_utf8(output.stdout).unwrap()).unwrap();
+    assert_eq!(ast.declarations.len(), 1);
+
+    let output = zuroxc(&dir)
+        .args(["-f", "bad.zx", "--error-format", "json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr
+            .trim()
+            .starts_with(r#"{"file":"bad.zx","severity":"Error","line":1,"col":12,"#),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_json_ast_schema() {
+    let dir = scratch_dir("json-ast-schema");
+    fs::write(
+        dir.join("main.zx"),
+        "enum E { A(u8) } fn main() { i32 x = 1; }",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .arg("--json-ast-schema")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(schema["title"], "AST");
+    let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--emit", "ast"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let ast: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(schema.is_valid(&ast), "{}", ast);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .args(["--json-ast-schema", "-f", "main.zx"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_emit_deps() {
+    let dir = scratch_dir("emit-deps");
+    fs::write(
+        dir.join("main.zx"),
+        "import std.io;\nimport geometry;\n\nfn main() { }",
+    )
+    .unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--emit", "deps"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "std.io\ngeometry\n"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unwritable_cache_is_not_fatal() {
+    let dir = scratch_dir("unwritable-cache");
+    fs::write(dir.join("main.zx"), "fn main() { }").unwrap();
+    // A file where the cache directory should be can't hold cache entries.
+    fs::write(dir.join("cache"), "").unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "-o", "main.out"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("main.out").is_file());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("Warning: Could not write to the cache"),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_no_cache_creates_no_directory() {
+    let dir = scratch_dir("no-cache");
+    fs::write(dir.join("main.zx"), "fn main() { }").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+        .current_dir(&dir)
+        .args(["-O", "o2", "--target-cpu", "native", "--no-cache"])
+        .args(["-f", "main.zx", "-o", "main.out"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("main.out").is_file());
+    assert!(!dir.join(".zuroxc").exists());
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "-o", "main.out", "--no-cache"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!dir.join("cache").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check_only_changed() {
+    let dir = scratch_dir("check-only-changed");
+    fs::write(dir.join("one.zx"), "pub fn one() {}").unwrap();
+    fs::write(dir.join("two.zx"), "pub fn two() {}").unwrap();
+    let check = |dir: &Path| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_zuroxc"));
+        command
+            .current_dir(dir)
+            .args(["-c", "cache", "-f", "one.zx", "-f", "two.zx"])
+            .args(["check", "--only-changed"]);
+        command.output().unwrap()
+    };
+
+    let output = check(&dir);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Checked 2 file(s), skipped 0 unchanged."),
+        "{}",
+        stdout
+    );
+
+    fs::write(dir.join("two.zx"), "pub fn two() { x = 1; }").unwrap();
+    let output = check(&dir);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Checked 1 file(s), skipped 1 unchanged."),
+        "{}",
+        stdout
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("two.zx") && !stderr.contains("one.zx"),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_keep_going_compiles_every_file() {
+    let dir = scratch_dir("keep-going");
+    fs::write(dir.join("bad.zx"), "fn bad() { x = 1; }").unwrap();
+    fs::write(dir.join("good.zx"), "fn good() {}").unwrap();
+    let args = ["-f", "bad.zx", "-f", "good.zx", "--out-dir", "out"];
+
+    let output = zuroxc(&dir).args(args).output().unwrap();
+    assert!(!output.status.success());
+    assert!(!dir.join("out/good.zxast").exists());
+
+    let output = zuroxc(&dir)
+        .args(args)
+        .arg("--keep-going")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(dir.join("out/good.zxast").is_file());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("bad.zx: "), "{}", stderr);
+    assert!(
+        stderr.contains("Error: 1 of 2 file(s) failed to compile: bad.zx"),
+        "{}",
+        stderr
+    );
+
+    // A missing file is reported like one that fails to compile, with or
+    // without the cache.
+    fs::remove_dir_all(dir.join("out")).unwrap();
+    for cache in [&[][..], &["--no-cache"]] {
+        let output = zuroxc(&dir)
+            .args(["-f", "missing.zx", "-f", "good.zx", "--out-dir", "out"])
+            .args(cache)
+            .arg("--keep-going")
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(dir.join("out/good.zxast").is_file());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Error reading missing.zx"), "{}", stderr);
+        assert!(
+            stderr.contains("Error: 1 of 2 file(s) failed to compile: missing.zx"),
+            "{}",
+            stderr
+        );
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unreadable_file_is_reported_in_order() {
+    let dir = scratch_dir("unreadable");
+    fs::write(dir.join("main.zx"), "fn main() { i32 x = 1; }").unwrap();
+
+    // The diagnostics of the files before the unreadable one are not lost.
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "-f", "missing.zx", "--no-cache"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let warning = stderr.find("Variable `x` is never used.");
+    let error = stderr.find("Error reading missing.zx");
+    assert!(warning.is_some() && error.is_some(), "{}", stderr);
+    assert!(warning < error, "{}", stderr);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_deny_turns_warnings_into_errors() {
+    let dir = scratch_dir("deny");
+    fs::write(dir.join("main.zx"), "fn main() { i32 x = 1; }").unwrap();
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Warning at line 1, col 16 -> Variable `x` is never used. [unused]"));
+
+    for deny in ["unused", "all"] {
+        let output = zuroxc(&dir)
+            .args(["-f", "main.zx", "--deny", deny])
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Error at line 1, col 16"), "{}", stderr);
+        assert!(stderr.contains(&format!("`--deny {}`", deny)), "{}", stderr);
+    }
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--deny", "all", "--warn", "unused"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--deny", "unusde"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("Error: Unknown lint `unusde`."),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_warn_naming() {
+    let dir = scratch_dir("warn-naming");
+    fs::write(dir.join("main.zx"), "pub fn MyApi() { }").unwrap();
+
+    // Without the lint, the file compiles cleanly and is cached, which must
+    // not keep the runs with the lint from reporting it.
+    let output = zuroxc(&dir).args(["-f", "main.zx"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--warn-naming"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(
+            "Warning at line 1, col 7 -> `MyApi` should be written in snake_case. [naming]"
+        ),
+        "{}",
+        stderr
+    );
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--warn-naming", "--deny", "naming"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cache_is_keyed_by_options() {
+    let dir = scratch_dir("cache-options");
+    fs::write(dir.join("main.zx"), "pub fn main() {\n\t  ret;\n}\n").unwrap();
+
+    // The first run caches the file, which has nothing to report by default.
+    let output = zuroxc(&dir).args(["-f", "main.zx"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--warn-mixed-indentation"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("[mixed-indentation]"));
+
+    // The identifiers of a file cached without an identifier set are still
+    // checked against the set.
+    fs::write(
+        dir.join("größe.zx"),
+        "pub fn main() {\n    i32 größe = 1;\n    größe = größe + 1;\n}\n",
+    )
+    .unwrap();
+    let output = zuroxc(&dir).args(["-f", "größe.zx"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let output = zuroxc(&dir)
+        .args(["-f", "größe.zx", "--ascii-identifiers"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{:?}", output);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("The character `ö` cannot be part of an identifier."));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_print_config() {
+    let dir = scratch_dir("print-config");
+    let print_config = |command: &mut Command| {
+        let output = command
+            .current_dir(&dir)
+            .env_remove("COLUMNS")
+            .arg("--print-config")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_zuroxc"));
+    let config = print_config(command.args(["--cache-dir", "build/cache", "--deny", "all"]));
+    assert_eq!(config["cache_dir"], "build/cache");
+    assert_eq!(config["optimization"], "o2");
+    assert_eq!(config["diagnostic_width"], 100);
+    assert_eq!(config["lints"]["unused"], "deny");
+    assert!(!dir.join("build").exists());
+
+    // The level is printed as it is passed to `-O`.
+    let mut command = Command::new(env!("CARGO_BIN_EXE_zuroxc"));
+    let config = print_config(command.args(["-O", "oz"]));
+    assert_eq!(config["optimization"], "oz");
+    let level = config["optimization"].as_str().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_zuroxc"));
+    assert_eq!(
+        print_config(command.args(["-O", level]))["optimization"],
+        "oz"
+    );
+
+    let config = print_config(zuroxc(&dir).args(["--no-cache", "--warn", "unused"]));
+    assert_eq!(config["cache_dir"], serde_json::Value::Null);
+    assert_eq!(config["optimization"], "o2");
+    assert_eq!(config["target_cpu"], "native");
+    assert_eq!(config["lints"]["unused"], "warn");
+
+    let config = print_config(zuroxc(&dir).args(["--target", "aarch64-apple-darwin"]));
+    assert_eq!(config["target"], "aarch64-apple-darwin");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_stats() {
+    let dir = scratch_dir("stats");
+    fs::write(
+        dir.join("main.zx"),
+        "enum E { A } pub fn main() { loop { if true { break; } } }",
+    )
+    .unwrap();
+    fs::write(dir.join("bad.zx"), "pub fn f() { ret 1 2 }").unwrap();
+
+    // The cached tree of the second run gives the same statistics.
+    for _ in 0..2 {
+        let output = zuroxc(&dir)
+            .args(["-f", "main.zx", "--stats"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("main.zx:\n"), "{}", stdout);
+        assert!(stdout.contains("Functions            1"), "{}", stdout);
+        assert!(stdout.contains("Enums                1"), "{}", stdout);
+        assert!(stdout.contains("Statements           3"), "{}", stdout);
+        assert!(stdout.contains("Max block depth      3"), "{}", stdout);
+        assert!(stdout.contains("Heap bytes"), "{}", stdout);
+    }
+
+    let output = zuroxc(&dir)
+        .args(["-f", "bad.zx", "--stats"])
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Error nodes          1"), "{}", stdout);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_fmt() {
+    let dir = scratch_dir("fmt");
+    fs::write(
+        dir.join("messy.zx"),
+        "fn main(){i32 x=1+2*3\n// done\nret x;}",
+    )
+    .unwrap();
+    fs::write(dir.join("broken.zx"), "fn main() { x = ; }").unwrap();
+    let fmt = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+            .current_dir(&dir)
+            .args(args)
+            .arg("fmt")
+            .output()
+            .unwrap()
+    };
+    let canonical = "fn main() {\n    i32 x = 1 + 2 * 3;\n    // done\n    ret x;\n}\n";
+
+    let output = fmt(&["-f", "messy.zx", "-o", "pretty.zx"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::read_to_string(dir.join("pretty.zx")).unwrap(),
+        canonical
+    );
+    assert_ne!(fs::read_to_string(dir.join("messy.zx")).unwrap(), canonical);
+
+    let output = fmt(&["-f", "messy.zx"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(dir.join("messy.zx")).unwrap(), canonical);
+
+    let output = fmt(&["-f", "broken.zx"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("broken.zx"));
+    assert_eq!(
+        fs::read_to_string(dir.join("broken.zx")).unwrap(),
+        "fn main() { x = ; }"
+    );
+}
+
+#[test]
+fn test_jobs() {
+    let dir = scratch_dir("jobs");
+    let mut args = vec!["--no-cache", "--keep-going"];
+    let names: Vec<String> = (0..8).map(|i| format!("f{}.zx", i)).collect();
+    for (i, name) in names.iter().enumerate() {
+        let src = format!("fn f{}() {{ x{} = 1; }}\nfn g() {{ ret y; }}", i, i);
+        fs::write(dir.join(name), src).unwrap();
+        args.extend(["-f", name]);
+    }
+    let compile = |jobs: &str| {
+        let output = zuroxc(&dir)
+            .env_remove("COLUMNS")
+            .args(&args)
+            .args(["--jobs", jobs])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let sequential = compile("1");
+    assert!(sequential.contains("f0.zx") && sequential.contains("f7.zx"));
+    assert!(sequential.find("f0.zx") < sequential.find("f1.zx"));
+    assert_eq!(compile("4"), sequential);
+    assert_eq!(compile("1"), sequential);
+
+    let output = zuroxc(&dir)
+        .args(["-f", "f0.zx", "-j", "0"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let output = zuroxc(&dir)
+        .args(["-j", "3", "--print-config"])
+        .output()
+        .unwrap();
+    let config: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(config["jobs"], 3);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_migrate_cache() {
+    let dir = scratch_dir("migrate-cache");
+    fs::write(dir.join("main.zx"), "fn main() {}").unwrap();
+
+    let output = zuroxc(&dir).args(["-f", "main.zx"]).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    fs::write(dir.join("cache/stale.zxcache"), [1, 2, 3]).unwrap();
+    fs::write(dir.join("cache/stale.zxdecls"), [1, 2, 3]).unwrap();
+
+    // The tree of main.zx and its declaration cache are up to date.
+    let output = zuroxc(&dir).arg("migrate-cache").output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Migrated 0 cache entries, removed 2 that could not be migrated, 2 already up to date.\n"
+    );
+    assert!(!dir.join("cache/stale.zxcache").exists());
+    assert!(!dir.join("cache/stale.zxdecls").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_optimization_folds_conditions() {
+    let dir = scratch_dir("fold-conditions");
+    fs::write(
+        dir.join("main.zx"),
+        "fn crash() -> bool { ret false; } fn main() { bool a = 1 < 2; bool b = true || crash(); }",
+    )
+    .unwrap();
+
+    let initializers = |level: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+            .current_dir(&dir)
+            .args(["-O", level, "--target-cpu", "native", "--no-cache"])
+            .args(["-f", "main.zx", "--emit", "ast"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let ast = zuroxc::ast::ast_from_json(&String::from_utf8(output.stdout).unwrap()).unwrap();
+        let zuroxc::ast::Declaration::Function(main) = ast.declarations[1].as_ref() else {
+            panic!("Expected a function, found {:?}", ast.declarations[1]);
+        };
+        main.block
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                zuroxc::ast::Statement::Var(declaration) => {
+                    zuroxc::pretty::to_source(declaration.init.as_ref().unwrap())
+                }
+                statement => panic!("Expected a variable declaration, found {:?}", statement),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(initializers("o2"), ["true", "true"]);
+    assert_eq!(initializers("o0"), ["1 < 2", "true || crash()"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_optimization_eliminates_dead_branches() {
+    let dir = scratch_dir("dead-branches");
+    fs::write(
+        dir.join("main.zx"),
+        "fn a() { } fn b() { } fn main() { if false { a(); } else { b(); } }",
+    )
+    .unwrap();
+
+    let compile = |level: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_zuroxc"))
+            .current_dir(&dir)
+            .args(level)
+            .args(["--target-cpu", "native", "--no-cache"])
+            .args(["-f", "main.zx", "--emit", "ast"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        let ast = zuroxc::ast::ast_from_json(&String::from_utf8(output.stdout).unwrap()).unwrap();
+        let zuroxc::ast::Declaration::Function(main) = ast.declarations[2].as_ref() else {
+            panic!("Expected a function, found {:?}", ast.declarations[2]);
+        };
+        let statement = main.block.statements[0].clone();
+        (statement, String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (statement, stderr) = compile(&["-O", "o1"]);
+    assert!(
+        matches!(statement, zuroxc::ast::Statement::FunctionCall(_)),
+        "{:?}",
+        statement
+    );
+    assert!(stderr.contains("`false` is constant"), "{}", stderr);
+    assert!(stderr.contains("[dead-code]"), "{}", stderr);
+
+    // Without `-O`, the default level folds constants too.
+    let (statement, _) = compile(&[]);
+    assert!(
+        matches!(statement, zuroxc::ast::Statement::FunctionCall(_)),
+        "{:?}",
+        statement
+    );
+
+    let (statement, stderr) = compile(&["-O", "o0"]);
+    assert!(
+        matches!(statement, zuroxc::ast::Statement::If(_)),
+        "{:?}",
+        statement
+    );
+    assert!(stderr.is_empty(), "{}", stderr);
+
+    let output = zuroxc(&dir)
+        .args(["-f", "main.zx", "--deny", "dead-code"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{:?}", output);
+
+    // The caret is placed the same with a byte order mark, whether the file
+    // is compiled or loaded from the cache.
+    let src = "fn a() { } fn main() { if false { a(); } }";
+    fs::write(dir.join("plain.zx"), src).unwrap();
+    fs::write(dir.join("bom.zx"), format!("\u{feff}{}", src)).unwrap();
+    let warn = |file: &str| {
+        let output = zuroxc(&dir).args(["-f", file]).output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .replace(file, "main.zx")
+    };
+    let expected = warn("plain.zx");
+    assert!(expected.contains("`false` is constant"), "{}", expected);
+    assert_eq!(warn("bom.zx"), expected);
+    assert_eq!(warn("bom.zx"), expected);
+
+    fs::remove_dir_all(&dir).unwrap();
+}